@@ -208,6 +208,55 @@ fn iterate_cached_100_by_50(b: &mut Bencher) {
     })
 }
 
+fn iterate_cached_growing_100_by_50(b: &mut Bencher) {
+    struct Marker<const N: usize>;
+
+    let mut world = World::new();
+    spawn_100_by_50(&mut world);
+    let mut query = PreparedQuery::<(&mut Position, &Velocity)>::default();
+    let _ = query.query(&world).iter();
+    let mut builder = EntityBuilder::new();
+    let mut mask = 1u16;
+    b.iter(|| {
+        // Occasionally introduce a new archetype, while most churn stays within existing ones
+        if mask & 0xF == 0 {
+            builder.add(Position(0.0)).add(Velocity(0.0));
+            let bits = mask >> 4;
+            if bits & 1 != 0 {
+                builder.add(Marker::<0>);
+            }
+            if bits & 2 != 0 {
+                builder.add(Marker::<1>);
+            }
+            if bits & 4 != 0 {
+                builder.add(Marker::<2>);
+            }
+            if bits & 8 != 0 {
+                builder.add(Marker::<3>);
+            }
+            if bits & 16 != 0 {
+                builder.add(Marker::<4>);
+            }
+            if bits & 32 != 0 {
+                builder.add(Marker::<5>);
+            }
+            if bits & 64 != 0 {
+                builder.add(Marker::<6>);
+            }
+            if bits & 128 != 0 {
+                builder.add(Marker::<7>);
+            }
+            world.spawn(builder.build());
+        }
+        mask = mask.wrapping_add(1);
+        let e = world.spawn((Position(0.0), Velocity(1.0), [(); 3]));
+        world.despawn(e).unwrap();
+        for (_, (pos, vel)) in query.query(&world).iter() {
+            pos.0 += vel.0;
+        }
+    })
+}
+
 fn iterate_mut_uncached_100_by_50(b: &mut Bencher) {
     let mut world = World::new();
     spawn_100_by_50(&mut world);
@@ -263,6 +312,73 @@ fn access_view(b: &mut Bencher) {
     });
 }
 
+fn spawn_lookup_targets(world: &mut World) -> Vec<Entity> {
+    world.spawn_batch((0..500).map(|_| (Position(0.0), Velocity(0.0), true)));
+    world
+        .spawn_batch((0..1_000).map(|_| (Position(0.0), Velocity(0.0))))
+        .step_by(2)
+        .collect()
+}
+
+fn query_one_1k(b: &mut Bencher) {
+    let mut world = World::new();
+    let targets = spawn_lookup_targets(&mut world);
+    b.iter(|| {
+        for &e in &targets {
+            let mut query = world.query_one::<(&Position, &Velocity)>(e).unwrap();
+            bencher::black_box(query.get().unwrap());
+        }
+    });
+}
+
+fn query_one_prepared_1k(b: &mut Bencher) {
+    let mut world = World::new();
+    let targets = spawn_lookup_targets(&mut world);
+    let mut prepared = PreparedQuery::<(&Position, &Velocity)>::new();
+    b.iter(|| {
+        for &e in &targets {
+            let mut query = prepared.query_one(&world, e).unwrap();
+            bencher::black_box(query.get().unwrap());
+        }
+    });
+}
+
+fn insert_remove_one_1k(b: &mut Bencher) {
+    let mut world = World::new();
+    let entities = world
+        .spawn_batch((0..1_000).map(|_| (Position(0.0),)))
+        .collect::<Vec<_>>();
+    b.iter(|| {
+        for &e in &entities {
+            world.insert_one(e, Velocity(0.0)).unwrap();
+        }
+        for &e in &entities {
+            world.remove_one::<Velocity>(e).unwrap();
+        }
+    });
+}
+
+fn insert_remove_batch_1k(b: &mut Bencher) {
+    let mut world = World::new();
+    let entities = world
+        .spawn_batch((0..1_000).map(|_| (Position(0.0),)))
+        .collect::<Vec<_>>();
+    b.iter(|| {
+        world.insert_batch(entities.iter().copied(), Velocity(0.0));
+        world.remove_batch::<Velocity>(entities.iter().copied());
+    });
+}
+
+fn clear_respawn_1k(b: &mut Bencher) {
+    let mut world = World::new();
+    b.iter(|| {
+        world.clear_retaining_capacity();
+        for i in 0..1_000 {
+            world.spawn((Position(i as f32), Velocity(0.0)));
+        }
+    });
+}
+
 fn spawn_buffered(b: &mut Bencher) {
     let mut world = World::new();
     let mut buffer = CommandBuffer::new();
@@ -287,11 +403,17 @@ benchmark_group!(
     iterate_uncached_100_by_50,
     iterate_uncached_1_of_100_by_50,
     iterate_cached_100_by_50,
+    iterate_cached_growing_100_by_50,
     iterate_mut_uncached_100_by_50,
     iterate_mut_cached_100_by_50,
     build,
     build_cloneable,
     access_view,
+    query_one_1k,
+    query_one_prepared_1k,
+    insert_remove_one_1k,
+    insert_remove_batch_1k,
+    clear_respawn_1k,
     spawn_buffered,
 );
 benchmark_main!(benches);
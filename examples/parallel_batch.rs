@@ -0,0 +1,96 @@
+use hecs::*;
+use std::time::Instant;
+
+/*
+ Parallel column batch construction
+ Spawns a million entities from a ColumnBatch, first filling every column on a single thread, then
+ filling each column on its own thread using the writers taken from ColumnBatchBuilder::writers.
+*/
+
+const COUNT: u32 = 1_000_000;
+
+#[derive(Debug, Copy, Clone)]
+struct Position([f32; 3]);
+
+#[derive(Debug, Copy, Clone)]
+struct Velocity([f32; 3]);
+
+#[derive(Debug, Copy, Clone)]
+struct Health(u32);
+
+/// Deterministic pseudo-random value in `[0, 1)`, standing in for expensive per-entity setup
+fn noise(i: u32, seed: u32) -> f32 {
+    let mut x = i ^ seed;
+    for _ in 0..32 {
+        x ^= x << 13;
+        x ^= x >> 17;
+        x ^= x << 5;
+    }
+    (x >> 8) as f32 / (1 << 24) as f32
+}
+
+fn position(i: u32) -> Position {
+    Position([noise(i, 1), noise(i, 2), noise(i, 3)])
+}
+
+fn velocity(i: u32) -> Velocity {
+    Velocity([noise(i, 4), noise(i, 5), noise(i, 6)])
+}
+
+fn health(i: u32) -> Health {
+    Health((noise(i, 7) * 100.0) as u32)
+}
+
+fn batch_type() -> ColumnBatchType {
+    let mut ty = ColumnBatchType::new();
+    ty.add::<Position>().add::<Velocity>().add::<Health>();
+    ty
+}
+
+fn serial() -> ColumnBatch {
+    let mut builder = batch_type().into_batch(COUNT);
+    let mut writer = builder.writer::<Position>().unwrap();
+    (0..COUNT).for_each(|i| writer.push(position(i)).unwrap());
+    let mut writer = builder.writer::<Velocity>().unwrap();
+    (0..COUNT).for_each(|i| writer.push(velocity(i)).unwrap());
+    let mut writer = builder.writer::<Health>().unwrap();
+    (0..COUNT).for_each(|i| writer.push(health(i)).unwrap());
+    builder.build().unwrap()
+}
+
+fn parallel() -> ColumnBatch {
+    let mut builder = batch_type().into_batch(COUNT);
+    let mut writers = builder.writers();
+    let mut positions = writers.take::<Position>().unwrap();
+    let mut velocities = writers.take::<Velocity>().unwrap();
+    let mut healths = writers.take::<Health>().unwrap();
+    std::thread::scope(|scope| {
+        scope.spawn(move || (0..COUNT).for_each(|i| positions.push(position(i)).unwrap()));
+        scope.spawn(move || (0..COUNT).for_each(|i| velocities.push(velocity(i)).unwrap()));
+        scope.spawn(move || (0..COUNT).for_each(|i| healths.push(health(i)).unwrap()));
+    });
+    builder.build().unwrap()
+}
+
+fn run(name: &str, build: fn() -> ColumnBatch) {
+    let start = Instant::now();
+    let batch = build();
+    let built = start.elapsed();
+    let mut world = World::new();
+    world.spawn_column_batch(batch);
+    let total = start.elapsed();
+    assert_eq!(world.len(), COUNT);
+    let checksum = world
+        .query_mut::<(&Position, &Velocity, &Health)>()
+        .into_iter()
+        .map(|(_, (p, v, h))| p.0[0] + v.0[0] + h.0 as f32)
+        .sum::<f32>();
+    println!(
+        "{name}: built in {built:?}, spawned {COUNT} entities in {total:?} (checksum {checksum})"
+    );
+}
+
+fn main() {
+    run("serial", serial);
+    run("parallel", parallel);
+}
@@ -197,12 +197,32 @@ fn derive_struct(
                     #(#fetches::release(archetype, state.#fields);)*
                 }
 
+                #[allow(unused_variables)]
+                fn filter(archetype: &::hecs::Archetype, state: Self::State, since: u32) -> bool {
+                    true #(&& #fetches::filter(archetype, state.#fields, since))*
+                }
+
                 #[allow(unused_variables, unused_mut)]
                 fn for_each_borrow(mut f: impl ::core::ops::FnMut(::core::any::TypeId, bool)) {
                     #(
                         <#fetches as ::hecs::Fetch>::for_each_borrow(&mut f);
                     )*
                 }
+
+                #[allow(unused_variables, unused_mut)]
+                fn for_each_access(mut f: impl ::core::ops::FnMut(::core::any::TypeId, ::hecs::Access)) {
+                    #(
+                        <#fetches as ::hecs::Fetch>::for_each_access(&mut f);
+                    )*
+                }
+
+                fn includes_disabled() -> bool {
+                    false #(|| <#fetches as ::hecs::Fetch>::includes_disabled())*
+                }
+
+                fn tracks_changes() -> bool {
+                    false #(|| <#fetches as ::hecs::Fetch>::tracks_changes())*
+                }
             }
         };
     })
@@ -223,7 +243,11 @@ fn derive_enum(
     let mut fetch_prepare_variants = TokenStream2::new();
     let mut fetch_execute_variants = TokenStream2::new();
     let mut fetch_release_variants = TokenStream2::new();
+    let mut fetch_filter_variants = TokenStream2::new();
     let mut fetch_for_each_borrow = TokenStream2::new();
+    let mut fetch_for_each_access = TokenStream2::new();
+    let mut fetch_includes_disabled = TokenStream2::new();
+    let mut fetch_tracks_changes = TokenStream2::new();
 
     for variant in &data.variants {
         let (fields, queries) = match variant.fields {
@@ -360,11 +384,31 @@ fn derive_enum(
             },
         });
 
+        fetch_filter_variants.extend(quote! {
+            Self::State::#ident { #(#named_fields),* } => {
+                true #(&& #fetches::filter(archetype, #named_fields, since))*
+            },
+        });
+
         fetch_for_each_borrow.extend(quote! {
             #(
                 <#fetches as ::hecs::Fetch>::for_each_borrow(&mut f);
             )*
         });
+
+        fetch_for_each_access.extend(quote! {
+            #(
+                <#fetches as ::hecs::Fetch>::for_each_access(&mut f);
+            )*
+        });
+
+        fetch_includes_disabled.extend(quote! {
+            #(|| <#fetches as ::hecs::Fetch>::includes_disabled())*
+        });
+
+        fetch_tracks_changes.extend(quote! {
+            #(|| <#fetches as ::hecs::Fetch>::tracks_changes())*
+        });
     }
 
     let dangling_constructor = if let Some(dangling_constructor) = dangling_constructor {
@@ -456,10 +500,30 @@ fn derive_enum(
                     }
                 }
 
+                #[allow(unused_variables)]
+                fn filter(archetype: &::hecs::Archetype, state: Self::State, since: u32) -> bool {
+                    match state {
+                        #fetch_filter_variants
+                    }
+                }
+
                 #[allow(unused_variables, unused_mut)]
                 fn for_each_borrow(mut f: impl ::core::ops::FnMut(::core::any::TypeId, bool)) {
                     #fetch_for_each_borrow
                 }
+
+                #[allow(unused_variables, unused_mut)]
+                fn for_each_access(mut f: impl ::core::ops::FnMut(::core::any::TypeId, ::hecs::Access)) {
+                    #fetch_for_each_access
+                }
+
+                fn includes_disabled() -> bool {
+                    false #fetch_includes_disabled
+                }
+
+                fn tracks_changes() -> bool {
+                    false #fetch_tracks_changes
+                }
             }
         };
     })
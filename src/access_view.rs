@@ -0,0 +1,91 @@
+use core::any::{type_name, TypeId};
+
+use crate::alloc::vec::Vec;
+use crate::{Access, Entity, NoSuchEntity, Query, QueryBorrow, QueryOne, World};
+
+/// A [`World`] restricted to a fixed set of component accesses, obtained from
+/// [`World::split_access`]
+///
+/// Every query is checked against the view's grant when it's constructed, and panics if it needs
+/// stronger access to any component than the view allows. Views obtained from the same call never
+/// grant conflicting access, so systems handed one each can run concurrently without borrow
+/// panics that depend on timing: a system that strays outside its view fails on every run.
+pub struct AccessView<'w> {
+    world: &'w World,
+    /// Strongest access granted to specific component types
+    listed: Vec<(TypeId, Access)>,
+    /// Strongest access granted to every other component type
+    default: Access,
+}
+
+impl<'w> AccessView<'w> {
+    /// Split `world` into views granting `reads` and `writes` to the first, and everything
+    /// compatible with that to the second
+    pub(crate) fn split(world: &'w World, reads: &[TypeId], writes: &[TypeId]) -> (Self, Self) {
+        let mut first = Vec::with_capacity(reads.len() + writes.len());
+        let mut second = Vec::with_capacity(reads.len() + writes.len());
+        for &ty in reads {
+            if !writes.contains(&ty) {
+                first.push((ty, Access::Read));
+                second.push((ty, Access::Read));
+            }
+        }
+        for &ty in writes {
+            first.push((ty, Access::Write));
+            second.push((ty, Access::Iterate));
+        }
+        (
+            Self {
+                world,
+                listed: first,
+                default: Access::Iterate,
+            },
+            Self {
+                world,
+                listed: second,
+                default: Access::Write,
+            },
+        )
+    }
+
+    /// Strongest access this view grants to the component type `ty`
+    pub fn access(&self, ty: TypeId) -> Access {
+        self.listed
+            .iter()
+            .find(|&&(x, _)| x == ty)
+            .map_or(self.default, |&(_, access)| access)
+    }
+
+    /// Whether this view grants every access `Q` needs
+    pub fn permits<Q: Query>(&self) -> bool {
+        Q::access()
+            .into_iter()
+            .all(|(ty, access)| access <= self.access(ty))
+    }
+
+    /// Query the world, as by [`World::query`]
+    ///
+    /// # Panics
+    /// Panics if `Q` needs access this view doesn't grant.
+    pub fn query<Q: Query>(&self) -> QueryBorrow<'w, Q> {
+        self.check::<Q>();
+        self.world.query()
+    }
+
+    /// Query a single entity, as by [`World::query_one`]
+    ///
+    /// # Panics
+    /// Panics if `Q` needs access this view doesn't grant.
+    pub fn query_one<Q: Query>(&self, entity: Entity) -> Result<QueryOne<'w, Q>, NoSuchEntity> {
+        self.check::<Q>();
+        self.world.query_one(entity)
+    }
+
+    fn check<Q: Query>(&self) {
+        assert!(
+            self.permits::<Q>(),
+            "query `{}` needs access this view doesn't grant",
+            type_name::<Q>()
+        );
+    }
+}
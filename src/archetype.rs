@@ -11,14 +11,16 @@ use crate::alloc::{vec, vec::Vec};
 use core::any::{type_name, TypeId};
 use core::fmt;
 use core::hash::{BuildHasher, BuildHasherDefault, Hasher};
+use core::mem;
 use core::ops::{Deref, DerefMut};
 use core::ptr::{self, NonNull};
+use core::sync::atomic::{AtomicU32, Ordering};
 
 use hashbrown::HashMap;
 
 use crate::borrow::AtomicBorrow;
 use crate::query::Fetch;
-use crate::{Access, Component, ComponentRef, Query};
+use crate::{Access, Component, ComponentRef, Disabled, Query};
 
 /// A collection of entities having the same component types
 ///
@@ -32,6 +34,10 @@ pub struct Archetype {
     entities: Box<[u32]>,
     /// One allocation per type, in the same order as `types`
     data: Box<[Data]>,
+    /// Whether this archetype contains [`Disabled`] entities
+    disabled: bool,
+    /// Copy of the owning world's change tick, at which changes are currently recorded
+    change_tick: AtomicU32,
 }
 
 impl Archetype {
@@ -57,6 +63,7 @@ impl Archetype {
         let max_align = types.first().map_or(1, |ty| ty.layout.align());
         Self::assert_type_info(&types);
         let component_count = types.len();
+        let disabled = types.iter().any(|ty| ty.id == TypeId::of::<Disabled>());
         Self {
             index: OrderedTypeIdMap::new(types.iter().enumerate().map(|(i, ty)| (ty.id, i))),
             type_ids: types.iter().map(|ty| ty.id()).collect(),
@@ -67,18 +74,25 @@ impl Archetype {
                 .map(|_| Data {
                     state: AtomicBorrow::new(),
                     storage: NonNull::new(max_align as *mut u8).unwrap(),
+                    added: AtomicU32::new(0),
+                    changed: AtomicU32::new(0),
                 })
                 .collect(),
+            disabled,
+            change_tick: AtomicU32::new(0),
         }
     }
 
     pub(crate) fn clear(&mut self) {
+        unsafe { self.clear_with(|removed, ty| (ty.drop)(removed)) }
+    }
+
+    /// Remove every entity, passing ownership of each component to `f`
+    pub(crate) unsafe fn clear_with(&mut self, mut f: impl FnMut(*mut u8, &TypeInfo)) {
         for (ty, data) in self.types.iter().zip(&*self.data) {
             for index in 0..self.len {
-                unsafe {
-                    let removed = data.storage.as_ptr().add(index as usize * ty.layout.size());
-                    (ty.drop)(removed);
-                }
+                let removed = data.storage.as_ptr().add(index as usize * ty.layout.size());
+                f(removed, ty);
             }
         }
         self.len = 0;
@@ -94,6 +108,12 @@ impl Archetype {
         self.index.contains_key(&id)
     }
 
+    /// Whether this archetype's entities are [`Disabled`]
+    #[inline]
+    pub fn is_disabled(&self) -> bool {
+        self.disabled
+    }
+
     /// Find the state index associated with `T`, if present
     pub(crate) fn get_state<T: Component>(&self) -> Option<usize> {
         self.index.get(&TypeId::of::<T>()).copied()
@@ -108,15 +128,64 @@ impl Archetype {
         }
     }
 
+    /// Find the state index associated with the type identified by `id`, if present
+    pub(crate) fn get_state_dynamic(&self, id: TypeId) -> Option<usize> {
+        self.index.get(&id).copied()
+    }
+
+    /// Get the address of the first component using an index from `get_state_dynamic`
+    pub(crate) fn get_base_dynamic(&self, state: usize) -> NonNull<u8> {
+        self.data[state].storage
+    }
+
     /// Borrow all components of a single type from these entities, if present
     ///
-    /// `T` must be a shared or unique reference to a component type.
+    /// `T` must be a shared or unique reference to a component type. The returned column
+    /// dereferences to a slice ordered like [`ids`](Self::ids) and is dynamically borrow-checked
+    /// like a query, so holding a unique column while a conflicting query runs will panic.
     ///
     /// Useful for efficient serialization.
+    ///
+    /// # Example
+    /// ```
+    /// # use hecs::*;
+    /// let mut world = World::new();
+    /// let a = world.spawn((1, true));
+    /// let b = world.spawn((2, true));
+    /// for archetype in world.archetypes() {
+    ///     let (numbers, mut flags) = match (archetype.get::<&i32>(), archetype.get::<&mut bool>()) {
+    ///         (Some(x), Some(y)) => (x, y),
+    ///         _ => continue,
+    ///     };
+    ///     let numbers: &[i32] = &numbers;
+    ///     let flags: &mut [bool] = &mut flags;
+    ///     flags.fill(false);
+    ///     let entities = world.archetype_entities(archetype).collect::<Vec<_>>();
+    ///     assert_eq!(entities, [a, b]);
+    ///     assert_eq!(numbers, [1, 2]);
+    /// }
+    /// assert!(!*world.get::<&bool>(a).unwrap());
+    /// ```
     pub fn get<'a, T: ComponentRef<'a>>(&'a self) -> Option<T::Column> {
         T::get_column(self)
     }
 
+    /// Address of the first component of the type identified by `ty`, and the distance in bytes
+    /// between consecutive components, if present
+    ///
+    /// The column holds [`len`](Self::len) components ordered like [`ids`](Self::ids). Intended for
+    /// handing component storage to foreign code, such as a plugin written in C. Obtaining the
+    /// pointer is safe, but the storage is not borrowed on the caller's behalf, so it's up to the
+    /// caller to ensure that accesses through it don't conflict with queries or other borrows, e.g.
+    /// by holding a `&mut World` throughout. The pointer is invalidated by any change to the set of
+    /// entities in this archetype: spawning into it, despawning from it, moving entities into or out
+    /// of it by inserting or removing components, or clearing the world.
+    pub fn get_base_ptr(&self, ty: TypeId) -> Option<(NonNull<u8>, usize)> {
+        let state = self.get_state_dynamic(ty)?;
+        let stride = self.types[state].layout.size();
+        Some((self.get_base_dynamic(state), stride))
+    }
+
     pub(crate) fn borrow<T: Component>(&self, state: usize) {
         assert_eq!(self.types[state].id, TypeId::of::<T>());
 
@@ -131,7 +200,19 @@ impl Archetype {
         }
     }
 
+    pub(crate) unsafe fn borrow_raw_mut(&self, state: usize) {
+        if !self.data[state].state.borrow_mut() {
+            panic!("state index {} already borrowed", state);
+        }
+    }
+
     pub(crate) fn borrow_mut<T: Component>(&self, state: usize) {
+        self.borrow_mut_untracked::<T>(state);
+        self.mark_changed(state);
+    }
+
+    /// Like `borrow_mut`, but leaves it to the caller to record any mutation
+    pub(crate) fn borrow_mut_untracked<T: Component>(&self, state: usize) {
         assert_eq!(self.types[state].id, TypeId::of::<T>());
 
         if !self.data[state].state.borrow_mut() {
@@ -157,6 +238,98 @@ impl Archetype {
         self.data[state].state.release_mut();
     }
 
+    /// Record that the column identified by `state` may have been mutated
+    #[inline]
+    pub(crate) fn mark_changed(&self, state: usize) {
+        self.data[state]
+            .changed
+            .store(self.change_tick.load(Ordering::Relaxed), Ordering::Relaxed);
+    }
+
+    /// Record subsequent changes at `tick`
+    pub(crate) fn set_change_tick(&mut self, tick: u32) {
+        *self.change_tick.get_mut() = tick;
+    }
+
+    /// Record subsequent changes at `tick`, unless a later tick was already set
+    pub(crate) fn advance_change_tick(&self, tick: u32) {
+        let _ = self
+            .change_tick
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |current| {
+                tick_is_newer(tick, current).then_some(tick)
+            });
+    }
+
+    /// Bring column ticks recorded more than [`MAX_TICK_AGE`] before `now` forward to that age
+    ///
+    /// Keeps comparisons against recent ticks accurate however long a column goes untouched, at
+    /// the cost of treating old columns as changed relative to ticks older than `MAX_TICK_AGE`.
+    pub(crate) fn clamp_ticks(&self, now: u32) {
+        let oldest = now.wrapping_sub(MAX_TICK_AGE);
+        for data in &*self.data {
+            for tick in [&data.added, &data.changed] {
+                let _ = tick.fetch_update(Ordering::Relaxed, Ordering::Relaxed, |tick| {
+                    tick_is_newer(oldest, tick).then_some(oldest)
+                });
+            }
+        }
+    }
+
+    /// Record that the column of type `ty`, if present, may hold components changed at `tick`,
+    /// unless it was already marked changed later
+    ///
+    /// Called when components move between archetypes, so that changes are not forgotten.
+    pub(crate) fn inherit_changed(&self, ty: TypeId, tick: u32) {
+        let Some(&state) = self.index.get(&ty) else {
+            return;
+        };
+        let changed = &self.data[state].changed;
+        if tick_is_newer(tick, changed.load(Ordering::Relaxed)) {
+            changed.store(tick, Ordering::Relaxed);
+        }
+    }
+
+    /// Record that components of type `ty` were inserted, if present
+    pub(crate) fn mark_added_dynamic(&self, ty: TypeId) {
+        if let Some(&state) = self.index.get(&ty) {
+            self.mark_added(state);
+        }
+    }
+
+    /// Record that components were inserted into every column
+    pub(crate) fn mark_all_added(&self) {
+        for state in 0..self.data.len() {
+            self.mark_added(state);
+        }
+    }
+
+    fn mark_added(&self, state: usize) {
+        let tick = self.change_tick.load(Ordering::Relaxed);
+        let data = &self.data[state];
+        data.added.store(tick, Ordering::Relaxed);
+        data.changed.store(tick, Ordering::Relaxed);
+    }
+
+    /// Change tick at which the column identified by `state` was last marked changed
+    pub(crate) fn changed_tick(&self, state: usize) -> u32 {
+        self.data[state].changed.load(Ordering::Relaxed)
+    }
+
+    /// Change tick at which components were last inserted into the column identified by `state`
+    pub(crate) fn added_tick(&self, state: usize) -> u32 {
+        self.data[state].added.load(Ordering::Relaxed)
+    }
+
+    /// Whether the column identified by `state` may have been mutated after `tick`
+    pub(crate) fn changed_since(&self, state: usize, tick: u32) -> bool {
+        recorded_since(self.data[state].changed.load(Ordering::Relaxed), tick)
+    }
+
+    /// Whether components were inserted into the column identified by `state` after `tick`
+    pub(crate) fn added_since(&self, state: usize, tick: u32) -> bool {
+        recorded_since(self.data[state].added.load(Ordering::Relaxed), tick)
+    }
+
     /// Number of entities in this archetype
     #[inline]
     pub fn len(&self) -> u32 {
@@ -191,6 +364,39 @@ impl Archetype {
         &self.type_ids
     }
 
+    /// Measure the memory occupied by this archetype's entities and component columns
+    ///
+    /// # Example
+    /// ```
+    /// # use hecs::*;
+    /// let mut world = World::new();
+    /// world.spawn((123u32, true));
+    /// let archetype = world.archetypes().find(|a| !a.is_empty()).unwrap();
+    /// let memory = archetype.memory_usage();
+    /// assert_eq!(memory.len, 1);
+    /// assert!(memory.capacity >= 1);
+    /// assert_eq!(memory.columns.len(), 2);
+    /// ```
+    pub fn memory_usage(&self) -> ArchetypeMemory {
+        let capacity = self.capacity() as usize;
+        let len = self.len as usize;
+        ArchetypeMemory {
+            len: self.len,
+            capacity: self.capacity(),
+            entity_bytes: capacity * mem::size_of::<u32>(),
+            columns: self
+                .types
+                .iter()
+                .map(|ty| ColumnMemory {
+                    type_id: ty.id,
+                    layout: ty.layout,
+                    used_bytes: ty.layout.size() * len,
+                    allocated_bytes: ty.layout.size() * capacity,
+                })
+                .collect(),
+        }
+    }
+
     /// Enumerate the types of the components of entities stored in this archetype.
     ///
     /// Convenient for dispatching logic which needs to be performed on sets of type ids.  For
@@ -262,9 +468,21 @@ impl Archetype {
 
     /// Increase capacity by exactly `increment`
     fn grow_exact(&mut self, increment: u32) {
+        self.set_capacity(self.entities.len() + increment as usize);
+    }
+
+    /// Release storage beyond that needed for the current entities
+    pub(crate) fn shrink_to_fit(&mut self) {
+        if self.capacity() > self.len {
+            self.set_capacity(self.len as usize);
+        }
+    }
+
+    /// Reallocate storage to fit exactly `new_cap` entities, which must be at least `len`
+    fn set_capacity(&mut self, new_cap: usize) {
+        debug_assert!(new_cap >= self.len as usize);
         let old_count = self.len as usize;
         let old_cap = self.entities.len();
-        let new_cap = self.entities.len() + increment as usize;
         let mut new_entities = vec![!0; new_cap].into_boxed_slice();
         new_entities[0..old_count].copy_from_slice(&self.entities[0..old_count]);
         self.entities = new_entities;
@@ -274,7 +492,7 @@ impl Archetype {
             .iter()
             .zip(&*self.data)
             .map(|(info, old)| {
-                let storage = if info.layout.size() == 0 {
+                let storage = if info.layout.size() == 0 || new_cap == 0 {
                     NonNull::new(info.layout.align() as *mut u8).unwrap()
                 } else {
                     let layout =
@@ -295,6 +513,8 @@ impl Archetype {
                 Data {
                     state: AtomicBorrow::new(), // &mut self guarantees no outstanding borrows
                     storage,
+                    added: AtomicU32::new(old.added.load(Ordering::Relaxed)),
+                    changed: AtomicU32::new(old.changed.load(Ordering::Relaxed)),
                 }
             })
             .collect::<Box<[_]>>();
@@ -342,16 +562,43 @@ impl Archetype {
         }
     }
 
+    /// Like `remove`, but passes ownership of each component to `f` rather than dropping it
+    ///
+    /// Returns the ID of the entity moved into `index`, if any
+    pub(crate) unsafe fn remove_with(
+        &mut self,
+        index: u32,
+        mut f: impl FnMut(*mut u8, &TypeInfo),
+    ) -> Option<u32> {
+        let last = self.len - 1;
+        for (ty, data) in self.types.iter().zip(&*self.data) {
+            let removed = data.storage.as_ptr().add(index as usize * ty.layout.size());
+            f(removed, ty);
+            if index != last {
+                let moved = data.storage.as_ptr().add(last as usize * ty.layout.size());
+                ptr::copy_nonoverlapping(moved, removed, ty.layout.size());
+            }
+        }
+        self.len = last;
+        if index != last {
+            self.entities[index as usize] = self.entities[last as usize];
+            Some(self.entities[last as usize])
+        } else {
+            None
+        }
+    }
+
     /// Returns the ID of the entity moved into `index`, if any
     pub(crate) unsafe fn move_to(
         &mut self,
         index: u32,
-        mut f: impl FnMut(*mut u8, TypeId, usize),
+        mut f: impl FnMut(*mut u8, TypeId, usize, u32),
     ) -> Option<u32> {
         let last = self.len - 1;
         for (ty, data) in self.types.iter().zip(&*self.data) {
             let moved_out = data.storage.as_ptr().add(index as usize * ty.layout.size());
-            f(moved_out, ty.id(), ty.layout().size());
+            let changed = data.changed.load(Ordering::Relaxed);
+            f(moved_out, ty.id(), ty.layout().size(), changed);
             if index != last {
                 let moved = data.storage.as_ptr().add(last as usize * ty.layout.size());
                 ptr::copy_nonoverlapping(moved, moved_out, ty.layout.size());
@@ -409,11 +656,43 @@ impl Archetype {
         }
         self.len += other.len;
         other.len = 0;
+        self.mark_all_added();
+    }
+
+    /// Move every entity to the end of `target`, returning the index of the first moved entity
+    ///
+    /// Only components whose types are present in both archetypes are moved.
+    ///
+    /// # Safety
+    /// Components of types absent from `target` must already have been dropped or moved out, and
+    /// components of types absent from `self` must be written to `target` before being accessed.
+    pub(crate) unsafe fn move_all_to(&mut self, target: &mut Archetype) -> u32 {
+        target.reserve(self.len);
+        let base = target.len;
+        for (info, src) in self.types.iter().zip(&*self.data) {
+            let state = match target.index.get(&info.id) {
+                Some(&x) => x,
+                None => continue,
+            };
+            let size = info.layout.size();
+            target.data[state]
+                .storage
+                .as_ptr()
+                .add(base as usize * size)
+                .copy_from_nonoverlapping(src.storage.as_ptr(), self.len as usize * size);
+            target.inherit_changed(info.id, src.changed.load(Ordering::Relaxed));
+        }
+        target.entities[base as usize..(base + self.len) as usize]
+            .copy_from_slice(&self.entities[..self.len as usize]);
+        target.len += self.len;
+        self.len = 0;
+        base
     }
 
     /// Raw IDs of the entities in this archetype
     ///
     /// Convertible into [`Entity`](crate::Entity)s with
+    /// [`World::archetype_entities()`](crate::World::archetype_entities) or
     /// [`World::find_entity_from_id()`](crate::World::find_entity_from_id). Useful for efficient
     /// serialization.
     #[inline]
@@ -425,7 +704,7 @@ impl Archetype {
 impl Drop for Archetype {
     fn drop(&mut self) {
         self.clear();
-        if self.entities.len() == 0 {
+        if self.entities.is_empty() {
             return;
         }
         for (info, data) in self.types.iter().zip(&*self.data) {
@@ -444,11 +723,77 @@ impl Drop for Archetype {
     }
 }
 
+/// Memory occupied by an [`Archetype`], as reported by [`Archetype::memory_usage`]
+#[derive(Debug, Clone)]
+pub struct ArchetypeMemory {
+    /// Number of entities stored
+    pub len: u32,
+    /// Number of entities that can be stored without reallocating
+    pub capacity: u32,
+    /// Bytes allocated for the IDs of stored entities
+    pub entity_bytes: usize,
+    /// Memory occupied by each component type, in the archetype's internal order
+    pub columns: Vec<ColumnMemory>,
+}
+
+impl ArchetypeMemory {
+    /// Total bytes allocated, including unused capacity
+    pub fn allocated_bytes(&self) -> usize {
+        self.entity_bytes
+            + self
+                .columns
+                .iter()
+                .map(|x| x.allocated_bytes)
+                .sum::<usize>()
+    }
+
+    /// Total bytes occupied by live components
+    pub fn used_bytes(&self) -> usize {
+        self.columns.iter().map(|x| x.used_bytes).sum()
+    }
+}
+
+/// Memory occupied by components of a single type
+#[derive(Debug, Copy, Clone)]
+pub struct ColumnMemory {
+    /// The component type
+    pub type_id: TypeId,
+    /// Layout of a single component
+    pub layout: Layout,
+    /// Bytes occupied by live components
+    pub used_bytes: usize,
+    /// Bytes allocated, including unused capacity
+    pub allocated_bytes: usize,
+}
+
 struct Data {
     state: AtomicBorrow,
     storage: NonNull<u8>,
+    /// Change tick at which components were last inserted into this column
+    added: AtomicU32,
+    /// Change tick at which this column was last borrowed uniquely
+    changed: AtomicU32,
+}
+
+/// Whether `tick` was recorded after `since`, tolerating wrapping
+fn tick_is_newer(tick: u32, since: u32) -> bool {
+    (tick.wrapping_sub(since) as i32) > 0
 }
 
+/// Like `tick_is_newer`, but `since` of 0 precedes every tick, as when no tick was supplied
+///
+/// A genuine tick of 0, reached by wrapping, thus conservatively reports every column as changed.
+fn recorded_since(tick: u32, since: u32) -> bool {
+    since == 0 || tick_is_newer(tick, since)
+}
+
+/// Age beyond which column ticks are clamped by [`Archetype::clamp_ticks`]
+pub(crate) const MAX_TICK_AGE: u32 = 1 << 30;
+
+/// Number of change tick increments between clamps, small enough that clamped ticks never become
+/// old enough to appear newer than the current tick
+pub(crate) const TICK_CLAMP_INTERVAL: u32 = 1 << 20;
+
 /// A hasher optimized for hashing a single TypeId.
 ///
 /// TypeId is already thoroughly hashed, so there's no reason to hash it again.
@@ -569,6 +914,19 @@ impl TypeInfo {
         self.layout
     }
 
+    /// The name of this component type, if known
+    ///
+    /// Only retained in debug builds.
+    #[cfg(debug_assertions)]
+    pub(crate) fn type_name(&self) -> Option<&'static str> {
+        Some(self.type_name)
+    }
+
+    #[cfg(not(debug_assertions))]
+    pub(crate) fn type_name(&self) -> Option<&'static str> {
+        None
+    }
+
     /// Directly call the destructor on a pointer to data of this component type.
     ///
     /// # Safety
@@ -1,10 +1,9 @@
-use crate::alloc::collections::BinaryHeap;
-use core::{any::TypeId, fmt, mem::MaybeUninit, slice};
+use crate::alloc::{boxed::Box, collections::BinaryHeap, vec::Vec};
+use core::alloc::Layout;
+use core::any::TypeId;
+use core::{fmt, marker::PhantomData, mem::MaybeUninit, ptr, ptr::NonNull, slice};
 
-use crate::{
-    archetype::{TypeIdMap, TypeInfo},
-    Archetype, Component,
-};
+use crate::{archetype::TypeInfo, Archetype, Component};
 
 /// A collection of component types
 #[derive(Debug, Clone, Default)]
@@ -30,11 +29,24 @@ impl ColumnBatchType {
         self
     }
 
+    /// [Self::add_dynamic()] with type information assembled from parts, e.g. by a registry of
+    /// component types known only at runtime
+    ///
+    /// See [`TypeInfo::from_parts`]. Components written to the column are dropped with `drop` if
+    /// the batch is abandoned before being spawned.
+    pub fn add_erased(
+        &mut self,
+        id: TypeId,
+        layout: Layout,
+        drop: unsafe fn(*mut u8),
+    ) -> &mut Self {
+        self.add_dynamic(TypeInfo::from_parts(id, layout, drop))
+    }
+
     /// Construct a [`ColumnBatchBuilder`] for *exactly* `size` entities with these components
     pub fn into_batch(self, size: u32) -> ColumnBatchBuilder {
-        let mut types = self.types.into_sorted_vec();
-        types.dedup();
-        let fill = TypeIdMap::with_capacity_and_hasher(types.len(), Default::default());
+        let types = self.sorted_types();
+        let fill = (0..types.len()).map(|_| 0).collect();
         let mut arch = Archetype::new(types);
         arch.reserve(size);
         ColumnBatchBuilder {
@@ -45,15 +57,26 @@ impl ColumnBatchType {
     }
 }
 
+impl ColumnBatchType {
+    /// The distinct component types, in archetype order
+    pub(crate) fn sorted_types(&self) -> Vec<TypeInfo> {
+        let mut types = self.types.clone().into_sorted_vec();
+        types.dedup();
+        types
+    }
+}
+
 /// An incomplete collection of component data for entities with the same component types
 pub struct ColumnBatchBuilder {
-    /// Number of components written so far for each component type
-    fill: TypeIdMap<u32>,
+    /// Number of components written so far for each component type, in archetype order
+    fill: Box<[u32]>,
     target_fill: u32,
     pub(crate) archetype: Option<Archetype>,
 }
 
+#[cfg(not(feature = "thread-local"))]
 unsafe impl Send for ColumnBatchBuilder {}
+#[cfg(not(feature = "thread-local"))]
 unsafe impl Sync for ColumnBatchBuilder {}
 
 impl ColumnBatchBuilder {
@@ -64,28 +87,52 @@ impl ColumnBatchBuilder {
 
     /// Get a handle for inserting `T` components if `T` was in the [`ColumnBatchType`]
     pub fn writer<T: Component>(&mut self) -> Option<BatchWriter<'_, T>> {
-        let archetype = self.archetype.as_mut().unwrap();
-        let state = archetype.get_state::<T>()?;
-        let base = archetype.get_base::<T>(state);
-        Some(BatchWriter {
-            fill: self.fill.entry(TypeId::of::<T>()).or_insert(0),
-            storage: unsafe {
-                slice::from_raw_parts_mut(base.as_ptr().cast(), self.target_fill as usize)
-                    .iter_mut()
-            },
-        })
+        self.writers().take::<T>()
+    }
+
+    /// Get a handle for inserting components of the type identified by `id` if it was in the
+    /// [`ColumnBatchType`]
+    pub fn writer_erased(&mut self, id: TypeId) -> Option<ErasedBatchWriter<'_>> {
+        self.writers().take_erased(id)
+    }
+
+    /// Get handles for inserting components of several types at once
+    ///
+    /// Each [`BatchWriter`] accesses a distinct column, so writers can be sent to different threads
+    /// to fill columns concurrently.
+    ///
+    /// # Example
+    /// ```
+    /// # use hecs::*;
+    /// let mut ty = ColumnBatchType::new();
+    /// ty.add::<i32>().add::<bool>();
+    /// let mut builder = ty.into_batch(1_000);
+    /// let mut writers = builder.writers();
+    /// let mut numbers = writers.take::<i32>().unwrap();
+    /// let mut flags = writers.take::<bool>().unwrap();
+    /// std::thread::scope(|scope| {
+    ///     scope.spawn(move || (0..1_000).for_each(|i| numbers.push(i).unwrap()));
+    ///     scope.spawn(move || (0..1_000).for_each(|i| flags.push(i % 2 == 0).unwrap()));
+    /// });
+    /// let mut world = World::new();
+    /// world.spawn_column_batch(builder.build().unwrap());
+    /// assert_eq!(world.len(), 1_000);
+    /// ```
+    pub fn writers(&mut self) -> BatchWriters<'_> {
+        let archetype = self.archetype.as_ref().unwrap();
+        BatchWriters {
+            archetype,
+            fill: self.fill.iter_mut().map(Some).collect(),
+            target_fill: self.target_fill,
+        }
     }
 
     /// Finish the batch, failing if any components are missing
     pub fn build(mut self) -> Result<ColumnBatch, BatchIncomplete> {
-        let mut archetype = self.archetype.take().unwrap();
-        if archetype
-            .types()
-            .iter()
-            .any(|ty| self.fill.get(&ty.id()).copied().unwrap_or(0) != self.target_fill)
-        {
+        if self.fill.iter().any(|&fill| fill != self.target_fill) {
             return Err(BatchIncomplete { _opaque: () });
         }
+        let mut archetype = self.archetype.take().unwrap();
         unsafe {
             archetype.set_len(self.target_fill);
         }
@@ -96,12 +143,11 @@ impl ColumnBatchBuilder {
 impl Drop for ColumnBatchBuilder {
     fn drop(&mut self) {
         if let Some(archetype) = self.archetype.take() {
-            for ty in archetype.types() {
-                let fill = self.fill.get(&ty.id()).copied().unwrap_or(0);
+            for (ty, &fill) in archetype.types().iter().zip(&*self.fill) {
                 unsafe {
                     let base = archetype.get_dynamic(ty.id(), 0, 0).unwrap();
                     for i in 0..fill {
-                        base.as_ptr().add(i as usize).drop_in_place()
+                        ty.drop(base.as_ptr().add(i as usize * ty.layout().size()));
                     }
                 }
             }
@@ -112,6 +158,55 @@ impl Drop for ColumnBatchBuilder {
 /// A collection of component data for entities with the same component types
 pub struct ColumnBatch(pub(crate) Archetype);
 
+/// Handles for appending components of distinct types to a [`ColumnBatchBuilder`]
+///
+/// Constructed by [`ColumnBatchBuilder::writers`].
+pub struct BatchWriters<'a> {
+    archetype: &'a Archetype,
+    /// Fill counters of columns whose writers haven't been taken, in archetype order
+    fill: Vec<Option<&'a mut u32>>,
+    target_fill: u32,
+}
+
+impl<'a> BatchWriters<'a> {
+    /// Get the handle for inserting `T` components
+    ///
+    /// Returns `None` if `T` wasn't in the [`ColumnBatchType`], or if its writer was already taken.
+    pub fn take<T: Component>(&mut self) -> Option<BatchWriter<'a, T>> {
+        let state = self.archetype.get_state::<T>()?;
+        let fill = self.fill[state].take()?;
+        let base = self.archetype.get_base::<T>(state);
+        Some(BatchWriter {
+            storage: unsafe {
+                slice::from_raw_parts_mut(base.as_ptr().cast(), self.target_fill as usize)
+                    [*fill as usize..]
+                    .iter_mut()
+            },
+            fill,
+        })
+    }
+
+    /// Get the handle for inserting components of the type identified by `id`
+    ///
+    /// Returns `None` under the same conditions as [`take`](Self::take).
+    pub fn take_erased(&mut self, id: TypeId) -> Option<ErasedBatchWriter<'a>> {
+        let state = self.archetype.get_state_dynamic(id)?;
+        let fill = self.fill[state].take()?;
+        Some(ErasedBatchWriter {
+            ty: self.archetype.types()[state],
+            base: self.archetype.get_base_dynamic(state),
+            fill,
+            target_fill: self.target_fill,
+            _marker: PhantomData,
+        })
+    }
+}
+
+#[cfg(not(feature = "thread-local"))]
+unsafe impl Send for BatchWriters<'_> {}
+#[cfg(not(feature = "thread-local"))]
+unsafe impl Sync for BatchWriters<'_> {}
+
 /// Handle for appending components
 pub struct BatchWriter<'a, T> {
     fill: &'a mut u32,
@@ -137,6 +232,55 @@ impl<T> BatchWriter<'_, T> {
     }
 }
 
+/// Handle for appending components of a type identified at runtime
+///
+/// Constructed by [`ColumnBatchBuilder::writer_erased`] or [`BatchWriters::take_erased`].
+pub struct ErasedBatchWriter<'a> {
+    ty: TypeInfo,
+    base: NonNull<u8>,
+    fill: &'a mut u32,
+    target_fill: u32,
+    _marker: PhantomData<&'a mut [u8]>,
+}
+
+impl ErasedBatchWriter<'_> {
+    /// Move a component into the batch if there's space remaining, returning whether it was moved
+    ///
+    /// # Safety
+    ///
+    /// `component` must point to a valid value of the type this writer was obtained for. If this
+    /// returns `true`, ownership of the value passes to the batch, so the caller must not use or
+    /// drop it afterwards.
+    pub unsafe fn push(&mut self, component: *mut u8) -> bool {
+        if *self.fill == self.target_fill {
+            return false;
+        }
+        let size = self.ty.layout().size();
+        ptr::copy_nonoverlapping(
+            component,
+            self.base.as_ptr().add(*self.fill as usize * size),
+            size,
+        );
+        *self.fill += 1;
+        true
+    }
+
+    /// How many components have been added so far
+    pub fn fill(&self) -> u32 {
+        *self.fill
+    }
+
+    /// Type of the components accepted by this writer
+    pub fn type_info(&self) -> TypeInfo {
+        self.ty
+    }
+}
+
+#[cfg(not(feature = "thread-local"))]
+unsafe impl Send for ErasedBatchWriter<'_> {}
+#[cfg(not(feature = "thread-local"))]
+unsafe impl Sync for ErasedBatchWriter<'_> {}
+
 /// Error indicating that a [`ColumnBatchBuilder`] was missing components
 #[derive(Debug, Clone, Eq, PartialEq, Hash)]
 pub struct BatchIncomplete {
@@ -164,4 +308,105 @@ mod tests {
         let mut writer = builder.writer::<usize>().unwrap();
         assert!(writer.push(42).is_err());
     }
+
+    #[test]
+    fn parallel_writers() {
+        let mut types = ColumnBatchType::new();
+        types.add::<u32>().add::<u64>().add::<bool>();
+        let mut builder = types.into_batch(100);
+        {
+            let mut writers = builder.writers();
+            let mut a = writers.take::<u32>().unwrap();
+            let mut b = writers.take::<u64>().unwrap();
+            assert!(writers.take::<u32>().is_none());
+            assert!(writers.take::<i8>().is_none());
+            std::thread::scope(|scope| {
+                scope.spawn(move || (0..100).for_each(|i| a.push(i).unwrap()));
+                scope.spawn(move || (0..100).for_each(|i| b.push(i).unwrap()));
+            });
+        }
+        let mut writer = builder.writer::<bool>().unwrap();
+        (0..99).for_each(|i| writer.push(i % 2 == 0).unwrap());
+        assert!(builder.build().is_err());
+    }
+
+    #[test]
+    fn drop_partial() {
+        use alloc::sync::Arc;
+        let marker = Arc::new(());
+        let mut types = ColumnBatchType::new();
+        types.add::<Arc<()>>();
+        let mut builder = types.into_batch(4);
+        let mut writer = builder.writer::<Arc<()>>().unwrap();
+        writer.push(marker.clone()).unwrap();
+        writer.push(marker.clone()).unwrap();
+        assert_eq!(Arc::strong_count(&marker), 3);
+        assert!(builder.build().is_err());
+        assert_eq!(Arc::strong_count(&marker), 1);
+    }
+
+    fn erased_arc_type() -> ColumnBatchType {
+        unsafe fn drop_arc(ptr: *mut u8) {
+            ptr.cast::<alloc::sync::Arc<()>>().drop_in_place();
+        }
+        let mut types = ColumnBatchType::new();
+        types
+            .add_erased(
+                TypeId::of::<alloc::sync::Arc<()>>(),
+                Layout::new::<alloc::sync::Arc<()>>(),
+                drop_arc,
+            )
+            .add::<u32>();
+        types
+    }
+
+    #[test]
+    fn erased_writer() {
+        use alloc::sync::Arc;
+        let marker = Arc::new(());
+        let mut builder = erased_arc_type().into_batch(2);
+        assert!(builder.writer_erased(TypeId::of::<u64>()).is_none());
+        let mut writers = builder.writers();
+        let mut erased = writers.take_erased(TypeId::of::<Arc<()>>()).unwrap();
+        assert!(writers.take::<Arc<()>>().is_none());
+        assert_eq!(erased.type_info().id(), TypeId::of::<Arc<()>>());
+        for _ in 0..2 {
+            let mut x = core::mem::ManuallyDrop::new(marker.clone());
+            assert!(unsafe { erased.push((&mut *x as *mut Arc<()>).cast()) });
+        }
+        let mut x = marker.clone();
+        assert!(!unsafe { erased.push((&mut x as *mut Arc<()>).cast()) });
+        drop(x);
+        assert_eq!(erased.fill(), 2);
+        let mut typed = writers.take::<u32>().unwrap();
+        typed.push(1).unwrap();
+        typed.push(2).unwrap();
+        assert_eq!(Arc::strong_count(&marker), 3);
+
+        let mut world = crate::World::new();
+        let entities = world
+            .spawn_column_batch(builder.build().unwrap())
+            .collect::<Vec<_>>();
+        assert!(Arc::ptr_eq(
+            &world.get::<&Arc<()>>(entities[1]).unwrap(),
+            &marker
+        ));
+        world.clear();
+        assert_eq!(Arc::strong_count(&marker), 1);
+    }
+
+    #[test]
+    fn erased_drop_partial() {
+        use alloc::sync::Arc;
+        let marker = Arc::new(());
+        let mut builder = erased_arc_type().into_batch(3);
+        let mut erased = builder.writer_erased(TypeId::of::<Arc<()>>()).unwrap();
+        let mut x = core::mem::ManuallyDrop::new(marker.clone());
+        assert!(unsafe { erased.push((&mut *x as *mut Arc<()>).cast()) });
+        builder.writer::<u32>().unwrap().push(1).unwrap();
+        assert_eq!(Arc::strong_count(&marker), 2);
+        // Abandoned with a partially filled erased column and a partially filled typed column
+        assert!(builder.build().is_err());
+        assert_eq!(Arc::strong_count(&marker), 1);
+    }
 }
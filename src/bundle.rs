@@ -134,6 +134,11 @@ impl MissingComponent {
     pub fn new<T: Component>() -> Self {
         Self(type_name::<T>())
     }
+
+    /// The [`type_name`] of the missing component
+    pub fn type_name(&self) -> &'static str {
+        self.0
+    }
 }
 
 impl fmt::Display for MissingComponent {
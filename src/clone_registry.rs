@@ -0,0 +1,92 @@
+use core::any::TypeId;
+use core::fmt;
+
+use crate::archetype::TypeIdMap;
+use crate::{Archetype, ColumnBatchBuilder, Component, TypeInfo};
+
+/// A set of component types that [`World::clone_with`](crate::World::clone_with) can copy
+///
+/// # Example
+/// ```
+/// # use hecs::*;
+/// let mut registry = CloneRegistry::new();
+/// registry.register::<i32>().register::<String>();
+///
+/// let mut world = World::new();
+/// let a = world.spawn((123, String::from("abc")));
+/// let b = world.spawn((456, 'x'));
+/// let cloned = world.clone_with(&registry);
+/// assert_eq!(*cloned.get::<&String>(a).unwrap(), "abc");
+/// assert_eq!(*cloned.get::<&i32>(b).unwrap(), 456);
+/// // Unregistered components are omitted
+/// assert!(cloned.get::<&char>(b).is_err());
+/// ```
+#[derive(Default)]
+pub struct CloneRegistry {
+    entries: TypeIdMap<CloneEntry>,
+}
+
+#[derive(Copy, Clone)]
+pub(crate) struct CloneEntry {
+    pub(crate) info: TypeInfo,
+    /// Push a clone of every component in the archetype's column into the batch
+    pub(crate) clone: fn(&Archetype, &mut ColumnBatchBuilder),
+}
+
+impl CloneRegistry {
+    /// Create a registry containing no component types
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Allow `T` components to be cloned
+    pub fn register<T: Component + Clone>(&mut self) -> &mut Self {
+        fn clone<T: Component + Clone>(src: &Archetype, dst: &mut ColumnBatchBuilder) {
+            let column = src.get::<&T>().unwrap();
+            let mut writer = dst.writer::<T>().unwrap();
+            for component in &*column {
+                if writer.push(component.clone()).is_err() {
+                    unreachable!("batch sized to match archetype");
+                }
+            }
+        }
+
+        self.entries.insert(
+            TypeId::of::<T>(),
+            CloneEntry {
+                info: TypeInfo::of::<T>(),
+                clone: clone::<T>,
+            },
+        );
+        self
+    }
+
+    /// Whether `T` components can be cloned
+    pub fn contains<T: Component>(&self) -> bool {
+        self.entries.contains_key(&TypeId::of::<T>())
+    }
+
+    pub(crate) fn get(&self, id: TypeId) -> Option<&CloneEntry> {
+        self.entries.get(&id)
+    }
+}
+
+/// Error indicating that a world contained a component type absent from a [`CloneRegistry`]
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub struct UnregisteredComponent(pub(crate) TypeId);
+
+impl UnregisteredComponent {
+    /// The unregistered component type
+    pub fn type_id(&self) -> TypeId {
+        self.0
+    }
+}
+
+impl fmt::Display for UnregisteredComponent {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.pad("component type not registered for cloning")
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for UnregisteredComponent {}
@@ -7,6 +7,7 @@
 
 use core::any::TypeId;
 use core::mem;
+use core::num::NonZeroU32;
 use core::ops::Range;
 use core::ptr::{self, NonNull};
 
@@ -37,6 +38,10 @@ pub struct CommandBuffer {
     cursor: usize,
     components: Vec<ComponentInfo>,
     ids: Vec<TypeId>,
+    /// Component types removed by `Cmd::RemoveDynamic`
+    removed: Vec<TypeId>,
+    /// Number of placeholders handed out by `reserve_entity`
+    placeholders: u32,
 }
 
 impl CommandBuffer {
@@ -73,7 +78,11 @@ impl CommandBuffer {
 
         let addr = self.storage.as_ptr().add(offset);
         ptr::copy_nonoverlapping(ptr, addr, ty.layout().size());
-        self.components.push(ComponentInfo { ty, offset });
+        self.components.push(ComponentInfo {
+            ty,
+            offset,
+            map: None,
+        });
         self.cursor = end;
     }
 
@@ -101,6 +110,53 @@ impl CommandBuffer {
         self.insert(entity, (component,));
     }
 
+    /// Add `component`, which may hold placeholders from [`reserve_entity`](Self::reserve_entity),
+    /// to `entity`
+    ///
+    /// Like [`insert_one`](Self::insert_one), but before any command is applied,
+    /// [`run_on`](Self::run_on) rewrites the handles held by `component` that are placeholders
+    /// to refer to the entities they were resolved to.
+    pub fn insert_mapped<T: MapEntities>(&mut self, entity: Entity, component: T) {
+        self.insert_one(entity, component);
+        self.components.last_mut().unwrap().map = Some(map_erased::<T>);
+    }
+
+    /// Reserve a placeholder handle for an entity to be spawned when the buffer is run
+    ///
+    /// The placeholder can be passed to this buffer's other methods, or stored in components
+    /// recorded with [`insert_mapped`](Self::insert_mapped), and every such use refers to the
+    /// same entity once [`run_on`](Self::run_on) resolves it. The entity is spawned even if no
+    /// components are recorded for it. Placeholders are meaningless outside of the buffer that
+    /// reserved them, or that buffer's [`merge`](Self::merge) target.
+    ///
+    /// # Example
+    /// ```
+    /// # use hecs::*;
+    /// struct ChildOf(Entity);
+    ///
+    /// impl MapEntities for ChildOf {
+    ///     fn map_entities(&mut self, f: &mut dyn FnMut(Entity) -> Entity) {
+    ///         self.0 = f(self.0);
+    ///     }
+    /// }
+    ///
+    /// let mut cmd = CommandBuffer::new();
+    /// let parent = cmd.reserve_entity();
+    /// let child = cmd.reserve_entity();
+    /// cmd.insert_mapped(child, ChildOf(parent));
+    /// cmd.insert_one(parent, "parent");
+    ///
+    /// let mut world = World::new();
+    /// cmd.run_on(&mut world);
+    /// let parent = world.query_mut::<&ChildOf>().into_iter().next().unwrap().1 .0;
+    /// assert_eq!(*world.get::<&&str>(parent).unwrap(), "parent");
+    /// ```
+    pub fn reserve_entity(&mut self) -> Entity {
+        let index = self.placeholders;
+        self.placeholders += 1;
+        placeholder(index)
+    }
+
     /// Remove components from `entity` if they exist
     ///
     /// When removing a single component, see [`remove_one`](Self::remove_one) for convenience.
@@ -108,8 +164,12 @@ impl CommandBuffer {
         fn remove_bundle_and_ignore_result<T: Bundle + 'static>(world: &mut World, ents: Entity) {
             let _ = world.remove::<T>(ents);
         }
+        fn ids<T: Bundle + 'static>() -> Vec<TypeId> {
+            T::with_static_ids(|ids| ids.to_vec())
+        }
         self.cmds.push(Cmd::Remove(RemovedComps {
             remove: remove_bundle_and_ignore_result::<T>,
+            ids: ids::<T>,
             entity: ent,
         }));
     }
@@ -142,8 +202,59 @@ impl CommandBuffer {
         }));
     }
 
+    /// Append the commands recorded in `other` after those recorded in `self`
+    ///
+    /// Useful for combining buffers recorded in parallel, e.g. by different threads, so they can be
+    /// applied with a single [`run_on`](Self::run_on). Entities reserved with
+    /// [`World::reserve_entity`] are valid across buffers, so commands referring to them behave
+    /// the same after merging.
+    pub fn merge(&mut self, mut other: CommandBuffer) {
+        // Renumber `other`'s placeholders to follow our own
+        let (offset, count) = (self.placeholders, other.placeholders);
+        other.map_entities(&mut |entity| {
+            placeholder_index(entity, count).map_or(entity, |i| placeholder(offset + i))
+        });
+        self.placeholders += count;
+
+        let base = self.components.len();
+        for info in &other.components {
+            unsafe {
+                self.add_inner(other.storage.as_ptr().add(info.offset), info.ty);
+            }
+            self.components.last_mut().unwrap().map = info.map;
+        }
+        // Ownership of the components has been transferred to `self`
+        other.components.clear();
+        let removed_base = self.removed.len();
+        self.removed.append(&mut other.removed);
+        self.cmds.extend(other.cmds.drain(..).map(|cmd| match cmd {
+            Cmd::SpawnOrInsert(mut entity) => {
+                entity.components = entity.components.start + base..entity.components.end + base;
+                Cmd::SpawnOrInsert(entity)
+            }
+            Cmd::RemoveDynamic { entity, ids } => Cmd::RemoveDynamic {
+                entity,
+                ids: ids.start + removed_base..ids.end + removed_base,
+            },
+            cmd => cmd,
+        }));
+    }
+
     /// Run recorded commands on `world`, clearing the command buffer
+    ///
+    /// Commands are applied in the order they were recorded, so the result is the same as if each
+    /// had been applied to `world` directly at the time of recording.
     pub fn run_on(&mut self, world: &mut World) {
+        if self.placeholders != 0 {
+            let resolved = (0..self.placeholders)
+                .map(|_| world.reserve_entity())
+                .collect::<Vec<_>>();
+            let count = self.placeholders;
+            self.map_entities(&mut |entity| {
+                placeholder_index(entity, count).map_or(entity, |i| resolved[i as usize])
+            });
+        }
+
         for i in 0..self.cmds.len() {
             match mem::replace(&mut self.cmds[i], Cmd::Despawn(Entity::DANGLING)) {
                 Cmd::SpawnOrInsert(entity) => {
@@ -161,6 +272,16 @@ impl CommandBuffer {
                 Cmd::Remove(remove) => {
                     (remove.remove)(world, remove.entity);
                 }
+                Cmd::RemoveDynamic { entity, ids } => {
+                    // As with `World::remove`, nothing is removed unless every component is present
+                    let ids = &self.removed[ids];
+                    let present = world.entity(entity).map_or(false, |e| {
+                        ids.iter().all(|&id| e.component_types().any(|x| x == id))
+                    });
+                    if present {
+                        world.remove_dynamic(entity, ids).unwrap();
+                    }
+                }
                 Cmd::Despawn(entity) => {
                     let _ = world.despawn(entity);
                 }
@@ -172,6 +293,30 @@ impl CommandBuffer {
         self.clear();
     }
 
+    /// Rewrite every recorded entity handle, including those held by components recorded with
+    /// `insert_mapped`
+    fn map_entities(&mut self, f: &mut dyn FnMut(Entity) -> Entity) {
+        for info in &self.components {
+            if let Some(map) = info.map {
+                unsafe {
+                    map(self.storage.as_ptr().add(info.offset), f);
+                }
+            }
+        }
+        for cmd in &mut self.cmds {
+            match cmd {
+                Cmd::SpawnOrInsert(EntityIndex {
+                    entity: Some(entity),
+                    ..
+                })
+                | Cmd::Remove(RemovedComps { entity, .. })
+                | Cmd::RemoveDynamic { entity, .. }
+                | Cmd::Despawn(entity) => *entity = f(*entity),
+                Cmd::SpawnOrInsert(EntityIndex { entity: None, .. }) => {}
+            }
+        }
+    }
+
     fn build(&mut self, components: Range<usize>) -> RecordedEntity<'_> {
         self.ids.clear();
         self.ids.extend(
@@ -195,10 +340,98 @@ impl CommandBuffer {
             }
         }
         self.cmds.clear();
+        self.removed.clear();
+        self.placeholders = 0;
     }
 }
 
+// Access for `serialize::column`
+#[cfg(feature = "column-serialize")]
+impl CommandBuffer {
+    /// Like `remove`, for component types identified at runtime
+    pub(crate) fn remove_dynamic(&mut self, entity: Entity, ids: &[TypeId]) {
+        let start = self.removed.len();
+        self.removed.extend_from_slice(ids);
+        self.cmds.push(Cmd::RemoveDynamic {
+            entity,
+            ids: start..self.removed.len(),
+        });
+    }
+
+    /// Number of placeholders handed out by [`reserve_entity`](Self::reserve_entity)
+    pub(crate) fn placeholders(&self) -> u32 {
+        self.placeholders
+    }
+
+    /// Reserve placeholders until `count` have been handed out
+    pub(crate) fn reserve_placeholders(&mut self, count: u32) {
+        self.placeholders = self.placeholders.max(count);
+    }
+
+    /// The recorded commands, in order
+    pub(crate) fn commands(&self) -> impl Iterator<Item = CommandView<'_>> + '_ {
+        self.cmds.iter().map(move |cmd| match *cmd {
+            Cmd::SpawnOrInsert(ref index) => {
+                let components = RecordedComponents {
+                    buffer: self,
+                    components: &self.components[index.components.clone()],
+                };
+                match index.entity {
+                    Some(entity) => CommandView::Insert(entity, components),
+                    None => CommandView::Spawn(components),
+                }
+            }
+            Cmd::Remove(ref remove) => CommandView::Remove(remove.entity, (remove.ids)()),
+            Cmd::RemoveDynamic { entity, ref ids } => {
+                CommandView::Remove(entity, self.removed[ids.clone()].to_vec())
+            }
+            Cmd::Despawn(entity) => CommandView::Despawn(entity),
+        })
+    }
+
+    /// Set the entity handle rewriting function of each component of the most recent spawn or
+    /// insert command to `map` of its type
+    pub(crate) fn set_maps(&mut self, map: impl Fn(TypeId) -> Option<MapFn>) {
+        if let Some(Cmd::SpawnOrInsert(index)) = self.cmds.last() {
+            for info in &mut self.components[index.components.clone()] {
+                info.map = map(info.ty.id());
+            }
+        }
+    }
+}
+
+/// A command recorded by a [`CommandBuffer`], as exposed for serialization
+#[cfg(feature = "column-serialize")]
+pub(crate) enum CommandView<'a> {
+    Spawn(RecordedComponents<'a>),
+    Insert(Entity, RecordedComponents<'a>),
+    /// Components to remove, all of which must be present
+    Remove(Entity, Vec<TypeId>),
+    Despawn(Entity),
+}
+
+/// The components of a spawn or insert command recorded by a [`CommandBuffer`]
+#[cfg(feature = "column-serialize")]
+pub(crate) struct RecordedComponents<'a> {
+    buffer: &'a CommandBuffer,
+    components: &'a [ComponentInfo],
+}
+
+#[cfg(feature = "column-serialize")]
+impl<'a> RecordedComponents<'a> {
+    /// Type and address of each component, sorted by type
+    pub(crate) fn iter(&self) -> impl ExactSizeIterator<Item = (TypeId, *const u8)> + 'a {
+        let storage = self.buffer.storage.as_ptr();
+        self.components.iter().map(move |info| {
+            (info.ty.id(), unsafe { storage.add(info.offset) }
+                as *const u8)
+        })
+    }
+}
+
+#[cfg(not(feature = "thread-local"))]
 unsafe impl Send for CommandBuffer {}
+#[cfg(not(feature = "thread-local"))]
 unsafe impl Sync for CommandBuffer {}
 
 impl Drop for CommandBuffer {
@@ -222,6 +455,8 @@ impl Default for CommandBuffer {
             cursor: 0,
             components: Vec::new(),
             ids: Vec::new(),
+            removed: Vec::new(),
+            placeholders: 0,
         }
     }
 }
@@ -267,13 +502,47 @@ impl Drop for RecordedEntity<'_> {
     }
 }
 
+/// A component holding [`Entity`] handles, which may be placeholders from
+/// [`CommandBuffer::reserve_entity`]
+///
+/// See [`CommandBuffer::insert_mapped`].
+pub trait MapEntities: Component {
+    /// Replace every [`Entity`] handle held by `self` with the result of passing it to `f`
+    fn map_entities(&mut self, f: &mut dyn FnMut(Entity) -> Entity);
+}
+
+pub(crate) unsafe fn map_erased<T: MapEntities>(ptr: *mut u8, f: &mut dyn FnMut(Entity) -> Entity) {
+    (*ptr.cast::<T>()).map_entities(f);
+}
+
+/// Placeholder handles count down from just below [`Entity::DANGLING`]
+fn placeholder(index: u32) -> Entity {
+    Entity {
+        id: u32::MAX - 1 - index,
+        generation: NonZeroU32::new(u32::MAX).unwrap(),
+    }
+}
+
+/// Index of the placeholder `entity`, if it's one of the first `count`
+fn placeholder_index(entity: Entity, count: u32) -> Option<u32> {
+    if entity.generation.get() != u32::MAX || entity.id == u32::MAX {
+        return None;
+    }
+    let index = u32::MAX - 1 - entity.id;
+    (index < count).then_some(index)
+}
+
 /// Data required to store components and their offset  
 struct ComponentInfo {
     ty: TypeInfo,
     // Position in 'storage'
     offset: usize,
+    /// Rewrites the entity handles held by the component, if recorded by `insert_mapped`
+    map: Option<MapFn>,
 }
 
+pub(crate) type MapFn = unsafe fn(*mut u8, &mut dyn FnMut(Entity) -> Entity);
+
 /// Data of buffered 'entity' and its relative position in component data
 struct EntityIndex {
     entity: Option<Entity>,
@@ -287,6 +556,9 @@ struct EntityIndex {
 /// Data required to remove components from 'entity'
 struct RemovedComps {
     remove: fn(&mut World, Entity),
+    /// Types of the removed components, for serialization
+    #[cfg_attr(not(feature = "column-serialize"), allow(dead_code))]
+    ids: fn() -> Vec<TypeId>,
     entity: Entity,
 }
 
@@ -294,6 +566,12 @@ struct RemovedComps {
 enum Cmd {
     SpawnOrInsert(EntityIndex),
     Remove(RemovedComps),
+    /// Remove the components whose types are in `CommandBuffer::removed[ids]`
+    #[cfg_attr(not(feature = "column-serialize"), allow(dead_code))]
+    RemoveDynamic {
+        entity: Entity,
+        ids: Range<usize>,
+    },
     Despawn(Entity),
 }
 
@@ -367,4 +645,174 @@ mod tests {
         cmd.run_on(&mut world);
         assert_eq!(*world.get::<&i32>(a).unwrap(), 42);
     }
+
+    #[test]
+    fn despawn_reserved() {
+        let mut world = World::new();
+        let a = world.reserve_entity();
+        let b = world.reserve_entity();
+        let mut cmd = CommandBuffer::new();
+        cmd.insert(a, (1i32, true));
+        cmd.insert(b, (2i32,));
+        cmd.despawn(a);
+        cmd.run_on(&mut world);
+        assert!(!world.contains(a));
+        assert_eq!(*world.get::<&i32>(b).unwrap(), 2);
+        assert_eq!(world.len(), 1);
+    }
+
+    #[test]
+    fn despawn_then_insert() {
+        let mut world = World::new();
+        let a = world.spawn((1i32,));
+        let mut cmd = CommandBuffer::new();
+        cmd.despawn(a);
+        // Quietly dropped, because `a` no longer exists
+        cmd.insert_one(a, true);
+        cmd.run_on(&mut world);
+        assert!(!world.contains(a));
+        assert_eq!(world.len(), 0);
+    }
+
+    #[test]
+    fn remove_bundle_sequence() {
+        let mut world = World::new();
+        let a = world.spawn((1i32, 'a'));
+        let mut cmd = CommandBuffer::new();
+        cmd.insert(a, (true, 2.0f32));
+        cmd.remove::<(i32, bool)>(a);
+        cmd.insert_one(a, false);
+        cmd.remove_one::<f32>(a);
+        cmd.run_on(&mut world);
+        assert!(!world.satisfies::<&i32>(a).unwrap());
+        assert!(!world.satisfies::<&f32>(a).unwrap());
+        assert!(!*world.get::<&bool>(a).unwrap());
+        assert_eq!(*world.get::<&char>(a).unwrap(), 'a');
+    }
+
+    #[test]
+    #[cfg(not(feature = "thread-local"))]
+    fn merge_parallel() {
+        let mut world = World::new();
+        let existing = world.spawn((0usize,));
+        let reserved = (0..4).map(|_| world.reserve_entity()).collect::<Vec<_>>();
+        let buffers = std::thread::scope(|scope| {
+            let threads = reserved
+                .iter()
+                .enumerate()
+                .map(|(i, &entity)| {
+                    scope.spawn(move || {
+                        let mut cmd = CommandBuffer::new();
+                        cmd.insert(entity, (i, "reserved"));
+                        cmd.spawn((i * 10, [i as u8; 3]));
+                        cmd.insert_one(existing, i);
+                        cmd
+                    })
+                })
+                .collect::<Vec<_>>();
+            threads
+                .into_iter()
+                .map(|x| x.join().unwrap())
+                .collect::<Vec<_>>()
+        });
+
+        let mut cmd = CommandBuffer::new();
+        cmd.despawn(reserved[0]);
+        for buffer in buffers {
+            cmd.merge(buffer);
+        }
+        cmd.run_on(&mut world);
+
+        // Commands from later buffers were applied last
+        assert_eq!(*world.get::<&usize>(existing).unwrap(), 3);
+        // Despawned before the insert was applied
+        assert!(!world.contains(reserved[0]));
+        for (i, &entity) in reserved.iter().enumerate().skip(1) {
+            assert_eq!(*world.get::<&usize>(entity).unwrap(), i);
+            assert_eq!(*world.get::<&&str>(entity).unwrap(), "reserved");
+        }
+        let mut spawned = world
+            .query_mut::<(&usize, &[u8; 3])>()
+            .into_iter()
+            .map(|(_, (&i, &x))| (i, x))
+            .collect::<Vec<_>>();
+        spawned.sort();
+        assert_eq!(
+            spawned,
+            [(0, [0; 3]), (10, [1; 3]), (20, [2; 3]), (30, [3; 3])]
+        );
+    }
+
+    #[derive(Debug, Copy, Clone, PartialEq)]
+    struct ChildOf(Entity);
+
+    impl MapEntities for ChildOf {
+        fn map_entities(&mut self, f: &mut dyn FnMut(Entity) -> Entity) {
+            self.0 = f(self.0);
+        }
+    }
+
+    #[test]
+    fn reserved_hierarchy() {
+        let mut world = World::new();
+        let existing = world.spawn(("existing",));
+        let mut cmd = CommandBuffer::new();
+        let root = cmd.reserve_entity();
+        cmd.insert_one(root, "root");
+        let child = cmd.reserve_entity();
+        cmd.insert(child, ("child",));
+        cmd.insert_mapped(child, ChildOf(root));
+        let grandchild = cmd.reserve_entity();
+        cmd.insert_mapped(grandchild, ChildOf(child));
+        cmd.insert_one(grandchild, "grandchild");
+        cmd.insert_mapped(existing, ChildOf(grandchild));
+        let doomed = cmd.reserve_entity();
+        cmd.insert_one(doomed, "doomed");
+        cmd.despawn(doomed);
+        cmd.run_on(&mut world);
+
+        assert_eq!(world.len(), 4);
+        let parent_of = |e| world.get::<&ChildOf>(e).unwrap().0;
+        let name = |e| *world.get::<&&str>(e).unwrap();
+        let grandchild = parent_of(existing);
+        assert_eq!(name(grandchild), "grandchild");
+        let child = parent_of(grandchild);
+        assert_eq!(name(child), "child");
+        let root = parent_of(child);
+        assert_eq!(name(root), "root");
+        assert!(!world.satisfies::<&ChildOf>(root).unwrap());
+    }
+
+    #[test]
+    fn merge_placeholders() {
+        let mut a = CommandBuffer::new();
+        let x = a.reserve_entity();
+        a.insert_one(x, 1);
+        let mut b = CommandBuffer::new();
+        let y = b.reserve_entity();
+        assert_eq!(x, y);
+        b.insert_one(y, 2);
+        let z = b.reserve_entity();
+        b.insert_mapped(z, ChildOf(y));
+        a.merge(b);
+
+        let mut world = World::new();
+        a.run_on(&mut world);
+        assert_eq!(world.len(), 3);
+        let target = world
+            .query_mut::<&ChildOf>()
+            .into_iter()
+            .next()
+            .unwrap()
+            .1
+             .0;
+        assert_eq!(*world.get::<&i32>(target).unwrap(), 2);
+
+        // Placeholders are reset after running
+        let z = a.reserve_entity();
+        assert_eq!(x, z);
+        a.insert_one(z, 3);
+        a.run_on(&mut world);
+        assert_eq!(world.len(), 4);
+    }
 }
@@ -0,0 +1,137 @@
+use core::marker::PhantomData;
+
+use crate::alloc::vec::Vec;
+use crate::hooks::Observer;
+use crate::{Component, Entity, Query, QueryBorrow};
+
+/// Stable, densely packed slot numbers for every entity with a `K` component, maintained by
+/// [`World::enable_dense_index`](crate::World::enable_dense_index)
+///
+/// An entity is assigned a slot when it gains a `K`, and keeps it until it loses its `K`, e.g. by
+/// being despawned. Freed slots are reused by the next entity to gain a `K`, so slots stay below
+/// [`slot_count`](Self::slot_count) and remain densely packed as entities come and go. Suited to
+/// mirroring components into GPU buffers or other arrays that are expensive to rearrange.
+pub struct DenseIndex<K> {
+    /// Slot of the entity with each ID, or `u32::MAX`
+    by_id: Vec<u32>,
+    /// Occupant of each slot
+    slots: Vec<Option<Entity>>,
+    /// Unoccupied slots, most recently freed last
+    free: Vec<u32>,
+    _marker: PhantomData<fn(K)>,
+}
+
+impl<K> DenseIndex<K> {
+    pub(crate) fn new() -> Self {
+        Self {
+            by_id: Vec::new(),
+            slots: Vec::new(),
+            free: Vec::new(),
+            _marker: PhantomData,
+        }
+    }
+
+    /// The slot of `entity`, if it has a `K`
+    pub fn slot_of(&self, entity: Entity) -> Option<u32> {
+        let slot = *self.by_id.get(entity.id() as usize)?;
+        let occupant = (*self.slots.get(slot as usize)?)?;
+        (occupant == entity).then_some(slot)
+    }
+
+    /// The entity occupying `slot`, if any
+    pub fn entity_at(&self, slot: u32) -> Option<Entity> {
+        *self.slots.get(slot as usize)?
+    }
+
+    /// One more than the greatest slot ever assigned; the size of an array that can hold every
+    /// slot
+    pub fn slot_count(&self) -> u32 {
+        self.slots.len() as u32
+    }
+
+    /// Number of occupied slots
+    pub fn len(&self) -> u32 {
+        self.slots.len() as u32 - self.free.len() as u32
+    }
+
+    /// Whether no slots are occupied
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Occupied slots and their entities, in slot order
+    pub fn iter(&self) -> impl Iterator<Item = (u32, Entity)> + '_ {
+        self.slots
+            .iter()
+            .enumerate()
+            .filter_map(|(slot, entity)| Some((slot as u32, (*entity)?)))
+    }
+
+    /// Execute `query`, pairing each result with its entity's slot
+    ///
+    /// Skips results for entities without a slot. Results are visited in the query's order rather
+    /// than slot order.
+    ///
+    /// # Example
+    /// ```
+    /// # use hecs::*;
+    /// struct Sprite;
+    /// let mut world = World::new();
+    /// world.enable_dense_index::<Sprite>();
+    /// let a = world.spawn((Sprite, 1.0f32));
+    /// let b = world.spawn((Sprite, 2.0f32));
+    /// world.despawn(a).unwrap();
+    /// let c = world.spawn((Sprite, 3.0f32));
+    ///
+    /// let index = world.dense_index::<Sprite>().unwrap();
+    /// let mut buffer = vec![0.0; index.slot_count() as usize];
+    /// for (slot, _, &x) in index.iter_dense(&mut world.query::<&f32>()) {
+    ///     buffer[slot as usize] = x;
+    /// }
+    /// // `c` reused the slot freed by `a`
+    /// assert_eq!(buffer, [3.0, 2.0]);
+    /// ```
+    pub fn iter_dense<'q, Q: Query>(
+        &'q self,
+        query: &'q mut QueryBorrow<'_, Q>,
+    ) -> impl Iterator<Item = (u32, Entity, Q::Item<'q>)> + 'q {
+        query
+            .iter()
+            .filter_map(move |(entity, item)| Some((self.slot_of(entity)?, entity, item)))
+    }
+
+    /// Give `entity` a slot
+    pub(crate) fn assign(&mut self, entity: Entity) {
+        let slot = match self.free.pop() {
+            Some(slot) => {
+                self.slots[slot as usize] = Some(entity);
+                slot
+            }
+            None => {
+                self.slots.push(Some(entity));
+                self.slots.len() as u32 - 1
+            }
+        };
+        let id = entity.id() as usize;
+        if self.by_id.len() <= id {
+            self.by_id.resize(id + 1, u32::MAX);
+        }
+        self.by_id[id] = slot;
+    }
+}
+
+impl<K: Component> Observer<K> for DenseIndex<K> {
+    fn insert(&mut self, entity: Entity, _: &K) {
+        self.assign(entity);
+    }
+
+    fn remove(&mut self, entity: Entity, _: &K) {
+        let slot = match self.slot_of(entity) {
+            Some(x) => x,
+            None => return,
+        };
+        self.by_id[entity.id() as usize] = u32::MAX;
+        self.slots[slot as usize] = None;
+        self.free.push(slot);
+    }
+}
@@ -0,0 +1,203 @@
+// Copyright 2019 Google LLC
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+use core::mem;
+use core::ptr::{self, NonNull};
+
+use spin::Mutex;
+
+use crate::align;
+use crate::alloc::alloc::{alloc, dealloc, Layout};
+use crate::alloc::sync::Arc;
+use crate::alloc::vec::Vec;
+use crate::archetype::TypeInfo;
+
+/// Components of despawned entities awaiting destruction
+///
+/// Installed with [`World::set_drop_queue`](crate::World::set_drop_queue), causing components of
+/// despawned entities to be moved into the queue rather than dropped immediately. Useful when
+/// `Drop` implementations are expensive, or need access to state that's borrowed along with the
+/// [`World`](crate::World). Clones refer to the same queue, so one may be kept by the caller while
+/// another is held by a world.
+///
+/// Components are dropped in the order they were queued: entities in the order they were
+/// despawned, and each entity's components in an unspecified order, when [`flush`](Self::flush) is
+/// called, or when the last handle to the queue is dropped.
+///
+/// ```
+/// # use hecs::*;
+/// let queue = DropQueue::new();
+/// let mut world = World::new();
+/// world.set_drop_queue(Some(queue.clone()));
+/// let a = world.spawn((String::from("expensive"),));
+/// world.despawn(a).unwrap();
+/// assert_eq!(queue.len(), 1);
+/// queue.flush();
+/// assert!(queue.is_empty());
+/// ```
+#[derive(Clone, Default)]
+pub struct DropQueue {
+    inner: Arc<Mutex<Components>>,
+}
+
+impl DropQueue {
+    /// Create an empty queue
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Drop every queued component
+    ///
+    /// The queue isn't locked while components are being dropped, so `Drop` implementations may
+    /// freely despawn more entities into the same queue. Such components are dropped before this
+    /// call returns.
+    pub fn flush(&self) {
+        loop {
+            let mut components = mem::take(&mut *self.inner.lock());
+            if components.entries.is_empty() {
+                break;
+            }
+            components.drop_all();
+        }
+    }
+
+    /// Number of components awaiting destruction
+    pub fn len(&self) -> usize {
+        self.inner.lock().entries.len()
+    }
+
+    /// Whether no components are awaiting destruction
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Take ownership of the component at `ptr`, to be dropped later
+    ///
+    /// # Safety
+    /// `ptr` must point to a valid instance of the type described by `ty`, which must not be
+    /// accessed again.
+    pub(crate) unsafe fn push(&self, ptr: *mut u8, ty: TypeInfo) {
+        self.inner.lock().push(ptr, ty);
+    }
+}
+
+struct Components {
+    storage: NonNull<u8>,
+    layout: Layout,
+    cursor: usize,
+    entries: Vec<Entry>,
+}
+
+struct Entry {
+    ty: TypeInfo,
+    offset: usize,
+}
+
+impl Components {
+    unsafe fn push(&mut self, ptr: *mut u8, ty: TypeInfo) {
+        let offset = align(self.cursor, ty.layout().align());
+        let end = offset + ty.layout().size();
+
+        if end > self.layout.size() || ty.layout().align() > self.layout.align() {
+            let new_align = self.layout.align().max(ty.layout().align());
+            let new_layout =
+                Layout::from_size_align(end.next_power_of_two().max(64), new_align).unwrap();
+            let new_storage = NonNull::new_unchecked(alloc(new_layout));
+            ptr::copy_nonoverlapping(self.storage.as_ptr(), new_storage.as_ptr(), self.cursor);
+            if self.layout.size() != 0 {
+                dealloc(self.storage.as_ptr(), self.layout);
+            }
+            self.storage = new_storage;
+            self.layout = new_layout;
+        }
+
+        ptr::copy_nonoverlapping(ptr, self.storage.as_ptr().add(offset), ty.layout().size());
+        self.entries.push(Entry { ty, offset });
+        self.cursor = end;
+    }
+
+    fn drop_all(&mut self) {
+        for entry in self.entries.drain(..) {
+            unsafe {
+                (entry.ty.drop_shim())(self.storage.as_ptr().add(entry.offset));
+            }
+        }
+        self.cursor = 0;
+    }
+}
+
+impl Default for Components {
+    fn default() -> Self {
+        Self {
+            storage: NonNull::dangling(),
+            layout: Layout::from_size_align(0, 8).unwrap(),
+            cursor: 0,
+            entries: Vec::new(),
+        }
+    }
+}
+
+impl Drop for Components {
+    fn drop(&mut self) {
+        self.drop_all();
+        if self.layout.size() != 0 {
+            unsafe {
+                dealloc(self.storage.as_ptr(), self.layout);
+            }
+        }
+    }
+}
+
+// Only ever contains `Component`s, which are `Send + Sync`
+#[cfg(not(feature = "thread-local"))]
+unsafe impl Send for Components {}
+#[cfg(not(feature = "thread-local"))]
+unsafe impl Sync for Components {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn drop_order() {
+        use crate::alloc::vec;
+        use core::mem::ManuallyDrop;
+
+        struct Noisy(u64, Arc<Mutex<Vec<u64>>>);
+
+        impl Drop for Noisy {
+            fn drop(&mut self) {
+                self.1.lock().push(self.0);
+            }
+        }
+
+        let log = Arc::new(Mutex::new(Vec::new()));
+        let queue = DropQueue::new();
+        for i in 0..3 {
+            let mut x = ManuallyDrop::new(Noisy(i, log.clone()));
+            unsafe {
+                queue.push((&mut *x as *mut Noisy).cast(), TypeInfo::of::<Noisy>());
+            }
+            let mut y = ManuallyDrop::new(i as u8);
+            unsafe {
+                queue.push((&mut *y as *mut u8).cast(), TypeInfo::of::<u8>());
+            }
+        }
+        assert_eq!(queue.len(), 6);
+        assert!(log.lock().is_empty());
+        queue.flush();
+        assert!(queue.is_empty());
+        assert_eq!(*log.lock(), vec![0, 1, 2]);
+
+        let mut x = ManuallyDrop::new(Noisy(3, log.clone()));
+        unsafe {
+            queue.push((&mut *x as *mut Noisy).cast(), TypeInfo::of::<Noisy>());
+        }
+        drop(queue);
+        assert_eq!(*log.lock(), vec![0, 1, 2, 3]);
+    }
+}
@@ -0,0 +1,241 @@
+use core::alloc::Layout;
+use core::any::TypeId;
+use core::ptr::NonNull;
+use core::slice;
+
+use crate::alloc::{boxed::Box, vec::Vec};
+use crate::entities::EntityMeta;
+use crate::{Access, Archetype, Disabled, Entity, World};
+
+/// A query whose component types are only known at runtime
+///
+/// Constructed by [`World::query_dynamic`]. Dynamic borrows of every matched column are acquired
+/// when the query is constructed and released when it is dropped, following the same rules as
+/// statically typed queries: iterating a [`QueryBorrow`](crate::QueryBorrow) that conflicts with a
+/// live `DynamicQueryBorrow`, or vice versa, will panic. [`Disabled`] entities are skipped unless
+/// `Disabled` is among the requested components.
+pub struct DynamicQueryBorrow<'w> {
+    world: &'w World,
+    components: Box<[(TypeId, Access)]>,
+    archetypes: Vec<DynamicArchetype>,
+}
+
+struct DynamicArchetype {
+    index: u32,
+    columns: Box<[Column]>,
+}
+
+#[derive(Copy, Clone)]
+struct Column {
+    state: usize,
+    base: NonNull<u8>,
+    layout: Layout,
+}
+
+impl<'w> DynamicQueryBorrow<'w> {
+    pub(crate) fn new(world: &'w World, components: &[(TypeId, Access)]) -> Self {
+        for (i, &(a, access)) in components.iter().enumerate() {
+            if access == Access::Write {
+                for (j, &(b, _)) in components.iter().enumerate() {
+                    assert!(i == j || a != b, "query violates a unique borrow");
+                }
+            }
+        }
+
+        let mut archetypes = Vec::new();
+        let includes_disabled = components
+            .iter()
+            .any(|&(ty, _)| ty == TypeId::of::<Disabled>());
+        'archetypes: for (index, archetype) in world.archetypes_inner().iter().enumerate() {
+            if archetype.is_disabled() && !includes_disabled {
+                continue;
+            }
+            let mut columns = Vec::with_capacity(components.len());
+            for &(ty, _) in components {
+                let state = match archetype.get_state_dynamic(ty) {
+                    Some(x) => x,
+                    None => continue 'archetypes,
+                };
+                columns.push(Column {
+                    state,
+                    base: archetype.get_base_dynamic(state),
+                    layout: archetype.types()[state].layout(),
+                });
+            }
+            for (column, &(_, access)) in columns.iter().zip(components) {
+                unsafe {
+                    match access {
+                        Access::Iterate => {}
+                        Access::Read => archetype.borrow_raw(column.state),
+                        Access::Write => {
+                            archetype.borrow_raw_mut(column.state);
+                            archetype.mark_changed(column.state);
+                        }
+                    }
+                }
+            }
+            archetypes.push(DynamicArchetype {
+                index: index as u32,
+                columns: columns.into(),
+            });
+        }
+
+        Self {
+            world,
+            components: components.into(),
+            archetypes,
+        }
+    }
+
+    /// Execute the query
+    pub fn iter(&mut self) -> DynamicQueryIter<'_> {
+        DynamicQueryIter {
+            meta: self.world.entities_meta(),
+            source: self.world.archetypes_inner(),
+            components: &self.components,
+            archetypes: self.archetypes.iter(),
+            current: None,
+            index: 0,
+        }
+    }
+}
+
+impl Drop for DynamicQueryBorrow<'_> {
+    fn drop(&mut self) {
+        let source = self.world.archetypes_inner();
+        for matched in &self.archetypes {
+            let archetype = &source[matched.index as usize];
+            for (column, &(_, access)) in matched.columns.iter().zip(&*self.components) {
+                unsafe {
+                    match access {
+                        Access::Iterate => {}
+                        Access::Read => archetype.release_raw(column.state),
+                        Access::Write => archetype.release_raw_mut(column.state),
+                    }
+                }
+            }
+        }
+    }
+}
+
+impl<'q> IntoIterator for &'q mut DynamicQueryBorrow<'_> {
+    type IntoIter = DynamicQueryIter<'q>;
+    type Item = (Entity, DynamicItem<'q>);
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+/// Iterator over the entities matched by a [`DynamicQueryBorrow`]
+pub struct DynamicQueryIter<'q> {
+    meta: &'q [EntityMeta],
+    source: &'q [Archetype],
+    components: &'q [(TypeId, Access)],
+    archetypes: slice::Iter<'q, DynamicArchetype>,
+    current: Option<(&'q Archetype, &'q [Column])>,
+    index: u32,
+}
+
+impl<'q> Iterator for DynamicQueryIter<'q> {
+    type Item = (Entity, DynamicItem<'q>);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            match self.current {
+                Some((archetype, columns)) if self.index < archetype.len() => {
+                    let index = self.index;
+                    self.index += 1;
+                    let id = archetype.entity_id(index);
+                    let entity = Entity {
+                        id,
+                        generation: self.meta[id as usize].generation,
+                    };
+                    let item = DynamicItem {
+                        components: self.components,
+                        columns,
+                        index: index as usize,
+                    };
+                    return Some((entity, item));
+                }
+                _ => {
+                    let next = self.archetypes.next()?;
+                    self.current = Some((&self.source[next.index as usize], &next.columns));
+                    self.index = 0;
+                }
+            }
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let n = self.len();
+        (n, Some(n))
+    }
+}
+
+impl ExactSizeIterator for DynamicQueryIter<'_> {
+    fn len(&self) -> usize {
+        let current = self
+            .current
+            .map_or(0, |(archetype, _)| archetype.len() - self.index);
+        self.archetypes
+            .clone()
+            .map(|x| self.source[x.index as usize].len())
+            .sum::<u32>() as usize
+            + current as usize
+    }
+}
+
+/// Type-erased components of a single entity matched by a [`DynamicQueryBorrow`]
+///
+/// Components are identified by their index in the list passed to [`World::query_dynamic`].
+#[derive(Copy, Clone)]
+pub struct DynamicItem<'q> {
+    components: &'q [(TypeId, Access)],
+    columns: &'q [Column],
+    index: usize,
+}
+
+impl<'q> DynamicItem<'q> {
+    /// Number of components requested by the query
+    pub fn len(&self) -> usize {
+        self.components.len()
+    }
+
+    /// Whether the query requested no components
+    pub fn is_empty(&self) -> bool {
+        self.components.is_empty()
+    }
+
+    /// Type of the `i`th component
+    pub fn type_id(&self, i: usize) -> TypeId {
+        self.components[i].0
+    }
+
+    /// Access requested for the `i`th component
+    pub fn access(&self, i: usize) -> Access {
+        self.components[i].1
+    }
+
+    /// Memory layout of the `i`th component
+    pub fn layout(&self, i: usize) -> Layout {
+        self.columns[i].layout
+    }
+
+    /// Address of the `i`th component, unless it was requested with [`Access::Iterate`]
+    ///
+    /// The pointer is valid for the lifetime `'q`. It may be read from if the component was
+    /// requested with [`Access::Read`] or [`Access::Write`], and written through only in the latter
+    /// case.
+    pub fn get(&self, i: usize) -> Option<NonNull<u8>> {
+        if self.components[i].1 == Access::Iterate {
+            return None;
+        }
+        let column = &self.columns[i];
+        unsafe {
+            Some(NonNull::new_unchecked(
+                column.base.as_ptr().add(column.layout.size() * self.index),
+            ))
+        }
+    }
+}
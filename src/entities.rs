@@ -2,8 +2,9 @@ use alloc::vec::Vec;
 use core::cmp;
 use core::convert::TryFrom;
 use core::iter::ExactSizeIterator;
-use core::num::{NonZeroU32, NonZeroU64};
+use core::num::{IntErrorKind, NonZeroU32, NonZeroU64, ParseIntError};
 use core::ops::Range;
+use core::str::FromStr;
 use core::sync::atomic::{AtomicIsize, Ordering};
 use core::{fmt, mem};
 #[cfg(feature = "std")]
@@ -73,6 +74,13 @@ impl Entity {
     pub const fn id(self) -> u32 {
         self.id
     }
+
+    /// Extract the generation distinguishing this entity from others that share its [`id`](Self::id)
+    ///
+    /// Together with `id`, suitable for passing to `World::resolve`.
+    pub const fn generation(self) -> u32 {
+        self.generation.get()
+    }
 }
 
 impl fmt::Debug for Entity {
@@ -81,13 +89,60 @@ impl fmt::Debug for Entity {
     }
 }
 
+/// Formats as `{id}v{generation}`, the same as [`Debug`](fmt::Debug)
+impl fmt::Display for Entity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}v{}", self.id, self.generation)
+    }
+}
+
+/// Parses the `{id}v{generation}` format produced by [`Display`](fmt::Display)
+///
+/// # Example
+/// ```
+/// # use hecs::*;
+/// let mut world = World::new();
+/// let a = world.spawn(());
+/// assert_eq!(a.to_string().parse::<Entity>(), Ok(a));
+/// assert!("12".parse::<Entity>().is_err());
+/// ```
+impl FromStr for Entity {
+    type Err = EntityParseError;
+
+    fn from_str(s: &str) -> Result<Self, EntityParseError> {
+        fn classify(e: ParseIntError, malformed: EntityParseError) -> EntityParseError {
+            match e.kind() {
+                IntErrorKind::PosOverflow | IntErrorKind::Zero => EntityParseError::OutOfRange,
+                _ => malformed,
+            }
+        }
+
+        let (id, generation) = s
+            .split_once('v')
+            .ok_or(EntityParseError::MissingSeparator)?;
+        Ok(Self {
+            id: id
+                .parse()
+                .map_err(|e| classify(e, EntityParseError::BadId))?,
+            generation: generation
+                .parse()
+                .map_err(|e| classify(e, EntityParseError::BadGeneration))?,
+        })
+    }
+}
+
 #[cfg(feature = "serde")]
 impl serde::Serialize for Entity {
     fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
     where
         S: serde::Serializer,
     {
-        self.to_bits().serialize(serializer)
+        #[cfg(all(feature = "row-serialize", feature = "std"))]
+        let entity =
+            crate::serialize::row::remap::apply(&crate::serialize::row::remap::SERIALIZE, *self);
+        #[cfg(not(all(feature = "row-serialize", feature = "std")))]
+        let entity = *self;
+        entity.to_bits().serialize(serializer)
     }
 }
 
@@ -100,6 +155,12 @@ impl<'de> serde::Deserialize<'de> for Entity {
         let bits = u64::deserialize(deserializer)?;
 
         match Entity::from_bits(bits) {
+            #[cfg(all(feature = "row-serialize", feature = "std"))]
+            Some(ent) => Ok(crate::serialize::row::remap::apply(
+                &crate::serialize::row::remap::DESERIALIZE,
+                ent,
+            )),
+            #[cfg(not(all(feature = "row-serialize", feature = "std")))]
             Some(ent) => Ok(ent),
             None => Err(serde::de::Error::invalid_value(
                 serde::de::Unexpected::Unsigned(bits),
@@ -109,6 +170,34 @@ impl<'de> serde::Deserialize<'de> for Entity {
     }
 }
 
+/// How a [`World`](crate::World) chooses IDs for new entities
+///
+/// See [`World::with_allocator`](crate::World::with_allocator).
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash, Default)]
+pub enum EntityAllocatorPolicy {
+    /// Reuse the IDs of despawned entities, with a new generation
+    ///
+    /// Keeps the table of entity metadata as small as the largest number of entities that were
+    /// ever simultaneously live.
+    #[default]
+    Recycle,
+    /// Never reuse IDs, so that each new entity's ID is one greater than the previous
+    ///
+    /// Allocation then depends only on the number of entities ever allocated, not the order in
+    /// which they were despawned. The entity metadata table grows by 8 bytes for every entity ever
+    /// allocated; call [`World::compact`](crate::World::compact) to allow despawned IDs to be
+    /// reused.
+    Sequential,
+    /// Never allocate IDs; every entity must be given one by
+    /// [`World::spawn_with_id`](crate::World::spawn_with_id)
+    ///
+    /// Methods that would choose an ID, such as [`World::spawn`](crate::World::spawn) and
+    /// [`World::reserve_entity`](crate::World::reserve_entity), panic instead, so IDs assigned by an
+    /// outside authority such as a server can never collide with IDs hecs picked itself. The caller
+    /// is responsible for keeping IDs unique.
+    External,
+}
+
 /// An iterator returning a sequence of Entity values from `Entities::reserve_entities`.
 pub struct ReserveEntitiesIterator<'a> {
     // Metas, so we can recover the current generation for anything in the freelist.
@@ -189,13 +278,60 @@ pub(crate) struct Entities {
     pending: Vec<u32>,
     free_cursor: AtomicIsize,
     len: u32,
+    /// Whether freed IDs are kept out of the freelist, per [`EntityAllocatorPolicy::Sequential`]
+    retire: bool,
+    /// Whether allocation is forbidden, per [`EntityAllocatorPolicy::External`]
+    external: bool,
+    /// Number of IDs permanently retired because their generation saturated
+    exhausted: u32,
+    /// Number of IDs held by `hold` and not yet allocated or released
+    held: u32,
+    /// Tag of each entity, indexed by ID, or empty if no tag was ever set
+    ///
+    /// IDs beyond the end have tag 0. Reset to 0 when an entity is freed.
+    tags: Vec<u8>,
+}
+
+impl Clone for Entities {
+    fn clone(&self) -> Self {
+        Self {
+            meta: self.meta.clone(),
+            pending: self.pending.clone(),
+            free_cursor: AtomicIsize::new(self.free_cursor.load(Ordering::Relaxed)),
+            len: self.len,
+            retire: self.retire,
+            external: self.external,
+            exhausted: self.exhausted,
+            held: self.held,
+            tags: self.tags.clone(),
+        }
+    }
 }
 
 impl Entities {
+    pub fn new(policy: EntityAllocatorPolicy) -> Self {
+        Self {
+            retire: policy != EntityAllocatorPolicy::Recycle,
+            external: policy == EntityAllocatorPolicy::External,
+            ..Self::default()
+        }
+    }
+
+    /// Panic if IDs may only be assigned explicitly
+    fn assert_internal(&self) {
+        assert!(
+            !self.external,
+            "this world's entity IDs are assigned externally; use `World::spawn_with_id`"
+        );
+    }
+
     /// Reserve entity IDs concurrently
     ///
     /// Storage for entity generation and location is lazily allocated by calling `flush`.
-    pub fn reserve_entities(&self, count: u32) -> ReserveEntitiesIterator {
+    pub fn reserve_entities(&self, count: u32) -> ReserveEntitiesIterator<'_> {
+        if count != 0 {
+            self.assert_internal();
+        }
         // Use one atomic subtract to grab a range of new IDs. The range might be
         // entirely nonnegative, meaning all IDs come from the freelist, or entirely
         // negative, meaning they are all new IDs to allocate, or a mix of both.
@@ -240,6 +376,7 @@ impl Entities {
     ///
     /// Equivalent to `self.reserve_entities(1).next().unwrap()`, but more efficient.
     pub fn reserve_entity(&self) -> Entity {
+        self.assert_internal();
         let n = self.free_cursor.fetch_sub(1, Ordering::Relaxed);
         if n > 0 {
             // Allocate from the freelist.
@@ -274,6 +411,7 @@ impl Entities {
     /// Location should be written immediately.
     pub fn alloc(&mut self) -> Entity {
         self.verify_flushed();
+        self.assert_internal();
 
         self.len += 1;
         if let Some(id) = self.pending.pop() {
@@ -293,11 +431,57 @@ impl Entities {
         }
     }
 
+    /// Allocate an entity ID directly without making it live
+    ///
+    /// The ID is withheld from all other allocation until it's claimed by `alloc_at` or returned
+    /// by `release`.
+    pub fn hold(&mut self) -> Entity {
+        let entity = self.alloc();
+        self.len -= 1;
+        self.held += 1;
+        self.meta[entity.id as usize].location = EntityMeta::HELD;
+        entity
+    }
+
+    /// Return an ID obtained from `hold` for reuse
+    pub fn release(&mut self, entity: Entity) -> Result<(), NoSuchEntity> {
+        self.verify_flushed();
+
+        let meta = self.meta.get_mut(entity.id as usize).ok_or(NoSuchEntity)?;
+        if meta.generation != entity.generation || !meta.is_held() {
+            return Err(NoSuchEntity);
+        }
+
+        if meta.advance_generation() {
+            if !self.retire {
+                self.pending.push(entity.id);
+            }
+        } else {
+            self.exhausted += 1;
+        }
+
+        let new_free_cursor = self.pending.len() as isize;
+        *self.free_cursor.get_mut() = new_free_cursor;
+        self.held -= 1;
+
+        Ok(())
+    }
+
+    /// Whether `entity` was obtained from `hold` and not yet allocated or released
+    pub fn is_held(&self, entity: Entity) -> bool {
+        self.meta.get(entity.id as usize).map_or(false, |meta| {
+            meta.generation == entity.generation && meta.is_held()
+        })
+    }
+
     /// Allocate and set locations for many entity IDs laid out contiguously in an archetype
     ///
     /// `self.finish_alloc_many()` must be called after!
     pub fn alloc_many(&mut self, n: u32, archetype: u32, mut first_index: u32) -> AllocManyState {
         self.verify_flushed();
+        if n != 0 {
+            self.assert_internal();
+        }
 
         let fresh = (n as usize).saturating_sub(self.pending.len()) as u32;
         assert!(
@@ -343,19 +527,38 @@ impl Entities {
         self.verify_flushed();
 
         let loc = if entity.id as usize >= self.meta.len() {
-            self.pending.extend((self.meta.len() as u32)..entity.id);
+            if !self.retire {
+                self.pending.extend((self.meta.len() as u32)..entity.id);
+            }
             let new_free_cursor = self.pending.len() as isize;
             *self.free_cursor.get_mut() = new_free_cursor;
             self.meta.resize(entity.id as usize + 1, EntityMeta::EMPTY);
             self.len += 1;
             None
+        } else if self.meta[entity.id as usize].is_exhausted() {
+            // Exhausted IDs aren't in the freelist, but may still be revived explicitly
+            self.exhausted -= 1;
+            self.len += 1;
+            None
+        } else if self.meta[entity.id as usize].is_held() {
+            // Held IDs aren't in the freelist either
+            self.held -= 1;
+            self.len += 1;
+            None
         } else if let Some(index) = self.pending.iter().position(|item| *item == entity.id) {
             self.pending.swap_remove(index);
             let new_free_cursor = self.pending.len() as isize;
             *self.free_cursor.get_mut() = new_free_cursor;
             self.len += 1;
             None
+        } else if self.retire && self.meta[entity.id as usize].location.index == u32::MAX {
+            // Retired IDs aren't in the freelist, unless returned to it by `compact`
+            self.len += 1;
+            None
         } else {
+            if let Some(tag) = self.tags.get_mut(entity.id as usize) {
+                *tag = 0;
+            }
             Some(mem::replace(
                 &mut self.meta[entity.id as usize].location,
                 EntityMeta::EMPTY.location,
@@ -378,12 +581,17 @@ impl Entities {
             return Err(NoSuchEntity);
         }
 
-        meta.generation = NonZeroU32::new(u32::from(meta.generation).wrapping_add(1))
-            .unwrap_or_else(|| NonZeroU32::new(1).unwrap());
-
-        let loc = mem::replace(&mut meta.location, EntityMeta::EMPTY.location);
-
-        self.pending.push(entity.id);
+        let loc = meta.location;
+        if let Some(tag) = self.tags.get_mut(entity.id as usize) {
+            *tag = 0;
+        }
+        if meta.advance_generation() {
+            if !self.retire {
+                self.pending.push(entity.id);
+            }
+        } else {
+            self.exhausted += 1;
+        }
 
         let new_free_cursor = self.pending.len() as isize;
         *self.free_cursor.get_mut() = new_free_cursor;
@@ -403,6 +611,39 @@ impl Entities {
         }
     }
 
+    /// The handle a new entity with the ID `id` should have, or the conflicting handle if `id` is
+    /// live, held, or has exhausted its generations
+    ///
+    /// Must not be called while reserved entities are awaiting `flush()`.
+    pub fn vacant(&self, id: u32) -> Result<Entity, Entity> {
+        match self.meta.get(id as usize) {
+            None => Ok(Entity {
+                id,
+                generation: NonZeroU32::new(1).unwrap(),
+            }),
+            Some(meta) => {
+                let entity = Entity {
+                    id,
+                    generation: meta.generation,
+                };
+                if meta.location.index == u32::MAX && !meta.is_exhausted() && !meta.is_held() {
+                    Ok(entity)
+                } else {
+                    Err(entity)
+                }
+            }
+        }
+    }
+
+    /// Whether a live entity has the ID `id`, regardless of generation
+    ///
+    /// Must not be called while reserved entities are awaiting `flush()`.
+    pub fn contains_id(&self, id: u32) -> bool {
+        self.meta
+            .get(id as usize)
+            .map_or(false, |meta| meta.location.index != u32::MAX)
+    }
+
     pub fn contains(&self, entity: Entity) -> bool {
         match self.meta.get(entity.id as usize) {
             Some(meta) => {
@@ -421,11 +662,52 @@ impl Entities {
         }
     }
 
+    /// Free every live entity, as if by `free`, without regard to order
+    pub fn free_all(&mut self) {
+        self.verify_flushed();
+        for meta in &mut self.meta {
+            if meta.location.index == u32::MAX {
+                // Held IDs remain held, since they were never live
+                continue;
+            }
+            if !meta.advance_generation() {
+                self.exhausted += 1;
+            }
+        }
+        self.len = 0;
+        self.tags.clear();
+        if !self.retire {
+            self.compact();
+        }
+    }
+
+    /// Make every unused ID available for reuse, lowest first
+    pub fn compact(&mut self) {
+        self.verify_flushed();
+        self.pending.clear();
+        self.pending
+            .extend((0..self.meta.len() as u32).rev().filter(|&id| {
+                let meta = &self.meta[id as usize];
+                meta.location.index == u32::MAX && !meta.is_exhausted() && !meta.is_held()
+            }));
+        *self.free_cursor.get_mut() = self.pending.len() as isize;
+    }
+
     pub fn clear(&mut self) {
         self.meta.clear();
         self.pending.clear();
         *self.free_cursor.get_mut() = 0;
         self.len = 0;
+        self.exhausted = 0;
+        self.held = 0;
+        self.tags.clear();
+    }
+
+    /// Release metadata storage beyond that needed for the current IDs
+    pub fn shrink_to_fit(&mut self) {
+        self.meta.shrink_to_fit();
+        self.pending.shrink_to_fit();
+        self.tags.shrink_to_fit();
     }
 
     /// Access the location storage of an entity
@@ -529,6 +811,35 @@ impl Entities {
     pub fn len(&self) -> u32 {
         self.len
     }
+
+    /// Number of IDs that will never be allocated again because their generation saturated
+    #[inline]
+    pub fn exhausted(&self) -> u32 {
+        self.exhausted
+    }
+
+    /// Number of IDs obtained from `hold` and not yet allocated or released
+    #[inline]
+    pub fn held(&self) -> u32 {
+        self.held
+    }
+
+    /// Set the tag of the live entity with ID `id`
+    pub fn set_tag(&mut self, id: u32, tag: u8) {
+        let id = id as usize;
+        if id >= self.tags.len() {
+            if tag == 0 {
+                return;
+            }
+            self.tags.resize(id + 1, 0);
+        }
+        self.tags[id] = tag;
+    }
+
+    #[inline]
+    pub fn tag(&self, id: u32) -> u8 {
+        self.tags.get(id as usize).copied().unwrap_or(0)
+    }
 }
 
 #[derive(Copy, Clone)]
@@ -548,6 +859,44 @@ impl EntityMeta {
             index: u32::MAX, // dummy value, to be filled in
         },
     };
+
+    /// Location of a dead entity whose ID can never be reused
+    const EXHAUSTED: Location = Location {
+        archetype: u32::MAX,
+        index: u32::MAX,
+    };
+
+    pub(crate) fn is_exhausted(&self) -> bool {
+        self.location.archetype == u32::MAX && self.location.index == u32::MAX
+    }
+
+    /// Location of an ID withheld from allocation by `Entities::hold`
+    const HELD: Location = Location {
+        archetype: u32::MAX - 1,
+        index: u32::MAX,
+    };
+
+    pub(crate) fn is_held(&self) -> bool {
+        self.location.archetype == u32::MAX - 1 && self.location.index == u32::MAX
+    }
+
+    /// Mark a live entity dead, returning whether its ID may be reused
+    ///
+    /// An ID whose generation is already saturated is retired instead, ensuring no handle to a
+    /// prior occupant can ever alias a later one.
+    fn advance_generation(&mut self) -> bool {
+        match NonZeroU32::new(self.generation.get().wrapping_add(1)) {
+            Some(generation) => {
+                self.generation = generation;
+                self.location = Self::EMPTY.location;
+                true
+            }
+            None => {
+                self.location = Self::EXHAUSTED;
+                false
+            }
+        }
+    }
 }
 
 #[derive(Copy, Clone)]
@@ -569,6 +918,34 @@ impl fmt::Display for NoSuchEntity {
 #[cfg(feature = "std")]
 impl Error for NoSuchEntity {}
 
+/// Error indicating that a string was not a valid `{id}v{generation}` [`Entity`]
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+pub enum EntityParseError {
+    /// The string didn't contain a `v` separating the ID from the generation
+    MissingSeparator,
+    /// The part before the `v` wasn't a decimal integer
+    BadId,
+    /// The part after the `v` wasn't a decimal integer
+    BadGeneration,
+    /// The ID exceeded `u32::MAX`, or the generation was zero or exceeded `u32::MAX`
+    OutOfRange,
+}
+
+impl fmt::Display for EntityParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        use EntityParseError::*;
+        f.pad(match *self {
+            MissingSeparator => "invalid entity: missing `v` separator",
+            BadId => "invalid entity: malformed ID",
+            BadGeneration => "invalid entity: malformed generation",
+            OutOfRange => "invalid entity: ID or generation out of range",
+        })
+    }
+}
+
+#[cfg(feature = "std")]
+impl Error for EntityParseError {}
+
 #[derive(Clone)]
 pub(crate) struct AllocManyState {
     pub pending_end: usize,
@@ -606,6 +983,72 @@ mod tests {
         assert_eq!(Entity::from_bits(e.to_bits().into()).unwrap(), e);
     }
 
+    #[test]
+    fn entity_string_roundtrip() {
+        use std::string::ToString;
+
+        for (id, generation) in [(0, 1), (7, 42), (u32::MAX, u32::MAX)] {
+            let e = Entity {
+                generation: NonZeroU32::new(generation).unwrap(),
+                id,
+            };
+            let s = e.to_string();
+            assert_eq!(s, std::format!("{}v{}", id, generation));
+            assert_eq!(s.parse::<Entity>(), Ok(e));
+        }
+        assert_eq!(Entity::DANGLING.to_string().parse(), Ok(Entity::DANGLING));
+        for (s, err) in [
+            ("", EntityParseError::MissingSeparator),
+            ("1", EntityParseError::MissingSeparator),
+            ("v", EntityParseError::BadId),
+            ("v1", EntityParseError::BadId),
+            ("-1v1", EntityParseError::BadId),
+            ("1 v1", EntityParseError::BadId),
+            ("1v", EntityParseError::BadGeneration),
+            ("1v1v1", EntityParseError::BadGeneration),
+            ("1v0", EntityParseError::OutOfRange),
+            ("4294967296v1", EntityParseError::OutOfRange),
+            ("1v4294967296", EntityParseError::OutOfRange),
+        ] {
+            assert_eq!(s.parse::<Entity>(), Err(err), "{:?}", s);
+        }
+    }
+
+    #[test]
+    fn sequential() {
+        let mut e = Entities::new(EntityAllocatorPolicy::Sequential);
+        let a = e.alloc();
+        e.meta[a.id as usize].location.index = 0;
+        let b = e.alloc();
+        e.meta[b.id as usize].location.index = 0;
+        e.free(a).unwrap();
+        let c = e.alloc();
+        e.meta[c.id as usize].location.index = 0;
+        assert_eq!((a.id, b.id, c.id), (0, 1, 2));
+        assert_eq!(e.len(), 2);
+
+        // Retired and skipped IDs can still be explicitly allocated
+        let d = Entity {
+            id: 5,
+            generation: NonZeroU32::new(1).unwrap(),
+        };
+        assert!(e.alloc_at(d).is_none());
+        e.meta[5].location.index = 0;
+        assert!(e.alloc_at(a).is_none());
+        e.meta[0].location.index = 0;
+        assert_eq!(e.len(), 4);
+        let f = e.alloc();
+        e.meta[f.id as usize].location.index = 0;
+        assert_eq!(f.id, 6);
+
+        e.free(b).unwrap();
+        e.compact();
+        assert_eq!(e.alloc().id, 1);
+        assert_eq!(e.alloc().id, 3);
+        assert_eq!(e.alloc().id, 4);
+        assert_eq!(e.alloc().id, 7);
+    }
+
     #[test]
     fn alloc_and_free() {
         let mut rng = StdRng::seed_from_u64(0xFEEDFACEDEADF00D);
@@ -738,6 +1181,42 @@ mod tests {
         }
     }
 
+    #[test]
+    fn exhausted_generation() {
+        let mut e = Entities::default();
+        let entity = Entity {
+            id: 0,
+            generation: NonZeroU32::new(u32::MAX - 1).unwrap(),
+        };
+        assert!(e.alloc_at(entity).is_none());
+        e.meta[0].location.index = 0;
+        e.free(entity).unwrap();
+        assert_eq!(e.exhausted(), 0);
+
+        // The final generation is still usable
+        let last = e.alloc();
+        assert_eq!(last.id, 0);
+        assert_eq!(last.generation.get(), u32::MAX);
+        e.meta[0].location.index = 0;
+        e.free(last).unwrap();
+        assert_eq!(e.exhausted(), 1);
+        assert!(!e.contains(last));
+        assert!(e.get(last).is_err());
+
+        // ...but the ID is never handed out again
+        assert_eq!(e.reserve_entity().id, 1);
+        e.flush(|_, loc| loc.index = 0);
+        assert_eq!(e.alloc().id, 2);
+        e.meta[2].location.index = 0;
+        e.compact();
+        assert!(!e.pending.contains(&0));
+        assert_eq!(e.exhausted(), 1);
+
+        e.free_all();
+        assert_eq!(e.exhausted(), 1);
+        assert_eq!(e.alloc().id, 1);
+    }
+
     // Shared test code parameterized by how we want to allocate an Entity block.
     fn reserve_test_helper(reserve_n: impl FnOnce(&mut Entities, u32) -> Vec<Entity>) {
         let mut e = Entities::default();
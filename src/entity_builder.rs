@@ -14,7 +14,7 @@ use core::ptr::{self, NonNull};
 use hashbrown::hash_map::Entry;
 
 use crate::archetype::{TypeIdMap, TypeInfo};
-use crate::{align, Component, ComponentRef, ComponentRefShared, DynamicBundle};
+use crate::{align, Component, ComponentRef, ComponentRefShared, DynamicBundle, Entity, World};
 
 /// Helper for incrementally constructing a bundle of components with dynamic component types
 ///
@@ -60,6 +60,35 @@ impl EntityBuilder {
         self
     }
 
+    /// Add the component at `ptr`, taking ownership of it
+    ///
+    /// # Safety
+    /// `ptr` must address a valid value of the type described by `ty`, which must not be used again
+    pub(crate) unsafe fn add_raw(&mut self, ptr: *mut u8, ty: TypeInfo) {
+        self.inner.add(ptr, ty, ());
+    }
+
+    /// Move all components from `other` into this builder
+    ///
+    /// Components in `other` replace any components of the same type already in this builder,
+    /// as if they had each been [`add`](Self::add)ed.
+    ///
+    /// # Example
+    /// ```
+    /// # use hecs::*;
+    /// let mut builder = EntityBuilder::new();
+    /// builder.add(123).add("abc");
+    /// let mut other = EntityBuilder::new();
+    /// other.add(456).add(true);
+    /// builder.merge(other);
+    /// assert_eq!(*builder.get::<&i32>().unwrap(), 456);
+    /// assert_eq!(builder.component_types().count(), 3);
+    /// ```
+    pub fn merge(&mut self, other: EntityBuilder) -> &mut Self {
+        self.inner.merge(other.inner);
+        self
+    }
+
     /// Construct a `Bundle` suitable for spawning
     pub fn build(&mut self) -> BuiltEntity<'_> {
         self.inner.info.sort_unstable_by_key(|x| x.0);
@@ -140,6 +169,84 @@ impl Drop for BuiltEntity<'_> {
     }
 }
 
+/// An owned bundle of components whose types are described at runtime
+///
+/// Useful when component types come from a reflection registry rather than Rust generics. Unlike
+/// [`EntityBuilder`], a `RuntimeBundle` is itself a [`DynamicBundle`] and is consumed when
+/// spawned. Components that are never spawned are dropped with the bundle.
+///
+/// ```
+/// # use hecs::*;
+/// # use std::mem::ManuallyDrop;
+/// let mut bundle = RuntimeBundle::new();
+/// let mut name = ManuallyDrop::new(String::from("abc"));
+/// unsafe {
+///     bundle.push_erased((&mut *name as *mut String).cast(), TypeInfo::of::<String>());
+/// }
+/// let mut world = World::new();
+/// let e = world.spawn(bundle);
+/// assert_eq!(*world.get::<&String>(e).unwrap(), "abc");
+/// ```
+#[derive(Default)]
+pub struct RuntimeBundle {
+    inner: Common<()>,
+}
+
+impl RuntimeBundle {
+    /// Create a bundle containing no components
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add the component at `ptr`, taking ownership of it
+    ///
+    /// If the bundle already contains a component of the same type, it will be dropped and
+    /// replaced. Use [`TypeInfo::from_parts`] to describe types with no Rust generic on hand.
+    ///
+    /// # Safety
+    /// `ptr` must address a valid, `Send + Sync` value of the type described by `ty`, which must not
+    /// be used or dropped again by the caller.
+    pub unsafe fn push_erased(&mut self, ptr: *mut u8, ty: TypeInfo) -> &mut Self {
+        let is_new = !self.inner.indices.contains_key(&ty.id());
+        self.inner.add(ptr, ty, ());
+        if is_new {
+            self.inner.sort();
+        }
+        self
+    }
+
+    /// Checks to see if the component of type `T` exists
+    pub fn has<T: Component>(&self) -> bool {
+        self.inner.has::<T>()
+    }
+
+    /// Enumerate the types of the bundle's components, in sorted order
+    pub fn component_types(&self) -> impl Iterator<Item = TypeId> + '_ {
+        self.inner.component_types()
+    }
+}
+
+unsafe impl DynamicBundle for RuntimeBundle {
+    fn has<T: Component>(&self) -> bool {
+        self.inner.has::<T>()
+    }
+
+    fn with_ids<T>(&self, f: impl FnOnce(&[TypeId]) -> T) -> T {
+        f(&self.inner.ids)
+    }
+
+    fn type_info(&self) -> Vec<TypeInfo> {
+        self.inner.info.iter().map(|x| x.0).collect()
+    }
+
+    unsafe fn put(mut self, mut f: impl FnMut(*mut u8, TypeInfo)) {
+        for (ty, offset, ()) in self.inner.info.drain(..) {
+            let ptr = self.inner.storage.as_ptr().add(offset);
+            f(ptr, ty);
+        }
+    }
+}
+
 /// Variant of [`EntityBuilder`] that clones components on use
 ///
 /// ```
@@ -194,6 +301,14 @@ impl EntityBuilderClone {
         self
     }
 
+    /// Move all components from `other` into this builder
+    ///
+    /// See [`EntityBuilder::merge`].
+    pub fn merge(&mut self, other: EntityBuilderClone) -> &mut Self {
+        self.inner.merge(other.inner);
+        self
+    }
+
     /// Convert into a value whose shared references are [`DynamicBundle`]s suitable for repeated
     /// spawning
     pub fn build(self) -> BuiltEntityClone {
@@ -240,6 +355,36 @@ impl EntityBuilderClone {
 #[derive(Clone)]
 pub struct BuiltEntityClone(Common<DynamicClone>);
 
+impl BuiltEntityClone {
+    /// Spawn a new entity in `world` with clones of these components
+    ///
+    /// Equivalent to `world.spawn(&self)`.
+    pub fn spawn_clone(&self, world: &mut World) -> Entity {
+        world.spawn(self)
+    }
+
+    /// Spawn `count` new entities in `world`, each with clones of these components
+    ///
+    /// Faster than calling [`spawn_clone`](Self::spawn_clone) repeatedly, as the destination
+    /// archetype is found and storage for every entity is reserved only once. Convenient for
+    /// instantiating prefabs.
+    ///
+    /// # Example
+    /// ```
+    /// # use hecs::*;
+    /// let mut world = World::new();
+    /// let mut builder = EntityBuilderClone::new();
+    /// builder.add(123).add(String::from("abc"));
+    /// let prefab = builder.build();
+    /// let entities = prefab.spawn_clones(&mut world, 100);
+    /// assert_eq!(world.len(), 100);
+    /// assert_eq!(*world.get::<&String>(entities[42]).unwrap(), "abc");
+    /// ```
+    pub fn spawn_clones(&self, world: &mut World, count: u32) -> Vec<Entity> {
+        world.spawn_clones(self, count)
+    }
+}
+
 unsafe impl DynamicBundle for &'_ BuiltEntityClone {
     fn has<T: Component>(&self) -> bool {
         self.0.has::<T>()
@@ -344,6 +489,28 @@ impl<M> Common<M> {
         }
     }
 
+    /// Sort components by type, as required to construct a bundle, and rebuild the lookup tables
+    fn sort(&mut self) {
+        self.info.sort_unstable_by_key(|x| x.0);
+        self.ids.clear();
+        self.ids.extend(self.info.iter().map(|x| x.0.id()));
+        self.indices.clear();
+        self.indices
+            .extend(self.info.iter().enumerate().map(|(i, x)| (x.0.id(), i)));
+    }
+
+    /// Move all components out of `other`, replacing any existing components of the same type
+    fn merge(&mut self, mut other: Self) {
+        other.indices.clear();
+        other.ids.clear();
+        other.cursor = 0;
+        for (ty, offset, meta) in other.info.drain(..) {
+            unsafe {
+                self.add(other.storage.as_ptr().add(offset), ty, meta);
+            }
+        }
+    }
+
     unsafe fn add(&mut self, ptr: *mut u8, ty: TypeInfo, meta: M) {
         match self.indices.entry(ty.id()) {
             Entry::Occupied(occupied) => {
@@ -382,7 +549,9 @@ impl<M> Common<M> {
     }
 }
 
+#[cfg(not(feature = "thread-local"))]
 unsafe impl<M> Send for Common<M> {}
+#[cfg(not(feature = "thread-local"))]
 unsafe impl<M> Sync for Common<M> {}
 
 impl<M> Drop for Common<M> {
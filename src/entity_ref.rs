@@ -5,6 +5,7 @@ use core::ops::{Deref, DerefMut, FnOnce};
 use core::ptr::NonNull;
 
 use crate::archetype::Archetype;
+use crate::query::assert_borrow;
 use crate::{
     ArchetypeColumn, ArchetypeColumnMut, Component, Entity, Fetch, MissingComponent, Query,
     QueryOne,
@@ -106,9 +107,108 @@ impl<'a> EntityRef<'a> {
     }
 }
 
+#[cfg(not(feature = "thread-local"))]
 unsafe impl<'a> Send for EntityRef<'a> {}
+#[cfg(not(feature = "thread-local"))]
 unsafe impl<'a> Sync for EntityRef<'a> {}
 
+/// Handle to an entity in a uniquely borrowed [`World`](crate::World)
+///
+/// Like [`EntityRef`], but components can be accessed without dynamic borrow checks. Constructed
+/// by [`World::spawn_ref`](crate::World::spawn_ref).
+pub struct EntityRefMut<'a> {
+    archetype: &'a Archetype,
+    entity: Entity,
+    index: u32,
+    _marker: PhantomData<&'a mut ()>,
+}
+
+impl<'a> EntityRefMut<'a> {
+    /// # Safety
+    ///
+    /// No component of the entity may be borrowed elsewhere while this handle is live.
+    pub(crate) unsafe fn new(archetype: &'a Archetype, entity: Entity, index: u32) -> Self {
+        Self {
+            archetype,
+            entity,
+            index,
+            _marker: PhantomData,
+        }
+    }
+
+    /// Get the [`Entity`] handle associated with this entity
+    #[inline]
+    pub fn entity(&self) -> Entity {
+        self.entity
+    }
+
+    /// Determine whether this entity would satisfy the query `Q`
+    pub fn satisfies<Q: Query>(&self) -> bool {
+        Q::Fetch::access(self.archetype).is_some()
+    }
+
+    /// Determine whether this entity has a `T` component
+    pub fn has<T: Component>(&self) -> bool {
+        self.archetype.has::<T>()
+    }
+
+    /// Get a shared reference to a single component, if it exists
+    pub fn get<T: Component>(&self) -> Option<&T> {
+        let state = self.archetype.get_state::<T>()?;
+        let base = self.archetype.get_base::<T>(state);
+        unsafe { Some(&*base.as_ptr().add(self.index as usize)) }
+    }
+
+    /// Get a unique reference to a single component, if it exists
+    ///
+    /// # Example
+    /// ```
+    /// # use hecs::*;
+    /// let mut world = World::new();
+    /// let mut e = world.spawn_ref((42, "abc"));
+    /// *e.get_mut::<i32>().unwrap() += 1;
+    /// let e = e.entity();
+    /// assert_eq!(*world.get::<&i32>(e).unwrap(), 43);
+    /// ```
+    pub fn get_mut<T: Component>(&mut self) -> Option<&mut T> {
+        let state = self.archetype.get_state::<T>()?;
+        self.archetype.mark_changed(state);
+        let base = self.archetype.get_base::<T>(state);
+        unsafe { Some(&mut *base.as_ptr().add(self.index as usize)) }
+    }
+
+    /// Run a query against this entity
+    ///
+    /// Like [`World::query_one_mut`](crate::World::query_one_mut), but without looking up the
+    /// entity again. Returns `None` if the entity doesn't satisfy `Q`.
+    pub fn query<Q: Query>(&mut self) -> Option<Q::Item<'_>> {
+        assert_borrow::<Q>();
+        let state = Q::Fetch::prepare(self.archetype)?;
+        let fetch = Q::Fetch::execute(self.archetype, state);
+        unsafe { Some(Q::get(&fetch, self.index as usize)) }
+    }
+
+    /// Enumerate the types of the entity's components
+    pub fn component_types(&self) -> impl Iterator<Item = TypeId> + 'a {
+        self.archetype.types().iter().map(|ty| ty.id())
+    }
+
+    /// Number of components in this entity
+    pub fn len(&self) -> usize {
+        self.archetype.types().len()
+    }
+
+    /// Shorthand for `self.len() == 0`
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+#[cfg(not(feature = "thread-local"))]
+unsafe impl<'a> Send for EntityRefMut<'a> {}
+#[cfg(not(feature = "thread-local"))]
+unsafe impl<'a> Sync for EntityRefMut<'a> {}
+
 /// Shared borrow of an entity's component
 pub struct Ref<'a, T: ?Sized> {
     borrow: ComponentBorrow<'a>,
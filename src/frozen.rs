@@ -0,0 +1,80 @@
+use crate::{
+    Archetype, Component, ComponentError, Entity, NoSuchEntity, Query, QueryBorrow, QueryOne,
+    QueryShared, Ref, ViewBorrow, World,
+};
+
+/// A [`World`] in which no entities or components can be added or removed, obtained from
+/// [`World::freeze`]
+///
+/// Exposes only methods that read the world or its archetypes. The world remains uniquely borrowed
+/// until the `FrozenWorld` is dropped, so neither the frozen world nor the original can change
+/// structurally in the meantime: every entity stays in the same archetype at the same index, and
+/// references to [`Archetype`]s remain accurate. Shares across threads like `&World`.
+///
+/// ```compile_fail
+/// # use hecs::*;
+/// let mut world = World::new();
+/// let frozen = world.freeze();
+/// world.spawn((1,)); // error: `world` is frozen
+/// frozen.len();
+/// ```
+pub struct FrozenWorld<'w> {
+    world: &'w World,
+}
+
+impl<'w> FrozenWorld<'w> {
+    pub(crate) fn new(world: &'w World) -> Self {
+        Self { world }
+    }
+
+    /// Query the world, as by [`World::query`]
+    pub fn query<Q: Query + QueryShared>(&self) -> QueryBorrow<'w, Q> {
+        self.world.query()
+    }
+
+    /// Query the world for random access, as by [`World::view`]
+    pub fn view<Q: Query + QueryShared>(&self) -> ViewBorrow<'w, Q> {
+        self.world.view()
+    }
+
+    /// Query a single entity, as by [`World::query_one`]
+    pub fn query_one<Q: Query + QueryShared>(
+        &self,
+        entity: Entity,
+    ) -> Result<QueryOne<'w, Q>, NoSuchEntity> {
+        self.world.query_one(entity)
+    }
+
+    /// Borrow the `T` component of `entity`, as by [`World::get`]
+    pub fn get<T: Component>(&self, entity: Entity) -> Result<Ref<'w, T>, ComponentError> {
+        self.world.get::<&T>(entity)
+    }
+
+    /// Get the resource of type `T`, as by [`World::get_resource`]
+    pub fn get_resource<T: Component>(&self) -> Option<&'w T> {
+        self.world.get_resource()
+    }
+
+    /// Whether `entity` still exists
+    pub fn contains(&self, entity: Entity) -> bool {
+        self.world.contains(entity)
+    }
+
+    /// Number of currently live entities
+    pub fn len(&self) -> u32 {
+        self.world.len()
+    }
+
+    /// Whether no entities are live
+    pub fn is_empty(&self) -> bool {
+        self.world.is_empty()
+    }
+
+    /// The world's archetypes, as by [`World::archetypes`]
+    ///
+    /// The references remain valid, and each entity's position within them unchanged, for as long
+    /// as the world is frozen.
+    pub fn archetypes(&self) -> impl ExactSizeIterator<Item = &'w Archetype> + 'w {
+        self.world.archetypes()
+    }
+}
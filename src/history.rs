@@ -0,0 +1,93 @@
+use core::any::TypeId;
+use core::marker::PhantomData;
+
+use crate::query::{FetchRead, QueryShared};
+use crate::{Access, Archetype, Component, Fetch, Query};
+
+/// Copy of a `T` as of the last [`World::snapshot_history`](crate::World::snapshot_history),
+/// stored as an ordinary component alongside the `T`
+pub(crate) struct History<T>(pub(crate) T);
+
+/// Query that yields an entity's `T` as of the last
+/// [`World::snapshot_history::<T>`](crate::World::snapshot_history)
+///
+/// Matches entities that have a `T` now and had one at the last snapshot. Only borrows the
+/// snapshotted copy, so may be combined with `&mut T` to compare old and new values.
+///
+/// # Example
+/// ```
+/// # use hecs::*;
+/// #[derive(Clone)]
+/// struct Position(f32);
+/// let mut world = World::new();
+/// let a = world.spawn((Position(0.0),));
+/// world.enable_history::<Position>();
+/// world.get::<&mut Position>(a).unwrap().0 = 3.0;
+/// for (_, (pos, prev)) in world.query_mut::<(&Position, Prev<Position>)>() {
+///     assert_eq!(pos.0 - prev.0, 3.0);
+/// }
+/// ```
+pub struct Prev<T>(PhantomData<fn(T)>);
+
+impl<T: Component> Query for Prev<T> {
+    type Item<'q> = &'q T;
+
+    type Fetch = FetchPrev<T>;
+
+    unsafe fn get<'q>(fetch: &Self::Fetch, n: usize) -> Self::Item<'q> {
+        &<&History<T> as Query>::get(&fetch.0, n).0
+    }
+}
+
+unsafe impl<T> QueryShared for Prev<T> {}
+
+#[doc(hidden)]
+pub struct FetchPrev<T>(FetchRead<History<T>>);
+
+unsafe impl<T: Component> Fetch for FetchPrev<T> {
+    type State = usize;
+
+    fn dangling() -> Self {
+        Self(FetchRead::dangling())
+    }
+
+    fn access(archetype: &Archetype) -> Option<Access> {
+        if archetype.has::<T>() {
+            FetchRead::<History<T>>::access(archetype)
+        } else {
+            None
+        }
+    }
+
+    fn borrow(archetype: &Archetype, state: Self::State) {
+        FetchRead::<History<T>>::borrow(archetype, state);
+    }
+    fn prepare(archetype: &Archetype) -> Option<Self::State> {
+        if !archetype.has::<T>() {
+            return None;
+        }
+        FetchRead::<History<T>>::prepare(archetype)
+    }
+    fn execute(archetype: &Archetype, state: Self::State) -> Self {
+        Self(FetchRead::execute(archetype, state))
+    }
+    fn release(archetype: &Archetype, state: Self::State) {
+        FetchRead::<History<T>>::release(archetype, state);
+    }
+
+    fn for_each_borrow(mut f: impl FnMut(TypeId, bool)) {
+        f(TypeId::of::<History<T>>(), false);
+    }
+
+    fn for_each_access(mut f: impl FnMut(TypeId, Access)) {
+        f(TypeId::of::<T>(), Access::Iterate);
+        f(TypeId::of::<History<T>>(), Access::Read);
+    }
+}
+
+impl<T> Clone for FetchPrev<T> {
+    #[inline]
+    fn clone(&self) -> Self {
+        Self(self.0.clone())
+    }
+}
@@ -0,0 +1,252 @@
+use core::any::{Any, TypeId};
+use core::marker::PhantomData;
+
+use crate::alloc::{boxed::Box, vec::Vec};
+use crate::archetype::TypeIdMap;
+use crate::{Archetype, Component, Entity};
+
+type Hook = Box<dyn FnMut(Entity, *mut u8) + Send + Sync>;
+
+/// Callbacks registered through [`World::on_insert`](crate::World::on_insert) and
+/// [`World::on_remove`](crate::World::on_remove), and removals recorded for
+/// [`World::track_removals`](crate::World::track_removals)
+#[derive(Default)]
+pub(crate) struct Hooks {
+    types: TypeIdMap<TypeHooks>,
+}
+
+#[derive(Default)]
+struct TypeHooks {
+    insert: Vec<Hook>,
+    remove: Vec<Hook>,
+    observers: Vec<Box<dyn AnyObserver>>,
+    /// Entities the component was removed from, if tracking was enabled
+    removed: Option<Vec<Entity>>,
+}
+
+impl Hooks {
+    #[inline]
+    pub(crate) fn is_empty(&self) -> bool {
+        self.types.is_empty()
+    }
+
+    /// Whether any of `types` has hooks or observers, or has its removals tracked
+    #[inline]
+    pub(crate) fn any(&self, types: impl IntoIterator<Item = TypeId>) -> bool {
+        !self.types.is_empty() && types.into_iter().any(|ty| self.types.contains_key(&ty))
+    }
+
+    pub(crate) fn add_insert<T: Component>(
+        &mut self,
+        mut f: impl FnMut(Entity, &T) + Send + Sync + 'static,
+    ) {
+        self.types
+            .entry(TypeId::of::<T>())
+            .or_default()
+            .insert
+            .push(Box::new(move |entity, ptr| unsafe {
+                f(entity, &*ptr.cast::<T>())
+            }));
+    }
+
+    pub(crate) fn add_remove<T: Component>(
+        &mut self,
+        mut f: impl FnMut(Entity, &mut T) + Send + Sync + 'static,
+    ) {
+        self.types
+            .entry(TypeId::of::<T>())
+            .or_default()
+            .remove
+            .push(Box::new(move |entity, ptr| unsafe {
+                f(entity, &mut *ptr.cast::<T>())
+            }));
+    }
+
+    /// Register `observer` to be kept in sync with every `T` component, replacing any existing
+    /// observer of the same type
+    pub(crate) fn add_observer<T: Component, O: Observer<T>>(&mut self, observer: O) -> &mut O {
+        let observers = &mut self.types.entry(TypeId::of::<T>()).or_default().observers;
+        let observer = Box::new(Typed::<T, O>(observer, PhantomData));
+        let index = match observers.iter().position(|x| x.as_any().is::<O>()) {
+            Some(index) => {
+                observers[index] = observer;
+                index
+            }
+            None => {
+                observers.push(observer);
+                observers.len() - 1
+            }
+        };
+        observers[index].as_any_mut().downcast_mut().unwrap()
+    }
+
+    /// Look up an observer registered by `add_observer`
+    pub(crate) fn observer<T: Component, O: Observer<T>>(&self) -> Option<&O> {
+        self.types
+            .get(&TypeId::of::<T>())?
+            .observers
+            .iter()
+            .find_map(|x| x.as_any().downcast_ref())
+    }
+
+    /// Uniquely look up an observer registered by `add_observer`
+    pub(crate) fn observer_mut<T: Component, O: Observer<T>>(&mut self) -> Option<&mut O> {
+        self.types
+            .get_mut(&TypeId::of::<T>())?
+            .observers
+            .iter_mut()
+            .find_map(|x| x.as_any_mut().downcast_mut())
+    }
+
+    pub(crate) fn track_removals<T: Component>(&mut self) {
+        self.types
+            .entry(TypeId::of::<T>())
+            .or_default()
+            .removed
+            .get_or_insert_with(Vec::new);
+    }
+
+    pub(crate) fn removed<T: Component>(&self) -> &[Entity] {
+        self.types
+            .get(&TypeId::of::<T>())
+            .and_then(|x| x.removed.as_deref())
+            .unwrap_or(&[])
+    }
+
+    pub(crate) fn clear_removed<T: Component>(&mut self) {
+        if let Some(removed) = self
+            .types
+            .get_mut(&TypeId::of::<T>())
+            .and_then(|x| x.removed.as_mut())
+        {
+            removed.clear();
+        }
+    }
+
+    /// Invoke insert hooks for the `types` components of the entity at `index` in `archetype`
+    ///
+    /// # Safety
+    /// `entity` must be stored at `index` in `archetype`, and the components must not be borrowed
+    pub(crate) unsafe fn fire_insert(
+        &mut self,
+        entity: Entity,
+        archetype: &Archetype,
+        index: u32,
+        types: impl IntoIterator<Item = TypeId>,
+    ) {
+        self.fire(entity, archetype, index, types, true, |x| &mut x.insert);
+    }
+
+    /// Invoke remove hooks for the `types` components of the entity at `index` in `archetype`, and
+    /// record their removal
+    ///
+    /// # Safety
+    /// `entity` must be stored at `index` in `archetype`, and the components must not be borrowed
+    pub(crate) unsafe fn fire_remove(
+        &mut self,
+        entity: Entity,
+        archetype: &Archetype,
+        index: u32,
+        types: impl IntoIterator<Item = TypeId>,
+    ) {
+        self.fire(entity, archetype, index, types, false, |x| {
+            if let Some(ref mut removed) = x.removed {
+                removed.push(entity);
+            }
+            &mut x.remove
+        });
+    }
+
+    /// Invoke remove hooks for the `types` components of the entity at `index` in `archetype`
+    /// which are about to be overwritten by new values
+    ///
+    /// # Safety
+    /// `entity` must be stored at `index` in `archetype`, and the components must not be borrowed
+    pub(crate) unsafe fn fire_replace(
+        &mut self,
+        entity: Entity,
+        archetype: &Archetype,
+        index: u32,
+        types: impl IntoIterator<Item = TypeId>,
+    ) {
+        self.fire(entity, archetype, index, types, false, |x| &mut x.remove);
+    }
+
+    unsafe fn fire(
+        &mut self,
+        entity: Entity,
+        archetype: &Archetype,
+        index: u32,
+        types: impl IntoIterator<Item = TypeId>,
+        insert: bool,
+        which: impl Fn(&mut TypeHooks) -> &mut Vec<Hook>,
+    ) {
+        for ty in types {
+            let x = match self.types.get_mut(&ty) {
+                Some(x) => x,
+                None => continue,
+            };
+            let state = match archetype.get_state_dynamic(ty) {
+                Some(x) => x,
+                None => continue,
+            };
+            let size = archetype.types()[state].layout().size();
+            let ptr = archetype
+                .get_base_dynamic(state)
+                .as_ptr()
+                .add(size * index as usize);
+            for hook in which(x) {
+                hook(entity, ptr);
+            }
+            // Observers see the value as the hooks left it
+            for observer in &mut x.observers {
+                if insert {
+                    observer.insert(entity, ptr);
+                } else {
+                    observer.remove(entity, ptr);
+                }
+            }
+        }
+    }
+}
+
+/// World-owned state kept in sync with the `T` components of every entity
+///
+/// Notified after a `T` is stored, and before one is dropped, moved out of the world, or
+/// overwritten, at the same points as the hooks registered with
+/// [`World::on_insert`](crate::World::on_insert) and [`World::on_remove`](crate::World::on_remove).
+pub(crate) trait Observer<T>: Any + Send + Sync {
+    fn insert(&mut self, entity: Entity, value: &T);
+    fn remove(&mut self, entity: Entity, value: &T);
+}
+
+trait AnyObserver: Send + Sync {
+    /// # Safety
+    /// `ptr` must point to a valid value of this observer's component type
+    unsafe fn insert(&mut self, entity: Entity, ptr: *mut u8);
+    /// # Safety
+    /// `ptr` must point to a valid value of this observer's component type
+    unsafe fn remove(&mut self, entity: Entity, ptr: *mut u8);
+    fn as_any(&self) -> &dyn Any;
+    fn as_any_mut(&mut self) -> &mut dyn Any;
+}
+
+struct Typed<T, O>(O, PhantomData<fn(&T)>);
+
+impl<T: Component, O: Observer<T>> AnyObserver for Typed<T, O> {
+    unsafe fn insert(&mut self, entity: Entity, ptr: *mut u8) {
+        self.0.insert(entity, &*ptr.cast::<T>());
+    }
+
+    unsafe fn remove(&mut self, entity: Entity, ptr: *mut u8) {
+        self.0.remove(entity, &*ptr.cast::<T>());
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        &self.0
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        &mut self.0
+    }
+}
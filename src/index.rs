@@ -0,0 +1,119 @@
+use core::hash::Hash;
+use core::mem;
+
+use hashbrown::HashMap;
+
+use crate::alloc::{boxed::Box, vec::Vec};
+use crate::entities::EntityMeta;
+use crate::hooks::Observer;
+use crate::{Archetype, Component, Entity};
+
+/// Entities grouped by a key computed from their `T` components, maintained by
+/// [`World::add_index`](crate::World::add_index)
+///
+/// Suited to spatial hashing: with a key that maps a position to a grid cell, each
+/// [`bucket`](Self::bucket) holds the entities in one cell.
+pub struct Index<T, B> {
+    key: Box<dyn Fn(&T) -> B + Send + Sync>,
+    buckets: HashMap<B, Vec<Entity>>,
+    /// Bucket of the entity with each ID, as of the last time its `T` was observed
+    current: HashMap<u32, B>,
+    /// Change tick as of the last refresh
+    tick: u32,
+}
+
+impl<T: Component, B: Hash + Eq + Clone + Send + Sync + 'static> Index<T, B> {
+    pub(crate) fn new(key: impl Fn(&T) -> B + Send + Sync + 'static, tick: u32) -> Self {
+        Self {
+            key: Box::new(key),
+            buckets: HashMap::default(),
+            current: HashMap::default(),
+            tick,
+        }
+    }
+
+    /// Entities whose `T` components had the key `key` when the index was last refreshed, in
+    /// unspecified order
+    pub fn bucket(&self, key: &B) -> &[Entity] {
+        self.buckets.get(key).map_or(&[], |x| &x[..])
+    }
+
+    /// The key `entity` is filed under, if it has a `T`
+    pub fn key_of(&self, entity: Entity) -> Option<&B> {
+        let key = self.current.get(&entity.id())?;
+        self.bucket(key).contains(&entity).then_some(key)
+    }
+
+    /// Every non-empty bucket and its key
+    pub fn buckets(&self) -> impl Iterator<Item = (&B, &[Entity])> + '_ {
+        self.buckets
+            .iter()
+            .map(|(key, entities)| (key, &entities[..]))
+    }
+
+    /// File `entity` under the bucket computed from `value`, moving it if necessary
+    fn file(&mut self, entity: Entity, value: &T) {
+        let key = (self.key)(value);
+        match self.current.get(&entity.id()) {
+            Some(old) if *old == key => return,
+            Some(_) => self.unfile(entity),
+            None => {}
+        }
+        self.buckets.entry(key.clone()).or_default().push(entity);
+        self.current.insert(entity.id(), key);
+    }
+
+    fn unfile(&mut self, entity: Entity) {
+        let key = match self.current.remove(&entity.id()) {
+            Some(x) => x,
+            None => return,
+        };
+        let entities = self.buckets.get_mut(&key).unwrap();
+        if let Some(i) = entities.iter().position(|&x| x == entity) {
+            entities.swap_remove(i);
+        }
+        if entities.is_empty() {
+            self.buckets.remove(&key);
+        }
+    }
+
+    /// Refile every `T` in `archetypes` that may have been mutated since the last refresh, or
+    /// every `T` if `all` is set, with `tick` as the world's newly superseded change tick
+    pub(crate) fn refresh(
+        &mut self,
+        meta: &[EntityMeta],
+        archetypes: &[Archetype],
+        tick: u32,
+        all: bool,
+    ) {
+        let since = mem::replace(&mut self.tick, tick);
+        for archetype in archetypes {
+            let state = match archetype.get_state::<T>() {
+                Some(x) => x,
+                None => continue,
+            };
+            if !all && !archetype.changed_since(state, since) {
+                continue;
+            }
+            let column = archetype.get::<&T>().unwrap();
+            for (&id, value) in archetype.ids().iter().zip(column.iter()) {
+                let entity = Entity {
+                    id,
+                    generation: meta[id as usize].generation,
+                };
+                self.file(entity, value);
+            }
+        }
+    }
+}
+
+impl<T: Component, B: Hash + Eq + Clone + Send + Sync + 'static> Observer<T> for Index<T, B> {
+    fn insert(&mut self, entity: Entity, value: &T) {
+        self.file(entity, value);
+    }
+
+    fn remove(&mut self, entity: Entity, _: &T) {
+        // The value may have been mutated since it was filed, so rely on the recorded key
+        self.unfile(entity);
+    }
+}
@@ -68,43 +68,85 @@ macro_rules! smaller_tuples_too {
     };
 }
 
+mod access_view;
 mod archetype;
 mod batch;
 mod borrow;
 mod bundle;
 mod change_tracker;
+mod clone_registry;
 mod command_buffer;
+mod dense_index;
+mod drop_queue;
+mod dynamic_query;
 mod entities;
 mod entity_builder;
 mod entity_ref;
+mod frozen;
+mod history;
+mod hooks;
+mod index;
+mod name;
 mod query;
 mod query_one;
+mod relation;
 #[cfg(any(feature = "row-serialize", feature = "column-serialize"))]
 pub mod serialize;
+mod snapshot;
+mod sparse;
 mod take;
+mod trait_query;
+mod transaction;
 mod world;
 
-pub use archetype::{Archetype, ArchetypeColumn, ArchetypeColumnMut, TypeIdMap, TypeInfo};
-pub use batch::{BatchIncomplete, BatchWriter, ColumnBatch, ColumnBatchBuilder, ColumnBatchType};
+pub use access_view::AccessView;
+pub use archetype::{
+    Archetype, ArchetypeColumn, ArchetypeColumnMut, ArchetypeMemory, ColumnMemory, TypeIdMap,
+    TypeInfo,
+};
+pub use batch::{
+    BatchIncomplete, BatchWriter, BatchWriters, ColumnBatch, ColumnBatchBuilder, ColumnBatchType,
+    ErasedBatchWriter,
+};
 pub use bundle::{
     bundle_satisfies_query, dynamic_bundle_satisfies_query, Bundle, DynamicBundle,
     DynamicBundleClone, MissingComponent,
 };
 pub use change_tracker::{ChangeTracker, Changes};
-pub use command_buffer::CommandBuffer;
-pub use entities::{Entity, NoSuchEntity};
-pub use entity_builder::{BuiltEntity, BuiltEntityClone, EntityBuilder, EntityBuilderClone};
-pub use entity_ref::{ComponentRef, ComponentRefShared, EntityRef, Ref, RefMut};
+pub use clone_registry::{CloneRegistry, UnregisteredComponent};
+pub use command_buffer::{CommandBuffer, MapEntities};
+pub use dense_index::DenseIndex;
+pub use drop_queue::DropQueue;
+pub use dynamic_query::{DynamicItem, DynamicQueryBorrow, DynamicQueryIter};
+pub use entities::{Entity, EntityAllocatorPolicy, EntityParseError, NoSuchEntity};
+pub use entity_builder::{
+    BuiltEntity, BuiltEntityClone, EntityBuilder, EntityBuilderClone, RuntimeBundle,
+};
+pub use entity_ref::{ComponentRef, ComponentRefShared, EntityRef, EntityRefMut, Ref, RefMut};
+pub use frozen::FrozenWorld;
+pub use history::Prev;
+pub use index::Index;
+pub use name::Name;
+#[cfg(all(feature = "rayon", not(feature = "thread-local")))]
+pub use query::ParIter;
 pub use query::{
-    Access, Batch, BatchedIter, Or, PreparedQuery, PreparedQueryBorrow, PreparedQueryIter,
-    PreparedView, Query, QueryBorrow, QueryIter, QueryMut, QueryShared, Satisfies, View,
-    ViewBorrow, With, Without,
+    Access, Added, ArchetypeSlices, ArchetypeSlicesIter, Batch, BatchedIter, Changed,
+    ComponentTypes, Disabled, InTag, IncludeDisabled, OneOf, Or, PairsMut, PreparedQuery,
+    PreparedQueryBorrow, PreparedQueryIter, PreparedView, Query, QueryArchetypeChunk, QueryBorrow,
+    QueryCursor, QueryCursorIter, QueryEach, QueryEachIter, QueryIter, QueryMut, QueryShared,
+    Satisfies, SliceQuery, Tracked, TrackedMut, View, ViewBorrow, With, Without,
 };
 pub use query_one::QueryOne;
+pub use relation::{OnTargetDespawn, Relation};
+pub use snapshot::{SnapshotRegistry, WorldDelta, WorldSnapshot};
 pub use take::TakenEntity;
+pub use trait_query::{TraitQueryBorrow, TraitQueryIter};
+pub use transaction::Transaction;
 pub use world::{
-    ArchetypesGeneration, Component, ComponentError, Iter, QueryOneError, SpawnBatchIter,
-    SpawnColumnBatchIter, World,
+    ArchetypeId, ArchetypeView, ArchetypesGeneration, Component, ComponentError, Drain,
+    EntityCollision, EntitySlot, GetManyMutError, InsertReport, Iter, MemoryReport, NeighborReader,
+    PresenceMask, QueryOneError, QuerySingleError, ReservedBlock, SpawnBatchIter,
+    SpawnColumnBatchIter, TypeNameRegistry, World,
 };
 
 // Unstable implementation details needed by the macros
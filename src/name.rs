@@ -0,0 +1,100 @@
+use core::fmt;
+use core::ops::Deref;
+
+use hashbrown::HashMap;
+
+use crate::alloc::{string::String, vec::Vec};
+use crate::hooks::Observer;
+use crate::Entity;
+
+/// A human-readable label for an entity, findable with [`World::find_by_name`]
+///
+/// An ordinary component with no special behavior until [`World::index_names`] is called. Names
+/// need not be unique.
+///
+/// The index only observes `Name`s being added and removed, so rename an entity by inserting a new
+/// `Name` rather than by overwriting one through a mutable borrow.
+///
+/// [`World::find_by_name`]: crate::World::find_by_name
+/// [`World::index_names`]: crate::World::index_names
+#[derive(Debug, Clone, Eq, PartialEq, Hash, Ord, PartialOrd, Default)]
+pub struct Name(String);
+
+impl Name {
+    /// Construct a name
+    pub fn new(name: impl Into<String>) -> Self {
+        Self(name.into())
+    }
+
+    /// The name as a string slice
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+impl Deref for Name {
+    type Target = str;
+
+    fn deref(&self) -> &str {
+        &self.0
+    }
+}
+
+impl From<String> for Name {
+    fn from(x: String) -> Self {
+        Self(x)
+    }
+}
+
+impl From<&str> for Name {
+    fn from(x: &str) -> Self {
+        Self(x.into())
+    }
+}
+
+impl From<Name> for String {
+    fn from(x: Name) -> Self {
+        x.0
+    }
+}
+
+impl fmt::Display for Name {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+/// Entities by [`Name`], maintained by [`World::index_names`](crate::World::index_names)
+#[derive(Default)]
+pub(crate) struct NameIndex {
+    /// Entities with each name, in the order they were given it
+    entities: HashMap<String, Vec<Entity>>,
+}
+
+impl NameIndex {
+    pub(crate) fn get(&self, name: &str) -> &[Entity] {
+        self.entities.get(name).map_or(&[], |x| &x[..])
+    }
+}
+
+impl Observer<Name> for NameIndex {
+    fn insert(&mut self, entity: Entity, value: &Name) {
+        self.entities
+            .entry_ref(value.as_str())
+            .or_default()
+            .push(entity);
+    }
+
+    fn remove(&mut self, entity: Entity, value: &Name) {
+        let entities = match self.entities.get_mut(value.as_str()) {
+            Some(x) => x,
+            None => return,
+        };
+        if let Some(i) = entities.iter().rposition(|&x| x == entity) {
+            entities.remove(i);
+        }
+        if entities.is_empty() {
+            self.entities.remove(value.as_str());
+        }
+    }
+}
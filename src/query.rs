@@ -6,18 +6,22 @@
 // copied, modified, or distributed except according to those terms.
 
 use core::any::TypeId;
+use core::fmt;
 use core::marker::PhantomData;
+use core::mem;
+use core::ops::{Deref, DerefMut};
 use core::ptr::NonNull;
 use core::slice::Iter as SliceIter;
 
-use crate::alloc::{boxed::Box, vec::Vec};
+use crate::alloc::{boxed::Box, vec, vec::Vec};
 use crate::archetype::Archetype;
 use crate::entities::EntityMeta;
-use crate::{Component, Entity, World};
+use crate::{Component, Entity, NoSuchEntity, QueryOne, QuerySingleError, World};
+use hashbrown::HashMap;
 
 /// A collection of component types to fetch from a [`World`](crate::World)
 ///
-/// The interface of this trait is a private implementation detail.
+/// Apart from [`Query::access`], the interface of this trait is a private implementation detail.
 pub trait Query {
     /// Type of results yielded by the query
     ///
@@ -36,6 +40,36 @@ pub trait Query {
     /// - Bounds-checking must be performed externally
     /// - Any resulting borrows must be legal (e.g. no &mut to something another iterator might access)
     unsafe fn get<'a>(fetch: &Self::Fetch, n: usize) -> Self::Item<'a>;
+
+    /// Component types this query may access, and the strongest access it needs to each
+    ///
+    /// Components that are only checked for presence, e.g. by [`With`], [`Without`], [`Satisfies`],
+    /// [`Changed`] or [`Added`], are reported with [`Access::Iterate`]. Two queries may run
+    /// concurrently if neither writes a component that the other reads or writes.
+    ///
+    /// # Example
+    /// ```
+    /// # use hecs::*;
+    /// # use std::any::TypeId;
+    /// assert_eq!(
+    ///     <With<(&i32, &mut bool), &u8>>::access(),
+    ///     [
+    ///         (TypeId::of::<i32>(), Access::Read),
+    ///         (TypeId::of::<bool>(), Access::Write),
+    ///         (TypeId::of::<u8>(), Access::Iterate),
+    ///     ]
+    /// );
+    /// ```
+    fn access() -> Vec<(TypeId, Access)> {
+        let mut result = Vec::<(TypeId, Access)>::new();
+        Self::Fetch::for_each_access(|ty, access| {
+            match result.iter_mut().find(|(x, _)| *x == ty) {
+                Some((_, existing)) => *existing = (*existing).max(access),
+                None => result.push((ty, access)),
+            }
+        });
+        result
+    }
 }
 
 /// Marker trait indicating whether a given [`Query`] will not produce unique references
@@ -64,8 +98,74 @@ pub unsafe trait Fetch: Clone + Sized {
     /// Release dynamic borrows acquired by `borrow`
     fn release(archetype: &Archetype, state: Self::State);
 
+    /// Whether `archetype` should be traversed by a query that has already observed every change
+    /// recorded up to the change tick `since`
+    ///
+    /// Only consulted by queries which track change ticks, after `prepare` succeeds.
+    #[allow(unused_variables)]
+    #[inline]
+    fn filter(archetype: &Archetype, state: Self::State, since: u32) -> bool {
+        true
+    }
+
+    /// Whether `filter` may depend on the change tick it's passed
+    ///
+    /// True for queries that contain [`Changed`] or [`Added`].
+    #[inline]
+    fn tracks_changes() -> bool {
+        false
+    }
+
     /// Invoke `f` for every component type that may be borrowed and whether the borrow is unique
     fn for_each_borrow(f: impl FnMut(TypeId, bool));
+
+    /// Invoke `f` for every component type that may be accessed and how
+    ///
+    /// Unlike `for_each_borrow`, includes components only checked for presence as
+    /// [`Access::Iterate`].
+    #[inline]
+    fn for_each_access(mut f: impl FnMut(TypeId, Access)) {
+        Self::for_each_borrow(|ty, unique| {
+            f(ty, if unique { Access::Write } else { Access::Read })
+        });
+    }
+
+    /// Whether iteration should visit [`Disabled`] entities
+    ///
+    /// True for queries that refer to `Disabled`, or are wrapped in [`IncludeDisabled`].
+    #[inline]
+    fn includes_disabled() -> bool {
+        false
+    }
+}
+
+/// Look up `F`'s state for `archetype` if it should be traversed by iteration
+///
+/// Unlike `F::prepare`, skips [`Disabled`] entities unless `F` includes them.
+#[inline]
+pub(crate) fn prepare_iter<F: Fetch>(archetype: &Archetype) -> Option<F::State> {
+    if archetype.is_disabled() && !F::includes_disabled() {
+        return None;
+    }
+    F::prepare(archetype)
+}
+
+/// A [`Query`] whose results for an entire archetype can be viewed as parallel slices
+///
+/// Implemented for `&T`, `&mut T`, tuples of other `SliceQuery`s, and [`With`] and [`Without`]
+/// filters applied to them. Queries with other elements, such as [`Option`] or [`Satisfies`], have
+/// no equivalent slice form, and must be iterated per entity instead. See [`QueryMut::into_archetype_slices`].
+pub trait SliceQuery: Query {
+    /// Components of every entity in an archetype, e.g. `(&'q [A], &'q mut [B])` for
+    /// `(&A, &mut B)`
+    type Slices<'q>;
+
+    #[doc(hidden)]
+    /// Access the first `len` items in this archetype
+    ///
+    /// # Safety
+    /// Same as [`Query::get`] for every item, and `len` must not exceed the archetype's length
+    unsafe fn slices<'q>(fetch: &Self::Fetch, len: usize) -> Self::Slices<'q>;
 }
 
 /// Type of access a [`Query`] may have to an [`Archetype`]
@@ -79,7 +179,7 @@ pub enum Access {
     Write,
 }
 
-impl<'a, T: Component> Query for &'a T {
+impl<T: Component> Query for &'_ T {
     type Item<'q> = &'q T;
 
     type Fetch = FetchRead<T>;
@@ -89,7 +189,15 @@ impl<'a, T: Component> Query for &'a T {
     }
 }
 
-unsafe impl<'a, T> QueryShared for &'a T {}
+unsafe impl<T> QueryShared for &'_ T {}
+
+impl<T: Component> SliceQuery for &'_ T {
+    type Slices<'q> = &'q [T];
+
+    unsafe fn slices<'q>(fetch: &FetchRead<T>, len: usize) -> &'q [T] {
+        core::slice::from_raw_parts(fetch.0.as_ptr(), len)
+    }
+}
 
 #[doc(hidden)]
 pub struct FetchRead<T>(NonNull<T>);
@@ -125,6 +233,10 @@ unsafe impl<T: Component> Fetch for FetchRead<T> {
     fn for_each_borrow(mut f: impl FnMut(TypeId, bool)) {
         f(TypeId::of::<T>(), false);
     }
+
+    fn includes_disabled() -> bool {
+        TypeId::of::<T>() == TypeId::of::<Disabled>()
+    }
 }
 
 impl<T> Clone for FetchRead<T> {
@@ -134,7 +246,7 @@ impl<T> Clone for FetchRead<T> {
     }
 }
 
-impl<'a, T: Component> Query for &'a mut T {
+impl<T: Component> Query for &'_ mut T {
     type Item<'q> = &'q mut T;
 
     type Fetch = FetchWrite<T>;
@@ -144,6 +256,14 @@ impl<'a, T: Component> Query for &'a mut T {
     }
 }
 
+impl<T: Component> SliceQuery for &'_ mut T {
+    type Slices<'q> = &'q mut [T];
+
+    unsafe fn slices<'q>(fetch: &FetchWrite<T>, len: usize) -> &'q mut [T] {
+        core::slice::from_raw_parts_mut(fetch.0.as_ptr(), len)
+    }
+}
+
 #[doc(hidden)]
 pub struct FetchWrite<T>(NonNull<T>);
 
@@ -163,13 +283,15 @@ unsafe impl<T: Component> Fetch for FetchWrite<T> {
     }
 
     fn borrow(archetype: &Archetype, state: Self::State) {
-        archetype.borrow_mut::<T>(state);
+        // Marked changed by `execute`, so archetypes excluded by filters are left as they were
+        archetype.borrow_mut_untracked::<T>(state);
     }
     #[allow(clippy::needless_question_mark)]
     fn prepare(archetype: &Archetype) -> Option<Self::State> {
         Some(archetype.get_state::<T>()?)
     }
     fn execute(archetype: &Archetype, state: Self::State) -> Self {
+        archetype.mark_changed(state);
         Self(archetype.get_base::<T>(state))
     }
     fn release(archetype: &Archetype, state: Self::State) {
@@ -179,6 +301,10 @@ unsafe impl<T: Component> Fetch for FetchWrite<T> {
     fn for_each_borrow(mut f: impl FnMut(TypeId, bool)) {
         f(TypeId::of::<T>(), true);
     }
+
+    fn includes_disabled() -> bool {
+        TypeId::of::<T>() == TypeId::of::<Disabled>()
+    }
 }
 
 impl<T> Clone for FetchWrite<T> {
@@ -235,9 +361,50 @@ unsafe impl<T: Fetch> Fetch for TryFetch<T> {
     fn for_each_borrow(f: impl FnMut(TypeId, bool)) {
         T::for_each_borrow(f);
     }
+
+    fn for_each_access(f: impl FnMut(TypeId, Access)) {
+        T::for_each_access(f);
+    }
+
+    fn includes_disabled() -> bool {
+        T::includes_disabled()
+    }
 }
 
 /// Holds an `L`, or an `R`, or both
+///
+/// As a [`Query`], matches entities that satisfy at least one of `L` and `R`. Used as the filter of
+/// a [`With`] or [`Without`], it selects entities by an arbitrary boolean combination of
+/// components without fetching them, and may be nested freely.
+///
+/// # Example
+/// ```
+/// # use hecs::*;
+/// struct Enemy;
+/// struct Hazard;
+/// struct Dead;
+/// let mut world = World::new();
+/// let a = world.spawn((1, Enemy));
+/// let b = world.spawn((2, Hazard));
+/// let c = world.spawn((3, Enemy, Dead));
+/// let d = world.spawn((4,));
+/// // Entities with an `i32` that are enemies or hazards
+/// let mut matched = world
+///     .query::<With<&i32, Or<&Enemy, &Hazard>>>()
+///     .iter()
+///     .map(|(e, _)| e)
+///     .collect::<Vec<_>>();
+/// matched.sort();
+/// assert_eq!(matched, [a, b, c]);
+/// // Entities with an `i32` that are neither living enemies nor hazards
+/// let matched = world
+///     .query::<Without<&i32, Or<Without<&Enemy, &Dead>, &Hazard>>>()
+///     .iter()
+///     .map(|(e, _)| e)
+///     .collect::<Vec<_>>();
+/// assert_eq!(matched.len(), 2);
+/// assert!(matched.contains(&c) && matched.contains(&d));
+/// ```
 #[derive(Debug, Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash)]
 pub enum Or<L, R> {
     /// Just an `L`
@@ -372,15 +539,35 @@ unsafe impl<L: Fetch, R: Fetch> Fetch for FetchOr<L, R> {
         state.map(|l| L::release(archetype, l), |r| R::release(archetype, r));
     }
 
+    fn filter(archetype: &Archetype, state: Self::State, since: u32) -> bool {
+        let (l, r) = state.split();
+        l.map_or(false, |l| L::filter(archetype, l, since))
+            || r.map_or(false, |r| R::filter(archetype, r, since))
+    }
+
     fn for_each_borrow(mut f: impl FnMut(TypeId, bool)) {
         L::for_each_borrow(&mut f);
         R::for_each_borrow(&mut f);
     }
+
+    fn for_each_access(mut f: impl FnMut(TypeId, Access)) {
+        L::for_each_access(&mut f);
+        R::for_each_access(&mut f);
+    }
+
+    fn includes_disabled() -> bool {
+        L::includes_disabled() || R::includes_disabled()
+    }
+
+    fn tracks_changes() -> bool {
+        L::tracks_changes() || R::tracks_changes()
+    }
 }
 
 /// Transforms query `Q` by skipping entities satisfying query `R`
 ///
-/// See also `QueryBorrow::without`.
+/// See also `QueryBorrow::without`. Entities are skipped if they have the components `R` refers
+/// to, so `R` mustn't contain [`Changed`] or [`Added`]; queries that do panic.
 ///
 /// # Example
 /// ```
@@ -409,6 +596,14 @@ impl<Q: Query, R: Query> Query for Without<Q, R> {
 
 unsafe impl<Q: QueryShared, R> QueryShared for Without<Q, R> {}
 
+impl<Q: SliceQuery, R: Query> SliceQuery for Without<Q, R> {
+    type Slices<'q> = Q::Slices<'q>;
+
+    unsafe fn slices<'q>(fetch: &Self::Fetch, len: usize) -> Self::Slices<'q> {
+        Q::slices(&fetch.0, len)
+    }
+}
+
 #[doc(hidden)]
 pub struct FetchWithout<F, G>(F, PhantomData<fn(G)>);
 
@@ -420,6 +615,7 @@ unsafe impl<F: Fetch, G: Fetch> Fetch for FetchWithout<F, G> {
     }
 
     fn access(archetype: &Archetype) -> Option<Access> {
+        assert_untracked::<G>();
         if G::access(archetype).is_some() {
             None
         } else {
@@ -431,6 +627,7 @@ unsafe impl<F: Fetch, G: Fetch> Fetch for FetchWithout<F, G> {
         F::borrow(archetype, state)
     }
     fn prepare(archetype: &Archetype) -> Option<Self::State> {
+        assert_untracked::<G>();
         if G::access(archetype).is_some() {
             return None;
         }
@@ -443,9 +640,36 @@ unsafe impl<F: Fetch, G: Fetch> Fetch for FetchWithout<F, G> {
         F::release(archetype, state)
     }
 
+    fn filter(archetype: &Archetype, state: Self::State, since: u32) -> bool {
+        F::filter(archetype, state, since)
+    }
+
     fn for_each_borrow(f: impl FnMut(TypeId, bool)) {
         F::for_each_borrow(f);
     }
+
+    fn for_each_access(mut f: impl FnMut(TypeId, Access)) {
+        F::for_each_access(&mut f);
+        G::for_each_access(|ty, _| f(ty, Access::Iterate));
+    }
+
+    fn includes_disabled() -> bool {
+        F::includes_disabled() || G::includes_disabled()
+    }
+
+    fn tracks_changes() -> bool {
+        F::tracks_changes()
+    }
+}
+
+/// Reject change filters as the excluded half of a [`Without`], which is decided by component
+/// types alone
+#[inline]
+fn assert_untracked<G: Fetch>() {
+    assert!(
+        !G::tracks_changes(),
+        "`Changed` and `Added` filters can't be excluded with `Without`"
+    );
 }
 
 impl<F: Clone, G> Clone for FetchWithout<F, G> {
@@ -488,6 +712,14 @@ impl<Q: Query, R: Query> Query for With<Q, R> {
 
 unsafe impl<Q: QueryShared, R> QueryShared for With<Q, R> {}
 
+impl<Q: SliceQuery, R: Query> SliceQuery for With<Q, R> {
+    type Slices<'q> = Q::Slices<'q>;
+
+    unsafe fn slices<'q>(fetch: &Self::Fetch, len: usize) -> Self::Slices<'q> {
+        Q::slices(&fetch.0, len)
+    }
+}
+
 #[doc(hidden)]
 pub struct FetchWith<F, G>(F, PhantomData<fn(G)>);
 
@@ -520,9 +752,27 @@ unsafe impl<F: Fetch, G: Fetch> Fetch for FetchWith<F, G> {
         F::release(archetype, state)
     }
 
+    fn filter(archetype: &Archetype, state: Self::State, since: u32) -> bool {
+        F::filter(archetype, state, since)
+            && G::prepare(archetype).map_or(false, |g| G::filter(archetype, g, since))
+    }
+
     fn for_each_borrow(f: impl FnMut(TypeId, bool)) {
         F::for_each_borrow(f);
     }
+
+    fn for_each_access(mut f: impl FnMut(TypeId, Access)) {
+        F::for_each_access(&mut f);
+        G::for_each_access(|ty, _| f(ty, Access::Iterate));
+    }
+
+    fn includes_disabled() -> bool {
+        F::includes_disabled() || G::includes_disabled()
+    }
+
+    fn tracks_changes() -> bool {
+        F::tracks_changes() || G::tracks_changes()
+    }
 }
 
 impl<F: Clone, G> Clone for FetchWith<F, G> {
@@ -535,6 +785,10 @@ impl<F: Clone, G> Clone for FetchWith<F, G> {
 /// A query that matches all entities, yielding `bool`s indicating whether each satisfies query `Q`
 ///
 /// Does not borrow any components, making it faster and more concurrency-friendly than `Option<Q>`.
+/// The result depends only on which components an entity has, never on their values, so it may be
+/// combined with unique borrows of the same components, e.g. `(&mut T, Satisfies<&T>)`, or
+/// executed while another query holds them uniquely. Whether `Q`'s references are shared or unique
+/// makes no difference.
 ///
 /// # Example
 /// ```
@@ -590,6 +844,14 @@ unsafe impl<F: Fetch> Fetch for FetchSatisfies<F> {
     fn release(_archetype: &Archetype, _state: Self::State) {}
 
     fn for_each_borrow(_: impl FnMut(TypeId, bool)) {}
+
+    fn for_each_access(mut f: impl FnMut(TypeId, Access)) {
+        F::for_each_access(|ty, _| f(ty, Access::Iterate));
+    }
+
+    fn includes_disabled() -> bool {
+        F::includes_disabled()
+    }
 }
 
 impl<T> Clone for FetchSatisfies<T> {
@@ -599,291 +861,1698 @@ impl<T> Clone for FetchSatisfies<T> {
     }
 }
 
-/// A borrow of a [`World`](crate::World) sufficient to execute the query `Q`
+/// A filter that matches entities satisfying exactly one of the queries in the tuple `T`
 ///
-/// Note that borrows are not released until this object is dropped.
-pub struct QueryBorrow<'w, Q: Query> {
-    world: &'w World,
-    borrowed: bool,
-    _marker: PhantomData<Q>,
+/// Useful for mutually exclusive marker components modeling a tagged union, e.g. an entity that
+/// should be exactly one of `Alive`, `Dead` or `Spawning`. Like [`Satisfies`], borrows no
+/// components, and matching depends only on which components an entity has. See also
+/// [`World::validate_exclusive`](crate::World::validate_exclusive).
+///
+/// # Example
+/// ```
+/// # use hecs::*;
+/// struct Alive;
+/// struct Dead;
+///
+/// let mut world = World::new();
+/// let a = world.spawn((Alive, 1));
+/// let b = world.spawn((Dead, 2));
+/// let c = world.spawn((Alive, Dead, 3));
+/// let d = world.spawn((4,));
+/// let mut entities = world.query::<With<&i32, OneOf<(&Alive, &Dead)>>>()
+///     .iter()
+///     .map(|(_, &i)| i)
+///     .collect::<Vec<_>>();
+/// entities.sort();
+/// assert_eq!(entities, [1, 2]);
+/// ```
+pub struct OneOf<T>(PhantomData<T>);
+
+impl<T: Query> Query for OneOf<T>
+where
+    T::Fetch: FetchAny,
+{
+    type Item<'q> = ();
+
+    type Fetch = FetchOneOf<T::Fetch>;
+
+    unsafe fn get<'q>(_: &Self::Fetch, _: usize) -> Self::Item<'q> {}
 }
 
-impl<'w, Q: Query> QueryBorrow<'w, Q> {
-    pub(crate) fn new(world: &'w World) -> Self {
-        Self {
-            world,
-            borrowed: false,
-            _marker: PhantomData,
-        }
-    }
+unsafe impl<T> QueryShared for OneOf<T> {}
 
-    /// Execute the query
-    // The lifetime narrowing here is required for soundness.
-    pub fn iter(&mut self) -> QueryIter<'_, Q> {
-        self.borrow();
-        unsafe { QueryIter::new(self.world) }
-    }
+/// Tuples of [`Fetch`]es whose matches can be counted
+#[doc(hidden)]
+pub trait FetchAny: Fetch {
+    /// Number of elements that `archetype` satisfies
+    fn count_matches(archetype: &Archetype) -> usize;
+}
 
-    /// Provide random access to the query results
-    pub fn view(&mut self) -> View<'_, Q> {
-        self.borrow();
-        unsafe { View::new(self.world.entities_meta(), self.world.archetypes_inner()) }
-    }
+#[doc(hidden)]
+pub struct FetchOneOf<F>(PhantomData<F>);
 
-    /// Like `iter`, but returns child iterators of at most `batch_size` elements
-    ///
-    /// Useful for distributing work over a threadpool.
-    // The lifetime narrowing here is required for soundness.
-    pub fn iter_batched(&mut self, batch_size: u32) -> BatchedIter<'_, Q> {
-        self.borrow();
-        unsafe {
-            BatchedIter::new(
-                self.world.entities_meta(),
-                self.world.archetypes_inner().iter(),
-                batch_size,
-            )
-        }
+unsafe impl<F: FetchAny> Fetch for FetchOneOf<F> {
+    type State = ();
+
+    fn dangling() -> Self {
+        Self(PhantomData)
     }
 
-    fn borrow(&mut self) {
-        if self.borrowed {
-            return;
-        }
-        start_borrow::<Q>(self.world.archetypes_inner());
-        self.borrowed = true;
+    fn access(archetype: &Archetype) -> Option<Access> {
+        (F::count_matches(archetype) == 1).then_some(Access::Iterate)
     }
 
-    /// Transform the query into one that requires another query be satisfied
-    ///
-    /// Convenient when the values of the components in the other query are not of interest.
-    ///
-    /// Equivalent to using a query type wrapped in `With`.
-    ///
-    /// # Example
-    /// ```
-    /// # use hecs::*;
-    /// let mut world = World::new();
-    /// let a = world.spawn((123, true, "abc"));
-    /// let b = world.spawn((456, false));
-    /// let c = world.spawn((42, "def"));
-    /// let entities = world.query::<&i32>()
-    ///     .with::<&bool>()
-    ///     .iter()
-    ///     .map(|(e, &i)| (e, i)) // Copy out of the world
-    ///     .collect::<Vec<_>>();
-    /// assert_eq!(entities.len(), 2);
-    /// assert!(entities.contains(&(a, 123)));
-    /// assert!(entities.contains(&(b, 456)));
-    /// ```
-    pub fn with<R: Query>(self) -> QueryBorrow<'w, With<Q, R>> {
-        self.transform()
+    fn borrow(_archetype: &Archetype, _state: Self::State) {}
+    fn prepare(archetype: &Archetype) -> Option<Self::State> {
+        (F::count_matches(archetype) == 1).then_some(())
+    }
+    fn execute(_archetype: &Archetype, _state: Self::State) -> Self {
+        Self(PhantomData)
     }
+    fn release(_archetype: &Archetype, _state: Self::State) {}
 
-    /// Transform the query into one that skips entities satisfying another
-    ///
-    /// Equivalent to using a query type wrapped in `Without`.
-    ///
-    /// # Example
-    /// ```
-    /// # use hecs::*;
-    /// let mut world = World::new();
-    /// let a = world.spawn((123, true, "abc"));
-    /// let b = world.spawn((456, false));
-    /// let c = world.spawn((42, "def"));
-    /// let entities = world.query::<&i32>()
-    ///     .without::<&bool>()
-    ///     .iter()
-    ///     .map(|(e, &i)| (e, i)) // Copy out of the world
-    ///     .collect::<Vec<_>>();
-    /// assert_eq!(entities, &[(c, 42)]);
-    /// ```
-    pub fn without<R: Query>(self) -> QueryBorrow<'w, Without<Q, R>> {
-        self.transform()
+    fn for_each_borrow(_: impl FnMut(TypeId, bool)) {}
+
+    fn for_each_access(mut f: impl FnMut(TypeId, Access)) {
+        F::for_each_access(|ty, _| f(ty, Access::Iterate));
     }
 
-    /// Helper to change the type of the query
-    fn transform<R: Query>(mut self) -> QueryBorrow<'w, R> {
-        let x = QueryBorrow {
-            world: self.world,
-            borrowed: self.borrowed,
-            _marker: PhantomData,
-        };
-        // Ensure `Drop` won't fire redundantly
-        self.borrowed = false;
-        x
+    fn includes_disabled() -> bool {
+        F::includes_disabled()
     }
 }
 
-unsafe impl<'w, Q: Query> Send for QueryBorrow<'w, Q> where for<'a> Q::Item<'a>: Send {}
-unsafe impl<'w, Q: Query> Sync for QueryBorrow<'w, Q> where for<'a> Q::Item<'a>: Send {}
-
-impl<'w, Q: Query> Drop for QueryBorrow<'w, Q> {
-    fn drop(&mut self) {
-        if self.borrowed {
-            release_borrow::<Q>(self.world.archetypes_inner());
-        }
+impl<F> Clone for FetchOneOf<F> {
+    #[inline]
+    fn clone(&self) -> Self {
+        Self(PhantomData)
     }
 }
 
-impl<'q, 'w, Q: Query> IntoIterator for &'q mut QueryBorrow<'w, Q> {
-    type Item = (Entity, Q::Item<'q>);
-    type IntoIter = QueryIter<'q, Q>;
+/// Query that yields the types of all of an entity's components
+///
+/// Borrows no components, so may be combined freely with other queries. Every entity in the same
+/// archetype yields the same slice. Pair with a [`TypeNameRegistry`](crate::TypeNameRegistry) to
+/// display the types.
+///
+/// # Example
+/// ```
+/// # use hecs::*;
+/// # use std::any::TypeId;
+/// let mut world = World::new();
+/// let a = world.spawn((123, true));
+/// let mut query = world.query_one::<ComponentTypes>(a).unwrap();
+/// let types = query.get().unwrap();
+/// assert_eq!(types.len(), 2);
+/// assert!(types.contains(&TypeId::of::<i32>()));
+/// assert!(types.contains(&TypeId::of::<bool>()));
+/// ```
+pub struct ComponentTypes;
 
-    fn into_iter(self) -> Self::IntoIter {
-        self.iter()
+impl Query for ComponentTypes {
+    type Item<'q> = &'q [TypeId];
+
+    type Fetch = FetchComponentTypes;
+
+    unsafe fn get<'q>(fetch: &Self::Fetch, _: usize) -> Self::Item<'q> {
+        core::slice::from_raw_parts(fetch.0.as_ptr(), fetch.1)
     }
 }
 
-/// Iterator over the set of entities with the components in `Q`
-pub struct QueryIter<'q, Q: Query> {
-    world: &'q World,
-    archetypes: core::ops::Range<usize>,
-    iter: ChunkIter<Q>,
-}
+unsafe impl QueryShared for ComponentTypes {}
 
-impl<'q, Q: Query> QueryIter<'q, Q> {
-    /// # Safety
+#[doc(hidden)]
+#[derive(Clone)]
+pub struct FetchComponentTypes(NonNull<TypeId>, usize);
+
+unsafe impl Fetch for FetchComponentTypes {
+    type State = ();
+
+    fn dangling() -> Self {
+        Self(NonNull::dangling(), 0)
+    }
+
+    fn access(_archetype: &Archetype) -> Option<Access> {
+        Some(Access::Iterate)
+    }
+
+    fn borrow(_archetype: &Archetype, _state: Self::State) {}
+    fn prepare(_archetype: &Archetype) -> Option<Self::State> {
+        Some(())
+    }
+    fn execute(archetype: &Archetype, _state: Self::State) -> Self {
+        let types = archetype.type_ids();
+        Self(
+            NonNull::new(types.as_ptr() as *mut TypeId).unwrap_or(NonNull::dangling()),
+            types.len(),
+        )
+    }
+    fn release(_archetype: &Archetype, _state: Self::State) {}
+
+    fn for_each_borrow(_: impl FnMut(TypeId, bool)) {}
+
+    fn for_each_access(_: impl FnMut(TypeId, Access)) {}
+}
+
+/// Marker component for entities that iteration should skip
+///
+/// Queries, [views](View), and [`World::despawn_matching`](crate::World::despawn_matching) skip
+/// entities having a `Disabled` component unless the query refers to `Disabled`, e.g. with
+/// `With<Q, &Disabled>`, or is wrapped in [`IncludeDisabled`]. Disabled entities keep their
+/// components, remain accessible by direct lookup such as [`World::get`](crate::World::get) and
+/// [`World::query_one`](crate::World::query_one), and are counted by
+/// [`World::len`](crate::World::len) and visited by [`World::iter`](crate::World::iter).
+///
+/// Usually added and removed with [`World::set_enabled`](crate::World::set_enabled).
+///
+/// # Example
+/// ```
+/// # use hecs::*;
+/// let mut world = World::new();
+/// let a = world.spawn((1,));
+/// let b = world.spawn((2,));
+/// world.set_enabled(b, false).unwrap();
+/// let visible = world.query::<&i32>().iter().map(|(e, _)| e).collect::<Vec<_>>();
+/// assert_eq!(visible, [a]);
+/// assert_eq!(world.query::<IncludeDisabled<&i32>>().iter().count(), 2);
+/// assert_eq!(world.query::<With<&i32, &Disabled>>().iter().count(), 1);
+/// ```
+#[derive(Debug, Default, Copy, Clone, Eq, PartialEq, Hash)]
+pub struct Disabled;
+
+/// Transforms query `Q` to also match [`Disabled`] entities
+pub struct IncludeDisabled<Q>(PhantomData<Q>);
+
+impl<Q: Query> Query for IncludeDisabled<Q> {
+    type Item<'q> = Q::Item<'q>;
+
+    type Fetch = FetchIncludeDisabled<Q::Fetch>;
+
+    unsafe fn get<'q>(fetch: &Self::Fetch, n: usize) -> Self::Item<'q> {
+        Q::get(&fetch.0, n)
+    }
+}
+
+unsafe impl<Q: QueryShared> QueryShared for IncludeDisabled<Q> {}
+
+#[doc(hidden)]
+pub struct FetchIncludeDisabled<F>(F);
+
+unsafe impl<F: Fetch> Fetch for FetchIncludeDisabled<F> {
+    type State = F::State;
+
+    fn dangling() -> Self {
+        Self(F::dangling())
+    }
+
+    fn access(archetype: &Archetype) -> Option<Access> {
+        F::access(archetype)
+    }
+
+    fn borrow(archetype: &Archetype, state: Self::State) {
+        F::borrow(archetype, state)
+    }
+    fn prepare(archetype: &Archetype) -> Option<Self::State> {
+        F::prepare(archetype)
+    }
+    fn execute(archetype: &Archetype, state: Self::State) -> Self {
+        Self(F::execute(archetype, state))
+    }
+    fn release(archetype: &Archetype, state: Self::State) {
+        F::release(archetype, state)
+    }
+
+    fn filter(archetype: &Archetype, state: Self::State, since: u32) -> bool {
+        F::filter(archetype, state, since)
+    }
+
+    fn for_each_borrow(f: impl FnMut(TypeId, bool)) {
+        F::for_each_borrow(f);
+    }
+
+    fn for_each_access(f: impl FnMut(TypeId, Access)) {
+        F::for_each_access(f);
+    }
+
+    fn includes_disabled() -> bool {
+        true
+    }
+
+    fn tracks_changes() -> bool {
+        F::tracks_changes()
+    }
+}
+
+impl<F: Clone> Clone for FetchIncludeDisabled<F> {
+    #[inline]
+    fn clone(&self) -> Self {
+        Self(self.0.clone())
+    }
+}
+
+/// A query that matches entities whose `T` component may have been mutated or inserted since a
+/// change tick
+///
+/// Yields `()`, so it's usually combined with other queries, e.g. in a tuple or as the second
+/// argument to [`With`]. Without a tick supplied by [`QueryBorrow::since`], [`QueryMut::since`],
+/// or a [`PreparedQuery`], every entity having a `T` matches.
+///
+/// Changes are tracked per archetype column, not per entity: uniquely borrowing any `T` in an
+/// archetype, e.g. with a `&mut T` query, marks every `T` in that archetype as changed. Entities
+/// moved to another archetype, e.g. by inserting an unrelated component, carry such marks with
+/// them.
+///
+/// # Example
+/// ```
+/// # use hecs::*;
+/// let mut world = World::new();
+/// let a = world.spawn((123, true));
+/// let b = world.spawn((456,));
+/// let tick = world.increment_change_tick();
+/// *world.get::<&mut bool>(a).unwrap() = false;
+/// let entities = world.query::<With<&i32, Changed<bool>>>()
+///     .since(tick)
+///     .iter()
+///     .map(|(e, &i)| (e, i))
+///     .collect::<Vec<_>>();
+/// assert_eq!(entities, &[(a, 123)]);
+/// ```
+pub struct Changed<T>(PhantomData<fn(T)>);
+
+impl<T: Component> Query for Changed<T> {
+    type Item<'q> = ();
+
+    type Fetch = FetchChanged<T>;
+
+    unsafe fn get<'q>(_: &Self::Fetch, _: usize) -> Self::Item<'q> {}
+}
+
+unsafe impl<T> QueryShared for Changed<T> {}
+
+#[doc(hidden)]
+pub struct FetchChanged<T>(PhantomData<fn(T)>);
+
+unsafe impl<T: Component> Fetch for FetchChanged<T> {
+    type State = usize;
+
+    fn dangling() -> Self {
+        Self(PhantomData)
+    }
+
+    fn access(archetype: &Archetype) -> Option<Access> {
+        if archetype.has::<T>() {
+            Some(Access::Iterate)
+        } else {
+            None
+        }
+    }
+
+    fn borrow(_archetype: &Archetype, _state: Self::State) {}
+    fn prepare(archetype: &Archetype) -> Option<Self::State> {
+        archetype.get_state::<T>()
+    }
+    fn execute(_archetype: &Archetype, _state: Self::State) -> Self {
+        Self(PhantomData)
+    }
+    fn release(_archetype: &Archetype, _state: Self::State) {}
+
+    fn filter(archetype: &Archetype, state: Self::State, since: u32) -> bool {
+        archetype.changed_since(state, since)
+    }
+
+    fn for_each_borrow(_: impl FnMut(TypeId, bool)) {}
+
+    fn for_each_access(mut f: impl FnMut(TypeId, Access)) {
+        f(TypeId::of::<T>(), Access::Iterate);
+    }
+
+    fn includes_disabled() -> bool {
+        TypeId::of::<T>() == TypeId::of::<Disabled>()
+    }
+
+    fn tracks_changes() -> bool {
+        true
+    }
+}
+
+impl<T> Clone for FetchChanged<T> {
+    #[inline]
+    fn clone(&self) -> Self {
+        Self(PhantomData)
+    }
+}
+
+/// A query that matches entities whose `T` component may have been inserted since a change tick
+///
+/// Like [`Changed`], but ignores mutation of existing components. Spawning an entity or inserting a
+/// `T` into it counts as an insertion.
+///
+/// # Example
+/// ```
+/// # use hecs::*;
+/// let mut world = World::new();
+/// let a = world.spawn((123,));
+/// let tick = world.increment_change_tick();
+/// let b = world.spawn((456, true));
+/// let entities = world.query::<With<&i32, Added<bool>>>()
+///     .since(tick)
+///     .iter()
+///     .map(|(e, &i)| (e, i))
+///     .collect::<Vec<_>>();
+/// assert_eq!(entities, &[(b, 456)]);
+/// ```
+pub struct Added<T>(PhantomData<fn(T)>);
+
+impl<T: Component> Query for Added<T> {
+    type Item<'q> = ();
+
+    type Fetch = FetchAdded<T>;
+
+    unsafe fn get<'q>(_: &Self::Fetch, _: usize) -> Self::Item<'q> {}
+}
+
+unsafe impl<T> QueryShared for Added<T> {}
+
+#[doc(hidden)]
+pub struct FetchAdded<T>(PhantomData<fn(T)>);
+
+unsafe impl<T: Component> Fetch for FetchAdded<T> {
+    type State = usize;
+
+    fn dangling() -> Self {
+        Self(PhantomData)
+    }
+
+    fn access(archetype: &Archetype) -> Option<Access> {
+        if archetype.has::<T>() {
+            Some(Access::Iterate)
+        } else {
+            None
+        }
+    }
+
+    fn borrow(_archetype: &Archetype, _state: Self::State) {}
+    fn prepare(archetype: &Archetype) -> Option<Self::State> {
+        archetype.get_state::<T>()
+    }
+    fn execute(_archetype: &Archetype, _state: Self::State) -> Self {
+        Self(PhantomData)
+    }
+    fn release(_archetype: &Archetype, _state: Self::State) {}
+
+    fn filter(archetype: &Archetype, state: Self::State, since: u32) -> bool {
+        archetype.added_since(state, since)
+    }
+
+    fn for_each_borrow(_: impl FnMut(TypeId, bool)) {}
+
+    fn for_each_access(mut f: impl FnMut(TypeId, Access)) {
+        f(TypeId::of::<T>(), Access::Iterate);
+    }
+
+    fn includes_disabled() -> bool {
+        TypeId::of::<T>() == TypeId::of::<Disabled>()
+    }
+
+    fn tracks_changes() -> bool {
+        true
+    }
+}
+
+impl<T> Clone for FetchAdded<T> {
+    #[inline]
+    fn clone(&self) -> Self {
+        Self(PhantomData)
+    }
+}
+
+/// Query element yielding a shared reference to a `T` component along with its change ticks
+///
+/// Like `&T`, but the item also reports when the component was last inserted or possibly mutated,
+/// for comparison with ticks from [`World::increment_change_tick`](crate::World::increment_change_tick).
+/// Unlike [`Changed`] and [`Added`], doesn't filter out any entities, leaving it to the caller to
+/// decide what to do with components that haven't changed. Like them, ticks are tracked per
+/// archetype column, so mutating any `T` in an archetype marks every `T` there as changed.
+///
+/// # Example
+/// ```
+/// # use hecs::*;
+/// let mut world = World::new();
+/// let a = world.spawn((123,));
+/// let tick = world.increment_change_tick();
+/// let b = world.spawn((456, true));
+/// for (e, x) in world.query::<Tracked<i32>>().iter() {
+///     assert_eq!(x.is_added_since(tick), e == b);
+/// }
+/// ```
+pub struct Tracked<'a, T> {
+    value: &'a T,
+    archetype: &'a Archetype,
+    state: usize,
+}
+
+impl<'a, T> Tracked<'a, T> {
+    /// Change tick at which the component was last possibly mutated or inserted
+    pub fn changed_tick(&self) -> u32 {
+        self.archetype.changed_tick(self.state)
+    }
+
+    /// Change tick at which the component was last possibly inserted
+    pub fn added_tick(&self) -> u32 {
+        self.archetype.added_tick(self.state)
+    }
+
+    /// Whether the component may have been mutated or inserted after `tick`
+    pub fn is_changed_since(&self, tick: u32) -> bool {
+        self.archetype.changed_since(self.state, tick)
+    }
+
+    /// Whether the component may have been inserted after `tick`
+    pub fn is_added_since(&self, tick: u32) -> bool {
+        self.archetype.added_since(self.state, tick)
+    }
+
+    /// The component, with the lifetime of the query rather than this wrapper
+    pub fn into_inner(self) -> &'a T {
+        self.value
+    }
+}
+
+impl<T> Deref for Tracked<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        self.value
+    }
+}
+
+impl<T: fmt::Debug> fmt::Debug for Tracked<'_, T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.value.fmt(f)
+    }
+}
+
+impl<'a, T: Component> Query for Tracked<'a, T> {
+    type Item<'q> = Tracked<'q, T>;
+
+    type Fetch = FetchTracked<T, false>;
+
+    unsafe fn get<'q>(fetch: &Self::Fetch, n: usize) -> Self::Item<'q> {
+        Tracked {
+            value: &*fetch.base.as_ptr().add(n),
+            archetype: &*fetch.archetype.as_ptr(),
+            state: fetch.state,
+        }
+    }
+}
+
+unsafe impl<T> QueryShared for Tracked<'_, T> {}
+
+/// Query element yielding a unique reference to a `T` component along with its change ticks
+///
+/// Like `&mut T`, but the component is only marked as changed when it's actually accessed through
+/// [`DerefMut`], so systems that only sometimes write don't cause spurious changes to be reported
+/// by [`Changed`]. See [`Tracked`] for details of the ticks.
+///
+/// # Example
+/// ```
+/// # use hecs::*;
+/// let mut world = World::new();
+/// world.spawn((1,));
+/// world.spawn((-1, true));
+/// let tick = world.increment_change_tick();
+/// for (_, mut x) in world.query_mut::<TrackedMut<i32>>() {
+///     if *x < 0 {
+///         *x = 0;
+///     }
+/// }
+/// let changed = world.query_mut::<With<&i32, Changed<i32>>>().since(tick);
+/// assert_eq!(changed.into_iter().map(|(_, &x)| x).collect::<Vec<_>>(), [0]);
+/// ```
+pub struct TrackedMut<'a, T> {
+    value: &'a mut T,
+    archetype: &'a Archetype,
+    state: usize,
+}
+
+impl<'a, T> TrackedMut<'a, T> {
+    /// Change tick at which the component was last possibly mutated or inserted
+    pub fn changed_tick(&self) -> u32 {
+        self.archetype.changed_tick(self.state)
+    }
+
+    /// Change tick at which the component was last possibly inserted
+    pub fn added_tick(&self) -> u32 {
+        self.archetype.added_tick(self.state)
+    }
+
+    /// Whether the component may have been mutated or inserted after `tick`
+    pub fn is_changed_since(&self, tick: u32) -> bool {
+        self.archetype.changed_since(self.state, tick)
+    }
+
+    /// Whether the component may have been inserted after `tick`
+    pub fn is_added_since(&self, tick: u32) -> bool {
+        self.archetype.added_since(self.state, tick)
+    }
+
+    /// Mutate the component without marking it as changed
+    ///
+    /// Useful e.g. for updating caches that observers of changes shouldn't care about.
+    pub fn bypass_change_detection(&mut self) -> &mut T {
+        self.value
+    }
+}
+
+impl<T> Deref for TrackedMut<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        self.value
+    }
+}
+
+impl<T> DerefMut for TrackedMut<'_, T> {
+    fn deref_mut(&mut self) -> &mut T {
+        self.archetype.mark_changed(self.state);
+        self.value
+    }
+}
+
+impl<T: fmt::Debug> fmt::Debug for TrackedMut<'_, T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.value.fmt(f)
+    }
+}
+
+impl<'a, T: Component> Query for TrackedMut<'a, T> {
+    type Item<'q> = TrackedMut<'q, T>;
+
+    type Fetch = FetchTracked<T, true>;
+
+    unsafe fn get<'q>(fetch: &Self::Fetch, n: usize) -> Self::Item<'q> {
+        TrackedMut {
+            value: &mut *fetch.base.as_ptr().add(n),
+            archetype: &*fetch.archetype.as_ptr(),
+            state: fetch.state,
+        }
+    }
+}
+
+#[doc(hidden)]
+pub struct FetchTracked<T, const UNIQUE: bool> {
+    base: NonNull<T>,
+    archetype: NonNull<Archetype>,
+    state: usize,
+}
+
+unsafe impl<T: Component, const UNIQUE: bool> Fetch for FetchTracked<T, UNIQUE> {
+    type State = usize;
+
+    fn dangling() -> Self {
+        Self {
+            base: NonNull::dangling(),
+            archetype: NonNull::dangling(),
+            state: 0,
+        }
+    }
+
+    fn access(archetype: &Archetype) -> Option<Access> {
+        if !archetype.has::<T>() {
+            return None;
+        }
+        Some(if UNIQUE { Access::Write } else { Access::Read })
+    }
+
+    fn borrow(archetype: &Archetype, state: Self::State) {
+        if UNIQUE {
+            archetype.borrow_mut_untracked::<T>(state);
+        } else {
+            archetype.borrow::<T>(state);
+        }
+    }
+    fn prepare(archetype: &Archetype) -> Option<Self::State> {
+        archetype.get_state::<T>()
+    }
+    fn execute(archetype: &Archetype, state: Self::State) -> Self {
+        Self {
+            base: archetype.get_base(state),
+            archetype: NonNull::from(archetype),
+            state,
+        }
+    }
+    fn release(archetype: &Archetype, state: Self::State) {
+        if UNIQUE {
+            archetype.release_mut::<T>(state);
+        } else {
+            archetype.release::<T>(state);
+        }
+    }
+
+    fn for_each_borrow(mut f: impl FnMut(TypeId, bool)) {
+        f(TypeId::of::<T>(), UNIQUE);
+    }
+
+    fn includes_disabled() -> bool {
+        TypeId::of::<T>() == TypeId::of::<Disabled>()
+    }
+}
+
+impl<T, const UNIQUE: bool> Clone for FetchTracked<T, UNIQUE> {
+    #[inline]
+    fn clone(&self) -> Self {
+        Self {
+            base: self.base,
+            archetype: self.archetype,
+            state: self.state,
+        }
+    }
+}
+
+/// A borrow of a [`World`](crate::World) sufficient to execute the query `Q`
+///
+/// Note that borrows are not released until this object is dropped.
+///
+/// Structural changes like spawning, despawning, inserting, and removing require `&mut World`, so
+/// the compiler rejects them while a `QueryBorrow` or its iterators are live; no runtime check is
+/// needed. Record such changes in a [`CommandBuffer`](crate::CommandBuffer) to be run afterwards
+/// instead.
+///
+/// ```compile_fail
+/// # use hecs::*;
+/// let mut world = World::new();
+/// world.spawn((1,));
+/// for (entity, _) in world.query::<&i32>().iter() {
+///     world.despawn(entity).unwrap(); // error: `world` is already borrowed
+/// }
+/// ```
+pub struct QueryBorrow<'w, Q: Query> {
+    world: &'w World,
+    borrowed: bool,
+    since: u32,
+    _marker: PhantomData<Q>,
+}
+
+impl<'w, Q: Query> QueryBorrow<'w, Q> {
+    pub(crate) fn new(world: &'w World) -> Self {
+        Self {
+            world,
+            borrowed: false,
+            since: 0,
+            _marker: PhantomData,
+        }
+    }
+
+    /// Execute the query
+    // The lifetime narrowing here is required for soundness.
+    pub fn iter(&mut self) -> QueryIter<'_, Q> {
+        self.borrow();
+        unsafe { QueryIter::new(self.world, self.since) }
+    }
+
+    /// Execute the query, yielding results in order of [`Entity::id`]
+    ///
+    /// Unlike [`iter`](Self::iter), whose order depends on how entities are laid out in storage,
+    /// the order is unaffected by unrelated spawns, despawns, and component insertions. Collects
+    /// and sorts every result up front, costing an allocation and O(n log n) time, so this is
+    /// suited to deterministic replays and tests rather than hot loops.
+    ///
+    /// # Example
+    /// ```
+    /// # use hecs::*;
+    /// let mut world = World::new();
+    /// let a = world.spawn((1, true));
+    /// let b = world.spawn((2,));
+    /// let ids = world.query::<&i32>().sorted_by_id().map(|(e, _)| e).collect::<Vec<_>>();
+    /// assert_eq!(ids, [a, b]);
+    /// ```
+    pub fn sorted_by_id(&mut self) -> vec::IntoIter<(Entity, Q::Item<'_>)> {
+        let mut results = self.iter().collect::<Vec<_>>();
+        results.sort_unstable_by_key(|&(entity, _)| entity);
+        results.into_iter()
+    }
+
+    /// Execute the query, yielding results in order of the key computed by `f` from each item
+    ///
+    /// Results with equal keys are ordered by [`Entity::id`]. `f` is called once per result. Like
+    /// [`sorted_by_id`](Self::sorted_by_id), allocates and takes O(n log n) time.
+    ///
+    /// # Example
+    /// ```
+    /// # use hecs::*;
+    /// struct Depth(i32);
+    /// let mut world = World::new();
+    /// let a = world.spawn((Depth(2),));
+    /// let b = world.spawn((Depth(-1),));
+    /// let c = world.spawn((Depth(2),));
+    /// let ids = world
+    ///     .query::<&Depth>()
+    ///     .sorted_by_key(|depth| depth.0)
+    ///     .map(|(e, _)| e)
+    ///     .collect::<Vec<_>>();
+    /// assert_eq!(ids, [b, a, c]);
+    /// ```
+    pub fn sorted_by_key<'q, K: Ord>(
+        &'q mut self,
+        mut f: impl FnMut(&Q::Item<'q>) -> K,
+    ) -> vec::IntoIter<(Entity, Q::Item<'q>)> {
+        let mut results = self.iter().collect::<Vec<_>>();
+        results.sort_by_cached_key(|(entity, item)| (f(item), *entity));
+        results.into_iter()
+    }
+
+    /// Execute the query, pairing each result with the element of `slice` at its [`Entity::id`]
+    ///
+    /// Yields `None` in place of the element for entities whose IDs are out of bounds. Suited to
+    /// external storage kept densely indexed by entity ID.
+    ///
+    /// # Example
+    /// ```
+    /// # use hecs::*;
+    /// let mut world = World::new();
+    /// let a = world.spawn((1,));
+    /// let b = world.spawn((2,));
+    /// let names = ["a"];
+    /// let mut results = world
+    ///     .query::<&i32>()
+    ///     .zip_by_id(&names)
+    ///     .map(|(e, &x, name)| (e, x, name.copied()))
+    ///     .collect::<Vec<_>>();
+    /// results.sort_by_key(|&(e, _, _)| e);
+    /// assert_eq!(results, [(a, 1, Some("a")), (b, 2, None)]);
+    /// ```
+    pub fn zip_by_id<'q, U>(
+        &'q mut self,
+        slice: &'q [U],
+    ) -> impl ExactSizeIterator<Item = (Entity, Q::Item<'q>, Option<&'q U>)> + 'q {
+        self.iter()
+            .map(move |(entity, item)| (entity, item, slice.get(entity.id() as usize)))
+    }
+
+    /// Execute the query, yielding each element of the slice `f` extracts from each result
+    ///
+    /// Suited to components holding collections. The elements borrow from the query rather than
+    /// from `f`'s argument, so they remain usable for as long as the query is borrowed, which is
+    /// awkward to achieve with `iter().flat_map(..)` when `f` takes its argument by reference.
+    ///
+    /// # Example
+    /// ```
+    /// # use hecs::*;
+    /// struct Inventory(Vec<&'static str>);
+    /// let mut world = World::new();
+    /// let a = world.spawn((Inventory(vec!["sword", "shield"]),));
+    /// world.spawn((Inventory(vec![]),));
+    /// let mut query = world.query::<&Inventory>();
+    /// let items = query.flatten_by(|inv| &inv.0).collect::<Vec<_>>();
+    /// assert_eq!(items, [(a, &"sword"), (a, &"shield")]);
+    /// ```
+    pub fn flatten_by<'q, C: 'q>(
+        &'q mut self,
+        mut f: impl FnMut(Q::Item<'q>) -> &'q [C] + 'q,
+    ) -> impl Iterator<Item = (Entity, &'q C)> + 'q {
+        self.iter()
+            .flat_map(move |(entity, item)| f(item).iter().map(move |x| (entity, x)))
+    }
+
+    /// Execute the query, skipping entities for which `pred` returns false
+    ///
+    /// Suited to filtering by runtime data rather than by components, which `Without` and `With`
+    /// are better for. `pred` is applied per entity, after archetype matching, so every entity
+    /// satisfying the query is still visited.
+    ///
+    /// # Example
+    /// ```
+    /// # use hecs::*;
+    /// let mut world = World::new();
+    /// let a = world.spawn((1,));
+    /// let b = world.spawn((2,));
+    /// let handled = [a];
+    /// let mut query = world.query::<&i32>();
+    /// let rest = query
+    ///     .filter_entities(|e| !handled.contains(&e))
+    ///     .collect::<Vec<_>>();
+    /// assert_eq!(rest, [(b, &2)]);
+    /// ```
+    pub fn filter_entities<'q>(
+        &'q mut self,
+        mut pred: impl FnMut(Entity) -> bool + 'q,
+    ) -> impl Iterator<Item = (Entity, Q::Item<'q>)> + 'q {
+        self.iter().filter(move |&(entity, _)| pred(entity))
+    }
+
+    /// Execute the query, skipping entities in `set`
+    ///
+    /// Shorthand for [`filter_entities`](Self::filter_entities) with a predicate testing membership
+    /// in `set`.
+    #[cfg(feature = "std")]
+    pub fn excluding<'q, S: core::hash::BuildHasher>(
+        &'q mut self,
+        set: &'q std::collections::HashSet<Entity, S>,
+    ) -> impl Iterator<Item = (Entity, Q::Item<'q>)> + 'q {
+        self.filter_entities(move |entity| !set.contains(&entity))
+    }
+
+    /// Get the only entity satisfying the query
+    ///
+    /// Fails if there are no or several such entities. Iteration stops at the first match, and
+    /// further matches are counted by archetype rather than visited, so this is cheap even when
+    /// many entities match.
+    ///
+    /// # Example
+    /// ```
+    /// # use hecs::*;
+    /// struct Player;
+    /// let mut world = World::new();
+    /// let a = world.spawn((Player, 100));
+    /// world.spawn((0,));
+    /// let (entity, (_, &health)) = world.query::<(&Player, &i32)>().single().unwrap();
+    /// assert_eq!((entity, health), (a, 100));
+    /// world.spawn((Player, 50));
+    /// assert_eq!(
+    ///     world.query::<&Player>().single().err(),
+    ///     Some(QuerySingleError::MultipleEntities { count: 2 })
+    /// );
+    /// ```
+    pub fn single(&mut self) -> Result<(Entity, Q::Item<'_>), QuerySingleError> {
+        single(self.iter())
+    }
+
+    /// Only consider changes recorded after `tick` when evaluating [`Changed`] and [`Added`]
+    ///
+    /// `tick` should be obtained from [`World::increment_change_tick`]. Affects iteration only;
+    /// random access through [`view`](Self::view) ignores change ticks.
+    pub fn since(mut self, tick: u32) -> Self {
+        self.since = tick;
+        self
+    }
+
+    /// Provide random access to the query results
+    pub fn view(&mut self) -> View<'_, Q> {
+        self.borrow();
+        unsafe { View::new(self.world.entities_meta(), self.world.archetypes_inner()) }
+    }
+
+    /// Execute the query, skipping entities not [tagged](World::set_tag) with `tag`
+    pub fn iter_in_tag(&mut self, tag: u8) -> InTag<'_, Q> {
+        InTag {
+            iter: self.iter(),
+            tag,
+        }
+    }
+
+    /// Like `iter`, but returns child iterators of at most `batch_size` elements
+    ///
+    /// Useful for distributing work over a threadpool. Batches never span multiple archetypes, and
+    /// are `Send` whenever the query's results are, so they can be handed to any executor. With
+    /// the `rayon` feature, `par_iter` does this for rayon's thread pool.
+    ///
+    /// # Example
+    #[cfg_attr(not(feature = "thread-local"), doc = "```")]
+    #[cfg_attr(feature = "thread-local", doc = "```ignore")]
+    /// # use hecs::*;
+    /// let mut world = World::new();
+    /// world.spawn_batch((0..1_000).map(|i| (i,)));
+    /// let mut query = world.query::<&i32>();
+    /// let batches = query.iter_batched(100).collect::<Vec<_>>();
+    /// let sum = std::thread::scope(|scope| {
+    ///     let workers = batches
+    ///         .into_iter()
+    ///         .map(|batch| scope.spawn(move || batch.map(|(_, &x)| x).sum::<i32>()))
+    ///         .collect::<Vec<_>>();
+    ///     workers.into_iter().map(|w| w.join().unwrap()).sum::<i32>()
+    /// });
+    /// assert_eq!(sum, (0..1_000).sum());
+    /// ```
+    // The lifetime narrowing here is required for soundness.
+    pub fn iter_batched(&mut self, batch_size: u32) -> BatchedIter<'_, Q> {
+        self.borrow();
+        unsafe {
+            BatchedIter::new(
+                self.world.entities_meta(),
+                self.world.archetypes_inner().iter(),
+                batch_size,
+                self.since,
+            )
+        }
+    }
+
+    /// Like `iter`, but distributes the results over rayon's thread pool
+    ///
+    /// Results arrive in no particular order. See [`ParIter`] for how work is divided.
+    ///
+    /// # Example
+    /// ```
+    /// # use hecs::*;
+    /// use rayon::prelude::*;
+    /// let mut world = World::new();
+    /// world.spawn_batch((0..1_000).map(|i| (i,)));
+    /// let sum = world.query::<&i32>().par_iter().map(|(_, &x)| x).sum::<i32>();
+    /// assert_eq!(sum, (0..1_000).sum());
+    /// ```
+    #[cfg(all(feature = "rayon", not(feature = "thread-local")))]
+    #[cfg_attr(docsrs, doc(cfg(feature = "rayon")))]
+    // The lifetime narrowing here is required for soundness.
+    pub fn par_iter(&mut self) -> ParIter<'_, Q> {
+        ParIter::new(self.iter_batched(u32::MAX))
+    }
+
+    fn borrow(&mut self) {
+        if self.borrowed {
+            return;
+        }
+        start_borrow::<Q>(self.world.archetypes_inner());
+        self.borrowed = true;
+    }
+
+    /// Transform the query into one that requires another query be satisfied
+    ///
+    /// Convenient when the values of the components in the other query are not of interest.
+    ///
+    /// Equivalent to using a query type wrapped in `With`.
+    ///
+    /// # Example
+    /// ```
+    /// # use hecs::*;
+    /// let mut world = World::new();
+    /// let a = world.spawn((123, true, "abc"));
+    /// let b = world.spawn((456, false));
+    /// let c = world.spawn((42, "def"));
+    /// let entities = world.query::<&i32>()
+    ///     .with::<&bool>()
+    ///     .iter()
+    ///     .map(|(e, &i)| (e, i)) // Copy out of the world
+    ///     .collect::<Vec<_>>();
+    /// assert_eq!(entities.len(), 2);
+    /// assert!(entities.contains(&(a, 123)));
+    /// assert!(entities.contains(&(b, 456)));
+    /// ```
+    pub fn with<R: Query>(self) -> QueryBorrow<'w, With<Q, R>> {
+        self.transform()
+    }
+
+    /// Transform the query into one that skips entities satisfying another
+    ///
+    /// Equivalent to using a query type wrapped in `Without`.
+    ///
+    /// # Example
+    /// ```
+    /// # use hecs::*;
+    /// let mut world = World::new();
+    /// let a = world.spawn((123, true, "abc"));
+    /// let b = world.spawn((456, false));
+    /// let c = world.spawn((42, "def"));
+    /// let entities = world.query::<&i32>()
+    ///     .without::<&bool>()
+    ///     .iter()
+    ///     .map(|(e, &i)| (e, i)) // Copy out of the world
+    ///     .collect::<Vec<_>>();
+    /// assert_eq!(entities, &[(c, 42)]);
+    /// ```
+    pub fn without<R: Query>(self) -> QueryBorrow<'w, Without<Q, R>> {
+        self.transform()
+    }
+
+    /// Helper to change the type of the query
+    fn transform<R: Query>(mut self) -> QueryBorrow<'w, R> {
+        let x = QueryBorrow {
+            world: self.world,
+            borrowed: self.borrowed,
+            since: self.since,
+            _marker: PhantomData,
+        };
+        // Ensure `Drop` won't fire redundantly
+        self.borrowed = false;
+        x
+    }
+}
+
+#[cfg(not(feature = "thread-local"))]
+unsafe impl<'w, Q: Query> Send for QueryBorrow<'w, Q> where for<'a> Q::Item<'a>: Send {}
+#[cfg(not(feature = "thread-local"))]
+unsafe impl<'w, Q: Query> Sync for QueryBorrow<'w, Q> where for<'a> Q::Item<'a>: Send {}
+
+impl<'w, Q: Query> Drop for QueryBorrow<'w, Q> {
+    fn drop(&mut self) {
+        if self.borrowed {
+            release_borrow::<Q>(self.world.archetypes_inner());
+        }
+    }
+}
+
+impl<'q, 'w, Q: Query> IntoIterator for &'q mut QueryBorrow<'w, Q> {
+    type Item = (Entity, Q::Item<'q>);
+    type IntoIter = QueryIter<'q, Q>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+/// Iterator over the set of entities with the components in `Q`
+pub struct QueryIter<'q, Q: Query> {
+    world: &'q World,
+    archetypes: core::ops::Range<usize>,
+    iter: ChunkIter<Q>,
+    since: u32,
+}
+
+impl<'q, Q: Query> QueryIter<'q, Q> {
+    /// # Safety
+    ///
+    /// `'q` must be sufficient to guarantee that `Q` cannot violate borrow safety, either with
+    /// dynamic borrow checks or by representing exclusive access to the `World`.
+    unsafe fn new(world: &'q World, since: u32) -> Self {
+        let n = world.archetypes().len();
+        Self {
+            world,
+            archetypes: 0..n,
+            iter: ChunkIter::empty(),
+            since,
+        }
+    }
+
+    /// Advance query to the next archetype
+    ///
+    /// Outlined from `Iterator::next` for improved iteration performance.
+    fn next_archetype(&mut self) -> Option<()> {
+        let archetype = self.world.visit_order(self.archetypes.next()?);
+        let archetype = unsafe { self.world.archetypes_inner().get_unchecked(archetype) };
+        let state = prepare_iter::<Q::Fetch>(archetype)
+            .filter(|&state| Q::Fetch::filter(archetype, state, self.since));
+        let fetch = state.map(|state| Q::Fetch::execute(archetype, state));
+        self.iter = fetch.map_or(ChunkIter::empty(), |fetch| ChunkIter::new(archetype, fetch));
+        Some(())
+    }
+}
+
+#[cfg(not(feature = "thread-local"))]
+unsafe impl<'q, Q: Query> Send for QueryIter<'q, Q> where for<'a> Q::Item<'a>: Send {}
+#[cfg(not(feature = "thread-local"))]
+unsafe impl<'q, Q: Query> Sync for QueryIter<'q, Q> where for<'a> Q::Item<'a>: Send {}
+
+impl<'q, Q: Query> Iterator for QueryIter<'q, Q> {
+    type Item = (Entity, Q::Item<'q>);
+
+    #[inline(always)]
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            match unsafe { self.iter.next() } {
+                None => {
+                    self.next_archetype()?;
+                    continue;
+                }
+                Some((id, components)) => {
+                    return Some((
+                        Entity {
+                            id,
+                            generation: unsafe {
+                                self.world
+                                    .entities_meta()
+                                    .get_unchecked(id as usize)
+                                    .generation
+                            },
+                        },
+                        components,
+                    ));
+                }
+            }
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let n = self.len();
+        (n, Some(n))
+    }
+}
+
+impl<'q, Q: Query> ExactSizeIterator for QueryIter<'q, Q> {
+    fn len(&self) -> usize {
+        self.archetypes
+            .clone()
+            .map(|x| unsafe {
+                self.world
+                    .archetypes_inner()
+                    .get_unchecked(self.world.visit_order(x))
+            })
+            .filter(|&x| {
+                prepare_iter::<Q::Fetch>(x)
+                    .map_or(false, |state| Q::Fetch::filter(x, state, self.since))
+            })
+            .map(|x| x.len() as usize)
+            .sum::<usize>()
+            + self.iter.remaining()
+    }
+}
+
+/// Iterator over the entities with a certain [tag](World::set_tag) and the components in `Q`
+///
+/// Returned by [`QueryBorrow::iter_in_tag`] and [`QueryMut::into_iter_in_tag`].
+pub struct InTag<'q, Q: Query> {
+    iter: QueryIter<'q, Q>,
+    tag: u8,
+}
+
+impl<'q, Q: Query> Iterator for InTag<'q, Q> {
+    type Item = (Entity, Q::Item<'q>);
+
+    #[inline]
+    fn next(&mut self) -> Option<Self::Item> {
+        let world = self.iter.world;
+        let tag = self.tag;
+        self.iter
+            .by_ref()
+            .find(|&(entity, _)| world.entity_tag(entity.id) == tag)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (0, Some(self.iter.len()))
+    }
+}
+
+/// A query builder that's convertible directly into an iterator
+pub struct QueryMut<'q, Q: Query> {
+    iter: QueryIter<'q, Q>,
+}
+
+impl<'q, Q: Query> QueryMut<'q, Q> {
+    pub(crate) fn new(world: &'q mut World) -> Self {
+        assert_borrow::<Q>();
+
+        Self {
+            iter: unsafe { QueryIter::new(world, 0) },
+        }
+    }
+
+    /// Only consider changes recorded after `tick` when evaluating [`Changed`] and [`Added`]
     ///
-    /// `'q` must be sufficient to guarantee that `Q` cannot violate borrow safety, either with
-    /// dynamic borrow checks or by representing exclusive access to the `World`.
-    unsafe fn new(world: &'q World) -> Self {
-        let n = world.archetypes().len();
+    /// See [`QueryBorrow::since`].
+    pub fn since(mut self, tick: u32) -> Self {
+        self.iter.since = tick;
+        self
+    }
+
+    /// Get the only entity satisfying the query
+    ///
+    /// See [`QueryBorrow::single`].
+    pub fn single(self) -> Result<(Entity, Q::Item<'q>), QuerySingleError> {
+        single(self.iter)
+    }
+
+    /// Provide random access to the query results
+    pub fn view(&mut self) -> View<'_, Q> {
+        unsafe {
+            View::new(
+                self.iter.world.entities_meta(),
+                self.iter.world.archetypes_inner(),
+            )
+        }
+    }
+
+    /// Transform the query into one that requires another query be satisfied
+    ///
+    /// See `QueryBorrow::with`
+    pub fn with<R: Query>(self) -> QueryMut<'q, With<Q, R>> {
+        self.transform()
+    }
+
+    /// Transform the query into one that skips entities satisfying another
+    ///
+    /// See `QueryBorrow::without`
+    pub fn without<R: Query>(self) -> QueryMut<'q, Without<Q, R>> {
+        self.transform()
+    }
+
+    /// Helper to change the type of the query
+    fn transform<R: Query>(self) -> QueryMut<'q, R> {
+        QueryMut {
+            iter: unsafe { QueryIter::new(self.iter.world, self.iter.since) },
+        }
+    }
+
+    /// Like `into_iter`, but skips entities not [tagged](World::set_tag) with `tag`
+    pub fn into_iter_in_tag(self, tag: u8) -> InTag<'q, Q> {
+        InTag {
+            iter: self.iter,
+            tag,
+        }
+    }
+
+    /// Like `into_iter`, but returns child iterators of at most `batch_size` elements
+    ///
+    /// Useful for distributing work over a threadpool. No two batches ever expose the same entity,
+    /// so batches of a query containing `&mut` may be processed concurrently. See
+    /// [`QueryBorrow::iter_batched`] for an example.
+    pub fn into_iter_batched(self, batch_size: u32) -> BatchedIter<'q, Q> {
+        unsafe {
+            BatchedIter::new(
+                self.iter.world.entities_meta(),
+                self.iter.world.archetypes_inner().iter(),
+                batch_size,
+                self.iter.since,
+            )
+        }
+    }
+
+    /// Like `into_iter`, but distributes the results over rayon's thread pool
+    ///
+    /// Results arrive in no particular order. No two workers ever expose the same entity, so
+    /// queries containing `&mut` are supported.
+    ///
+    /// # Example
+    /// ```
+    /// # use hecs::*;
+    /// use rayon::prelude::*;
+    /// let mut world = World::new();
+    /// world.spawn_batch((0..1_000).map(|i| (i,)));
+    /// world
+    ///     .query_mut::<&mut i32>()
+    ///     .into_par_iter()
+    ///     .for_each(|(_, x)| *x *= 2);
+    /// let sum = world.query_mut::<&i32>().into_iter().map(|(_, &x)| x).sum::<i32>();
+    /// assert_eq!(sum, (0..1_000).map(|x| x * 2).sum());
+    /// ```
+    #[cfg(all(feature = "rayon", not(feature = "thread-local")))]
+    #[cfg_attr(docsrs, doc(cfg(feature = "rayon")))]
+    pub fn into_par_iter(self) -> ParIter<'q, Q>
+    where
+        Q: 'q,
+    {
+        ParIter::new(self.into_iter_batched(u32::MAX))
+    }
+
+    /// Divide the query results into one chunk per matching archetype
+    ///
+    /// No two chunks ever expose the same entity, so chunks of a query containing `&mut` may be
+    /// sent to and iterated on different threads without further synchronization, making this a
+    /// building block for custom job systems. Archetypes with no matching entities are omitted.
+    ///
+    /// # Example
+    #[cfg_attr(not(feature = "thread-local"), doc = "```")]
+    #[cfg_attr(feature = "thread-local", doc = "```ignore")]
+    /// # use hecs::*;
+    /// let mut world = World::new();
+    /// world.spawn((1, true));
+    /// world.spawn((2, "abc"));
+    /// let chunks = world.query_mut::<&mut i32>().split_at_archetype();
+    /// assert_eq!(chunks.len(), 2);
+    /// std::thread::scope(|scope| {
+    ///     for chunk in chunks {
+    ///         scope.spawn(move || chunk.for_each(|(_, x)| *x *= 10));
+    ///     }
+    /// });
+    /// assert_eq!(world.query_mut::<&i32>().into_iter().map(|(_, &x)| x).sum::<i32>(), 30);
+    /// ```
+    pub fn split_at_archetype(self) -> Vec<QueryArchetypeChunk<'q, Q>> {
+        let meta = self.iter.world.entities_meta();
+        self.iter
+            .world
+            .archetypes_inner()
+            .iter()
+            .filter(|archetype| !archetype.is_empty())
+            .filter_map(|archetype| {
+                let state = prepare_iter::<Q::Fetch>(archetype)
+                    .filter(|&state| Q::Fetch::filter(archetype, state, self.iter.since))?;
+                let fetch = Q::Fetch::execute(archetype, state);
+                Some(QueryArchetypeChunk {
+                    meta,
+                    archetype,
+                    state: ChunkIter::new(archetype, fetch),
+                })
+            })
+            .collect()
+    }
+
+    /// Iterate over the components of each matching archetype as parallel slices
+    ///
+    /// Each [`ArchetypeSlices`] exposes a slice per element of `Q`, all indexed like
+    /// [`ArchetypeSlices::ids`], allowing tight per-column loops that the compiler can readily
+    /// vectorize. Only queries made up of `&T` and `&mut T` have a slice form; see [`SliceQuery`].
+    /// Archetypes with no entities are omitted.
+    ///
+    /// # Example
+    /// ```
+    /// # use hecs::*;
+    /// struct Position(f32);
+    /// struct Velocity(f32);
+    /// let mut world = World::new();
+    /// let a = world.spawn((Position(0.0), Velocity(1.0)));
+    /// world.spawn((Position(0.0), Velocity(2.0), true));
+    /// for chunk in world
+    ///     .query_mut::<(&mut Position, &Velocity)>()
+    ///     .into_archetype_slices()
+    /// {
+    ///     let (pos, vel) = chunk.into_slices();
+    ///     for (p, v) in pos.iter_mut().zip(vel) {
+    ///         p.0 += v.0;
+    ///     }
+    /// }
+    /// assert_eq!(world.get::<&Position>(a).unwrap().0, 1.0);
+    /// ```
+    pub fn into_archetype_slices(self) -> ArchetypeSlicesIter<'q, Q>
+    where
+        Q: SliceQuery,
+    {
+        ArchetypeSlicesIter {
+            meta: self.iter.world.entities_meta(),
+            archetypes: self.iter.world.archetypes_inner().iter(),
+            since: self.iter.since,
+            marker: PhantomData,
+        }
+    }
+}
+
+/// Iterator over the archetypes matched by a [`QueryMut`], obtained from
+/// [`QueryMut::into_archetype_slices`]
+pub struct ArchetypeSlicesIter<'q, Q> {
+    meta: &'q [EntityMeta],
+    archetypes: SliceIter<'q, Archetype>,
+    since: u32,
+    marker: PhantomData<fn() -> Q>,
+}
+
+impl<'q, Q: SliceQuery> Iterator for ArchetypeSlicesIter<'q, Q> {
+    type Item = ArchetypeSlices<'q, Q>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let archetype = self.archetypes.next()?;
+            if archetype.is_empty() {
+                continue;
+            }
+            let state = match prepare_iter::<Q::Fetch>(archetype) {
+                Some(state) if Q::Fetch::filter(archetype, state, self.since) => state,
+                _ => continue,
+            };
+            let fetch = Q::Fetch::execute(archetype, state);
+            return Some(ArchetypeSlices {
+                meta: self.meta,
+                archetype,
+                // Safety: the `QueryMut` this came from uniquely borrowed the world for `'q`, and
+                // each archetype is visited only once
+                slices: unsafe { Q::slices(&fetch, archetype.len() as usize) },
+            });
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (0, Some(self.archetypes.len()))
+    }
+}
+
+/// The components of every entity in a single archetype matched by a [`QueryMut`], as parallel
+/// slices
+///
+/// Obtained from [`QueryMut::into_archetype_slices`].
+pub struct ArchetypeSlices<'q, Q: SliceQuery> {
+    meta: &'q [EntityMeta],
+    archetype: &'q Archetype,
+    slices: Q::Slices<'q>,
+}
+
+impl<'q, Q: SliceQuery> ArchetypeSlices<'q, Q> {
+    /// The archetype containing every entity in this chunk
+    pub fn archetype(&self) -> &'q Archetype {
+        self.archetype
+    }
+
+    /// Number of entities in this chunk, and hence the length of each slice
+    pub fn len(&self) -> usize {
+        self.archetype.len() as usize
+    }
+
+    /// Whether this chunk contains no entities, which is never true of chunks produced by
+    /// [`QueryMut::into_archetype_slices`]
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// IDs of the entities in this chunk, in the same order as each slice
+    pub fn ids(&self) -> &'q [u32] {
+        self.archetype.ids()
+    }
+
+    /// The entities in this chunk, in the same order as each slice
+    pub fn entities(&self) -> impl ExactSizeIterator<Item = Entity> + 'q {
+        let meta = self.meta;
+        self.ids().iter().map(move |&id| Entity {
+            id,
+            generation: meta[id as usize].generation,
+        })
+    }
+
+    /// The slices of the components matched by `Q`
+    pub fn into_slices(self) -> Q::Slices<'q> {
+        self.slices
+    }
+}
+
+/// The entities of a single archetype matched by a [`QueryMut`], obtained from
+/// [`QueryMut::split_at_archetype`]
+pub struct QueryArchetypeChunk<'q, Q: Query> {
+    meta: &'q [EntityMeta],
+    archetype: &'q Archetype,
+    state: ChunkIter<Q>,
+}
+
+impl<'q, Q: Query> QueryArchetypeChunk<'q, Q> {
+    /// The archetype containing every entity in this chunk
+    pub fn archetype(&self) -> &'q Archetype {
+        self.archetype
+    }
+}
+
+impl<'q, Q: Query> Iterator for QueryArchetypeChunk<'q, Q> {
+    type Item = (Entity, Q::Item<'q>);
+
+    #[inline]
+    fn next(&mut self) -> Option<Self::Item> {
+        let (id, components) = unsafe { self.state.next()? };
+        Some((
+            Entity {
+                id,
+                generation: self.meta[id as usize].generation,
+            },
+            components,
+        ))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let n = self.state.remaining();
+        (n, Some(n))
+    }
+}
+
+impl<'q, Q: Query> ExactSizeIterator for QueryArchetypeChunk<'q, Q> {}
+
+#[cfg(not(feature = "thread-local"))]
+unsafe impl<'q, Q: Query> Send for QueryArchetypeChunk<'q, Q> where for<'a> Q::Item<'a>: Send {}
+#[cfg(not(feature = "thread-local"))]
+unsafe impl<'q, Q: Query> Sync for QueryArchetypeChunk<'q, Q> where for<'a> Q::Item<'a>: Send {}
+
+impl<'q, Q: Query> IntoIterator for QueryMut<'q, Q> {
+    type Item = <QueryIter<'q, Q> as Iterator>::Item;
+    type IntoIter = QueryIter<'q, Q>;
+
+    #[inline]
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter
+    }
+}
+
+/// Position in a query's results that can be iterated from incrementally, obtained from
+/// [`World::query_cursor`]
+///
+/// Useful for spreading expensive work over several frames. Each
+/// [`advance`](Self::advance) resumes where the previous one stopped, and once a pass over every
+/// matching entity completes, the next `advance` begins a new pass.
+///
+/// Archetypes are visited in the same order as by iteration, including under
+/// [`World::set_deterministic_order`]. The cursor records a position within an archetype, so
+/// structural changes between advances can't cause unsound access, but may affect which entities
+/// are visited in the current pass:
+/// - Archetypes are never removed, so a change to the
+///   [`ArchetypesGeneration`](crate::ArchetypesGeneration) doesn't invalidate the position.
+///   Entities of newly created archetypes are visited later in the same pass if the archetype is
+///   visited after the cursor's, as is always the case unless deterministic order is enabled.
+/// - Despawning or moving an entity out of an archetype moves that archetype's last entity into its
+///   slot, so an entity located after the cursor may be skipped until the next pass. Entities moved
+///   into archetypes the cursor hasn't reached yet may be visited twice.
+/// - Using the cursor with a different world than the last `advance` restarts the pass.
+pub struct QueryCursor<Q: Query> {
+    world: u64,
+    /// Position of the current archetype in visit order
+    position: u32,
+    /// Index of the current archetype, to detect archetypes being created earlier in visit order
+    archetype: u32,
+    index: u32,
+    finished: bool,
+    _marker: PhantomData<fn(Q)>,
+}
+
+impl<Q: Query> QueryCursor<Q> {
+    pub(crate) fn new(world: &World) -> Self {
         Self {
-            world,
-            archetypes: 0..n,
-            iter: ChunkIter::empty(),
+            world: world.memo().0,
+            position: 0,
+            archetype: 0,
+            index: 0,
+            finished: false,
+            _marker: PhantomData,
         }
     }
 
-    /// Advance query to the next archetype
+    /// Iterate over at most `max` more entities matching `Q`, advancing the cursor past each entity
+    /// as it's yielded
     ///
-    /// Outlined from `Iterator::next` for improved iteration performance.
-    fn next_archetype(&mut self) -> Option<()> {
-        let archetype = self.archetypes.next()?;
-        let archetype = unsafe { self.world.archetypes_inner().get_unchecked(archetype) };
-        let state = Q::Fetch::prepare(archetype);
-        let fetch = state.map(|state| Q::Fetch::execute(archetype, state));
-        self.iter = fetch.map_or(ChunkIter::empty(), |fetch| ChunkIter::new(archetype, fetch));
-        Some(())
+    /// # Example
+    /// ```
+    /// # use hecs::*;
+    /// let mut world = World::new();
+    /// world.spawn_batch((0..5).map(|i| (i,)));
+    /// let mut cursor = world.query_cursor::<&mut i32>();
+    /// assert_eq!(cursor.advance(&mut world, 3).count(), 3);
+    /// assert_eq!(cursor.advance(&mut world, 3).count(), 2);
+    /// assert!(cursor.is_finished());
+    /// // A new pass begins
+    /// assert_eq!(cursor.advance(&mut world, 3).count(), 3);
+    /// ```
+    pub fn advance<'a>(&'a mut self, world: &'a mut World, max: usize) -> QueryCursorIter<'a, Q> {
+        assert_borrow::<Q>();
+        let id = world.memo().0;
+        if self.finished || self.world != id {
+            self.world = id;
+            self.reset();
+        }
+        // Archetypes are never removed, so the position remains in bounds, but it shifts if
+        // archetypes are created earlier in deterministic order
+        if world.visit_order(self.position as usize) != self.archetype as usize {
+            self.position = (0..world.archetypes().len())
+                .position(|n| world.visit_order(n) == self.archetype as usize)
+                .unwrap() as u32;
+        }
+        let archetypes = world.archetypes_inner();
+        let chunk = Self::chunk(&archetypes[self.archetype as usize], self.index);
+        QueryCursorIter {
+            meta: world.entities_meta(),
+            world,
+            archetypes,
+            cursor: self,
+            chunk,
+            remaining: max,
+        }
+    }
+
+    /// Whether the most recent [`advance`](Self::advance) reached the end of a pass
+    pub fn is_finished(&self) -> bool {
+        self.finished
+    }
+
+    /// Begin a new pass from the first matching entity
+    pub fn reset(&mut self) {
+        self.position = 0;
+        // The archetype with no components is first in every order
+        self.archetype = 0;
+        self.index = 0;
+        self.finished = false;
+    }
+
+    fn chunk(archetype: &Archetype, index: u32) -> ChunkIter<Q> {
+        match prepare_iter::<Q::Fetch>(archetype) {
+            Some(state) if index < archetype.len() => {
+                let mut chunk = ChunkIter::new(archetype, Q::Fetch::execute(archetype, state));
+                chunk.position = index as usize;
+                chunk
+            }
+            _ => ChunkIter::empty(),
+        }
     }
 }
 
-unsafe impl<'q, Q: Query> Send for QueryIter<'q, Q> where for<'a> Q::Item<'a>: Send {}
-unsafe impl<'q, Q: Query> Sync for QueryIter<'q, Q> where for<'a> Q::Item<'a>: Send {}
+/// Iterator returned by [`QueryCursor::advance`]
+pub struct QueryCursorIter<'a, Q: Query> {
+    meta: &'a [EntityMeta],
+    world: &'a World,
+    archetypes: &'a [Archetype],
+    cursor: &'a mut QueryCursor<Q>,
+    chunk: ChunkIter<Q>,
+    remaining: usize,
+}
 
-impl<'q, Q: Query> Iterator for QueryIter<'q, Q> {
-    type Item = (Entity, Q::Item<'q>);
+impl<'a, Q: Query> Iterator for QueryCursorIter<'a, Q> {
+    type Item = (Entity, Q::Item<'a>);
 
-    #[inline(always)]
     fn next(&mut self) -> Option<Self::Item> {
+        if self.remaining == 0 || self.cursor.finished {
+            return None;
+        }
         loop {
-            match unsafe { self.iter.next() } {
-                None => {
-                    self.next_archetype()?;
-                    continue;
-                }
+            match unsafe { self.chunk.next() } {
                 Some((id, components)) => {
+                    self.remaining -= 1;
+                    self.cursor.index += 1;
+                    if self.remaining == 0 && self.chunk.remaining() == 0 {
+                        self.skip_exhausted();
+                    }
                     return Some((
                         Entity {
                             id,
-                            generation: unsafe {
-                                self.world
-                                    .entities_meta()
-                                    .get_unchecked(id as usize)
-                                    .generation
-                            },
+                            generation: self.meta[id as usize].generation,
                         },
                         components,
                     ));
                 }
+                None => {
+                    if !self.next_archetype() {
+                        return None;
+                    }
+                    let archetype = &self.archetypes[self.cursor.archetype as usize];
+                    self.chunk = QueryCursor::chunk(archetype, 0);
+                }
             }
         }
     }
 
     fn size_hint(&self) -> (usize, Option<usize>) {
-        let n = self.len();
-        (n, Some(n))
+        (
+            self.chunk.remaining().min(self.remaining),
+            Some(self.remaining),
+        )
     }
 }
 
-impl<'q, Q: Query> ExactSizeIterator for QueryIter<'q, Q> {
-    fn len(&self) -> usize {
-        self.archetypes
-            .clone()
-            .map(|x| unsafe { self.world.archetypes_inner().get_unchecked(x) })
-            .filter(|&x| Q::Fetch::access(x).is_some())
-            .map(|x| x.len() as usize)
-            .sum::<usize>()
-            + self.iter.remaining()
-    }
-}
-
-/// A query builder that's convertible directly into an iterator
-pub struct QueryMut<'q, Q: Query> {
-    iter: QueryIter<'q, Q>,
-}
-
-impl<'q, Q: Query> QueryMut<'q, Q> {
-    pub(crate) fn new(world: &'q mut World) -> Self {
-        assert_borrow::<Q>();
-
-        Self {
-            iter: unsafe { QueryIter::new(world) },
-        }
-    }
-
-    /// Provide random access to the query results
-    pub fn view(&mut self) -> View<'_, Q> {
-        unsafe {
-            View::new(
-                self.iter.world.entities_meta(),
-                self.iter.world.archetypes_inner(),
-            )
-        }
-    }
-
-    /// Transform the query into one that requires another query be satisfied
-    ///
-    /// See `QueryBorrow::with`
-    pub fn with<R: Query>(self) -> QueryMut<'q, With<Q, R>> {
-        self.transform()
-    }
-
-    /// Transform the query into one that skips entities satisfying another
-    ///
-    /// See `QueryBorrow::without`
-    pub fn without<R: Query>(self) -> QueryMut<'q, Without<Q, R>> {
-        self.transform()
-    }
-
-    /// Helper to change the type of the query
-    fn transform<R: Query>(self) -> QueryMut<'q, R> {
-        QueryMut {
-            iter: unsafe { QueryIter::new(self.iter.world) },
+impl<Q: Query> QueryCursorIter<'_, Q> {
+    /// Move the cursor to the start of the next archetype in visit order, or finish the pass
+    fn next_archetype(&mut self) -> bool {
+        let position = self.cursor.position + 1;
+        if position as usize >= self.archetypes.len() {
+            self.cursor.finished = true;
+            return false;
         }
+        self.cursor.position = position;
+        self.cursor.archetype = self.world.visit_order(position as usize) as u32;
+        self.cursor.index = 0;
+        true
     }
 
-    /// Like `into_iter`, but returns child iterators of at most `batch_size` elements
+    /// Move the cursor past archetypes with no entities left to visit, so that a pass is reported
+    /// finished as soon as its last entity is yielded
     ///
-    /// Useful for distributing work over a threadpool.
-    pub fn into_iter_batched(self, batch_size: u32) -> BatchedIter<'q, Q> {
-        unsafe {
-            BatchedIter::new(
-                self.iter.world.entities_meta(),
-                self.iter.world.archetypes_inner().iter(),
-                batch_size,
-            )
+    /// Doesn't execute the query, so columns aren't marked changed ahead of being visited.
+    fn skip_exhausted(&mut self) {
+        while self.next_archetype() {
+            let archetype = &self.archetypes[self.cursor.archetype as usize];
+            if !archetype.is_empty() && prepare_iter::<Q::Fetch>(archetype).is_some() {
+                return;
+            }
         }
     }
 }
 
-impl<'q, Q: Query> IntoIterator for QueryMut<'q, Q> {
-    type Item = <QueryIter<'q, Q> as Iterator>::Item;
-    type IntoIter = QueryIter<'q, Q>;
-
-    #[inline]
-    fn into_iter(self) -> Self::IntoIter {
-        self.iter
+fn single<Q: Query>(mut iter: QueryIter<'_, Q>) -> Result<(Entity, Q::Item<'_>), QuerySingleError> {
+    let item = iter.next().ok_or(QuerySingleError::NoEntities)?;
+    match iter.len() {
+        0 => Ok(item),
+        n => Err(QuerySingleError::MultipleEntities { count: n + 1 }),
     }
 }
 
@@ -955,6 +2624,7 @@ pub struct BatchedIter<'q, Q: Query> {
     archetypes: SliceIter<'q, Archetype>,
     batch_size: u32,
     batch: u32,
+    since: u32,
 }
 
 impl<'q, Q: Query> BatchedIter<'q, Q> {
@@ -966,6 +2636,7 @@ impl<'q, Q: Query> BatchedIter<'q, Q> {
         meta: &'q [EntityMeta],
         archetypes: SliceIter<'q, Archetype>,
         batch_size: u32,
+        since: u32,
     ) -> Self {
         Self {
             _marker: PhantomData,
@@ -973,11 +2644,14 @@ impl<'q, Q: Query> BatchedIter<'q, Q> {
             archetypes,
             batch_size,
             batch: 0,
+            since,
         }
     }
 }
 
+#[cfg(not(feature = "thread-local"))]
 unsafe impl<'q, Q: Query> Send for BatchedIter<'q, Q> where for<'a> Q::Item<'a>: Send {}
+#[cfg(not(feature = "thread-local"))]
 unsafe impl<'q, Q: Query> Sync for BatchedIter<'q, Q> where for<'a> Q::Item<'a>: Send {}
 
 impl<'q, Q: Query> Iterator for BatchedIter<'q, Q> {
@@ -993,7 +2667,8 @@ impl<'q, Q: Query> Iterator for BatchedIter<'q, Q> {
                 self.batch = 0;
                 continue;
             }
-            let state = Q::Fetch::prepare(archetype);
+            let state = prepare_iter::<Q::Fetch>(archetype)
+                .filter(|&state| Q::Fetch::filter(archetype, state, self.since));
             let fetch = state.map(|state| Q::Fetch::execute(archetype, state));
             if let Some(fetch) = fetch {
                 self.batch += 1;
@@ -1037,9 +2712,76 @@ impl<'q, Q: Query> Iterator for Batch<'q, Q> {
     }
 }
 
+#[cfg(not(feature = "thread-local"))]
 unsafe impl<'q, Q: Query> Send for Batch<'q, Q> where for<'a> Q::Item<'a>: Send {}
+#[cfg(not(feature = "thread-local"))]
 unsafe impl<'q, Q: Query> Sync for Batch<'q, Q> where for<'a> Q::Item<'a>: Send {}
 
+#[cfg(all(feature = "rayon", not(feature = "thread-local")))]
+impl<'q, Q: Query> Batch<'q, Q> {
+    /// Move the latter half of the remaining entities into a new batch, if there are at least two
+    fn split(mut self) -> (Self, Option<Self>) {
+        let remaining = self.state.remaining();
+        if remaining < 2 {
+            return (self, None);
+        }
+        let mid = self.state.position + remaining / 2;
+        // Both halves address the same columns, but never the same rows
+        let back = Batch {
+            meta: self.meta,
+            state: ChunkIter {
+                entities: self.state.entities,
+                fetch: self.state.fetch.clone(),
+                position: mid,
+                len: self.state.len,
+            },
+        };
+        self.state.len = mid;
+        (self, Some(back))
+    }
+}
+
+/// Parallel iterator over the results of a query, obtained from [`QueryBorrow::par_iter`] or
+/// [`QueryMut::into_par_iter`]
+///
+/// Each matching archetype's entities are handed to workers separately, and those of large
+/// archetypes are further divided into disjoint ranges of rows as rayon sees fit, so no two
+/// workers ever access the same component of the same entity.
+#[cfg(all(feature = "rayon", not(feature = "thread-local")))]
+#[cfg_attr(docsrs, doc(cfg(feature = "rayon")))]
+pub struct ParIter<'q, Q: Query> {
+    batches: Vec<Batch<'q, Q>>,
+}
+
+#[cfg(all(feature = "rayon", not(feature = "thread-local")))]
+impl<'q, Q: Query> ParIter<'q, Q> {
+    fn new(archetypes: BatchedIter<'q, Q>) -> Self {
+        Self {
+            batches: archetypes.collect(),
+        }
+    }
+}
+
+#[cfg(all(feature = "rayon", not(feature = "thread-local")))]
+impl<'q, Q: Query> rayon::iter::ParallelIterator for ParIter<'q, Q>
+where
+    for<'a> Q::Item<'a>: Send,
+{
+    type Item = (Entity, Q::Item<'q>);
+
+    fn drive_unindexed<C>(self, consumer: C) -> C::Result
+    where
+        C: rayon::iter::plumbing::UnindexedConsumer<Self::Item>,
+    {
+        use rayon::iter::IntoParallelIterator;
+        self.batches
+            .into_par_iter()
+            .flat_map(|batch| rayon::iter::split(batch, Batch::split))
+            .flat_map_iter(|batch| batch)
+            .drive_unindexed(consumer)
+    }
+}
+
 macro_rules! tuple_impl {
     ($($name: ident),*) => {
         unsafe impl<$($name: Fetch),*> Fetch for ($($name,)*) {
@@ -1081,10 +2823,29 @@ macro_rules! tuple_impl {
                 $($name::release(archetype, $name);)*
             }
 
+            #[allow(unused_variables, non_snake_case)]
+            fn filter(archetype: &Archetype, state: Self::State, since: u32) -> bool {
+                let ($($name,)*) = state;
+                true $(&& $name::filter(archetype, $name, since))*
+            }
+
             #[allow(unused_variables, unused_mut, clippy::unused_unit)]
             fn for_each_borrow(mut f: impl FnMut(TypeId, bool)) {
                 $($name::for_each_borrow(&mut f);)*
             }
+
+            #[allow(unused_variables, unused_mut)]
+            fn for_each_access(mut f: impl FnMut(TypeId, Access)) {
+                $($name::for_each_access(&mut f);)*
+            }
+
+            fn includes_disabled() -> bool {
+                false $(|| $name::includes_disabled())*
+            }
+
+            fn tracks_changes() -> bool {
+                false $(|| $name::tracks_changes())*
+            }
         }
 
         impl<$($name: Query),*> Query for ($($name,)*) {
@@ -1101,6 +2862,24 @@ macro_rules! tuple_impl {
         }
 
         unsafe impl<$($name: QueryShared),*> QueryShared for ($($name,)*) {}
+
+        impl<$($name: SliceQuery),*> SliceQuery for ($($name,)*) {
+            type Slices<'q> = ($($name::Slices<'q>,)*);
+
+            #[allow(unused_variables, clippy::unused_unit)]
+            unsafe fn slices<'q>(fetch: &Self::Fetch, len: usize) -> Self::Slices<'q> {
+                #[allow(non_snake_case)]
+                let ($(ref $name,)*) = *fetch;
+                ($($name::slices($name, len),)*)
+            }
+        }
+
+        impl<$($name: Fetch),*> FetchAny for ($($name,)*) {
+            #[allow(unused_variables)]
+            fn count_matches(archetype: &Archetype) -> usize {
+                0 $(+ usize::from($name::access(archetype).is_some()))*
+            }
+        }
     };
 }
 
@@ -1108,10 +2887,15 @@ macro_rules! tuple_impl {
 smaller_tuples_too!(tuple_impl, O, N, M, L, K, J, I, H, G, F, E, D, C, B, A);
 
 /// A prepared query can be stored independently of the [`World`] to amortize query set-up costs.
+///
+/// Each iteration of a prepared query containing [`Changed`] or [`Added`] records the current
+/// change tick, so that they consider only changes made since the previous iteration, including
+/// changes made through the previous iteration's own results.
 pub struct PreparedQuery<Q: Query> {
     memo: (u64, u32),
-    state: Box<[(usize, <Q::Fetch as Fetch>::State)]>,
-    fetch: Box<[Option<Q::Fetch>]>,
+    state: Vec<(usize, <Q::Fetch as Fetch>::State)>,
+    fetch: Vec<Option<Q::Fetch>>,
+    tick: u32,
 }
 
 impl<Q: Query> Default for PreparedQuery<Q> {
@@ -1128,22 +2912,46 @@ impl<Q: Query> PreparedQuery<Q> {
             memo: (0, 0),
             state: Default::default(),
             fetch: Default::default(),
+            tick: 0,
         }
     }
 
+    /// Bring the cached archetype states up to date with `world`
+    ///
+    /// Archetypes are never removed from a world, so only those created since this query was last
+    /// prepared for the same world need to be inspected.
     #[cold]
-    fn prepare(world: &World) -> Self {
-        let memo = world.memo();
-
-        let state = world
-            .archetypes()
-            .enumerate()
-            .filter_map(|(idx, x)| Q::Fetch::prepare(x).map(|state| (idx, state)))
-            .collect();
-
-        let fetch = world.archetypes().map(|_| None).collect();
-
-        Self { memo, state, fetch }
+    fn prepare(&mut self, world: &World) {
+        let (id, generation) = world.memo();
+        if self.memo.0 != id {
+            self.state.clear();
+            self.fetch.clear();
+        }
+        let start = self.fetch.len();
+        self.state.extend(
+            world
+                .archetypes()
+                .enumerate()
+                .skip(start)
+                .filter_map(|(idx, x)| prepare_iter::<Q::Fetch>(x).map(|state| (idx, state))),
+        );
+        self.fetch.resize_with(generation as usize, || None);
+        self.memo = (id, generation);
+    }
+
+    /// Prepare for `world` if necessary, then record the current change tick if `Q` has change
+    /// filters
+    ///
+    /// Returns the change tick recorded by the previous iteration.
+    fn advance(&mut self, world: &World) -> u32 {
+        if self.memo != world.memo() {
+            self.prepare(world);
+        }
+        if !Q::Fetch::tracks_changes() {
+            // Advancing the tick touches every archetype, and nothing would consult it
+            return self.tick;
+        }
+        core::mem::replace(&mut self.tick, world.increment_change_tick())
     }
 
     /// Query `world`, using dynamic borrow checking
@@ -1151,14 +2959,12 @@ impl<Q: Query> PreparedQuery<Q> {
     /// This will panic if it would violate an existing unique reference
     /// or construct an invalid unique reference.
     pub fn query<'q>(&'q mut self, world: &'q World) -> PreparedQueryBorrow<'q, Q> {
-        if self.memo != world.memo() {
-            *self = Self::prepare(world);
-        }
+        let since = self.advance(world);
 
         let meta = world.entities_meta();
         let archetypes = world.archetypes_inner();
 
-        PreparedQueryBorrow::new(meta, archetypes, &self.state, &mut self.fetch)
+        PreparedQueryBorrow::new(meta, archetypes, &self.state, &mut self.fetch, since)
     }
 
     /// Query a uniquely borrowed world
@@ -1167,14 +2973,55 @@ impl<Q: Query> PreparedQuery<Q> {
     pub fn query_mut<'q>(&'q mut self, world: &'q mut World) -> PreparedQueryIter<'q, Q> {
         assert_borrow::<Q>();
 
-        if self.memo != world.memo() {
-            *self = Self::prepare(world);
-        }
+        let since = self.advance(world);
 
         let meta = world.entities_meta();
         let archetypes = world.archetypes_inner();
 
-        unsafe { PreparedQueryIter::new(meta, archetypes, self.state.iter()) }
+        unsafe { PreparedQueryIter::new(meta, archetypes, self.state.iter(), since) }
+    }
+
+    /// Prepare a query against a single entity, using dynamic borrow checking
+    ///
+    /// Like [`World::query_one`], but reuses the layout information cached by this prepared query
+    /// so long as the world's archetypes haven't changed, making it cheaper when many entities are
+    /// looked up in a tight loop. Doesn't record a change tick.
+    ///
+    /// # Example
+    /// ```
+    /// # use hecs::*;
+    /// let mut world = World::new();
+    /// let a = world.spawn((123, true));
+    /// let mut query = PreparedQuery::<(&i32, &bool)>::new();
+    /// let mut one = query.query_one(&world, a).unwrap();
+    /// assert_eq!(one.get(), Some((&123, &true)));
+    /// ```
+    pub fn query_one<'q>(
+        &'q mut self,
+        world: &'q World,
+        entity: Entity,
+    ) -> Result<QueryOne<'q, Q>, NoSuchEntity> {
+        if self.memo != world.memo() {
+            self.prepare(world);
+        }
+
+        let meta = world
+            .entities_meta()
+            .get(entity.id as usize)
+            .filter(|meta| meta.generation == entity.generation && meta.location.index != u32::MAX)
+            .ok_or(NoSuchEntity)?;
+        let loc = meta.location;
+        let archetype = &world.archetypes_inner()[loc.archetype as usize];
+        let state = self
+            .state
+            .binary_search_by_key(&(loc.archetype as usize), |&(idx, _)| idx)
+            .map(|i| self.state[i].1);
+        unsafe {
+            Ok(match state {
+                Ok(state) => QueryOne::new_prepared(archetype, loc.index, state),
+                Err(_) => QueryOne::new(archetype, loc.index),
+            })
+        }
     }
 
     /// Provide random access to query results for a uniquely borrow world
@@ -1182,7 +3029,7 @@ impl<Q: Query> PreparedQuery<Q> {
         assert_borrow::<Q>();
 
         if self.memo != world.memo() {
-            *self = Self::prepare(world);
+            self.prepare(world);
         }
 
         let meta = world.entities_meta();
@@ -1192,12 +3039,70 @@ impl<Q: Query> PreparedQuery<Q> {
     }
 }
 
+/// [`PreparedQuery`]s owned by a [`World`], looked up by label
+#[derive(Default)]
+pub(crate) struct QueryCache {
+    queries: HashMap<&'static str, CachedQuery>,
+}
+
+impl QueryCache {
+    /// Get the query cached under `key`, creating it if necessary
+    ///
+    /// The query is allocated separately so that it can be borrowed alongside the `World`.
+    pub(crate) fn get<Q: Query + 'static>(
+        &mut self,
+        key: &'static str,
+    ) -> NonNull<PreparedQuery<Q>> {
+        let ty = TypeId::of::<Q>();
+        let entry = self
+            .queries
+            .entry(key)
+            .or_insert_with(CachedQuery::new::<Q>);
+        debug_assert!(
+            entry.ty == ty,
+            "cached query {:?} used with a different query type",
+            key
+        );
+        if entry.ty != ty {
+            *entry = CachedQuery::new::<Q>();
+        }
+        entry.query.cast()
+    }
+}
+
+struct CachedQuery {
+    ty: TypeId,
+    query: NonNull<u8>,
+    drop: unsafe fn(NonNull<u8>),
+}
+
+impl CachedQuery {
+    fn new<Q: Query + 'static>() -> Self {
+        unsafe fn drop<Q: Query>(query: NonNull<u8>) {
+            mem::drop(Box::from_raw(query.cast::<PreparedQuery<Q>>().as_ptr()));
+        }
+        let query = Box::new(PreparedQuery::<Q>::new());
+        Self {
+            ty: TypeId::of::<Q>(),
+            query: unsafe { NonNull::new_unchecked(Box::into_raw(query).cast()) },
+            drop: drop::<Q>,
+        }
+    }
+}
+
+impl Drop for CachedQuery {
+    fn drop(&mut self) {
+        unsafe { (self.drop)(self.query) }
+    }
+}
+
 /// Combined borrow of a [`PreparedQuery`] and a [`World`]
 pub struct PreparedQueryBorrow<'q, Q: Query> {
     meta: &'q [EntityMeta],
     archetypes: &'q [Archetype],
     state: &'q [(usize, <Q::Fetch as Fetch>::State)],
     fetch: &'q mut [Option<Q::Fetch>],
+    since: u32,
 }
 
 impl<'q, Q: Query> PreparedQueryBorrow<'q, Q> {
@@ -1206,6 +3111,7 @@ impl<'q, Q: Query> PreparedQueryBorrow<'q, Q> {
         archetypes: &'q [Archetype],
         state: &'q [(usize, <Q::Fetch as Fetch>::State)],
         fetch: &'q mut [Option<Q::Fetch>],
+        since: u32,
     ) -> Self {
         for (idx, state) in state {
             if archetypes[*idx].is_empty() {
@@ -1219,13 +3125,14 @@ impl<'q, Q: Query> PreparedQueryBorrow<'q, Q> {
             archetypes,
             state,
             fetch,
+            since,
         }
     }
 
     /// Execute the prepared query
     // The lifetime narrowing here is required for soundness.
     pub fn iter(&mut self) -> PreparedQueryIter<'_, Q> {
-        unsafe { PreparedQueryIter::new(self.meta, self.archetypes, self.state.iter()) }
+        unsafe { PreparedQueryIter::new(self.meta, self.archetypes, self.state.iter(), self.since) }
     }
 
     /// Provides random access to the results of the prepared query
@@ -1251,6 +3158,7 @@ pub struct PreparedQueryIter<'q, Q: Query> {
     archetypes: &'q [Archetype],
     state: SliceIter<'q, (usize, <Q::Fetch as Fetch>::State)>,
     iter: ChunkIter<Q>,
+    since: u32,
 }
 
 impl<'q, Q: Query> PreparedQueryIter<'q, Q> {
@@ -1262,17 +3170,21 @@ impl<'q, Q: Query> PreparedQueryIter<'q, Q> {
         meta: &'q [EntityMeta],
         archetypes: &'q [Archetype],
         state: SliceIter<'q, (usize, <Q::Fetch as Fetch>::State)>,
+        since: u32,
     ) -> Self {
         Self {
             meta,
             archetypes,
             state,
             iter: ChunkIter::empty(),
+            since,
         }
     }
 }
 
+#[cfg(not(feature = "thread-local"))]
 unsafe impl<'q, Q: Query> Send for PreparedQueryIter<'q, Q> where for<'a> Q::Item<'a>: Send {}
+#[cfg(not(feature = "thread-local"))]
 unsafe impl<'q, Q: Query> Sync for PreparedQueryIter<'q, Q> where for<'a> Q::Item<'a>: Send {}
 
 impl<'q, Q: Query> Iterator for PreparedQueryIter<'q, Q> {
@@ -1285,6 +3197,9 @@ impl<'q, Q: Query> Iterator for PreparedQueryIter<'q, Q> {
                 None => {
                     let (idx, state) = self.state.next()?;
                     let archetype = &self.archetypes[*idx];
+                    if !Q::Fetch::filter(archetype, *state, self.since) {
+                        continue;
+                    }
                     self.iter = ChunkIter::new(archetype, Q::Fetch::execute(archetype, *state));
                     continue;
                 }
@@ -1311,6 +3226,7 @@ impl<Q: Query> ExactSizeIterator for PreparedQueryIter<'_, Q> {
     fn len(&self) -> usize {
         self.state
             .clone()
+            .filter(|&&(idx, state)| Q::Fetch::filter(&self.archetypes[idx], state, self.since))
             .map(|(idx, _)| self.archetypes[*idx].len() as usize)
             .sum::<usize>()
             + self.iter.remaining()
@@ -1324,7 +3240,9 @@ pub struct View<'q, Q: Query> {
     fetch: Vec<Option<Q::Fetch>>,
 }
 
+#[cfg(not(feature = "thread-local"))]
 unsafe impl<'q, Q: Query> Send for View<'q, Q> where for<'a> Q::Item<'a>: Send {}
+#[cfg(not(feature = "thread-local"))]
 unsafe impl<'q, Q: Query> Sync for View<'q, Q> where for<'a> Q::Item<'a>: Send {}
 
 impl<'q, Q: Query> View<'q, Q> {
@@ -1336,7 +3254,7 @@ impl<'q, Q: Query> View<'q, Q> {
         let fetch = archetypes
             .iter()
             .map(|archetype| {
-                Q::Fetch::prepare(archetype).map(|state| Q::Fetch::execute(archetype, state))
+                prepare_iter::<Q::Fetch>(archetype).map(|state| Q::Fetch::execute(archetype, state))
             })
             .collect();
 
@@ -1357,7 +3275,7 @@ impl<'q, Q: Query> View<'q, Q> {
         Q: QueryShared,
     {
         let meta = self.meta.get(entity.id as usize)?;
-        if meta.generation != entity.generation {
+        if meta.generation != entity.generation || meta.location.index == u32::MAX {
             return None;
         }
 
@@ -1378,7 +3296,7 @@ impl<'q, Q: Query> View<'q, Q> {
         let Some(meta) = self.meta.get(entity.id as usize) else {
             return false;
         };
-        if meta.generation != entity.generation {
+        if meta.generation != entity.generation || meta.location.index == u32::MAX {
             return false;
         }
         self.fetch[meta.location.archetype as usize].is_some()
@@ -1391,7 +3309,7 @@ impl<'q, Q: Query> View<'q, Q> {
     /// Must not be invoked while any unique borrow of the fetched components of `entity` is live.
     pub unsafe fn get_unchecked(&self, entity: Entity) -> Option<Q::Item<'_>> {
         let meta = self.meta.get(entity.id as usize)?;
-        if meta.generation != entity.generation {
+        if meta.generation != entity.generation || meta.location.index == u32::MAX {
             return None;
         }
 
@@ -1511,7 +3429,9 @@ pub struct PreparedView<'q, Q: Query> {
     fetch: &'q mut [Option<Q::Fetch>],
 }
 
+#[cfg(not(feature = "thread-local"))]
 unsafe impl<'q, Q: Query> Send for PreparedView<'q, Q> where for<'a> Q::Item<'a>: Send {}
+#[cfg(not(feature = "thread-local"))]
 unsafe impl<'q, Q: Query> Sync for PreparedView<'q, Q> where for<'a> Q::Item<'a>: Send {}
 
 impl<'q, Q: Query> PreparedView<'q, Q> {
@@ -1549,7 +3469,7 @@ impl<'q, Q: Query> PreparedView<'q, Q> {
         Q: QueryShared,
     {
         let meta = self.meta.get(entity.id as usize)?;
-        if meta.generation != entity.generation {
+        if meta.generation != entity.generation || meta.location.index == u32::MAX {
             return None;
         }
 
@@ -1570,7 +3490,7 @@ impl<'q, Q: Query> PreparedView<'q, Q> {
         let Some(meta) = self.meta.get(entity.id as usize) else {
             return false;
         };
-        if meta.generation != entity.generation {
+        if meta.generation != entity.generation || meta.location.index == u32::MAX {
             return false;
         }
         self.fetch[meta.location.archetype as usize].is_some()
@@ -1583,7 +3503,7 @@ impl<'q, Q: Query> PreparedView<'q, Q> {
     /// Must not be invoked while any unique borrow of the fetched components of `entity` is live.
     pub unsafe fn get_unchecked(&self, entity: Entity) -> Option<Q::Item<'_>> {
         let meta = self.meta.get(entity.id as usize)?;
-        if meta.generation != entity.generation {
+        if meta.generation != entity.generation || meta.location.index == u32::MAX {
             return None;
         }
 
@@ -1641,6 +3561,130 @@ impl<'a, 'q, Q: Query> IntoIterator for &'a mut PreparedView<'q, Q> {
     }
 }
 
+/// The results of the query `Q` for each of a list of entities
+///
+/// Constructed by [`World::query_each`] or [`World::query_each_mut`]. Borrows are not released
+/// until this object is dropped.
+pub struct QueryEach<'w, Q: Query> {
+    view: View<'w, Q>,
+    entities: &'w [Entity],
+    /// Whether dynamic borrows must be released on drop
+    borrowed: bool,
+}
+
+impl<'w, Q: Query> QueryEach<'w, Q> {
+    pub(crate) fn new(world: &'w World, entities: &'w [Entity]) -> Self
+    where
+        Q: QueryShared,
+    {
+        start_borrow::<Q>(world.archetypes_inner());
+        Self {
+            view: unsafe { View::new(world.entities_meta(), world.archetypes_inner()) },
+            entities,
+            borrowed: true,
+        }
+    }
+
+    pub(crate) fn new_mut(world: &'w mut World, entities: &'w [Entity]) -> Self {
+        assert_borrow::<Q>();
+        let mut sorted = entities.to_vec();
+        sorted.sort_unstable();
+        for pair in sorted.windows(2) {
+            assert_ne!(pair[0], pair[1], "entity appears more than once");
+        }
+        Self {
+            view: unsafe { View::new(world.entities_meta(), world.archetypes_inner()) },
+            entities,
+            borrowed: false,
+        }
+    }
+
+    /// Look up each entity in order, yielding `None` for those that don't exist or don't satisfy
+    /// the query
+    pub fn iter(&mut self) -> QueryEachIter<'_, Q> {
+        QueryEachIter {
+            view: &self.view,
+            entities: self.entities.iter(),
+        }
+    }
+}
+
+impl<Q: Query> Drop for QueryEach<'_, Q> {
+    fn drop(&mut self) {
+        if self.borrowed {
+            release_borrow::<Q>(self.view.archetypes);
+        }
+    }
+}
+
+impl<'q, Q: Query> IntoIterator for &'q mut QueryEach<'_, Q> {
+    type Item = Option<Q::Item<'q>>;
+    type IntoIter = QueryEachIter<'q, Q>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+/// Iterator over the results of a [`QueryEach`]
+pub struct QueryEachIter<'q, Q: Query> {
+    view: &'q View<'q, Q>,
+    entities: SliceIter<'q, Entity>,
+}
+
+impl<'q, Q: Query> Iterator for QueryEachIter<'q, Q> {
+    type Item = Option<Q::Item<'q>>;
+
+    #[inline]
+    fn next(&mut self) -> Option<Self::Item> {
+        let entity = *self.entities.next()?;
+        // Safe because entities are distinct or `Q` is shared
+        Some(unsafe { self.view.get_unchecked(entity) })
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.entities.size_hint()
+    }
+}
+
+impl<Q: Query> ExactSizeIterator for QueryEachIter<'_, Q> {}
+
+/// Unique borrows of the `T` components of pairs of distinct entities
+///
+/// Constructed by [`World::query_pairs_mut`]. This isn't an [`Iterator`] because each pair is only
+/// borrowed until the next is requested, which permits entities to appear in multiple pairs.
+pub struct PairsMut<'w, T: Component, I> {
+    view: View<'w, &'w mut T>,
+    pairs: I,
+}
+
+impl<'w, T: Component, I: Iterator<Item = (Entity, Entity)>> PairsMut<'w, T, I> {
+    pub(crate) fn new(world: &'w mut World, pairs: I) -> Self {
+        Self {
+            view: world.view_mut::<&mut T>(),
+            pairs,
+        }
+    }
+
+    /// Borrow the components of the next pair in which both entities exist and have a `T`
+    ///
+    /// Pairs that don't satisfy this are skipped. Panics if a pair names the same entity twice.
+    #[allow(clippy::should_implement_trait)]
+    pub fn next(&mut self) -> Option<(&mut T, &mut T)> {
+        for (a, b) in self.pairs.by_ref() {
+            assert_ne!(a, b, "entity paired with itself");
+            // Safe because `a` and `b` are distinct and `&mut self` ends any previous borrows
+            unsafe {
+                if let (Some(x), Some(y)) = (self.view.get_unchecked(a), self.view.get_unchecked(b))
+                {
+                    return Some((x, y));
+                }
+            }
+        }
+        None
+    }
+}
+
 /// A borrow of a [`World`](crate::World) sufficient to random-access the results of the query `Q`.
 ///
 /// Note that borrows are not released until this object is dropped.
@@ -1779,7 +3823,7 @@ fn start_borrow<Q: Query>(archetypes: &[Archetype]) {
             continue;
         }
         // TODO: Release prior borrows on failure?
-        if let Some(state) = Q::Fetch::prepare(x) {
+        if let Some(state) = prepare_iter::<Q::Fetch>(x) {
             Q::Fetch::borrow(x, state);
         }
     }
@@ -1791,7 +3835,7 @@ fn release_borrow<Q: Query>(archetypes: &[Archetype]) {
         if x.is_empty() {
             continue;
         }
-        if let Some(state) = Q::Fetch::prepare(x) {
+        if let Some(state) = prepare_iter::<Q::Fetch>(x) {
             Q::Fetch::release(x, state);
         }
     }
@@ -7,6 +7,8 @@ use crate::{Archetype, Query};
 pub struct QueryOne<'a, Q: Query> {
     archetype: &'a Archetype,
     index: u32,
+    /// Fetch state for `archetype`, if already known
+    state: Option<<Q::Fetch as Fetch>::State>,
     borrowed: bool,
     _marker: PhantomData<Q>,
 }
@@ -23,11 +25,31 @@ impl<'a, Q: Query> QueryOne<'a, Q> {
         Self {
             archetype,
             index,
+            state: None,
             borrowed: false,
             _marker: PhantomData,
         }
     }
 
+    /// Like `new`, but reusing `state` previously prepared for `archetype`
+    ///
+    /// # Safety
+    ///
+    /// `index` must be in-bounds for `archetype`, and `state` must have been prepared for it
+    pub(crate) unsafe fn new_prepared(
+        archetype: &'a Archetype,
+        index: u32,
+        state: <Q::Fetch as Fetch>::State,
+    ) -> Self {
+        let mut query = Self::new(archetype, index);
+        query.state = Some(state);
+        query
+    }
+
+    fn state(&self) -> Option<<Q::Fetch as Fetch>::State> {
+        self.state.or_else(|| Q::Fetch::prepare(self.archetype))
+    }
+
     /// Get the query result, or `None` if the entity does not satisfy the query
     ///
     /// Must be called at most once.
@@ -39,7 +61,7 @@ impl<'a, Q: Query> QueryOne<'a, Q> {
         if self.borrowed {
             panic!("called QueryOnce::get twice; construct a new query instead");
         }
-        let state = Q::Fetch::prepare(self.archetype)?;
+        let state = self.state()?;
         Q::Fetch::borrow(self.archetype, state);
         let fetch = Q::Fetch::execute(self.archetype, state);
         self.borrowed = true;
@@ -65,6 +87,7 @@ impl<'a, Q: Query> QueryOne<'a, Q> {
         let x = QueryOne {
             archetype: self.archetype,
             index: self.index,
+            state: None,
             borrowed: self.borrowed,
             _marker: PhantomData,
         };
@@ -77,11 +100,13 @@ impl<'a, Q: Query> QueryOne<'a, Q> {
 impl<Q: Query> Drop for QueryOne<'_, Q> {
     fn drop(&mut self) {
         if self.borrowed {
-            let state = Q::Fetch::prepare(self.archetype).unwrap();
+            let state = self.state().unwrap();
             Q::Fetch::release(self.archetype, state);
         }
     }
 }
 
+#[cfg(not(feature = "thread-local"))]
 unsafe impl<Q: Query> Send for QueryOne<'_, Q> {}
+#[cfg(not(feature = "thread-local"))]
 unsafe impl<Q: Query> Sync for QueryOne<'_, Q> {}
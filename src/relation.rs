@@ -0,0 +1,82 @@
+use core::any::TypeId;
+
+use alloc::vec::Vec;
+
+use crate::{Component, Entity, IncludeDisabled, World};
+
+/// A component which refers to another entity
+///
+/// Once registered with [`World::register_relation`], the [`World`] keeps relations consistent:
+/// when an entity is despawned, every entity holding a relation targeting it is handled according
+/// to the registered [`OnTargetDespawn`] policy.
+///
+/// # Example
+/// ```
+/// # use hecs::*;
+/// struct ChildOf(Entity);
+///
+/// impl Relation for ChildOf {
+///     fn target(&self) -> Entity {
+///         self.0
+///     }
+/// }
+///
+/// let mut world = World::new();
+/// world.register_relation::<ChildOf>(OnTargetDespawn::Despawn);
+/// let parent = world.spawn(());
+/// let child = world.spawn((ChildOf(parent),));
+/// let grandchild = world.spawn((ChildOf(child),));
+/// world.despawn(parent).unwrap();
+/// assert!(!world.contains(child));
+/// assert!(!world.contains(grandchild));
+/// ```
+pub trait Relation: Component {
+    /// The entity this relation refers to
+    fn target(&self) -> Entity;
+}
+
+/// What to do with an entity whose [`Relation`] target is despawned
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+pub enum OnTargetDespawn {
+    /// Despawn the relating entity too, recursively handling its own relations
+    Despawn,
+    /// Remove the relation component, leaving the relating entity otherwise intact
+    Orphan,
+}
+
+/// Type-erased operations on a registered [`Relation`]
+#[derive(Copy, Clone)]
+pub(crate) struct RelationInfo {
+    pub(crate) id: TypeId,
+    pub(crate) policy: OnTargetDespawn,
+    /// Append every entity whose relation targets the given entity
+    pub(crate) sources: fn(&World, Entity, &mut Vec<Entity>),
+    /// Remove the relation component from an entity
+    pub(crate) strip: fn(&mut World, Entity),
+}
+
+impl RelationInfo {
+    pub(crate) fn of<R: Relation>(policy: OnTargetDespawn) -> Self {
+        fn sources<R: Relation>(world: &World, target: Entity, out: &mut Vec<Entity>) {
+            // Disabled entities still hold relations that must be kept consistent
+            out.extend(
+                world
+                    .query::<IncludeDisabled<&R>>()
+                    .iter()
+                    .filter(|(_, relation)| relation.target() == target)
+                    .map(|(entity, _)| entity),
+            );
+        }
+
+        fn strip<R: Relation>(world: &mut World, entity: Entity) {
+            let _ = world.remove_one::<R>(entity);
+        }
+
+        Self {
+            id: TypeId::of::<R>(),
+            policy,
+            sources: sources::<R>,
+            strip: strip::<R>,
+        }
+    }
+}
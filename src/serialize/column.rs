@@ -14,16 +14,23 @@
 //! identified by the corresponding component ID.
 
 use crate::alloc::vec::Vec;
-use core::{any::type_name, cell::RefCell, fmt, marker::PhantomData};
+use core::{
+    any::{type_name, TypeId},
+    cell::RefCell,
+    fmt,
+    marker::PhantomData,
+};
 
 use serde::{
     de::{self, DeserializeSeed, SeqAccess, Unexpected, Visitor},
-    ser::{SerializeSeq, SerializeTuple},
+    ser::{self, SerializeSeq, SerializeTuple},
     Deserialize, Deserializer, Serialize, Serializer,
 };
 
+use crate::command_buffer::{map_erased, CommandView, MapFn};
 use crate::{
-    Archetype, ColumnBatch, ColumnBatchBuilder, ColumnBatchType, Component, Entity, Query, World,
+    Archetype, ColumnBatch, ColumnBatchBuilder, ColumnBatchType, CommandBuffer, Component, Entity,
+    EntityBuilder, MapEntities, Query, World,
 };
 
 /// Implements serialization of archetypes
@@ -501,34 +508,58 @@ where
     C: DeserializeContext,
     D: Deserializer<'de>,
 {
-    deserializer.deserialize_seq(WorldVisitor(context))
+    let mut world = World::new();
+    deserialize_batches(context, deserializer, |entities, batch| {
+        world.spawn_column_batch_at(entities, batch)
+    })?;
+    Ok(world)
+}
+
+/// Deserialize archetypes one at a time, passing each to `f` before reading the next
+///
+/// `f` receives the handles of the archetype's entities and a complete [`ColumnBatch`] of their
+/// components, suitable for [`World::spawn_column_batch_at`]. Because each batch is handed off
+/// before the next archetype is read, peak memory use is bounded by the largest archetype rather
+/// than the whole world. An archetype missing data is reported as a deserialization error, so `f`
+/// is never given an incomplete batch.
+pub fn deserialize_batches<'de, C, D, F>(
+    context: &mut C,
+    deserializer: D,
+    f: F,
+) -> Result<(), D::Error>
+where
+    C: DeserializeContext,
+    D: Deserializer<'de>,
+    F: FnMut(&[Entity], ColumnBatch),
+{
+    deserializer.deserialize_seq(BatchesVisitor(context, f))
 }
 
-struct WorldVisitor<'a, C>(&'a mut C);
+struct BatchesVisitor<'a, C, F>(&'a mut C, F);
 
-impl<'de, 'a, C> Visitor<'de> for WorldVisitor<'a, C>
+impl<'de, 'a, C, F> Visitor<'de> for BatchesVisitor<'a, C, F>
 where
     C: DeserializeContext,
+    F: FnMut(&[Entity], ColumnBatch),
 {
-    type Value = World;
+    type Value = ();
 
     fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
         formatter.write_str("a sequence of archetypes")
     }
 
-    fn visit_seq<A>(self, mut seq: A) -> Result<World, A::Error>
+    fn visit_seq<A>(mut self, mut seq: A) -> Result<(), A::Error>
     where
         A: SeqAccess<'de>,
     {
-        let mut world = World::new();
         let mut entities = Vec::new();
         while let Some(bundle) =
             seq.next_element_seed(DeserializeArchetype(self.0, &mut entities))?
         {
-            world.spawn_column_batch_at(&entities, bundle);
+            (self.1)(&entities, bundle);
             entities.clear();
         }
-        Ok(world)
+        Ok(())
     }
 }
 
@@ -738,6 +769,878 @@ impl<'de, 'a> Visitor<'de> for EntitiesVisitor<'a> {
     }
 }
 
+/// A [`SerializeContext`] and [`DeserializeContext`] for a fixed set of component types
+///
+/// Covers the common case of saving and loading every entity's components of certain types,
+/// without implementing either context by hand. Each type is registered with a string ID that
+/// tags its columns in the serialized data, so IDs, not registration order, must remain stable
+/// across versions of an application for old data to remain readable. Components of unregistered
+/// types are skipped when serializing, and unrecognized IDs are an error when deserializing.
+///
+/// Any serde data format may be used. [`world`](Self::world) produces a value implementing
+/// [`Serialize`] for formats that serialize values directly to a buffer, and
+/// [`deserialize`](Self::deserialize) accepts any [`Deserializer`].
+///
+/// # Example
+///
+/// ```
+/// # use serde::{Serialize, Deserialize};
+/// # #[derive(Serialize, Deserialize, Debug, PartialEq)]
+/// # struct Position([f32; 3]);
+/// # #[derive(Serialize, Deserialize)]
+/// # struct Velocity([f32; 3]);
+/// use hecs::{*, serialize::column::*};
+///
+/// let serializer = ColumnSerializer::new()
+///     .register::<Position>("position")
+///     .register::<Velocity>("velocity");
+///
+/// let mut world = World::new();
+/// let a = world.spawn((Position([1.0, 2.0, 3.0]), Velocity([0.0; 3])));
+///
+/// use bincode::Options;
+/// let bytes = bincode::options().serialize(&serializer.world(&world)).unwrap();
+/// let mut deserializer = bincode::Deserializer::from_slice(&bytes, bincode::options());
+/// let loaded = serializer.deserialize(&mut deserializer).unwrap();
+/// assert_eq!(*loaded.get::<&Position>(a).unwrap(), Position([1.0, 2.0, 3.0]));
+/// ```
+pub struct ColumnSerializer<L = ()> {
+    components: L,
+}
+
+impl ColumnSerializer {
+    /// Create a serializer with no registered component types
+    pub fn new() -> Self {
+        Self { components: () }
+    }
+}
+
+impl Default for ColumnSerializer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<L: ComponentList> ColumnSerializer<L> {
+    /// Serialize components of type `T`, tagged with `id`
+    ///
+    /// # Panics
+    ///
+    /// Panics if `T` or `id` is already registered.
+    pub fn register<T>(self, id: &'static str) -> ColumnSerializer<Registered<T, L>>
+    where
+        T: Component + Serialize + for<'de> Deserialize<'de>,
+    {
+        self.register_inner(id, None)
+    }
+
+    /// Like [`register`](Self::register), for components holding [`Entity`] handles
+    ///
+    /// Components of type `T` in command buffers produced by
+    /// [`deserialize_commands`](Self::deserialize_commands) are treated as if recorded with
+    /// [`CommandBuffer::insert_mapped`], so that placeholder handles they hold are resolved.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `T` or `id` is already registered.
+    pub fn register_mapped<T>(self, id: &'static str) -> ColumnSerializer<Registered<T, L>>
+    where
+        T: MapEntities + Serialize + for<'de> Deserialize<'de>,
+    {
+        self.register_inner(id, Some(map_erased::<T>))
+    }
+
+    fn register_inner<T>(
+        self,
+        id: &'static str,
+        map: Option<MapFn>,
+    ) -> ColumnSerializer<Registered<T, L>>
+    where
+        T: Component + Serialize + for<'de> Deserialize<'de>,
+    {
+        assert!(
+            !self.components.contains(id, TypeId::of::<T>()),
+            "{} or ID {:?} is already registered",
+            type_name::<T>(),
+            id
+        );
+        ColumnSerializer {
+            components: Registered {
+                id,
+                map,
+                rest: self.components,
+                marker: PhantomData,
+            },
+        }
+    }
+
+    /// Serialize `world` to `serializer`
+    pub fn serialize<S: Serializer>(
+        &self,
+        world: &World,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        serialize(world, &mut ColumnContext::new(&self.components), serializer)
+    }
+
+    /// A [`Serialize`] implementation for `world`, for use with e.g. `bincode::serialize`
+    pub fn world<'a>(&'a self, world: &'a World) -> SerializeWorld<'a, L> {
+        SerializeWorld {
+            components: &self.components,
+            world,
+        }
+    }
+
+    /// Deserialize a [`World`] produced by [`serialize`](Self::serialize) or
+    /// [`world`](Self::world) from `deserializer`
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        &self,
+        deserializer: D,
+    ) -> Result<World, D::Error> {
+        deserialize(&mut ColumnContext::new(&self.components), deserializer)
+    }
+
+    /// A [`Serialize`] implementation for the commands recorded in `buffer`
+    ///
+    /// Every component recorded for spawning or insertion, and every component type recorded for
+    /// removal, must be registered, or serialization fails. Placeholders from
+    /// [`CommandBuffer::reserve_entity`] remain placeholders, which the buffer produced by
+    /// [`deserialize_commands`](Self::deserialize_commands) resolves in the same order. Other
+    /// handles are written as-is, so they're only meaningful to worlds that allocated entities
+    /// identically, e.g. in a lockstep simulation.
+    ///
+    /// # Example
+    /// ```
+    /// # use serde::{Serialize, Deserialize};
+    /// # #[derive(Serialize, Deserialize, Debug, PartialEq)]
+    /// # struct Health(u32);
+    /// use hecs::{*, serialize::column::*};
+    ///
+    /// let serializer = ColumnSerializer::new().register::<Health>("health");
+    /// let mut cmd = CommandBuffer::new();
+    /// let e = cmd.reserve_entity();
+    /// cmd.insert_one(e, Health(10));
+    ///
+    /// use bincode::Options;
+    /// let bytes = bincode::options().serialize(&serializer.commands(&cmd)).unwrap();
+    /// let mut deserializer = bincode::Deserializer::from_slice(&bytes, bincode::options());
+    /// let mut remote = serializer.deserialize_commands(&mut deserializer).unwrap();
+    /// let mut world = World::new();
+    /// remote.run_on(&mut world);
+    /// assert_eq!(world.query_mut::<&Health>().into_iter().count(), 1);
+    /// ```
+    pub fn commands<'a>(&'a self, buffer: &'a CommandBuffer) -> RecordedCommands<'a, L> {
+        RecordedCommands {
+            components: &self.components,
+            buffer,
+        }
+    }
+
+    /// Deserialize the commands produced by [`commands`](Self::commands) into a new
+    /// [`CommandBuffer`]
+    pub fn deserialize_commands<'de, D: Deserializer<'de>>(
+        &self,
+        deserializer: D,
+    ) -> Result<CommandBuffer, D::Error> {
+        deserializer.deserialize_tuple(2, CommandsVisitor(&self.components))
+    }
+}
+
+/// A [`World`] serialized through a [`ColumnSerializer`], as returned by [`ColumnSerializer::world`]
+pub struct SerializeWorld<'a, L> {
+    components: &'a L,
+    world: &'a World,
+}
+
+impl<L: ComponentList> Serialize for SerializeWorld<'_, L> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serialize(
+            self.world,
+            &mut ColumnContext::new(self.components),
+            serializer,
+        )
+    }
+}
+
+/// A component type registered with a [`ColumnSerializer`], followed by those registered before it
+#[doc(hidden)]
+pub struct Registered<T, L> {
+    id: &'static str,
+    map: Option<MapFn>,
+    rest: L,
+    marker: PhantomData<fn() -> T>,
+}
+
+/// Component types registered with a [`ColumnSerializer`]
+///
+/// Implemented for `()` and [`Registered`]; not intended to be implemented directly.
+#[doc(hidden)]
+pub trait ComponentList {
+    /// Number of registered types
+    fn registered(&self) -> usize;
+    /// Whether `id` or `ty` is registered
+    fn contains(&self, id: &str, ty: TypeId) -> bool;
+    /// Number of registered types present in `archetype`
+    fn count(&self, archetype: &Archetype) -> usize;
+    /// Position of the type registered with `id`, in registration order
+    fn index_of(&self, id: &str) -> Option<usize>;
+    fn serialize_ids<S: SerializeTuple>(
+        &self,
+        archetype: &Archetype,
+        out: &mut S,
+    ) -> Result<(), S::Error>;
+    fn serialize_components<S: SerializeTuple>(
+        &self,
+        archetype: &Archetype,
+        out: &mut S,
+    ) -> Result<(), S::Error>;
+    fn add_type(&self, index: usize, batch: &mut ColumnBatchType);
+    fn deserialize_column<'de, A: SeqAccess<'de>>(
+        &self,
+        index: usize,
+        entity_count: u32,
+        seq: &mut A,
+        out: &mut ColumnBatchBuilder,
+    ) -> Result<(), A::Error>;
+    /// ID and entity handle rewriting function of the type `ty`, if registered
+    fn lookup(&self, ty: TypeId) -> Option<(&'static str, Option<MapFn>)>;
+    /// Type registered at `index`
+    fn type_id(&self, index: usize) -> TypeId;
+    /// Serialize the component of type `ty` at `ptr`, returning whether `ty` is registered
+    ///
+    /// # Safety
+    ///
+    /// `ptr` must point to a valid component of type `ty`.
+    unsafe fn serialize_one<S: SerializeTuple>(
+        &self,
+        ty: TypeId,
+        ptr: *const u8,
+        out: &mut S,
+    ) -> Result<bool, S::Error>;
+    fn deserialize_one<'de, A: SeqAccess<'de>>(
+        &self,
+        index: usize,
+        seq: &mut A,
+        out: &mut EntityBuilder,
+    ) -> Result<(), A::Error>;
+}
+
+impl ComponentList for () {
+    fn registered(&self) -> usize {
+        0
+    }
+
+    fn contains(&self, _: &str, _: TypeId) -> bool {
+        false
+    }
+
+    fn count(&self, _: &Archetype) -> usize {
+        0
+    }
+
+    fn index_of(&self, _: &str) -> Option<usize> {
+        None
+    }
+
+    fn serialize_ids<S: SerializeTuple>(&self, _: &Archetype, _: &mut S) -> Result<(), S::Error> {
+        Ok(())
+    }
+
+    fn serialize_components<S: SerializeTuple>(
+        &self,
+        _: &Archetype,
+        _: &mut S,
+    ) -> Result<(), S::Error> {
+        Ok(())
+    }
+
+    fn add_type(&self, _: usize, _: &mut ColumnBatchType) {
+        unreachable!()
+    }
+
+    fn deserialize_column<'de, A: SeqAccess<'de>>(
+        &self,
+        _: usize,
+        _: u32,
+        _: &mut A,
+        _: &mut ColumnBatchBuilder,
+    ) -> Result<(), A::Error> {
+        unreachable!()
+    }
+
+    fn lookup(&self, _: TypeId) -> Option<(&'static str, Option<MapFn>)> {
+        None
+    }
+
+    fn type_id(&self, _: usize) -> TypeId {
+        unreachable!()
+    }
+
+    unsafe fn serialize_one<S: SerializeTuple>(
+        &self,
+        _: TypeId,
+        _: *const u8,
+        _: &mut S,
+    ) -> Result<bool, S::Error> {
+        Ok(false)
+    }
+
+    fn deserialize_one<'de, A: SeqAccess<'de>>(
+        &self,
+        _: usize,
+        _: &mut A,
+        _: &mut EntityBuilder,
+    ) -> Result<(), A::Error> {
+        unreachable!()
+    }
+}
+
+// Earlier registrations are handled first, so data is laid out in registration order
+impl<T, L> ComponentList for Registered<T, L>
+where
+    T: Component + Serialize + for<'de> Deserialize<'de>,
+    L: ComponentList,
+{
+    fn registered(&self) -> usize {
+        self.rest.registered() + 1
+    }
+
+    fn contains(&self, id: &str, ty: TypeId) -> bool {
+        self.id == id || ty == TypeId::of::<T>() || self.rest.contains(id, ty)
+    }
+
+    fn count(&self, archetype: &Archetype) -> usize {
+        self.rest.count(archetype) + usize::from(archetype.has::<T>())
+    }
+
+    fn index_of(&self, id: &str) -> Option<usize> {
+        if self.id == id {
+            Some(self.rest.registered())
+        } else {
+            self.rest.index_of(id)
+        }
+    }
+
+    fn serialize_ids<S: SerializeTuple>(
+        &self,
+        archetype: &Archetype,
+        out: &mut S,
+    ) -> Result<(), S::Error> {
+        self.rest.serialize_ids(archetype, out)?;
+        try_serialize_id::<T, _, _>(archetype, self.id, out)
+    }
+
+    fn serialize_components<S: SerializeTuple>(
+        &self,
+        archetype: &Archetype,
+        out: &mut S,
+    ) -> Result<(), S::Error> {
+        self.rest.serialize_components(archetype, out)?;
+        try_serialize::<T, _>(archetype, out)
+    }
+
+    fn add_type(&self, index: usize, batch: &mut ColumnBatchType) {
+        if index == self.rest.registered() {
+            batch.add::<T>();
+        } else {
+            self.rest.add_type(index, batch);
+        }
+    }
+
+    fn deserialize_column<'de, A: SeqAccess<'de>>(
+        &self,
+        index: usize,
+        entity_count: u32,
+        seq: &mut A,
+        out: &mut ColumnBatchBuilder,
+    ) -> Result<(), A::Error> {
+        if index == self.rest.registered() {
+            deserialize_column::<T, _>(entity_count, seq, out)
+        } else {
+            self.rest.deserialize_column(index, entity_count, seq, out)
+        }
+    }
+
+    fn lookup(&self, ty: TypeId) -> Option<(&'static str, Option<MapFn>)> {
+        if ty == TypeId::of::<T>() {
+            Some((self.id, self.map))
+        } else {
+            self.rest.lookup(ty)
+        }
+    }
+
+    fn type_id(&self, index: usize) -> TypeId {
+        if index == self.rest.registered() {
+            TypeId::of::<T>()
+        } else {
+            self.rest.type_id(index)
+        }
+    }
+
+    unsafe fn serialize_one<S: SerializeTuple>(
+        &self,
+        ty: TypeId,
+        ptr: *const u8,
+        out: &mut S,
+    ) -> Result<bool, S::Error> {
+        if ty == TypeId::of::<T>() {
+            out.serialize_element(&*ptr.cast::<T>())?;
+            Ok(true)
+        } else {
+            self.rest.serialize_one(ty, ptr, out)
+        }
+    }
+
+    fn deserialize_one<'de, A: SeqAccess<'de>>(
+        &self,
+        index: usize,
+        seq: &mut A,
+        out: &mut EntityBuilder,
+    ) -> Result<(), A::Error> {
+        if index == self.rest.registered() {
+            let component = seq
+                .next_element::<T>()?
+                .ok_or_else(|| de::Error::invalid_length(1, &"a component ID and value"))?;
+            out.add(component);
+            Ok(())
+        } else {
+            self.rest.deserialize_one(index, seq, out)
+        }
+    }
+}
+
+/// Context used by [`ColumnSerializer`]
+struct ColumnContext<'a, L> {
+    components: &'a L,
+    /// Registration indices of the components of the archetype currently being deserialized
+    indices: Vec<usize>,
+}
+
+impl<'a, L> ColumnContext<'a, L> {
+    fn new(components: &'a L) -> Self {
+        Self {
+            components,
+            indices: Vec::new(),
+        }
+    }
+}
+
+impl<L: ComponentList> SerializeContext for ColumnContext<'_, L> {
+    fn component_count(&self, archetype: &Archetype) -> usize {
+        self.components.count(archetype)
+    }
+
+    fn serialize_component_ids<S: SerializeTuple>(
+        &mut self,
+        archetype: &Archetype,
+        mut out: S,
+    ) -> Result<S::Ok, S::Error> {
+        self.components.serialize_ids(archetype, &mut out)?;
+        out.end()
+    }
+
+    fn serialize_components<S: SerializeTuple>(
+        &mut self,
+        archetype: &Archetype,
+        mut out: S,
+    ) -> Result<S::Ok, S::Error> {
+        self.components.serialize_components(archetype, &mut out)?;
+        out.end()
+    }
+}
+
+impl<L: ComponentList> DeserializeContext for ColumnContext<'_, L> {
+    fn deserialize_component_ids<'de, A>(&mut self, mut seq: A) -> Result<ColumnBatchType, A::Error>
+    where
+        A: SeqAccess<'de>,
+    {
+        self.indices.clear();
+        let mut batch = ColumnBatchType::new();
+        while let Some(index) = seq.next_element_seed(ComponentIndex(self.components))? {
+            self.components.add_type(index, &mut batch);
+            self.indices.push(index);
+        }
+        Ok(batch)
+    }
+
+    fn deserialize_components<'de, A>(
+        &mut self,
+        entity_count: u32,
+        mut seq: A,
+        batch: &mut ColumnBatchBuilder,
+    ) -> Result<(), A::Error>
+    where
+        A: SeqAccess<'de>,
+    {
+        for &index in &self.indices {
+            self.components
+                .deserialize_column(index, entity_count, &mut seq, batch)?;
+        }
+        Ok(())
+    }
+}
+
+/// Resolves a component ID to its registration index without allocating
+struct ComponentIndex<'a, L>(&'a L);
+
+impl<'de, L: ComponentList> DeserializeSeed<'de> for ComponentIndex<'_, L> {
+    type Value = usize;
+
+    fn deserialize<D>(self, deserializer: D) -> Result<usize, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        deserializer.deserialize_str(self)
+    }
+}
+
+impl<'de, L: ComponentList> Visitor<'de> for ComponentIndex<'_, L> {
+    type Value = usize;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("a registered component ID")
+    }
+
+    fn visit_str<E: de::Error>(self, v: &str) -> Result<usize, E> {
+        self.0
+            .index_of(v)
+            .ok_or_else(|| de::Error::invalid_value(Unexpected::Str(v), &self))
+    }
+}
+
+/// The commands recorded in a [`CommandBuffer`], serialized through a [`ColumnSerializer`], as
+/// returned by [`ColumnSerializer::commands`]
+///
+/// In terms of the serde data model, the buffer is a 2-tuple of the number of placeholders it
+/// reserved and a sequence of commands. Each command is a 3-tuple of a tag (0 for spawn, 1 for
+/// insert, 2 for remove, 3 for despawn), an optional target entity, and a sequence whose elements
+/// are 2-tuples of a component ID and value for spawns and inserts, component IDs for removals,
+/// and absent for despawns.
+pub struct RecordedCommands<'a, L> {
+    components: &'a L,
+    buffer: &'a CommandBuffer,
+}
+
+impl<L: ComponentList> Serialize for RecordedCommands<'_, L> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut tuple = serializer.serialize_tuple(2)?;
+        tuple.serialize_element(&self.buffer.placeholders())?;
+        tuple.serialize_element(&SerializeCommands {
+            components: self.components,
+            buffer: self.buffer,
+        })?;
+        tuple.end()
+    }
+}
+
+struct SerializeCommands<'a, L> {
+    components: &'a L,
+    buffer: &'a CommandBuffer,
+}
+
+impl<L: ComponentList> Serialize for SerializeCommands<'_, L> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut seq = serializer.serialize_seq(Some(self.buffer.commands().count()))?;
+        for command in self.buffer.commands() {
+            seq.serialize_element(&SerializeCommand {
+                components: self.components,
+                command,
+            })?;
+        }
+        seq.end()
+    }
+}
+
+struct SerializeCommand<'a, L> {
+    components: &'a L,
+    command: CommandView<'a>,
+}
+
+impl<L: ComponentList> Serialize for SerializeCommand<'_, L> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut tuple = serializer.serialize_tuple(3)?;
+        match self.command {
+            CommandView::Spawn(ref recorded) | CommandView::Insert(_, ref recorded) => {
+                let (tag, entity) = match self.command {
+                    CommandView::Insert(entity, _) => (1u8, Some(entity)),
+                    _ => (0u8, None),
+                };
+                tuple.serialize_element(&tag)?;
+                tuple.serialize_element(&entity)?;
+                tuple.serialize_element(&SerializeRecorded {
+                    components: self.components,
+                    recorded: recorded.iter().collect(),
+                })?;
+            }
+            CommandView::Remove(entity, ref ids) => {
+                tuple.serialize_element(&2u8)?;
+                tuple.serialize_element(&Some(entity))?;
+                let ids = ids
+                    .iter()
+                    .map(|&ty| match self.components.lookup(ty) {
+                        Some((id, _)) => Ok(id),
+                        None => Err(ser::Error::custom(UNREGISTERED)),
+                    })
+                    .collect::<Result<Vec<_>, S::Error>>()?;
+                tuple.serialize_element(&ids[..])?;
+            }
+            CommandView::Despawn(entity) => {
+                tuple.serialize_element(&3u8)?;
+                tuple.serialize_element(&Some(entity))?;
+                tuple.serialize_element::<[&str]>(&[])?;
+            }
+        }
+        tuple.end()
+    }
+}
+
+const UNREGISTERED: &str = "command buffer contains a component of an unregistered type";
+
+struct SerializeRecorded<'a, L> {
+    components: &'a L,
+    recorded: Vec<(TypeId, *const u8)>,
+}
+
+impl<L: ComponentList> Serialize for SerializeRecorded<'_, L> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut seq = serializer.serialize_seq(Some(self.recorded.len()))?;
+        for &(ty, ptr) in &self.recorded {
+            seq.serialize_element(&SerializeRecordedComponent {
+                components: self.components,
+                ty,
+                ptr,
+            })?;
+        }
+        seq.end()
+    }
+}
+
+struct SerializeRecordedComponent<'a, L> {
+    components: &'a L,
+    ty: TypeId,
+    ptr: *const u8,
+}
+
+impl<L: ComponentList> Serialize for SerializeRecordedComponent<'_, L> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let (id, _) = self
+            .components
+            .lookup(self.ty)
+            .ok_or_else(|| ser::Error::custom(UNREGISTERED))?;
+        let mut tuple = serializer.serialize_tuple(2)?;
+        tuple.serialize_element(id)?;
+        // Safety: `ptr` was obtained from the command buffer alongside `ty`
+        unsafe {
+            self.components
+                .serialize_one(self.ty, self.ptr, &mut tuple)?;
+        }
+        tuple.end()
+    }
+}
+
+struct CommandsVisitor<'a, L>(&'a L);
+
+impl<'de, L: ComponentList> Visitor<'de> for CommandsVisitor<'_, L> {
+    type Value = CommandBuffer;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("a placeholder count and a sequence of commands")
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<CommandBuffer, A::Error> {
+        let placeholders = seq
+            .next_element::<u32>()?
+            .ok_or_else(|| de::Error::invalid_length(0, &self))?;
+        let mut buffer = CommandBuffer::new();
+        buffer.reserve_placeholders(placeholders);
+        seq.next_element_seed(DeserializeCommands {
+            components: self.0,
+            buffer: &mut buffer,
+        })?
+        .ok_or_else(|| de::Error::invalid_length(1, &self))?;
+        Ok(buffer)
+    }
+}
+
+struct DeserializeCommands<'a, L> {
+    components: &'a L,
+    buffer: &'a mut CommandBuffer,
+}
+
+impl<'de, L: ComponentList> DeserializeSeed<'de> for DeserializeCommands<'_, L> {
+    type Value = ();
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<(), D::Error> {
+        deserializer.deserialize_seq(self)
+    }
+}
+
+impl<'de, L: ComponentList> Visitor<'de> for DeserializeCommands<'_, L> {
+    type Value = ();
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("a sequence of commands")
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<(), A::Error> {
+        while let Some(()) = seq.next_element_seed(DeserializeCommand {
+            components: self.components,
+            buffer: &mut *self.buffer,
+        })? {}
+        Ok(())
+    }
+}
+
+struct DeserializeCommand<'a, L> {
+    components: &'a L,
+    buffer: &'a mut CommandBuffer,
+}
+
+impl<'de, L: ComponentList> DeserializeSeed<'de> for DeserializeCommand<'_, L> {
+    type Value = ();
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<(), D::Error> {
+        deserializer.deserialize_tuple(3, self)
+    }
+}
+
+impl<'de, L: ComponentList> Visitor<'de> for DeserializeCommand<'_, L> {
+    type Value = ();
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("a command tag, an optional entity, and a sequence of components")
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<(), A::Error> {
+        let tag = seq
+            .next_element::<u8>()?
+            .ok_or_else(|| de::Error::invalid_length(0, &self))?;
+        let entity = seq
+            .next_element::<Option<Entity>>()?
+            .ok_or_else(|| de::Error::invalid_length(1, &self))?;
+        let target = || entity.ok_or_else(|| de::Error::invalid_value(Unexpected::Option, &self));
+        match tag {
+            0 | 1 => {
+                let mut builder = EntityBuilder::new();
+                seq.next_element_seed(DeserializeRecorded {
+                    components: self.components,
+                    builder: &mut builder,
+                })?
+                .ok_or_else(|| de::Error::invalid_length(2, &self))?;
+                if tag == 0 {
+                    self.buffer.spawn(builder.build());
+                } else {
+                    self.buffer.insert(target()?, builder.build());
+                }
+                self.buffer
+                    .set_maps(|ty| self.components.lookup(ty).and_then(|(_, map)| map));
+            }
+            2 | 3 => {
+                let ids = seq
+                    .next_element_seed(DeserializeRemoved(self.components))?
+                    .ok_or_else(|| de::Error::invalid_length(2, &self))?;
+                if tag == 2 {
+                    self.buffer.remove_dynamic(target()?, &ids);
+                } else {
+                    self.buffer.despawn(target()?);
+                }
+            }
+            _ => {
+                return Err(de::Error::invalid_value(
+                    Unexpected::Unsigned(tag.into()),
+                    &"a command tag between 0 and 3",
+                ))
+            }
+        }
+        Ok(())
+    }
+}
+
+struct DeserializeRecorded<'a, L> {
+    components: &'a L,
+    builder: &'a mut EntityBuilder,
+}
+
+impl<'de, L: ComponentList> DeserializeSeed<'de> for DeserializeRecorded<'_, L> {
+    type Value = ();
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<(), D::Error> {
+        deserializer.deserialize_seq(self)
+    }
+}
+
+impl<'de, L: ComponentList> Visitor<'de> for DeserializeRecorded<'_, L> {
+    type Value = ();
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("a sequence of component IDs and values")
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<(), A::Error> {
+        while let Some(()) = seq.next_element_seed(DeserializeRecordedComponent {
+            components: self.components,
+            builder: &mut *self.builder,
+        })? {}
+        Ok(())
+    }
+}
+
+struct DeserializeRecordedComponent<'a, L> {
+    components: &'a L,
+    builder: &'a mut EntityBuilder,
+}
+
+impl<'de, L: ComponentList> DeserializeSeed<'de> for DeserializeRecordedComponent<'_, L> {
+    type Value = ();
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<(), D::Error> {
+        deserializer.deserialize_tuple(2, self)
+    }
+}
+
+impl<'de, L: ComponentList> Visitor<'de> for DeserializeRecordedComponent<'_, L> {
+    type Value = ();
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("a component ID and value")
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<(), A::Error> {
+        let index = seq
+            .next_element_seed(ComponentIndex(self.components))?
+            .ok_or_else(|| de::Error::invalid_length(0, &self))?;
+        self.components
+            .deserialize_one(index, &mut seq, self.builder)
+    }
+}
+
+/// Deserializes a sequence of component IDs into the types they identify
+struct DeserializeRemoved<'a, L>(&'a L);
+
+impl<'de, L: ComponentList> DeserializeSeed<'de> for DeserializeRemoved<'_, L> {
+    type Value = Vec<TypeId>;
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<Vec<TypeId>, D::Error> {
+        deserializer.deserialize_seq(self)
+    }
+}
+
+impl<'de, L: ComponentList> Visitor<'de> for DeserializeRemoved<'_, L> {
+    type Value = Vec<TypeId>;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("a sequence of component IDs")
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Vec<TypeId>, A::Error> {
+        let mut ids = Vec::new();
+        while let Some(index) = seq.next_element_seed(ComponentIndex(self.0))? {
+            ids.push(self.0.type_id(index));
+        }
+        Ok(ids)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use crate::alloc::vec::Vec;
@@ -1000,6 +1903,88 @@ mod tests {
         ])
     }
 
+    #[test]
+    fn deserialize_batches_incrementally() {
+        use bincode::Options;
+        use core::cell::Cell;
+
+        /// Counts archetypes whose components have been read
+        struct Counting<'a> {
+            inner: Context,
+            archetypes: &'a Cell<usize>,
+        }
+
+        impl DeserializeContext for Counting<'_> {
+            fn deserialize_component_ids<'de, A>(
+                &mut self,
+                seq: A,
+            ) -> Result<ColumnBatchType, A::Error>
+            where
+                A: SeqAccess<'de>,
+            {
+                self.inner.deserialize_component_ids(seq)
+            }
+
+            fn deserialize_components<'de, A>(
+                &mut self,
+                entity_count: u32,
+                seq: A,
+                batch: &mut ColumnBatchBuilder,
+            ) -> Result<(), A::Error>
+            where
+                A: SeqAccess<'de>,
+            {
+                self.archetypes.set(self.archetypes.get() + 1);
+                self.inner.deserialize_components(entity_count, seq, batch)
+            }
+        }
+
+        struct Ser<'a>(&'a World);
+
+        impl Serialize for Ser<'_> {
+            fn serialize<S: Serializer>(&self, s: S) -> Result<S::Ok, S::Error> {
+                serialize(self.0, &mut Context::default(), s)
+            }
+        }
+
+        let mut source = World::new();
+        let a = source
+            .spawn_batch((0..100).map(|i| (Position([i as f32; 3]),)))
+            .collect::<Vec<_>>();
+        let b = source
+            .spawn_batch((0..50).map(|i| (Position([i as f32; 3]), Velocity([1.0; 3]))))
+            .collect::<Vec<_>>();
+        let c = source
+            .spawn_batch((0..10).map(|i| (Velocity([i as f32; 3]),)))
+            .collect::<Vec<_>>();
+        let bytes = bincode::options().serialize(&Ser(&source)).unwrap();
+
+        let archetypes = Cell::new(0);
+        let mut context = Counting {
+            inner: Context::default(),
+            archetypes: &archetypes,
+        };
+        let mut world = World::new();
+        let mut sizes = Vec::new();
+        let mut de = bincode::Deserializer::from_slice(&bytes, bincode::options());
+        deserialize_batches(&mut context, &mut de, |entities, batch| {
+            // Only the archetype being delivered has been read so far
+            assert_eq!(archetypes.get(), sizes.len() + 1);
+            sizes.push(entities.len());
+            world.spawn_column_batch_at(entities, batch);
+        })
+        .unwrap();
+
+        sizes.sort_unstable();
+        assert_eq!(sizes, [10, 50, 100]);
+        assert_eq!(world.len(), 160);
+        for (i, &e) in a.iter().enumerate() {
+            assert_eq!(world.get::<&Position>(e).unwrap().0, [i as f32; 3]);
+        }
+        assert!(world.satisfies::<(&Position, &Velocity)>(b[49]).unwrap());
+        assert_eq!(world.get::<&Velocity>(c[9]).unwrap().0, [9.0; 3]);
+    }
+
     #[test]
     #[rustfmt::skip]
     fn test_serialize_satisfying() {
@@ -1051,4 +2036,132 @@ mod tests {
             Token::TupleStructEnd,
         ])
     }
+
+    #[test]
+    fn column_serializer() {
+        use bincode::Options;
+
+        #[derive(Serialize, Deserialize, PartialEq, Debug)]
+        struct Unregistered;
+
+        let serializer = ColumnSerializer::new()
+            .register::<Position>("position")
+            .register::<Velocity>("velocity");
+        let mut world = World::new();
+        let a = world.spawn((Position([1.0; 3]), Velocity([2.0; 3])));
+        let b = world.spawn((Velocity([3.0; 3]), Unregistered));
+        let bytes = bincode::options()
+            .serialize(&serializer.world(&world))
+            .unwrap();
+
+        let loaded = serializer
+            .deserialize(&mut bincode::Deserializer::from_slice(
+                &bytes,
+                bincode::options(),
+            ))
+            .unwrap();
+        assert_eq!(loaded.len(), 2);
+        assert_eq!(*loaded.get::<&Position>(a).unwrap(), Position([1.0; 3]));
+        assert_eq!(*loaded.get::<&Velocity>(a).unwrap(), Velocity([2.0; 3]));
+        assert_eq!(*loaded.get::<&Velocity>(b).unwrap(), Velocity([3.0; 3]));
+        assert!(loaded.get::<&Unregistered>(b).is_err());
+
+        // IDs, not registration order, identify columns
+        let reordered = ColumnSerializer::new()
+            .register::<Velocity>("velocity")
+            .register::<Position>("position");
+        let loaded = reordered
+            .deserialize(&mut bincode::Deserializer::from_slice(
+                &bytes,
+                bincode::options(),
+            ))
+            .unwrap();
+        assert_eq!(*loaded.get::<&Position>(a).unwrap(), Position([1.0; 3]));
+
+        let partial = ColumnSerializer::new().register::<Position>("position");
+        assert!(partial
+            .deserialize(&mut bincode::Deserializer::from_slice(
+                &bytes,
+                bincode::options(),
+            ))
+            .is_err());
+    }
+
+    #[test]
+    fn recorded_commands() {
+        use bincode::Options;
+
+        #[derive(Serialize, Deserialize, PartialEq, Debug)]
+        struct Parent(Entity);
+
+        impl MapEntities for Parent {
+            fn map_entities(&mut self, f: &mut dyn FnMut(Entity) -> Entity) {
+                self.0 = f(self.0);
+            }
+        }
+
+        let serializer = ColumnSerializer::new()
+            .register::<Position>("position")
+            .register::<Velocity>("velocity")
+            .register_mapped::<Parent>("parent");
+
+        // Two worlds in the same state, as in a lockstep simulation
+        let setup = || {
+            let mut world = World::new();
+            let a = world.spawn((Position([1.0; 3]), Velocity([0.0; 3])));
+            let b = world.spawn((Position([2.0; 3]),));
+            (world, a, b)
+        };
+        let (mut local, a, b) = setup();
+        let (mut remote, _, _) = setup();
+
+        let mut cmd = CommandBuffer::new();
+        let parent = cmd.reserve_entity();
+        let child = cmd.reserve_entity();
+        cmd.insert_mapped(child, Parent(parent));
+        cmd.insert(parent, (Position([3.0; 3]), Velocity([1.0; 3])));
+        cmd.spawn((Velocity([4.0; 3]),));
+        cmd.remove::<(Position, Velocity)>(a);
+        cmd.remove::<(Position, Velocity)>(b);
+        cmd.remove_one::<Position>(b);
+        cmd.insert_mapped(b, Parent(child));
+        cmd.despawn(parent);
+
+        let bytes = bincode::options()
+            .serialize(&serializer.commands(&cmd))
+            .unwrap();
+        let mut decoded = serializer
+            .deserialize_commands(&mut bincode::Deserializer::from_slice(
+                &bytes,
+                bincode::options(),
+            ))
+            .unwrap();
+        cmd.run_on(&mut local);
+        decoded.run_on(&mut remote);
+
+        let dump = |world: &World| {
+            bincode::options()
+                .serialize(&serializer.world(world))
+                .unwrap()
+        };
+        assert_eq!(dump(&local), dump(&remote));
+        assert_eq!(remote.len(), 4);
+        assert!(remote.get::<&Position>(a).is_err());
+        assert!(remote.get::<&Position>(b).is_err());
+        let child = remote.get::<&Parent>(b).unwrap().0;
+        assert!(remote.get::<&Parent>(child).unwrap().0 != parent);
+        assert!(!remote.contains(remote.get::<&Parent>(child).unwrap().0));
+
+        // Unregistered components can't be serialized
+        let mut cmd = CommandBuffer::new();
+        cmd.spawn((true,));
+        assert!(bincode::options()
+            .serialize(&serializer.commands(&cmd))
+            .is_err());
+        cmd.clear();
+        cmd.remove_one::<bool>(a);
+        assert!(bincode::options()
+            .serialize(&serializer.commands(&cmd))
+            .is_err());
+    }
 }
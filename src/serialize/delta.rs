@@ -0,0 +1,617 @@
+//! Compact serialization of [`WorldDelta`]s for replication
+//!
+//! Only the components a delta records are written, so components that were unchanged between the
+//! two snapshots occupy no space at all. As with whole worlds, components are written and read
+//! through purpose-defined contexts that dispatch on component type.
+//!
+//! In terms of the serde data model, we treat a [`WorldDelta`] as a tuple of four sequences: the
+//! spawned entities, the despawned entities, pairs of an entity and a user-controlled map of
+//! component IDs to data for each entity with added or modified components, and pairs of an entity
+//! and a component ID for each removed component.
+
+use core::{any::TypeId, cell::RefCell, fmt};
+
+use serde::{
+    de::{self, DeserializeSeed, MapAccess, SeqAccess, Visitor},
+    ser::{SerializeMap, SerializeSeq, SerializeTuple},
+    Deserializer, Serialize, Serializer,
+};
+
+use crate::alloc::vec::Vec;
+use crate::snapshot::Removal;
+use crate::{Component, Entity, EntityBuilderClone, SnapshotRegistry, WorldDelta};
+
+/// Implements serialization of the components recorded in a [`WorldDelta`]
+///
+/// # Example
+/// ```
+/// # use serde::{Serialize, Deserialize};
+/// # #[derive(Serialize, Clone, PartialEq)]
+/// # struct Position([f32; 3]);
+/// # #[derive(Serialize, Clone, PartialEq)]
+/// # struct Velocity([f32; 3]);
+/// use core::any::TypeId;
+/// use hecs::{*, serialize::delta::*};
+///
+/// #[derive(Serialize, Deserialize)]
+/// enum ComponentId { Position, Velocity }
+///
+/// struct Context;
+///
+/// impl SerializeContext for Context {
+///     fn serialize_components<S>(
+///         &mut self,
+///         components: &EntityBuilderClone,
+///         mut map: S,
+///     ) -> Result<S::Ok, S::Error>
+///     where
+///         S: serde::ser::SerializeMap,
+///     {
+///         try_serialize::<Position, _, _>(components, &ComponentId::Position, &mut map)?;
+///         try_serialize::<Velocity, _, _>(components, &ComponentId::Velocity, &mut map)?;
+///         map.end()
+///     }
+///
+///     fn serialize_component_id<S>(&mut self, ty: TypeId, serializer: S) -> Result<S::Ok, S::Error>
+///     where
+///         S: serde::Serializer,
+///     {
+///         if ty == TypeId::of::<Position>() {
+///             ComponentId::Position.serialize(serializer)
+///         } else {
+///             ComponentId::Velocity.serialize(serializer)
+///         }
+///     }
+/// }
+/// ```
+pub trait SerializeContext {
+    /// Serialize the added or modified components of a single entity into a map
+    ///
+    /// Must write every component in `components`, since the map's length is declared up front.
+    fn serialize_components<S>(
+        &mut self,
+        components: &EntityBuilderClone,
+        map: S,
+    ) -> Result<S::Ok, S::Error>
+    where
+        S: SerializeMap;
+
+    /// Serialize the ID of the component type `ty`, for a removed component
+    fn serialize_component_id<S>(&mut self, ty: TypeId, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer;
+}
+
+/// If `components` contains a `T`, serialize it under `key` in `map`
+///
+/// Convenience method for [`SerializeContext`] implementations.
+pub fn try_serialize<T: Component + Serialize, K: Serialize + ?Sized, S: SerializeMap>(
+    components: &EntityBuilderClone,
+    key: &K,
+    map: &mut S,
+) -> Result<(), S::Error> {
+    if let Some(x) = components.get::<&T>() {
+        map.serialize_key(key)?;
+        map.serialize_value(x)?;
+    }
+    Ok(())
+}
+
+/// Implements deserialization of the components recorded in a [`WorldDelta`]
+///
+/// Must invert the [`SerializeContext`] the delta was written with. Component types must be
+/// [`Clone`], like every type a [`SnapshotRegistry`] records, to be added to an
+/// [`EntityBuilderClone`].
+pub trait DeserializeContext {
+    /// Deserialize the added or modified components of a single entity
+    fn deserialize_components<'de, M>(
+        &mut self,
+        map: M,
+        components: &mut EntityBuilderClone,
+    ) -> Result<(), M::Error>
+    where
+        M: MapAccess<'de>;
+
+    /// Deserialize the ID of a removed component's type
+    fn deserialize_component_id<'de, D>(&mut self, deserializer: D) -> Result<TypeId, D::Error>
+    where
+        D: Deserializer<'de>;
+}
+
+/// Serialize a [`WorldDelta`] through a [`SerializeContext`] to a [`Serializer`]
+pub fn serialize<C, S>(
+    delta: &WorldDelta,
+    context: &mut C,
+    serializer: S,
+) -> Result<S::Ok, S::Error>
+where
+    C: SerializeContext,
+    S: Serializer,
+{
+    let context = RefCell::new(context);
+    let mut tuple = serializer.serialize_tuple(4)?;
+    tuple.serialize_element(&delta.spawned[..])?;
+    tuple.serialize_element(&delta.despawned[..])?;
+    tuple.serialize_element(&SerializeChanged(&context, &delta.changed))?;
+    tuple.serialize_element(&SerializeRemoved(&context, &delta.removed))?;
+    tuple.end()
+}
+
+struct SerializeChanged<'a, 'c, C>(&'a RefCell<&'c mut C>, &'a [(Entity, EntityBuilderClone)]);
+
+impl<C: SerializeContext> Serialize for SerializeChanged<'_, '_, C> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let mut seq = serializer.serialize_seq(Some(self.1.len()))?;
+        for (entity, components) in self.1 {
+            seq.serialize_element(&(entity, SerializeComponents(self.0, components)))?;
+        }
+        seq.end()
+    }
+}
+
+struct SerializeComponents<'a, 'c, C>(&'a RefCell<&'c mut C>, &'a EntityBuilderClone);
+
+impl<C: SerializeContext> Serialize for SerializeComponents<'_, '_, C> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let map = serializer.serialize_map(Some(self.1.component_types().count()))?;
+        self.0.borrow_mut().serialize_components(self.1, map)
+    }
+}
+
+struct SerializeRemoved<'a, 'c, C>(&'a RefCell<&'c mut C>, &'a [Removal]);
+
+impl<C: SerializeContext> Serialize for SerializeRemoved<'_, '_, C> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let mut seq = serializer.serialize_seq(Some(self.1.len()))?;
+        for removal in self.1 {
+            seq.serialize_element(&(removal.entity, SerializeComponentId(self.0, removal.ty)))?;
+        }
+        seq.end()
+    }
+}
+
+struct SerializeComponentId<'a, 'c, C>(&'a RefCell<&'c mut C>, TypeId);
+
+impl<C: SerializeContext> Serialize for SerializeComponentId<'_, '_, C> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        self.0
+            .borrow_mut()
+            .serialize_component_id(self.1, serializer)
+    }
+}
+
+/// Deserialize a [`WorldDelta`] with a [`DeserializeContext`] and a [`Deserializer`]
+///
+/// Removed components must be of types registered in `registry`, which supplies the means of
+/// removing them when the delta is [applied](WorldDelta::apply).
+pub fn deserialize<'de, C, D>(
+    registry: &SnapshotRegistry,
+    context: &mut C,
+    deserializer: D,
+) -> Result<WorldDelta, D::Error>
+where
+    C: DeserializeContext,
+    D: Deserializer<'de>,
+{
+    deserializer.deserialize_tuple(4, DeltaVisitor(registry, context))
+}
+
+struct DeltaVisitor<'a, C>(&'a SnapshotRegistry, &'a mut C);
+
+impl<'de, C: DeserializeContext> Visitor<'de> for DeltaVisitor<'_, C> {
+    type Value = WorldDelta;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("a world delta")
+    }
+
+    fn visit_seq<A>(self, mut seq: A) -> Result<WorldDelta, A::Error>
+    where
+        A: SeqAccess<'de>,
+    {
+        let spawned = seq
+            .next_element_seed(DeserializeEntities)?
+            .ok_or_else(|| de::Error::invalid_length(0, &self))?;
+        let despawned = seq
+            .next_element_seed(DeserializeEntities)?
+            .ok_or_else(|| de::Error::invalid_length(1, &self))?;
+        let changed = seq
+            .next_element_seed(DeserializeChanged(&mut *self.1))?
+            .ok_or_else(|| de::Error::invalid_length(2, &self))?;
+        let removed = seq
+            .next_element_seed(DeserializeRemoved(self.0, &mut *self.1))?
+            .ok_or_else(|| de::Error::invalid_length(3, &self))?;
+        Ok(WorldDelta {
+            spawned,
+            despawned,
+            changed,
+            removed,
+        })
+    }
+}
+
+struct DeserializeEntities;
+
+impl<'de> DeserializeSeed<'de> for DeserializeEntities {
+    type Value = Vec<Entity>;
+
+    fn deserialize<D>(self, deserializer: D) -> Result<Self::Value, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        deserializer.deserialize_seq(self)
+    }
+}
+
+impl<'de> Visitor<'de> for DeserializeEntities {
+    type Value = Vec<Entity>;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("a sequence of entities")
+    }
+
+    fn visit_seq<A>(self, mut seq: A) -> Result<Self::Value, A::Error>
+    where
+        A: SeqAccess<'de>,
+    {
+        let mut entities = Vec::with_capacity(seq.size_hint().unwrap_or(0));
+        while let Some(entity) = seq.next_element()? {
+            entities.push(entity);
+        }
+        Ok(entities)
+    }
+}
+
+struct DeserializeChanged<'a, C>(&'a mut C);
+
+impl<'de, C: DeserializeContext> DeserializeSeed<'de> for DeserializeChanged<'_, C> {
+    type Value = Vec<(Entity, EntityBuilderClone)>;
+
+    fn deserialize<D>(self, deserializer: D) -> Result<Self::Value, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        deserializer.deserialize_seq(self)
+    }
+}
+
+impl<'de, C: DeserializeContext> Visitor<'de> for DeserializeChanged<'_, C> {
+    type Value = Vec<(Entity, EntityBuilderClone)>;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("a sequence of changed entities")
+    }
+
+    fn visit_seq<A>(self, mut seq: A) -> Result<Self::Value, A::Error>
+    where
+        A: SeqAccess<'de>,
+    {
+        let mut changed = Vec::with_capacity(seq.size_hint().unwrap_or(0));
+        while let Some(entry) = seq.next_element_seed(DeserializeChangedEntity(&mut *self.0))? {
+            changed.push(entry);
+        }
+        Ok(changed)
+    }
+}
+
+struct DeserializeChangedEntity<'a, C>(&'a mut C);
+
+impl<'de, C: DeserializeContext> DeserializeSeed<'de> for DeserializeChangedEntity<'_, C> {
+    type Value = (Entity, EntityBuilderClone);
+
+    fn deserialize<D>(self, deserializer: D) -> Result<Self::Value, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        deserializer.deserialize_tuple(2, self)
+    }
+}
+
+impl<'de, C: DeserializeContext> Visitor<'de> for DeserializeChangedEntity<'_, C> {
+    type Value = (Entity, EntityBuilderClone);
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("an entity and its changed components")
+    }
+
+    fn visit_seq<A>(self, mut seq: A) -> Result<Self::Value, A::Error>
+    where
+        A: SeqAccess<'de>,
+    {
+        let entity = seq
+            .next_element()?
+            .ok_or_else(|| de::Error::invalid_length(0, &self))?;
+        let mut components = EntityBuilderClone::new();
+        seq.next_element_seed(DeserializeComponents(&mut *self.0, &mut components))?
+            .ok_or_else(|| de::Error::invalid_length(1, &self))?;
+        Ok((entity, components))
+    }
+}
+
+struct DeserializeComponents<'a, C>(&'a mut C, &'a mut EntityBuilderClone);
+
+impl<'de, C: DeserializeContext> DeserializeSeed<'de> for DeserializeComponents<'_, C> {
+    type Value = ();
+
+    fn deserialize<D>(self, deserializer: D) -> Result<(), D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        deserializer.deserialize_map(self)
+    }
+}
+
+impl<'de, C: DeserializeContext> Visitor<'de> for DeserializeComponents<'_, C> {
+    type Value = ();
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("an entity's changed components")
+    }
+
+    fn visit_map<A>(self, map: A) -> Result<(), A::Error>
+    where
+        A: MapAccess<'de>,
+    {
+        self.0.deserialize_components(map, self.1)
+    }
+}
+
+struct DeserializeRemoved<'a, C>(&'a SnapshotRegistry, &'a mut C);
+
+impl<'de, C: DeserializeContext> DeserializeSeed<'de> for DeserializeRemoved<'_, C> {
+    type Value = Vec<Removal>;
+
+    fn deserialize<D>(self, deserializer: D) -> Result<Self::Value, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        deserializer.deserialize_seq(self)
+    }
+}
+
+impl<'de, C: DeserializeContext> Visitor<'de> for DeserializeRemoved<'_, C> {
+    type Value = Vec<Removal>;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("a sequence of removed components")
+    }
+
+    fn visit_seq<A>(self, mut seq: A) -> Result<Self::Value, A::Error>
+    where
+        A: SeqAccess<'de>,
+    {
+        let mut removed = Vec::with_capacity(seq.size_hint().unwrap_or(0));
+        while let Some(removal) = seq.next_element_seed(DeserializeRemoval(self.0, &mut *self.1))? {
+            removed.push(removal);
+        }
+        Ok(removed)
+    }
+}
+
+struct DeserializeRemoval<'a, C>(&'a SnapshotRegistry, &'a mut C);
+
+impl<'de, C: DeserializeContext> DeserializeSeed<'de> for DeserializeRemoval<'_, C> {
+    type Value = Removal;
+
+    fn deserialize<D>(self, deserializer: D) -> Result<Removal, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        deserializer.deserialize_tuple(2, self)
+    }
+}
+
+impl<'de, C: DeserializeContext> Visitor<'de> for DeserializeRemoval<'_, C> {
+    type Value = Removal;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("an entity and a removed component ID")
+    }
+
+    fn visit_seq<A>(self, mut seq: A) -> Result<Removal, A::Error>
+    where
+        A: SeqAccess<'de>,
+    {
+        let entity = seq
+            .next_element()?
+            .ok_or_else(|| de::Error::invalid_length(0, &self))?;
+        let ty = seq
+            .next_element_seed(DeserializeComponentId(&mut *self.1))?
+            .ok_or_else(|| de::Error::invalid_length(1, &self))?;
+        let remove = self
+            .0
+            .remover(ty)
+            .ok_or_else(|| de::Error::custom("removed component type isn't registered"))?;
+        Ok(Removal { entity, ty, remove })
+    }
+}
+
+struct DeserializeComponentId<'a, C>(&'a mut C);
+
+impl<'de, C: DeserializeContext> DeserializeSeed<'de> for DeserializeComponentId<'_, C> {
+    type Value = TypeId;
+
+    fn deserialize<D>(self, deserializer: D) -> Result<TypeId, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        self.0.deserialize_component_id(deserializer)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bincode::Options;
+    use serde::{Deserialize, Serialize};
+
+    use super::*;
+    use crate::alloc::string::String;
+    use crate::*;
+
+    #[derive(Serialize, Deserialize, PartialEq, Debug, Clone)]
+    struct Position([f32; 3]);
+    #[derive(Serialize, Deserialize, PartialEq, Debug, Clone)]
+    struct Label(String);
+
+    #[derive(Serialize, Deserialize)]
+    enum ComponentId {
+        Position,
+        Label,
+    }
+
+    struct Context;
+
+    impl SerializeContext for Context {
+        fn serialize_components<S>(
+            &mut self,
+            components: &EntityBuilderClone,
+            mut map: S,
+        ) -> Result<S::Ok, S::Error>
+        where
+            S: SerializeMap,
+        {
+            try_serialize::<Position, _, _>(components, &ComponentId::Position, &mut map)?;
+            try_serialize::<Label, _, _>(components, &ComponentId::Label, &mut map)?;
+            map.end()
+        }
+
+        fn serialize_component_id<S>(
+            &mut self,
+            ty: TypeId,
+            serializer: S,
+        ) -> Result<S::Ok, S::Error>
+        where
+            S: Serializer,
+        {
+            if ty == TypeId::of::<Position>() {
+                ComponentId::Position.serialize(serializer)
+            } else {
+                ComponentId::Label.serialize(serializer)
+            }
+        }
+    }
+
+    impl DeserializeContext for Context {
+        fn deserialize_components<'de, M>(
+            &mut self,
+            mut map: M,
+            components: &mut EntityBuilderClone,
+        ) -> Result<(), M::Error>
+        where
+            M: MapAccess<'de>,
+        {
+            while let Some(key) = map.next_key()? {
+                match key {
+                    ComponentId::Position => {
+                        components.add(map.next_value::<Position>()?);
+                    }
+                    ComponentId::Label => {
+                        components.add(map.next_value::<Label>()?);
+                    }
+                }
+            }
+            Ok(())
+        }
+
+        fn deserialize_component_id<'de, D>(&mut self, deserializer: D) -> Result<TypeId, D::Error>
+        where
+            D: Deserializer<'de>,
+        {
+            Ok(match ComponentId::deserialize(deserializer)? {
+                ComponentId::Position => TypeId::of::<Position>(),
+                ComponentId::Label => TypeId::of::<Label>(),
+            })
+        }
+    }
+
+    struct Ser<'a>(&'a WorldDelta);
+
+    impl Serialize for Ser<'_> {
+        fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            super::serialize(self.0, &mut Context, serializer)
+        }
+    }
+
+    fn roundtrip(registry: &SnapshotRegistry, delta: &WorldDelta) -> (usize, WorldDelta) {
+        let bytes = bincode::options().serialize(&Ser(delta)).unwrap();
+        let mut de = bincode::Deserializer::from_slice(&bytes, bincode::options());
+        (
+            bytes.len(),
+            super::deserialize(registry, &mut Context, &mut de).unwrap(),
+        )
+    }
+
+    #[test]
+    fn delta_roundtrip() {
+        let mut registry = SnapshotRegistry::new();
+        registry.register::<Position>().register::<Label>();
+        let label = Label("unchanged ".repeat(32));
+
+        let mut server = World::new();
+        let a = server.spawn((Position([0.0; 3]), label.clone()));
+        let b = server.spawn((Position([1.0; 3]), label.clone()));
+        let c = server.spawn((label.clone(),));
+        let mut client = World::new();
+        let initial = WorldSnapshot::diff(&WorldSnapshot::default(), &server.snapshot(&registry));
+        let (initial_len, decoded) = roundtrip(&registry, &initial);
+        assert_eq!(decoded.spawned(), [a, b, c]);
+        decoded.apply(&mut client);
+        assert_eq!(*client.get::<&Label>(c).unwrap(), label);
+
+        let old = server.snapshot(&registry);
+        *server.get::<&mut Position>(a).unwrap() = Position([2.0; 3]);
+        server.remove_one::<Position>(b).unwrap();
+        server.despawn(c).unwrap();
+        let d = server.spawn((Position([3.0; 3]),));
+        let delta = WorldSnapshot::diff(&old, &server.snapshot(&registry));
+        let (len, decoded) = roundtrip(&registry, &delta);
+        // Unchanged labels aren't written
+        assert!(len < label.0.len());
+        assert!(initial_len > 3 * label.0.len());
+        assert_eq!(decoded.spawned(), [d]);
+        assert_eq!(decoded.despawned(), [c]);
+        assert_eq!(
+            decoded.removed().collect::<Vec<_>>(),
+            [(b, TypeId::of::<Position>())]
+        );
+        assert_eq!(decoded.changed().len(), 2);
+
+        decoded.apply(&mut client);
+        assert_eq!(*client.get::<&Position>(a).unwrap(), Position([2.0; 3]));
+        assert_eq!(*client.get::<&Label>(a).unwrap(), label);
+        assert!(client.get::<&Position>(b).is_err());
+        assert_eq!(*client.get::<&Label>(b).unwrap(), label);
+        assert!(!client.contains(c));
+        assert_eq!(*client.get::<&Position>(d).unwrap(), Position([3.0; 3]));
+
+        let empty = WorldSnapshot::diff(&old, &old);
+        assert!(roundtrip(&registry, &empty).1.is_empty());
+    }
+
+    #[test]
+    fn unregistered_removal() {
+        let mut registry = SnapshotRegistry::new();
+        registry.register::<Position>();
+        let mut world = World::new();
+        let a = world.spawn((Position([0.0; 3]),));
+        let old = world.snapshot(&registry);
+        world.remove_one::<Position>(a).unwrap();
+        let delta = WorldSnapshot::diff(&old, &world.snapshot(&registry));
+        let bytes = bincode::options().serialize(&Ser(&delta)).unwrap();
+        let mut de = bincode::Deserializer::from_slice(&bytes, bincode::options());
+        let result = super::deserialize(&SnapshotRegistry::new(), &mut Context, &mut de);
+        assert!(result.is_err());
+    }
+}
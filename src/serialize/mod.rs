@@ -3,7 +3,7 @@
 //! [`Component`](crate::Component)s are not necessarily serializable, so we cannot directly
 //! implement [`serde::Serialize`] for [`World`](crate::World). The helpers defined in this module
 //! allow serialization and deserialization based on purpose-defined traits to control the
-//! procedures explicitly.
+//! procedures explicitly, as [`delta`] does for [`WorldDelta`](crate::WorldDelta)s.
 //!
 //! Backwards-incompatible changes to the serde data models herein are subject to the same semantic
 //! versioning stability guarantees as the hecs API.
@@ -11,6 +11,7 @@
 #[cfg(feature = "column-serialize")]
 #[cfg_attr(docsrs, doc(cfg(feature = "column-serialize")))]
 pub mod column;
+pub mod delta;
 #[cfg(feature = "row-serialize")]
 #[cfg_attr(docsrs, doc(cfg(feature = "row-serialize")))]
 pub mod row;
@@ -9,15 +9,19 @@
 //! In terms of the serde data model, we treat a [`World`] as a map of entity IDs to user-controlled
 //! maps of component IDs to data.
 
-use core::{cell::RefCell, fmt};
+#[cfg(feature = "std")]
+use crate::alloc::boxed::Box;
+use core::{any::TypeId, cell::RefCell, fmt, marker::PhantomData};
 
 use serde::{
-    de::{DeserializeSeed, MapAccess, Visitor},
+    de::{self, DeserializeSeed, MapAccess, SeqAccess, Unexpected, Visitor},
     ser::SerializeMap,
-    Deserializer, Serialize, Serializer,
+    Deserialize, Deserializer, Serialize, Serializer,
 };
 
-use crate::{Component, EntityBuilder, EntityRef, Query, World};
+use hashbrown::{HashMap, HashSet};
+
+use crate::{Component, Entity, EntityBuilder, EntityRef, Query, World};
 
 /// Implements serialization of individual entities
 ///
@@ -72,6 +76,129 @@ pub trait SerializeContext {
         let _ = entity;
         None
     }
+
+    /// Serialize the handle under which `entity`'s components are stored
+    ///
+    /// Defaults to [`Entity`]'s [`Serialize`] implementation. Override to write handles in a
+    /// different scheme, e.g. to give each save slot its own entity namespace. Must be inverted by
+    /// [`DeserializeContext::deserialize_entity_id`].
+    fn serialize_entity_id<S>(&mut self, entity: Entity, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        entity.serialize(serializer)
+    }
+
+    /// Serialize `entity`'s component of type `ty` as a single entry in `map`, returning whether
+    /// it was written
+    ///
+    /// Invoked by [`serialize_components`] and [`count_components`] for each of an entity's
+    /// components, allowing [`serialize_entity`](Self::serialize_entity) and
+    /// [`component_count`](Self::component_count) to be implemented by dispatching on type. Return
+    /// `Ok(false)` without touching `map` to skip a component with no serialized representation;
+    /// the entity is then saved, and later restored, without it. Defaults to skipping everything.
+    fn serialize_component<S>(
+        &self,
+        entity: &EntityRef<'_>,
+        ty: TypeId,
+        map: &mut S,
+    ) -> Result<bool, S::Error>
+    where
+        S: SerializeMap,
+    {
+        let _ = (entity, ty, map);
+        Ok(false)
+    }
+
+    /// Function applied to every [`Entity`] serialized by this context's world, including handles
+    /// stored inside components
+    ///
+    /// Called once per [`serialize`], [`serialize_satisfying`], or [`serialize_filtered`] call. While
+    /// it runs, [`Entity`]'s [`Serialize`] implementation writes `f(entity)` rather than `entity`, so
+    /// nested handles are rewritten consistently without custom logic in each component. Also
+    /// applies to the handles written by the default
+    /// [`serialize_entity_id`](Self::serialize_entity_id). Must be inverted by
+    /// [`DeserializeContext::entity_remap`]. Defaults to `None`, leaving handles unchanged.
+    ///
+    /// Requires the `std` feature, since the function is found through a thread-local variable.
+    #[cfg(feature = "std")]
+    fn entity_remap(&self) -> Option<Box<dyn Fn(Entity) -> Entity>> {
+        None
+    }
+}
+
+/// Serialize every component of `entity` that `context` doesn't skip into `map`
+///
+/// Convenience method for [`SerializeContext::serialize_entity`] implementations that handle each
+/// component individually with [`SerializeContext::serialize_component`].
+pub fn serialize_components<C, S>(
+    context: &C,
+    entity: EntityRef<'_>,
+    mut map: S,
+) -> Result<S::Ok, S::Error>
+where
+    C: SerializeContext + ?Sized,
+    S: SerializeMap,
+{
+    for ty in entity.component_types() {
+        context.serialize_component(&entity, ty, &mut map)?;
+    }
+    map.end()
+}
+
+/// Number of entries [`serialize_components`] will write for `entity`
+///
+/// Convenience method for [`SerializeContext::component_count`] implementations, ensuring skipped
+/// components don't throw off the length of the map. Determined by invoking
+/// [`SerializeContext::serialize_component`] on a map that discards its contents.
+pub fn count_components<C>(context: &C, entity: EntityRef<'_>) -> usize
+where
+    C: SerializeContext + ?Sized,
+{
+    let mut count = 0;
+    for ty in entity.component_types() {
+        if let Ok(true) = context.serialize_component(&entity, ty, &mut DiscardMap) {
+            count += 1;
+        }
+    }
+    count
+}
+
+/// A [`SerializeMap`] that ignores everything written to it
+struct DiscardMap;
+
+impl SerializeMap for DiscardMap {
+    type Ok = ();
+    type Error = DiscardError;
+
+    fn serialize_key<T: Serialize + ?Sized>(&mut self, _: &T) -> Result<(), DiscardError> {
+        Ok(())
+    }
+
+    fn serialize_value<T: Serialize + ?Sized>(&mut self, _: &T) -> Result<(), DiscardError> {
+        Ok(())
+    }
+
+    fn end(self) -> Result<(), DiscardError> {
+        Ok(())
+    }
+}
+
+#[derive(Debug)]
+struct DiscardError;
+
+impl fmt::Display for DiscardError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.pad("discarded")
+    }
+}
+
+impl serde::ser::StdError for DiscardError {}
+
+impl serde::ser::Error for DiscardError {
+    fn custom<T: fmt::Display>(_: T) -> Self {
+        DiscardError
+    }
 }
 
 /// If `entity` has component `T`, serialize it under `key` in `map`
@@ -89,7 +216,158 @@ pub fn try_serialize<T: Component + Serialize, K: Serialize + ?Sized, S: Seriali
     Ok(())
 }
 
+/// A component whose serialized representation may change between versions of an application
+///
+/// Serialize with [`try_serialize_versioned`] and deserialize with [`deserialize_versioned`] to
+/// store a version number alongside each value, allowing data written by older versions to be
+/// migrated when loaded.
+///
+/// # Example
+/// ```
+/// # use serde::{Serialize, Deserialize, Deserializer};
+/// use hecs::serialize::row::Versioned;
+///
+/// #[derive(Deserialize)]
+/// struct HealthV0(u8);
+///
+/// #[derive(Serialize, Deserialize)]
+/// struct Health { current: u32, max: u32 }
+///
+/// impl Versioned for Health {
+///     fn component_version() -> u32 {
+///         1
+///     }
+///
+///     fn deserialize_migrated<'de, D>(version: u32, deserializer: D) -> Result<Self, D::Error>
+///     where
+///         D: Deserializer<'de>,
+///     {
+///         match version {
+///             0 => {
+///                 let old = HealthV0::deserialize(deserializer)?;
+///                 Ok(Health { current: old.0.into(), max: 100 })
+///             }
+///             _ => Err(serde::de::Error::custom("unknown Health version")),
+///         }
+///     }
+/// }
+/// ```
+pub trait Versioned: Sized {
+    /// The version of the representation produced by this type's [`Serialize`] implementation
+    fn component_version() -> u32;
+
+    /// Deserialize a value that was serialized with an older `version`
+    ///
+    /// Only invoked when `version` is less than [`component_version`](Self::component_version);
+    /// data of the current version is read with the [`Deserialize`] implementation.
+    fn deserialize_migrated<'de, D>(version: u32, deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>;
+}
+
+/// If `entity` has component `T`, serialize it along with its version under `key` in `map`
+///
+/// The value is stored as a 2-tuple of `T::component_version()` and the component. Convenience
+/// method for [`SerializeContext`] implementations; read the result with
+/// [`deserialize_versioned`].
+pub fn try_serialize_versioned<T, K, S>(
+    entity: &EntityRef<'_>,
+    key: &K,
+    map: &mut S,
+) -> Result<(), S::Error>
+where
+    T: Component + Serialize + Versioned,
+    K: Serialize + ?Sized,
+    S: SerializeMap,
+{
+    if let Some(x) = entity.get::<&T>() {
+        map.serialize_key(key)?;
+        map.serialize_value(&(T::component_version(), &*x))?;
+    }
+    Ok(())
+}
+
+/// Deserialize the next value in `map` as a `T` written by [`try_serialize_versioned`], migrating
+/// it if necessary
+///
+/// Fails if the stored version is newer than `T::component_version()`. Convenience method for
+/// [`DeserializeContext`] implementations.
+pub fn deserialize_versioned<'de, T, M>(map: &mut M) -> Result<T, M::Error>
+where
+    T: Deserialize<'de> + Versioned,
+    M: MapAccess<'de>,
+{
+    map.next_value_seed(VersionedSeed(PhantomData))
+}
+
+struct VersionedSeed<T>(PhantomData<T>);
+
+impl<'de, T> DeserializeSeed<'de> for VersionedSeed<T>
+where
+    T: Deserialize<'de> + Versioned,
+{
+    type Value = T;
+
+    fn deserialize<D>(self, deserializer: D) -> Result<T, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        deserializer.deserialize_tuple(2, self)
+    }
+}
+
+impl<'de, T> Visitor<'de> for VersionedSeed<T>
+where
+    T: Deserialize<'de> + Versioned,
+{
+    type Value = T;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("a 2-tuple of a version and a component")
+    }
+
+    fn visit_seq<A>(self, mut seq: A) -> Result<T, A::Error>
+    where
+        A: SeqAccess<'de>,
+    {
+        let version = seq
+            .next_element::<u32>()?
+            .ok_or_else(|| de::Error::invalid_length(0, &self))?;
+        let current = T::component_version();
+        if version > current {
+            return Err(de::Error::invalid_value(
+                Unexpected::Unsigned(version.into()),
+                &"a version no newer than the current component version",
+            ));
+        }
+        let value = if version == current {
+            seq.next_element::<T>()?
+        } else {
+            seq.next_element_seed(MigrateSeed(version, PhantomData))?
+        };
+        value.ok_or_else(|| de::Error::invalid_length(1, &self))
+    }
+}
+
+struct MigrateSeed<T>(u32, PhantomData<T>);
+
+impl<'de, T: Versioned> DeserializeSeed<'de> for MigrateSeed<T> {
+    type Value = T;
+
+    fn deserialize<D>(self, deserializer: D) -> Result<T, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        T::deserialize_migrated(self.0, deserializer)
+    }
+}
+
 /// Serialize a [`World`] through a [`SerializeContext`] to a [`Serializer`]
+///
+/// Entities are handed to `serializer` one at a time as the world is traversed, and no serialized
+/// form of the whole world is ever built, so memory use is bounded by whatever `serializer` itself
+/// buffers. To save a large world to disk without holding it in memory, use a serializer that
+/// writes incrementally to a `std::io::Write`, e.g. `bincode::Serializer` over a `BufWriter`.
 // Note: deliberately not implemented in terms of `serialize_satisying::<(), _, _>` to avoid an
 // extra loop over the archetypes
 pub fn serialize<C, S>(world: &World, context: &mut C, serializer: S) -> Result<S::Ok, S::Error>
@@ -97,10 +375,13 @@ where
     C: SerializeContext,
     S: Serializer,
 {
+    #[cfg(feature = "std")]
+    let remap = context.entity_remap();
+    #[cfg(feature = "std")]
+    let _scope = remap::Scope::new(&remap::SERIALIZE, remap.as_deref());
     let mut seq = serializer.serialize_map(Some(world.len() as usize))?;
     for entity in world {
-        seq.serialize_key(&entity.entity())?;
-        seq.serialize_value(&SerializeComponents(RefCell::new((context, Some(entity)))))?;
+        serialize_entry(&mut seq, context, entity)?;
     }
     seq.end()
 }
@@ -115,6 +396,10 @@ where
     C: SerializeContext,
     S: Serializer,
 {
+    #[cfg(feature = "std")]
+    let remap = context.entity_remap();
+    #[cfg(feature = "std")]
+    let _scope = remap::Scope::new(&remap::SERIALIZE, remap.as_deref());
     let entity_count = world
         .archetypes()
         .filter(|a| a.satisfies::<Q>())
@@ -123,13 +408,74 @@ where
     let mut seq = serializer.serialize_map(Some(entity_count))?;
     for entity in world {
         if entity.satisfies::<Q>() {
-            seq.serialize_key(&entity.entity())?;
-            seq.serialize_value(&SerializeComponents(RefCell::new((context, Some(entity)))))?;
+            serialize_entry(&mut seq, context, entity)?;
+        }
+    }
+    seq.end()
+}
+
+/// Serialize all entities in a [`World`] for which `filter` returns `true` through a
+/// [`SerializeContext`] to a [`Serializer`]
+///
+/// Entities are serialized with their original [`Entity`] handles, so components referring to
+/// entities that were filtered out still observe those handles through the `SerializeContext`,
+/// which may remap or omit them as appropriate.
+///
+/// `filter` is invoked twice per entity: once to count the entities to be serialized, and again
+/// while serializing them. Use [`serialize_satisfying`] when the criteria can be expressed as a
+/// [`Query`].
+pub fn serialize_filtered<C, S, F>(
+    world: &World,
+    context: &mut C,
+    filter: F,
+    serializer: S,
+) -> Result<S::Ok, S::Error>
+where
+    C: SerializeContext,
+    S: Serializer,
+    F: Fn(Entity) -> bool,
+{
+    #[cfg(feature = "std")]
+    let remap = context.entity_remap();
+    #[cfg(feature = "std")]
+    let _scope = remap::Scope::new(&remap::SERIALIZE, remap.as_deref());
+    let entity_count = world.iter().filter(|e| filter(e.entity())).count();
+    let mut seq = serializer.serialize_map(Some(entity_count))?;
+    for entity in world {
+        if filter(entity.entity()) {
+            serialize_entry(&mut seq, context, entity)?;
         }
     }
     seq.end()
 }
 
+fn serialize_entry<C, M>(
+    map: &mut M,
+    context: &mut C,
+    entity: EntityRef<'_>,
+) -> Result<(), M::Error>
+where
+    C: SerializeContext,
+    M: SerializeMap,
+{
+    map.serialize_key(&SerializeEntityId(
+        RefCell::new(&mut *context),
+        entity.entity(),
+    ))?;
+    map.serialize_value(&SerializeComponents(RefCell::new((context, Some(entity)))))
+}
+
+struct SerializeEntityId<'a, C>(RefCell<&'a mut C>, Entity);
+
+impl<'a, C: SerializeContext> Serialize for SerializeEntityId<'a, C> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        self.0.borrow_mut().serialize_entity_id(self.1, serializer)
+    }
+}
+
 struct SerializeComponents<'a, C>(RefCell<(&'a mut C, Option<EntityRef<'a>>)>);
 
 impl<'a, C: SerializeContext> Serialize for SerializeComponents<'a, C> {
@@ -150,9 +496,153 @@ where
     C: DeserializeContext,
     D: Deserializer<'de>,
 {
+    #[cfg(feature = "std")]
+    let remap = context.entity_remap();
+    #[cfg(feature = "std")]
+    let _scope = remap::Scope::new(&remap::DESERIALIZE, remap.as_deref());
     deserializer.deserialize_map(WorldVisitor(context))
 }
 
+/// Deserialize entities with a [`DeserializeContext`] and a [`Deserializer`], spawning them into
+/// an existing `world`
+///
+/// Each saved entity keeps its ID if no live entity in `world` has that ID when the saved entity is
+/// first encountered; otherwise, a fresh entity is spawned in its place. Handles to other entities stored inside
+/// components must be rewritten with the [`EntityRemap`] passed to
+/// [`DeserializeContext::deserialize_entity_remapped`]. Returns a map from each saved entity to
+/// the entity it was loaded as.
+///
+/// Handles to entities absent from the saved data are remapped consistently, but refer to entities
+/// that are dead when loading completes. If an error occurs, entities loaded so far remain in
+/// `world`.
+pub fn deserialize_into<'de, C, D>(
+    world: &mut World,
+    context: &mut C,
+    deserializer: D,
+) -> Result<HashMap<Entity, Entity>, D::Error>
+where
+    C: DeserializeContext,
+    D: Deserializer<'de>,
+{
+    #[cfg(feature = "std")]
+    let context_remap = context.entity_remap();
+    #[cfg(feature = "std")]
+    let _scope = remap::Scope::new(&remap::DESERIALIZE, context_remap.as_deref());
+    let mut remap = EntityRemap {
+        world,
+        map: HashMap::new(),
+        loaded: HashSet::new(),
+    };
+    deserializer.deserialize_map(IntoWorldVisitor(context, &mut remap))?;
+    let EntityRemap {
+        world,
+        mut map,
+        loaded,
+    } = remap;
+    map.retain(|saved, &mut entity| {
+        let keep = loaded.contains(saved);
+        if !keep {
+            world.despawn(entity).unwrap();
+        }
+        keep
+    });
+    Ok(map)
+}
+
+/// Mapping from saved entities to the entities they're loaded as by [`deserialize_into`]
+pub struct EntityRemap<'a> {
+    world: &'a mut World,
+    map: HashMap<Entity, Entity>,
+    loaded: HashSet<Entity>,
+}
+
+impl EntityRemap<'_> {
+    /// Find the entity that the saved entity `saved` is loaded as
+    ///
+    /// May be called before `saved` itself has been deserialized.
+    pub fn get(&mut self, saved: Entity) -> Entity {
+        let world = &mut *self.world;
+        *self.map.entry(saved).or_insert_with(|| {
+            if world.contains_id(saved.id()) {
+                world.spawn(())
+            } else {
+                world.spawn_at(saved, ());
+                saved
+            }
+        })
+    }
+}
+
+struct IntoWorldVisitor<'a, 'w, C>(&'a mut C, &'a mut EntityRemap<'w>);
+
+impl<'de, 'a, 'w, C> Visitor<'de> for IntoWorldVisitor<'a, 'w, C>
+where
+    C: DeserializeContext,
+{
+    type Value = ();
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("a world")
+    }
+
+    fn visit_map<A>(self, mut map: A) -> Result<(), A::Error>
+    where
+        A: MapAccess<'de>,
+    {
+        let mut builder = EntityBuilder::new();
+        while let Some(saved) = map.next_key_seed(DeserializeEntityId(&mut *self.0))? {
+            if !self.1.loaded.insert(saved) {
+                return Err(de::Error::custom(format_args!(
+                    "duplicate entity {:?}",
+                    saved
+                )));
+            }
+            let entity = self.1.get(saved);
+            map.next_value_seed(DeserializeRemappedComponents(self.0, &mut builder, self.1))?;
+            self.1.world.insert(entity, builder.build()).unwrap();
+        }
+        Ok(())
+    }
+}
+
+struct DeserializeRemappedComponents<'a, 'w, C>(
+    &'a mut C,
+    &'a mut EntityBuilder,
+    &'a mut EntityRemap<'w>,
+);
+
+impl<'de, 'a, 'w, C> DeserializeSeed<'de> for DeserializeRemappedComponents<'a, 'w, C>
+where
+    C: DeserializeContext,
+{
+    type Value = ();
+
+    fn deserialize<D>(self, deserializer: D) -> Result<(), D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        deserializer.deserialize_map(self)
+    }
+}
+
+impl<'de, 'a, 'w, C> Visitor<'de> for DeserializeRemappedComponents<'a, 'w, C>
+where
+    C: DeserializeContext,
+{
+    type Value = ();
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("an entity's components")
+    }
+
+    fn visit_map<A>(self, map: A) -> Result<(), A::Error>
+    where
+        A: MapAccess<'de>,
+    {
+        self.0.deserialize_entity_remapped(map, self.1, self.2)
+    }
+}
+
 /// Implements deserialization of entities from a serde [`MapAccess`] into an [`EntityBuilder`]
 ///
 /// Data external to the [`World`] can be populated during deserialization by storing mutable
@@ -205,6 +695,65 @@ pub trait DeserializeContext {
     ) -> Result<(), M::Error>
     where
         M: MapAccess<'de>;
+
+    /// Deserialize a single entity for [`deserialize_into`], rewriting handles to other saved
+    /// entities with `remap`
+    ///
+    /// Defaults to [`deserialize_entity`](Self::deserialize_entity), which is correct for
+    /// components that don't contain entity handles.
+    fn deserialize_entity_remapped<'de, M>(
+        &mut self,
+        map: M,
+        entity: &mut EntityBuilder,
+        remap: &mut EntityRemap<'_>,
+    ) -> Result<(), M::Error>
+    where
+        M: MapAccess<'de>,
+    {
+        let _ = remap;
+        self.deserialize_entity(map, entity)
+    }
+
+    /// Deserialize the handle under which an entity's components were stored
+    ///
+    /// Defaults to [`Entity`]'s [`Deserialize`] implementation. Must invert
+    /// [`SerializeContext::serialize_entity_id`].
+    fn deserialize_entity_id<'de, D>(&mut self, deserializer: D) -> Result<Entity, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        Entity::deserialize(deserializer)
+    }
+
+    /// Function applied to every [`Entity`] deserialized into this context's world, including
+    /// handles stored inside components
+    ///
+    /// The inverse of [`SerializeContext::entity_remap`]. Called once per [`deserialize`] or
+    /// [`deserialize_into`] call, and applied by [`Entity`]'s [`Deserialize`] implementation until
+    /// it returns. With `deserialize_into`, handles are remapped by this function before any
+    /// [`EntityRemap`] is applied. Defaults to `None`, leaving handles unchanged.
+    ///
+    /// Requires the `std` feature, since the function is found through a thread-local variable.
+    #[cfg(feature = "std")]
+    fn entity_remap(&self) -> Option<Box<dyn Fn(Entity) -> Entity>> {
+        None
+    }
+}
+
+struct DeserializeEntityId<'a, C>(&'a mut C);
+
+impl<'de, 'a, C> DeserializeSeed<'de> for DeserializeEntityId<'a, C>
+where
+    C: DeserializeContext,
+{
+    type Value = Entity;
+
+    fn deserialize<D>(self, deserializer: D) -> Result<Entity, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        self.0.deserialize_entity_id(deserializer)
+    }
 }
 
 struct WorldVisitor<'a, C>(&'a mut C);
@@ -225,7 +774,7 @@ where
     {
         let mut world = World::new();
         let mut builder = EntityBuilder::new();
-        while let Some(id) = map.next_key()? {
+        while let Some(id) = map.next_key_seed(DeserializeEntityId(&mut *self.0))? {
             map.next_value_seed(DeserializeComponents(self.0, &mut builder))?;
             world.spawn_at(id, builder.build());
         }
@@ -269,10 +818,66 @@ where
     }
 }
 
+/// Functions applied by `Entity`'s serde implementations on behalf of the innermost context
+/// currently serializing or deserializing on this thread
+#[cfg(feature = "std")]
+pub(crate) mod remap {
+    use core::cell::Cell;
+    use core::ptr::NonNull;
+    use std::thread::LocalKey;
+
+    use crate::Entity;
+
+    type Remap = Cell<Option<NonNull<dyn Fn(Entity) -> Entity>>>;
+
+    std::thread_local! {
+        pub(crate) static SERIALIZE: Remap = const { Cell::new(None) };
+        pub(crate) static DESERIALIZE: Remap = const { Cell::new(None) };
+    }
+
+    /// Installs a function in `key` until dropped, restoring the previous one
+    pub(crate) struct Scope<'a> {
+        key: &'static LocalKey<Remap>,
+        prev: Option<NonNull<dyn Fn(Entity) -> Entity>>,
+        _marker: core::marker::PhantomData<&'a ()>,
+    }
+
+    impl<'a> Scope<'a> {
+        pub(crate) fn new(
+            key: &'static LocalKey<Remap>,
+            f: Option<&'a (dyn Fn(Entity) -> Entity + 'static)>,
+        ) -> Self {
+            let prev = key.with(|x| x.replace(f.map(NonNull::from)));
+            Self {
+                key,
+                prev,
+                _marker: core::marker::PhantomData,
+            }
+        }
+    }
+
+    impl Drop for Scope<'_> {
+        fn drop(&mut self) {
+            self.key.with(|x| x.set(self.prev));
+        }
+    }
+
+    /// Apply the function installed in `key`, if any
+    pub(crate) fn apply(key: &'static LocalKey<Remap>, entity: Entity) -> Entity {
+        match key.with(|x| x.get()) {
+            // Safety: the function outlives the `Scope` that installed it, which restores the
+            // previous value before returning
+            Some(f) => unsafe { f.as_ref()(entity) },
+            None => entity,
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use core::marker::PhantomData;
     use std::fmt;
+    use std::vec::Vec;
 
     use serde::{Deserialize, Serialize};
 
@@ -579,4 +1184,550 @@ mod tests {
             Token::TupleStructEnd,
         ])
     }
+
+    #[test]
+    fn versioned_migration() {
+        use bincode::Options;
+
+        #[derive(Serialize)]
+        struct HealthV0(u8);
+
+        #[derive(Serialize, Deserialize, Debug, PartialEq)]
+        struct Health {
+            current: u32,
+            max: u32,
+        }
+
+        impl Versioned for Health {
+            fn component_version() -> u32 {
+                1
+            }
+
+            fn deserialize_migrated<'de, D>(version: u32, d: D) -> Result<Self, D::Error>
+            where
+                D: Deserializer<'de>,
+            {
+                assert_eq!(version, 0);
+                let old = <u8 as Deserialize>::deserialize(d)?;
+                Ok(Health {
+                    current: old.into(),
+                    max: 100,
+                })
+            }
+        }
+
+        struct Versions;
+
+        impl SerializeContext for Versions {
+            fn serialize_entity<S>(
+                &mut self,
+                entity: EntityRef<'_>,
+                mut map: S,
+            ) -> Result<S::Ok, S::Error>
+            where
+                S: serde::ser::SerializeMap,
+            {
+                try_serialize_versioned::<Health, _, _>(&entity, "health", &mut map)?;
+                if let Some(x) = entity.get::<&HealthV0>() {
+                    // Emulate data written before `Health` was versioned
+                    map.serialize_entry("health", &(0u32, &*x))?;
+                }
+                map.end()
+            }
+
+            fn component_count(&self, _: EntityRef<'_>) -> Option<usize> {
+                Some(1)
+            }
+        }
+
+        impl DeserializeContext for Versions {
+            fn deserialize_entity<'de, M>(
+                &mut self,
+                mut map: M,
+                entity: &mut EntityBuilder,
+            ) -> Result<(), M::Error>
+            where
+                M: serde::de::MapAccess<'de>,
+            {
+                while map.next_key::<&str>()?.is_some() {
+                    entity.add(deserialize_versioned::<Health, _>(&mut map)?);
+                }
+                Ok(())
+            }
+        }
+
+        struct Ser<'a>(&'a World);
+
+        impl Serialize for Ser<'_> {
+            fn serialize<S: Serializer>(&self, s: S) -> Result<S::Ok, S::Error> {
+                serialize(self.0, &mut Versions, s)
+            }
+        }
+
+        let mut world = World::new();
+        let old = world.spawn((HealthV0(7),));
+        let new = world.spawn((Health { current: 3, max: 4 },));
+        let bytes = bincode::options().serialize(&Ser(&world)).unwrap();
+        let mut de = bincode::Deserializer::from_slice(&bytes, bincode::options());
+        let restored = deserialize(&mut Versions, &mut de).unwrap();
+        assert_eq!(
+            *restored.get::<&Health>(old).unwrap(),
+            Health {
+                current: 7,
+                max: 100
+            }
+        );
+        assert_eq!(
+            *restored.get::<&Health>(new).unwrap(),
+            Health { current: 3, max: 4 }
+        );
+
+        // Data from the future is rejected
+        let mut future = World::new();
+        future.spawn((HealthV0(1),));
+        struct Future<'a>(&'a World);
+        impl Serialize for Future<'_> {
+            fn serialize<S: Serializer>(&self, s: S) -> Result<S::Ok, S::Error> {
+                struct FutureCtx;
+                impl SerializeContext for FutureCtx {
+                    fn serialize_entity<S>(
+                        &mut self,
+                        _: EntityRef<'_>,
+                        mut map: S,
+                    ) -> Result<S::Ok, S::Error>
+                    where
+                        S: serde::ser::SerializeMap,
+                    {
+                        map.serialize_entry("health", &(2u32, 0u8))?;
+                        map.end()
+                    }
+
+                    fn component_count(&self, _: EntityRef<'_>) -> Option<usize> {
+                        Some(1)
+                    }
+                }
+                serialize(self.0, &mut FutureCtx, s)
+            }
+        }
+        let bytes = bincode::options().serialize(&Future(&future)).unwrap();
+        let mut de = bincode::Deserializer::from_slice(&bytes, bincode::options());
+        assert!(deserialize(&mut Versions, &mut de).is_err());
+    }
+
+    /// Context that reports component counts, as required by bincode
+    struct Counted;
+
+    impl SerializeContext for Counted {
+        fn serialize_entity<S>(&mut self, entity: EntityRef<'_>, map: S) -> Result<S::Ok, S::Error>
+        where
+            S: serde::ser::SerializeMap,
+        {
+            Context.serialize_entity(entity, map)
+        }
+
+        fn component_count(&self, entity: EntityRef<'_>) -> Option<usize> {
+            Some(entity.has::<Position>() as usize + entity.has::<Velocity>() as usize)
+        }
+    }
+
+    #[test]
+    fn serialize_filtered_roundtrip() {
+        use bincode::Options;
+
+        struct Filtered<'a>(&'a World, &'a [Entity]);
+
+        impl Serialize for Filtered<'_> {
+            fn serialize<S: Serializer>(&self, s: S) -> Result<S::Ok, S::Error> {
+                serialize_filtered(self.0, &mut Counted, |e| self.1.contains(&e), s)
+            }
+        }
+
+        let mut world = World::new();
+        let entities = (0..10)
+            .map(|i| {
+                let p = Position([i as f32; 3]);
+                if i % 3 == 0 {
+                    world.spawn((p, Velocity([1.0; 3])))
+                } else {
+                    world.spawn((p,))
+                }
+            })
+            .collect::<Vec<_>>();
+        let kept = entities.iter().copied().step_by(2).collect::<Vec<_>>();
+
+        let bytes = bincode::options()
+            .serialize(&Filtered(&world, &kept))
+            .unwrap();
+        let mut de = bincode::Deserializer::from_slice(&bytes, bincode::options());
+        let restored = deserialize(&mut Context, &mut de).unwrap();
+
+        assert_eq!(restored.len() as usize, kept.len());
+        for &e in &entities {
+            assert_eq!(restored.contains(e), kept.contains(&e));
+            if !kept.contains(&e) {
+                continue;
+            }
+            assert_eq!(
+                *restored.get::<&Position>(e).unwrap(),
+                *world.get::<&Position>(e).unwrap()
+            );
+            assert_eq!(
+                restored.get::<&Velocity>(e).ok().map(|x| *x),
+                world.get::<&Velocity>(e).ok().map(|x| *x)
+            );
+        }
+    }
+
+    #[test]
+    fn deserialize_into_remaps() {
+        use bincode::Options;
+
+        #[derive(Serialize, Deserialize, Debug, PartialEq, Copy, Clone)]
+        struct Parent(Entity);
+
+        struct Hierarchy;
+
+        impl SerializeContext for Hierarchy {
+            fn serialize_entity<S>(
+                &mut self,
+                entity: EntityRef<'_>,
+                mut map: S,
+            ) -> Result<S::Ok, S::Error>
+            where
+                S: serde::ser::SerializeMap,
+            {
+                try_serialize::<Position, _, _>(&entity, "position", &mut map)?;
+                try_serialize::<Parent, _, _>(&entity, "parent", &mut map)?;
+                map.end()
+            }
+
+            fn component_count(&self, entity: EntityRef<'_>) -> Option<usize> {
+                Some(entity.has::<Position>() as usize + entity.has::<Parent>() as usize)
+            }
+        }
+
+        impl DeserializeContext for Hierarchy {
+            fn deserialize_entity<'de, M>(
+                &mut self,
+                _: M,
+                _: &mut EntityBuilder,
+            ) -> Result<(), M::Error>
+            where
+                M: serde::de::MapAccess<'de>,
+            {
+                unreachable!()
+            }
+
+            fn deserialize_entity_remapped<'de, M>(
+                &mut self,
+                mut map: M,
+                entity: &mut EntityBuilder,
+                remap: &mut EntityRemap<'_>,
+            ) -> Result<(), M::Error>
+            where
+                M: serde::de::MapAccess<'de>,
+            {
+                while let Some(key) = map.next_key::<&str>()? {
+                    match key {
+                        "position" => {
+                            entity.add(map.next_value::<Position>()?);
+                        }
+                        _ => {
+                            let Parent(saved) = map.next_value()?;
+                            entity.add(Parent(remap.get(saved)));
+                        }
+                    }
+                }
+                Ok(())
+            }
+        }
+
+        struct Ser<'a>(&'a World);
+
+        impl Serialize for Ser<'_> {
+            fn serialize<S: Serializer>(&self, s: S) -> Result<S::Ok, S::Error> {
+                serialize(self.0, &mut Hierarchy, s)
+            }
+        }
+
+        // Children are saved before their parents
+        let mut saved = World::new();
+        let child = saved.spawn(());
+        let parent = saved.spawn((Position([1.0; 3]),));
+        let orphan = saved.spawn(());
+        let missing = saved.spawn(());
+        saved.insert_one(child, Parent(parent)).unwrap();
+        saved.insert_one(orphan, Parent(missing)).unwrap();
+        saved.despawn(missing).unwrap();
+        let bytes = bincode::options().serialize(&Ser(&saved)).unwrap();
+
+        // `child`'s ID is taken
+        let mut world = World::new();
+        let existing = world.spawn((Position([2.0; 3]),));
+        assert_eq!(existing.id(), child.id());
+
+        let mut de = bincode::Deserializer::from_slice(&bytes, bincode::options());
+        let map = deserialize_into(&mut world, &mut Hierarchy, &mut de).unwrap();
+        assert_eq!(map.len(), 3);
+        assert_eq!(world.len(), 4);
+        assert!(map.values().all(|&x| x != existing && world.contains(x)));
+        assert_eq!(
+            *world.get::<&Position>(existing).unwrap(),
+            Position([2.0; 3])
+        );
+        let loaded_parent = world.get::<&Parent>(map[&child]).unwrap().0;
+        assert_eq!(loaded_parent, map[&parent]);
+        assert_eq!(
+            *world.get::<&Position>(loaded_parent).unwrap(),
+            Position([1.0; 3])
+        );
+        assert!(!world.contains(world.get::<&Parent>(map[&orphan]).unwrap().0));
+    }
+
+    #[test]
+    fn contextual_entity_ids() {
+        use bincode::Options;
+
+        /// Tags every handle with the save slot it was written from
+        struct Slot(u8);
+
+        impl SerializeContext for Slot {
+            fn serialize_entity<S>(
+                &mut self,
+                entity: EntityRef<'_>,
+                mut map: S,
+            ) -> Result<S::Ok, S::Error>
+            where
+                S: serde::ser::SerializeMap,
+            {
+                try_serialize::<Position, _, _>(&entity, "position", &mut map)?;
+                map.end()
+            }
+
+            fn component_count(&self, entity: EntityRef<'_>) -> Option<usize> {
+                Some(entity.has::<Position>() as usize)
+            }
+
+            fn serialize_entity_id<S>(
+                &mut self,
+                entity: Entity,
+                serializer: S,
+            ) -> Result<S::Ok, S::Error>
+            where
+                S: Serializer,
+            {
+                (self.0, entity.to_bits().get()).serialize(serializer)
+            }
+        }
+
+        impl DeserializeContext for Slot {
+            fn deserialize_entity<'de, M>(
+                &mut self,
+                mut map: M,
+                entity: &mut EntityBuilder,
+            ) -> Result<(), M::Error>
+            where
+                M: serde::de::MapAccess<'de>,
+            {
+                while map.next_key::<&str>()?.is_some() {
+                    entity.add(map.next_value::<Position>()?);
+                }
+                Ok(())
+            }
+
+            fn deserialize_entity_id<'de, D>(&mut self, deserializer: D) -> Result<Entity, D::Error>
+            where
+                D: Deserializer<'de>,
+            {
+                let (slot, bits) = <(u8, u64)>::deserialize(deserializer)?;
+                if slot != self.0 {
+                    return Err(de::Error::custom("entity from another slot"));
+                }
+                Entity::from_bits(bits).ok_or_else(|| de::Error::custom("invalid entity"))
+            }
+        }
+
+        struct Ser<'a>(&'a World, u8);
+
+        impl Serialize for Ser<'_> {
+            fn serialize<S: Serializer>(&self, s: S) -> Result<S::Ok, S::Error> {
+                serialize(self.0, &mut Slot(self.1), s)
+            }
+        }
+
+        let mut world = World::new();
+        let a = world.spawn((Position([1.0; 3]),));
+        let b = world.spawn(());
+        let first = bincode::options().serialize(&Ser(&world, 1)).unwrap();
+        let second = bincode::options().serialize(&Ser(&world, 2)).unwrap();
+        assert_ne!(first, second);
+
+        let mut de = bincode::Deserializer::from_slice(&first, bincode::options());
+        let loaded = deserialize(&mut Slot(1), &mut de).unwrap();
+        assert_eq!(loaded.len(), 2);
+        assert_eq!(*loaded.get::<&Position>(a).unwrap(), Position([1.0; 3]));
+        assert!(loaded.contains(b));
+
+        let mut de = bincode::Deserializer::from_slice(&second, bincode::options());
+        assert!(deserialize(&mut Slot(1), &mut de).is_err());
+    }
+
+    #[test]
+    fn skip_unserializable() {
+        use bincode::Options;
+
+        /// Not serializable
+        struct Handle(#[allow(dead_code)] fn());
+
+        struct Dispatch;
+
+        impl SerializeContext for Dispatch {
+            fn serialize_entity<S>(
+                &mut self,
+                entity: EntityRef<'_>,
+                map: S,
+            ) -> Result<S::Ok, S::Error>
+            where
+                S: serde::ser::SerializeMap,
+            {
+                serialize_components(self, entity, map)
+            }
+
+            fn component_count(&self, entity: EntityRef<'_>) -> Option<usize> {
+                Some(count_components(self, entity))
+            }
+
+            fn serialize_component<S>(
+                &self,
+                entity: &EntityRef<'_>,
+                ty: TypeId,
+                map: &mut S,
+            ) -> Result<bool, S::Error>
+            where
+                S: SerializeMap,
+            {
+                if ty == TypeId::of::<Position>() {
+                    try_serialize::<Position, _, _>(entity, &ComponentId::Position, map)?;
+                } else if ty == TypeId::of::<Velocity>() {
+                    try_serialize::<Velocity, _, _>(entity, &ComponentId::Velocity, map)?;
+                } else {
+                    return Ok(false);
+                }
+                Ok(true)
+            }
+        }
+
+        struct Ser<'a>(&'a World);
+
+        impl Serialize for Ser<'_> {
+            fn serialize<S: Serializer>(&self, s: S) -> Result<S::Ok, S::Error> {
+                serialize(self.0, &mut Dispatch, s)
+            }
+        }
+
+        let mut world = World::new();
+        let a = world.spawn((Position([1.0; 3]), Handle(|| ()), Velocity([2.0; 3])));
+        let b = world.spawn((Handle(|| ()),));
+        let c = world.spawn((Velocity([3.0; 3]),));
+        assert_eq!(count_components(&Dispatch, world.entity(a).unwrap()), 2);
+
+        let bytes = bincode::options().serialize(&Ser(&world)).unwrap();
+        let mut de = bincode::Deserializer::from_slice(&bytes, bincode::options());
+        let loaded = deserialize(&mut Context, &mut de).unwrap();
+        assert_eq!(loaded.len(), 3);
+        let a = loaded.entity(a).unwrap();
+        assert_eq!(*a.get::<&Position>().unwrap(), Position([1.0; 3]));
+        assert_eq!(*a.get::<&Velocity>().unwrap(), Velocity([2.0; 3]));
+        assert!(!a.has::<Handle>());
+        assert_eq!(loaded.entity(b).unwrap().len(), 0);
+        assert_eq!(*loaded.get::<&Velocity>(c).unwrap(), Velocity([3.0; 3]));
+    }
+
+    #[test]
+    fn entity_remap() {
+        use bincode::Options;
+        use std::collections::BTreeMap;
+
+        #[derive(Serialize, Deserialize, Debug, PartialEq)]
+        struct Target(Option<Entity>);
+
+        /// Offsets every entity ID by 1000 in the saved data
+        struct Offset;
+
+        fn shift(entity: Entity, by: i64) -> Entity {
+            Entity::from_bits((entity.to_bits().get() as i64 + by) as u64).unwrap()
+        }
+
+        impl SerializeContext for Offset {
+            fn serialize_entity<S>(
+                &mut self,
+                entity: EntityRef<'_>,
+                mut map: S,
+            ) -> Result<S::Ok, S::Error>
+            where
+                S: serde::ser::SerializeMap,
+            {
+                try_serialize::<Target, _, _>(&entity, "target", &mut map)?;
+                map.end()
+            }
+
+            fn component_count(&self, entity: EntityRef<'_>) -> Option<usize> {
+                Some(entity.has::<Target>() as usize)
+            }
+
+            fn entity_remap(&self) -> Option<Box<dyn Fn(Entity) -> Entity>> {
+                Some(Box::new(|e| shift(e, 1000)))
+            }
+        }
+
+        impl DeserializeContext for Offset {
+            fn deserialize_entity<'de, M>(
+                &mut self,
+                mut map: M,
+                entity: &mut EntityBuilder,
+            ) -> Result<(), M::Error>
+            where
+                M: serde::de::MapAccess<'de>,
+            {
+                while map.next_key::<&str>()?.is_some() {
+                    entity.add(map.next_value::<Target>()?);
+                }
+                Ok(())
+            }
+
+            fn entity_remap(&self) -> Option<Box<dyn Fn(Entity) -> Entity>> {
+                Some(Box::new(|e| shift(e, -1000)))
+            }
+        }
+
+        struct Ser<'a>(&'a World);
+
+        impl Serialize for Ser<'_> {
+            fn serialize<S: Serializer>(&self, s: S) -> Result<S::Ok, S::Error> {
+                serialize(self.0, &mut Offset, s)
+            }
+        }
+
+        let mut world = World::new();
+        let a = world.spawn((Target(None),));
+        let b = world.spawn((Target(Some(a)),));
+        let bytes = bincode::options().serialize(&Ser(&world)).unwrap();
+
+        // Both keys and nested handles are remapped in the saved data
+        let raw = bincode::options()
+            .deserialize::<BTreeMap<Entity, BTreeMap<std::string::String, Target>>>(&bytes)
+            .unwrap();
+        assert_eq!(raw[&shift(b, 1000)]["target"], Target(Some(shift(a, 1000))));
+
+        let mut de = bincode::Deserializer::from_slice(&bytes, bincode::options());
+        let loaded = deserialize(&mut Offset, &mut de).unwrap();
+        assert_eq!(*loaded.get::<&Target>(b).unwrap(), Target(Some(a)));
+        assert_eq!(*loaded.get::<&Target>(a).unwrap(), Target(None));
+
+        // The remap only applies within the serialization call
+        assert_eq!(
+            bincode::options().serialize(&a).unwrap(),
+            bincode::options().serialize(&a.to_bits().get()).unwrap()
+        );
+    }
 }
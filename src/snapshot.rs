@@ -0,0 +1,265 @@
+use core::any::TypeId;
+
+use crate::alloc::collections::BTreeMap;
+use crate::alloc::vec::Vec;
+use crate::archetype::TypeIdMap;
+use crate::{Component, Entity, EntityBuilderClone, EntityRef, World};
+
+/// A set of component types that [`World::snapshot`] records
+///
+/// Registered components must implement [`PartialEq`] so that [`WorldSnapshot::diff`] can omit
+/// components whose values haven't changed.
+///
+/// # Example
+/// ```
+/// # use hecs::*;
+/// let mut registry = SnapshotRegistry::new();
+/// registry.register::<i32>().register::<bool>();
+///
+/// let mut server = World::new();
+/// let a = server.spawn((1, true));
+/// let b = server.spawn((2,));
+/// let mut client = World::new();
+/// WorldSnapshot::diff(&WorldSnapshot::default(), &server.snapshot(&registry)).apply(&mut client);
+///
+/// let old = server.snapshot(&registry);
+/// *server.get::<&mut i32>(a).unwrap() = 3;
+/// server.despawn(b).unwrap();
+/// let delta = WorldSnapshot::diff(&old, &server.snapshot(&registry));
+/// // Only the modified component is included
+/// assert_eq!(delta.changed().count(), 1);
+/// delta.apply(&mut client);
+/// assert_eq!(*client.get::<&i32>(a).unwrap(), 3);
+/// assert!(!client.contains(b));
+/// ```
+#[derive(Default)]
+pub struct SnapshotRegistry {
+    entries: TypeIdMap<SnapshotEntry>,
+}
+
+#[derive(Copy, Clone)]
+struct SnapshotEntry {
+    /// Add a clone of the entity's component, if present, to the builder
+    capture: fn(EntityRef<'_>, &mut EntityBuilderClone),
+    /// Add the component from `new` to `out` if it's absent from or different in `old`
+    diff: fn(&EntityBuilderClone, &EntityBuilderClone, &mut EntityBuilderClone),
+    /// Remove the component from an entity
+    remove: fn(&mut World, Entity),
+}
+
+impl SnapshotRegistry {
+    /// Create a registry containing no component types
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Record `T` components in snapshots
+    pub fn register<T: Component + Clone + PartialEq>(&mut self) -> &mut Self {
+        fn capture<T: Component + Clone>(entity: EntityRef<'_>, out: &mut EntityBuilderClone) {
+            if let Some(x) = entity.get::<&T>() {
+                out.add((*x).clone());
+            }
+        }
+
+        fn diff<T: Component + Clone + PartialEq>(
+            old: &EntityBuilderClone,
+            new: &EntityBuilderClone,
+            out: &mut EntityBuilderClone,
+        ) {
+            let new = match new.get::<&T>() {
+                Some(x) => x,
+                None => return,
+            };
+            if old.get::<&T>() != Some(new) {
+                out.add(new.clone());
+            }
+        }
+
+        fn remove<T: Component>(world: &mut World, entity: Entity) {
+            let _ = world.remove_one::<T>(entity);
+        }
+
+        self.entries.insert(
+            TypeId::of::<T>(),
+            SnapshotEntry {
+                capture: capture::<T>,
+                diff: diff::<T>,
+                remove: remove::<T>,
+            },
+        );
+        self
+    }
+
+    /// Whether `T` components are recorded
+    pub fn contains<T: Component>(&self) -> bool {
+        self.entries.contains_key(&TypeId::of::<T>())
+    }
+
+    /// How to remove a registered component of type `ty` from an entity
+    #[cfg(any(feature = "row-serialize", feature = "column-serialize"))]
+    pub(crate) fn remover(&self, ty: TypeId) -> Option<fn(&mut World, Entity)> {
+        self.entries.get(&ty).map(|x| x.remove)
+    }
+}
+
+/// Copies of the registered components of every entity in a [`World`] at some point in time
+///
+/// Constructed by [`World::snapshot`]. The default value contains no entities, and can be diffed
+/// against to produce a [`WorldDelta`] that recreates an entire world.
+#[derive(Default)]
+pub struct WorldSnapshot {
+    entries: TypeIdMap<SnapshotEntry>,
+    entities: BTreeMap<Entity, EntityBuilderClone>,
+}
+
+impl WorldSnapshot {
+    pub(crate) fn capture(world: &World, registry: &SnapshotRegistry) -> Self {
+        let entities = world
+            .iter()
+            .map(|entity| {
+                let mut components = EntityBuilderClone::new();
+                for entry in registry.entries.values() {
+                    (entry.capture)(entity, &mut components);
+                }
+                (entity.entity(), components)
+            })
+            .collect();
+        Self {
+            entries: registry.entries.clone(),
+            entities,
+        }
+    }
+
+    /// Number of entities recorded
+    pub fn len(&self) -> usize {
+        self.entities.len()
+    }
+
+    /// Whether no entities were recorded
+    pub fn is_empty(&self) -> bool {
+        self.entities.is_empty()
+    }
+
+    /// Whether `entity` was recorded
+    pub fn contains(&self, entity: Entity) -> bool {
+        self.entities.contains_key(&entity)
+    }
+
+    /// Recorded components of `entity`
+    pub fn get(&self, entity: Entity) -> Option<&EntityBuilderClone> {
+        self.entities.get(&entity)
+    }
+
+    /// Compute the changes that transform `old` into `new`
+    ///
+    /// Components that are equal in both snapshots are omitted.
+    pub fn diff(old: &WorldSnapshot, new: &WorldSnapshot) -> WorldDelta {
+        let empty = EntityBuilderClone::new();
+        let mut delta = WorldDelta::default();
+        for &entity in old.entities.keys() {
+            if !new.entities.contains_key(&entity) {
+                delta.despawned.push(entity);
+            }
+        }
+        for (&entity, components) in &new.entities {
+            let previous = match old.entities.get(&entity) {
+                Some(x) => x,
+                None => {
+                    delta.spawned.push(entity);
+                    &empty
+                }
+            };
+            let mut changed = EntityBuilderClone::new();
+            for entry in new.entries.values() {
+                (entry.diff)(previous, components, &mut changed);
+            }
+            if changed.component_types().next().is_some() {
+                delta.changed.push((entity, changed));
+            }
+            for ty in previous.component_types() {
+                if components.component_types().any(|x| x == ty) {
+                    continue;
+                }
+                if let Some(entry) = new.entries.get(&ty).or_else(|| old.entries.get(&ty)) {
+                    delta.removed.push(Removal {
+                        entity,
+                        ty,
+                        remove: entry.remove,
+                    });
+                }
+            }
+        }
+        delta
+    }
+}
+
+/// Entities and components that differ between two [`WorldSnapshot`]s
+///
+/// Produced by [`WorldSnapshot::diff`].
+#[derive(Default, Clone)]
+pub struct WorldDelta {
+    pub(crate) spawned: Vec<Entity>,
+    pub(crate) despawned: Vec<Entity>,
+    pub(crate) changed: Vec<(Entity, EntityBuilderClone)>,
+    pub(crate) removed: Vec<Removal>,
+}
+
+#[derive(Copy, Clone)]
+pub(crate) struct Removal {
+    pub(crate) entity: Entity,
+    pub(crate) ty: TypeId,
+    pub(crate) remove: fn(&mut World, Entity),
+}
+
+impl WorldDelta {
+    /// Whether the snapshots were identical
+    pub fn is_empty(&self) -> bool {
+        self.spawned.is_empty()
+            && self.despawned.is_empty()
+            && self.changed.is_empty()
+            && self.removed.is_empty()
+    }
+
+    /// Entities that exist only in the newer snapshot
+    pub fn spawned(&self) -> &[Entity] {
+        &self.spawned
+    }
+
+    /// Entities that exist only in the older snapshot
+    pub fn despawned(&self) -> &[Entity] {
+        &self.despawned
+    }
+
+    /// Components which were added or modified, by entity
+    ///
+    /// Includes every component of newly spawned entities.
+    pub fn changed(&self) -> impl ExactSizeIterator<Item = (Entity, &EntityBuilderClone)> + '_ {
+        self.changed.iter().map(|(entity, x)| (*entity, x))
+    }
+
+    /// Components which were removed from entities that still exist
+    pub fn removed(&self) -> impl ExactSizeIterator<Item = (Entity, TypeId)> + '_ {
+        self.removed.iter().map(|x| (x.entity, x.ty))
+    }
+
+    /// Apply these changes to `world`
+    ///
+    /// Spawned entities are created with [`World::spawn_at`] if they don't already exist, so handles
+    /// remain valid across worlds. Changes to entities absent from `world` are ignored.
+    pub fn apply(&self, world: &mut World) {
+        for &entity in &self.despawned {
+            let _ = world.despawn(entity);
+        }
+        for &entity in &self.spawned {
+            if !world.contains(entity) {
+                world.spawn_at(entity, ());
+            }
+        }
+        for removal in &self.removed {
+            (removal.remove)(world, removal.entity);
+        }
+        for (entity, components) in &self.changed {
+            let _ = world.insert(*entity, &components.clone().build());
+        }
+    }
+}
@@ -0,0 +1,249 @@
+use core::any::{type_name, Any, TypeId};
+
+use crate::alloc::boxed::Box;
+use crate::alloc::vec::Vec;
+use crate::archetype::TypeIdMap;
+use crate::{Component, Entity};
+
+/// Storage for component types opted out of archetypes by
+/// [`World::set_sparse`](crate::World::set_sparse)
+#[derive(Default)]
+pub(crate) struct SparseStorage {
+    sets: TypeIdMap<Box<dyn AnySparseSet>>,
+}
+
+impl SparseStorage {
+    pub(crate) fn is_empty(&self) -> bool {
+        self.sets.is_empty()
+    }
+
+    pub(crate) fn register<T: Component>(&mut self) -> &mut SparseSet<T> {
+        self.sets
+            .entry(TypeId::of::<T>())
+            .or_insert_with(|| Box::new(SparseSet::<T>::default()))
+            .as_any_mut()
+            .downcast_mut()
+            .unwrap()
+    }
+
+    pub(crate) fn get<T: Component>(&self) -> Option<&SparseSet<T>> {
+        if self.sets.is_empty() {
+            return None;
+        }
+        let set = self.sets.get(&TypeId::of::<T>())?;
+        Some(set.as_any().downcast_ref().unwrap())
+    }
+
+    pub(crate) fn get_mut<T: Component>(&mut self) -> Option<&mut SparseSet<T>> {
+        if self.sets.is_empty() {
+            return None;
+        }
+        let set = self.sets.get_mut(&TypeId::of::<T>())?;
+        Some(set.as_any_mut().downcast_mut().unwrap())
+    }
+
+    /// Panic if any of `ids` must be stored sparsely
+    pub(crate) fn assert_dense(&self, ids: &[TypeId]) {
+        for id in ids {
+            if let Some(set) = self.sets.get(id) {
+                panic!(
+                    "{} is stored sparsely, and must be inserted with `insert_one`",
+                    set.type_name()
+                );
+            }
+        }
+    }
+
+    /// Types and names of the sparse components of the entity with ID `id`
+    pub(crate) fn component_types(
+        &self,
+        id: u32,
+    ) -> impl Iterator<Item = (TypeId, &'static str)> + '_ {
+        self.sets
+            .iter()
+            .filter(move |(_, set)| set.contains(id))
+            .map(|(&ty, set)| (ty, set.type_name()))
+    }
+
+    /// Move every sparse component of the entity with ID `id` out, to be restored later by
+    /// [`restore_entity`](Self::restore_entity)
+    pub(crate) fn take_entity(&mut self, id: u32) -> SparseComponents {
+        SparseComponents(
+            self.sets
+                .iter_mut()
+                .filter_map(|(&ty, set)| Some((ty, set.take(id)?)))
+                .collect(),
+        )
+    }
+
+    /// Give `entity` the sparse components previously taken by
+    /// [`take_entity`](Self::take_entity)
+    pub(crate) fn restore_entity(&mut self, entity: Entity, components: SparseComponents) {
+        for (ty, value) in components.0 {
+            self.sets.get_mut(&ty).unwrap().put(entity, value);
+        }
+    }
+
+    /// Drop every sparse component of the entity with ID `id`
+    pub(crate) fn remove_entity(&mut self, id: u32) {
+        for set in self.sets.values_mut() {
+            set.remove_entity(id);
+        }
+    }
+
+    /// Drop every sparse component
+    pub(crate) fn clear(&mut self) {
+        for set in self.sets.values_mut() {
+            set.clear();
+        }
+    }
+}
+
+/// Type-erased sparse components of a single entity, from [`SparseStorage::take_entity`]
+#[derive(Default)]
+pub(crate) struct SparseComponents(Vec<(TypeId, Box<dyn Any>)>);
+
+/// Components of a single type, densely packed and indexed by entity ID
+pub(crate) struct SparseSet<T> {
+    /// Maps entity IDs to indices into `entities` and `values`, or `u32::MAX`
+    index: Vec<u32>,
+    entities: Vec<Entity>,
+    values: Vec<T>,
+}
+
+impl<T> Default for SparseSet<T> {
+    fn default() -> Self {
+        Self {
+            index: Vec::new(),
+            entities: Vec::new(),
+            values: Vec::new(),
+        }
+    }
+}
+
+impl<T> SparseSet<T> {
+    fn slot(&self, id: u32) -> Option<usize> {
+        match self.index.get(id as usize) {
+            Some(&i) if i != u32::MAX => Some(i as usize),
+            _ => None,
+        }
+    }
+
+    /// Store `value` for `entity`, returning the value it replaced
+    pub(crate) fn insert(&mut self, entity: Entity, value: T) -> Option<T> {
+        if let Some(i) = self.slot(entity.id) {
+            self.entities[i] = entity;
+            return Some(core::mem::replace(&mut self.values[i], value));
+        }
+        if self.index.len() <= entity.id as usize {
+            self.index.resize(entity.id as usize + 1, u32::MAX);
+        }
+        self.index[entity.id as usize] = self.values.len() as u32;
+        self.entities.push(entity);
+        self.values.push(value);
+        None
+    }
+
+    pub(crate) fn remove(&mut self, id: u32) -> Option<T> {
+        let i = self.slot(id)?;
+        self.index[id as usize] = u32::MAX;
+        self.entities.swap_remove(i);
+        let value = self.values.swap_remove(i);
+        if let Some(moved) = self.entities.get(i) {
+            self.index[moved.id as usize] = i as u32;
+        }
+        Some(value)
+    }
+
+    pub(crate) fn get(&self, id: u32) -> Option<&T> {
+        Some(&self.values[self.slot(id)?])
+    }
+
+    pub(crate) fn get_mut(&mut self, id: u32) -> Option<&mut T> {
+        let i = self.slot(id)?;
+        Some(&mut self.values[i])
+    }
+
+    pub(crate) fn entities(&self) -> &[Entity] {
+        &self.entities
+    }
+
+    pub(crate) fn values(&self) -> &[T] {
+        &self.values
+    }
+
+    /// Entities and their values, with `entities()[i]` owning `values[i]`
+    pub(crate) fn split_mut(&mut self) -> (&[Entity], &mut [T]) {
+        (&self.entities, &mut self.values)
+    }
+}
+
+// `World` is `Send + Sync` only because every `Component` is, so no such bound is needed here
+trait AnySparseSet {
+    fn as_any(&self) -> &dyn Any;
+    fn as_any_mut(&mut self) -> &mut dyn Any;
+    fn type_name(&self) -> &'static str;
+    fn contains(&self, id: u32) -> bool;
+    fn remove_entity(&mut self, id: u32);
+    fn take(&mut self, id: u32) -> Option<Box<dyn Any>>;
+    fn put(&mut self, entity: Entity, value: Box<dyn Any>);
+    fn clear(&mut self);
+}
+
+impl<T: Component> AnySparseSet for SparseSet<T> {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+
+    fn type_name(&self) -> &'static str {
+        type_name::<T>()
+    }
+
+    fn contains(&self, id: u32) -> bool {
+        self.slot(id).is_some()
+    }
+
+    fn remove_entity(&mut self, id: u32) {
+        self.remove(id);
+    }
+
+    fn take(&mut self, id: u32) -> Option<Box<dyn Any>> {
+        Some(Box::new(self.remove(id)?))
+    }
+
+    fn put(&mut self, entity: Entity, value: Box<dyn Any>) {
+        self.insert(entity, *value.downcast().unwrap());
+    }
+
+    fn clear(&mut self) {
+        self.index.clear();
+        self.entities.clear();
+        self.values.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn swap_remove() {
+        let mut set = SparseSet::default();
+        let generation = core::num::NonZeroU32::new(1).unwrap();
+        let [a, b, c] = [0, 5, 2].map(|id| Entity { id, generation });
+        assert_eq!(set.insert(a, 'a'), None);
+        assert_eq!(set.insert(b, 'b'), None);
+        assert_eq!(set.insert(c, 'c'), None);
+        assert_eq!(set.insert(b, 'B'), Some('b'));
+        assert_eq!(set.remove(a.id), Some('a'));
+        assert_eq!(set.remove(a.id), None);
+        assert_eq!(set.get(b.id), Some(&'B'));
+        assert_eq!(set.get(c.id), Some(&'c'));
+        assert_eq!(set.get(7), None);
+        assert_eq!(set.entities(), [c, b]);
+    }
+}
@@ -0,0 +1,192 @@
+use core::any::{Any, TypeId};
+use core::ptr::NonNull;
+use core::slice;
+
+use crate::alloc::{boxed::Box, vec::Vec};
+use crate::archetype::TypeIdMap;
+use crate::entities::EntityMeta;
+use crate::{Archetype, Component, Entity, World};
+
+/// Component types registered by [`World::register_trait`], keyed by the trait object type
+#[derive(Default)]
+pub(crate) struct TraitRegistry {
+    /// Each value is a `TraitImpls<D>` for the `D` identified by the key
+    traits: TypeIdMap<Box<dyn Any>>,
+}
+
+impl TraitRegistry {
+    pub(crate) fn register<D: ?Sized + 'static, T: Component>(&mut self, cast: fn(&T) -> &D) {
+        let impls = self
+            .traits
+            .entry(TypeId::of::<D>())
+            .or_insert_with(|| Box::new(TraitImpls::<D> { impls: Vec::new() }))
+            .downcast_mut::<TraitImpls<D>>()
+            .unwrap();
+        // Safety: `upcast` is only ever invoked on pointers to `T`
+        let upcast: Upcast<D> =
+            Box::new(move |ptr| NonNull::from(cast(unsafe { ptr.cast::<T>().as_ref() })));
+        match impls.impls.iter_mut().find(|x| x.ty == TypeId::of::<T>()) {
+            Some(existing) => existing.upcast = upcast,
+            None => impls.impls.push(TraitImpl {
+                ty: TypeId::of::<T>(),
+                upcast,
+            }),
+        }
+    }
+
+    fn get<D: ?Sized + 'static>(&self) -> &[TraitImpl<D>] {
+        self.traits
+            .get(&TypeId::of::<D>())
+            .map_or(&[], |x| &x.downcast_ref::<TraitImpls<D>>().unwrap().impls)
+    }
+}
+
+struct TraitImpls<D: ?Sized> {
+    impls: Vec<TraitImpl<D>>,
+}
+
+/// Converts a pointer to a component of the type `ty` into a trait object
+type Upcast<D> = Box<dyn Fn(NonNull<u8>) -> NonNull<D> + Send + Sync>;
+
+struct TraitImpl<D: ?Sized> {
+    ty: TypeId,
+    upcast: Upcast<D>,
+}
+
+/// Every component whose type was registered for the trait object type `D`
+///
+/// Constructed by [`World::query_trait`]. Every matched column is borrowed when the query is
+/// constructed and released when it is dropped, following the same rules as statically typed
+/// queries: iterating a [`QueryBorrow`](crate::QueryBorrow) that uniquely borrows a matched
+/// component type while a `TraitQueryBorrow` is live, or vice versa, will panic.
+pub struct TraitQueryBorrow<'w, D: ?Sized + 'static> {
+    world: &'w World,
+    columns: Vec<TraitColumn<'w, D>>,
+}
+
+struct TraitColumn<'w, D: ?Sized> {
+    archetype: u32,
+    state: usize,
+    base: NonNull<u8>,
+    stride: usize,
+    upcast: &'w Upcast<D>,
+}
+
+impl<'w, D: ?Sized + 'static> TraitQueryBorrow<'w, D> {
+    pub(crate) fn new(world: &'w World, registry: &'w TraitRegistry) -> Self {
+        let impls = registry.get::<D>();
+        let mut columns = Vec::new();
+        for (index, archetype) in world.archetypes_inner().iter().enumerate() {
+            if archetype.is_disabled() {
+                continue;
+            }
+            for imp in impls {
+                let state = match archetype.get_state_dynamic(imp.ty) {
+                    Some(x) => x,
+                    None => continue,
+                };
+                unsafe {
+                    archetype.borrow_raw(state);
+                }
+                columns.push(TraitColumn {
+                    archetype: index as u32,
+                    state,
+                    base: archetype.get_base_dynamic(state),
+                    stride: archetype.types()[state].layout().size(),
+                    upcast: &imp.upcast,
+                });
+            }
+        }
+        Self { world, columns }
+    }
+
+    /// Execute the query
+    pub fn iter(&self) -> TraitQueryIter<'_, D> {
+        TraitQueryIter {
+            meta: self.world.entities_meta(),
+            source: self.world.archetypes_inner(),
+            columns: self.columns.iter(),
+            current: None,
+            index: 0,
+        }
+    }
+}
+
+impl<D: ?Sized + 'static> Drop for TraitQueryBorrow<'_, D> {
+    fn drop(&mut self) {
+        let source = self.world.archetypes_inner();
+        for column in &self.columns {
+            unsafe {
+                source[column.archetype as usize].release_raw(column.state);
+            }
+        }
+    }
+}
+
+impl<'q, D: ?Sized + 'static> IntoIterator for &'q TraitQueryBorrow<'_, D> {
+    type IntoIter = TraitQueryIter<'q, D>;
+    type Item = (Entity, &'q D);
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+/// Iterator over the components matched by a [`TraitQueryBorrow`]
+pub struct TraitQueryIter<'q, D: ?Sized + 'static> {
+    meta: &'q [EntityMeta],
+    source: &'q [Archetype],
+    columns: slice::Iter<'q, TraitColumn<'q, D>>,
+    current: Option<(&'q Archetype, &'q TraitColumn<'q, D>)>,
+    index: u32,
+}
+
+impl<'q, D: ?Sized + 'static> Iterator for TraitQueryIter<'q, D> {
+    type Item = (Entity, &'q D);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            match self.current {
+                Some((archetype, column)) if self.index < archetype.len() => {
+                    let index = self.index;
+                    self.index += 1;
+                    let id = archetype.entity_id(index);
+                    let entity = Entity {
+                        id,
+                        generation: self.meta[id as usize].generation,
+                    };
+                    // Safety: the column is borrowed for `'q` and holds `archetype.len()`
+                    // components of the type `upcast` was registered for
+                    let component = unsafe {
+                        let ptr = column.base.as_ptr().add(index as usize * column.stride);
+                        (column.upcast)(NonNull::new_unchecked(ptr)).as_ref()
+                    };
+                    return Some((entity, component));
+                }
+                _ => {
+                    let next = self.columns.next()?;
+                    self.current = Some((&self.source[next.archetype as usize], next));
+                    self.index = 0;
+                }
+            }
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let n = self.len();
+        (n, Some(n))
+    }
+}
+
+impl<D: ?Sized + 'static> ExactSizeIterator for TraitQueryIter<'_, D> {
+    fn len(&self) -> usize {
+        let current = self
+            .current
+            .map_or(0, |(archetype, _)| archetype.len() - self.index);
+        self.columns
+            .clone()
+            .map(|x| self.source[x.archetype as usize].len())
+            .sum::<u32>() as usize
+            + current as usize
+    }
+}
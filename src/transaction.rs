@@ -0,0 +1,162 @@
+use core::any::TypeId;
+
+use crate::alloc::vec::Vec;
+use crate::sparse::SparseComponents;
+use crate::{Bundle, ComponentError, DynamicBundle, Entity, EntityBuilder, NoSuchEntity, World};
+
+/// A sequence of structural changes to a [`World`] that can be undone, obtained from
+/// [`World::transaction`]
+///
+/// Each operation is applied immediately, so its effects are visible through
+/// [`world`](Self::world), and recorded along with whatever is needed to revert it. Reverting
+/// applies the inverse of each operation in reverse order: spawned entities are despawned,
+/// despawned entities are respawned with their original handles and components, inserted
+/// components are removed or restored to their previous values, and removed components are
+/// reinserted.
+///
+/// Only operations performed through the transaction are recorded. In particular, changes made to
+/// component values through [`World::get`] on [`world`](Self::world) are not undone. Hooks fire for
+/// reverted operations just as for any other structural change, and reverted entities are
+/// reported by [`Added`](crate::Added) as if newly added.
+pub struct Transaction<'w> {
+    world: &'w mut World,
+    journal: Vec<Undo>,
+}
+
+enum Undo {
+    Spawned(Entity),
+    Despawned(Entity, EntityBuilder, SparseComponents),
+    Inserted {
+        entity: Entity,
+        added: Vec<TypeId>,
+        replaced: EntityBuilder,
+    },
+    Removed(Entity, EntityBuilder),
+}
+
+impl<'w> Transaction<'w> {
+    pub(crate) fn new(world: &'w mut World) -> Self {
+        Self {
+            world,
+            journal: Vec::new(),
+        }
+    }
+
+    /// Shared access to the world, reflecting every operation performed so far
+    pub fn world(&self) -> &World {
+        self.world
+    }
+
+    /// Create an entity with certain components
+    ///
+    /// See [`World::spawn`].
+    pub fn spawn(&mut self, components: impl DynamicBundle) -> Entity {
+        let entity = self.world.spawn(components);
+        self.journal.push(Undo::Spawned(entity));
+        entity
+    }
+
+    /// Destroy an entity and all its components
+    ///
+    /// Unlike [`World::despawn`], [relations](World::register_relation) targeting `entity` are left
+    /// as-is, since despawning their sources could not be reliably undone.
+    pub fn despawn(&mut self, entity: Entity) -> Result<(), NoSuchEntity> {
+        let mut components = EntityBuilder::new();
+        let mut sparse = SparseComponents::default();
+        components.add_bundle(self.world.take_with_sparse(entity, &mut sparse)?);
+        self.journal
+            .push(Undo::Despawned(entity, components, sparse));
+        Ok(())
+    }
+
+    /// Add `components` to `entity`
+    ///
+    /// See [`World::insert`].
+    pub fn insert(
+        &mut self,
+        entity: Entity,
+        components: impl DynamicBundle,
+    ) -> Result<(), NoSuchEntity> {
+        let mut added = components.with_ids(|ids| ids.to_vec());
+        let replaced = self.world.replace(entity, components)?;
+        added.retain(|&id| !replaced.component_types().any(|x| x == id));
+        self.journal.push(Undo::Inserted {
+            entity,
+            added,
+            replaced,
+        });
+        Ok(())
+    }
+
+    /// Add `component` to `entity`
+    ///
+    /// See [`insert`](Self::insert).
+    pub fn insert_one(
+        &mut self,
+        entity: Entity,
+        component: impl crate::Component,
+    ) -> Result<(), NoSuchEntity> {
+        self.insert(entity, (component,))
+    }
+
+    /// Remove components from `entity`
+    ///
+    /// See [`World::remove`]. The removed components are retained in case the transaction is
+    /// reverted, and otherwise dropped when it completes.
+    pub fn remove<T: Bundle + 'static>(&mut self, entity: Entity) -> Result<(), ComponentError> {
+        let removed = self.world.remove::<T>(entity)?;
+        let mut components = EntityBuilder::new();
+        components.add_bundle(removed);
+        self.journal.push(Undo::Removed(entity, components));
+        Ok(())
+    }
+
+    /// Remove the `T` component from `entity`
+    ///
+    /// See [`remove`](Self::remove).
+    pub fn remove_one<T: crate::Component>(
+        &mut self,
+        entity: Entity,
+    ) -> Result<(), ComponentError> {
+        self.remove::<(T,)>(entity)
+    }
+
+    /// Keep every operation performed so far
+    pub(crate) fn commit(mut self) {
+        self.journal.clear();
+    }
+
+    /// Undo every operation performed so far
+    fn revert(&mut self) {
+        while let Some(undo) = self.journal.pop() {
+            // Operations are undone in reverse order, so each entity is in the state it was left
+            // in by the operation being undone.
+            match undo {
+                Undo::Spawned(entity) => {
+                    drop(self.world.take(entity).unwrap());
+                }
+                Undo::Despawned(entity, mut components, sparse) => {
+                    self.world.spawn_at(entity, components.build());
+                    self.world.restore_sparse(entity, sparse);
+                }
+                Undo::Inserted {
+                    entity,
+                    added,
+                    mut replaced,
+                } => {
+                    self.world.remove_dynamic(entity, &added).unwrap();
+                    self.world.insert(entity, replaced.build()).unwrap();
+                }
+                Undo::Removed(entity, mut components) => {
+                    self.world.insert(entity, components.build()).unwrap();
+                }
+            }
+        }
+    }
+}
+
+impl Drop for Transaction<'_> {
+    fn drop(&mut self) {
+        self.revert();
+    }
+}
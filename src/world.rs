@@ -5,27 +5,49 @@
 // http://opensource.org/licenses/MIT>, at your option. This file may not be
 // copied, modified, or distributed except according to those terms.
 
-use crate::alloc::{vec, vec::Vec};
-use core::any::TypeId;
+use crate::alloc::{format, string::String, vec, vec::Vec};
+use core::alloc::Layout;
+use core::any::{type_name, Any, TypeId};
 use core::borrow::Borrow;
 use core::convert::TryFrom;
-use core::hash::{BuildHasherDefault, Hasher};
+use core::hash::{BuildHasherDefault, Hash, Hasher};
 use spin::Mutex;
 
-use core::{fmt, ptr};
+use core::num::NonZeroU32;
+use core::ops::Deref;
+use core::ptr::NonNull;
+use core::sync::atomic::{AtomicU32, Ordering};
+use core::{fmt, mem, ptr};
 
 #[cfg(feature = "std")]
 use std::error::Error;
 
 use hashbrown::hash_map::{Entry, HashMap};
+use hashbrown::HashSet;
 
 use crate::alloc::boxed::Box;
-use crate::archetype::{Archetype, TypeIdMap, TypeInfo};
-use crate::entities::{Entities, EntityMeta, Location, ReserveEntitiesIterator};
-use crate::query::{assert_borrow, assert_distinct};
+use crate::archetype::{
+    Archetype, ArchetypeColumn, ArchetypeMemory, ColumnMemory, TypeIdMap, TypeInfo,
+    TICK_CLAMP_INTERVAL,
+};
+use crate::dynamic_query::DynamicQueryBorrow;
+use crate::entities::{
+    Entities, EntityAllocatorPolicy, EntityMeta, Location, ReserveEntitiesIterator,
+};
+use crate::history::History;
+use crate::hooks::{Hooks, Observer};
+use crate::name::NameIndex;
+use crate::query::{assert_borrow, assert_distinct, prepare_iter, FetchAny, QueryCache};
+use crate::relation::RelationInfo;
+use crate::sparse::{SparseComponents, SparseStorage};
+use crate::trait_query::{TraitQueryBorrow, TraitRegistry};
 use crate::{
-    Bundle, ColumnBatch, ComponentRef, DynamicBundle, Entity, EntityRef, Fetch, MissingComponent,
-    NoSuchEntity, Query, QueryBorrow, QueryMut, QueryOne, TakenEntity, View, ViewBorrow,
+    Access, AccessView, BuiltEntityClone, Bundle, CloneRegistry, ColumnBatch, ColumnBatchType,
+    ComponentRef, DenseIndex, Disabled, DropQueue, DynamicBundle, Entity, EntityBuilder, EntityRef,
+    EntityRefMut, Fetch, FrozenWorld, IncludeDisabled, Index, MissingComponent, Name, NoSuchEntity,
+    OnTargetDespawn, OneOf, PairsMut, PreparedQueryBorrow, Query, QueryBorrow, QueryCursor,
+    QueryEach, QueryMut, QueryOne, QueryShared, Ref, Relation, SnapshotRegistry, TakenEntity,
+    Transaction, UnregisteredComponent, View, ViewBorrow, Without, WorldSnapshot,
 };
 
 /// An unordered collection of entities, each having any number of distinctly typed components
@@ -57,12 +79,52 @@ pub struct World {
     /// Maps source archetype and static bundle types to the archetype that an entity is moved to
     /// after removing the components from that bundle.
     remove_edges: IndexTypeIdMap<u32>,
+    /// Relation component types whose targets are kept consistent on despawn
+    relations: Vec<RelationInfo>,
+    /// Singleton `Component`s not associated with any entity
+    resources: TypeIdMap<Box<dyn Any>>,
+    /// Callbacks invoked when components are inserted or removed
+    hooks: Hooks,
+    /// Prepared queries stored by [`cached_query`](Self::cached_query)
+    query_cache: QueryCache,
+    /// Destination for components of despawned entities, if they shouldn't be dropped immediately
+    drop_queue: Option<DropQueue>,
+    /// Names supplied for component types, for introspection
+    type_names: TypeNameRegistry,
+    /// Component types stored outside of archetypes
+    sparse: SparseStorage,
+    /// Component types registered for trait object queries
+    traits: TraitRegistry,
+    /// Descriptions of component types with no Rust type available, for lookup by `TypeId`
+    dynamic_types: TypeIdMap<TypeInfo>,
+    /// Component types whose previous values are recorded by
+    /// [`snapshot_history`](Self::snapshot_history)
+    history: Vec<TypeId>,
     id: u64,
 }
 
 impl World {
     /// Create an empty world
     pub fn new() -> Self {
+        Self::with_allocator(EntityAllocatorPolicy::default())
+    }
+
+    /// Create an empty world that allocates entity IDs according to `policy`
+    ///
+    /// [`EntityAllocatorPolicy::Sequential`] makes IDs a function of the number of entities ever
+    /// allocated, which is useful for keeping IDs identical across machines in lockstep
+    /// simulations without depending on the order in which entities were despawned.
+    ///
+    /// # Example
+    /// ```
+    /// # use hecs::*;
+    /// let mut world = World::with_allocator(EntityAllocatorPolicy::Sequential);
+    /// let a = world.spawn(());
+    /// world.despawn(a).unwrap();
+    /// let b = world.spawn(());
+    /// assert_eq!(b.id(), a.id() + 1);
+    /// ```
+    pub fn with_allocator(policy: EntityAllocatorPolicy) -> Self {
         // AtomicU64 is unsupported on 32-bit MIPS and PPC architectures
         // For compatibility, use Mutex<u64>
         static ID: Mutex<u64> = Mutex::new(1);
@@ -73,15 +135,44 @@ impl World {
             next
         };
         Self {
-            entities: Entities::default(),
+            entities: Entities::new(policy),
             archetypes: ArchetypeSet::new(),
             bundle_to_archetype: HashMap::default(),
             insert_edges: HashMap::default(),
             remove_edges: HashMap::default(),
+            relations: Vec::new(),
+            resources: HashMap::default(),
+            hooks: Hooks::default(),
+            query_cache: QueryCache::default(),
+            drop_queue: None,
+            type_names: TypeNameRegistry::default(),
+            sparse: SparseStorage::default(),
+            traits: TraitRegistry::default(),
+            dynamic_types: HashMap::default(),
+            history: Vec::new(),
             id,
         }
     }
 
+    /// Create an empty world whose entity IDs are all chosen by the caller
+    ///
+    /// Shorthand for `World::with_allocator(EntityAllocatorPolicy::External)`. Entities must be
+    /// created with [`spawn_with_id`](Self::spawn_with_id) or the other methods taking an explicit
+    /// [`Entity`], such as [`spawn_at`](Self::spawn_at); anything that would allocate an ID panics.
+    /// Useful when IDs are assigned by an outside authority, e.g. a server replicating its entities
+    /// to clients.
+    ///
+    /// # Example
+    /// ```
+    /// # use hecs::*;
+    /// let mut world = World::external_ids();
+    /// let a = world.spawn_with_id(7, (true,)).unwrap();
+    /// assert_eq!(a.id(), 7);
+    /// ```
+    pub fn external_ids() -> Self {
+        Self::with_allocator(EntityAllocatorPolicy::External)
+    }
+
     /// Create an entity with certain components
     ///
     /// Returns the ID of the newly created entity.
@@ -113,11 +204,38 @@ impl World {
         entity
     }
 
+    /// Create an entity with certain components, returning a handle for immediate access to them
+    ///
+    /// Like [`spawn`](Self::spawn), but saves looking the new entity up again to configure its
+    /// components. The world can't be otherwise accessed while the handle is live.
+    ///
+    /// # Example
+    /// ```
+    /// # use hecs::*;
+    /// let mut world = World::new();
+    /// let mut a = world.spawn_ref((123, "abc"));
+    /// *a.get_mut::<i32>().unwrap() *= 2;
+    /// let a = a.entity();
+    /// assert_eq!(*world.get::<&i32>(a).unwrap(), 246);
+    /// ```
+    pub fn spawn_ref(&mut self, components: impl DynamicBundle) -> EntityRefMut<'_> {
+        let entity = self.spawn(components);
+        let loc = self.entities.meta[entity.id as usize].location;
+        unsafe {
+            EntityRefMut::new(
+                &self.archetypes.archetypes[loc.archetype as usize],
+                entity,
+                loc.index,
+            )
+        }
+    }
+
     /// Create an entity with certain components and a specific [`Entity`] handle.
     ///
     /// See [`spawn`](Self::spawn).
     ///
-    /// Despawns any existing entity with the same [`Entity::id`].
+    /// Despawns any existing entity with the same [`Entity::id`], exactly as by
+    /// [`despawn`](Self::despawn), so hooks and relation policies apply.
     ///
     /// Useful for easy handle-preserving deserialization. Be cautious resurrecting old `Entity`
     /// handles in already-populated worlds as it vastly increases the likelihood of collisions.
@@ -139,19 +257,93 @@ impl World {
         // necessary
         self.flush();
 
-        let loc = self.entities.alloc_at(handle);
-        if let Some(loc) = loc {
-            if let Some(moved) = unsafe {
-                self.archetypes.archetypes[loc.archetype as usize].remove(loc.index, true)
-            } {
-                self.entities.meta[moved as usize].location.index = loc.index;
-            }
+        self.despawn_id(handle.id);
+        self.entities.alloc_at(handle);
+        self.spawn_inner(handle, components);
+    }
+
+    /// Despawn the live entity with ID `id`, if any, as by [`despawn`](Self::despawn)
+    fn despawn_id(&mut self, id: u32) {
+        if self.entities.contains_id(id) {
+            let existing = unsafe { self.entities.resolve_unknown_gen(id) };
+            self.despawn(existing).unwrap();
         }
+    }
 
-        self.spawn_inner(handle, components);
+    /// Create an entity with certain components and exactly the [`Entity`] handle `handle`
+    ///
+    /// Like [`spawn_at`](Self::spawn_at), the new entity has both the ID and the generation of
+    /// `handle`, so existing copies of `handle`, e.g. parsed from [`Entity`]'s `Display` form, refer
+    /// to it. Unlike `spawn_at`, fails rather than despawning a live entity with the same ID and a
+    /// different generation. If `handle` itself is live, its components are replaced as by
+    /// `spawn_at`.
+    ///
+    /// # Example
+    /// ```
+    /// # use hecs::*;
+    /// let mut world = World::new();
+    /// let a = world.spawn(());
+    /// world.despawn(a).unwrap();
+    /// let b = world.spawn((true,));
+    /// assert_eq!(a.id(), b.id());
+    /// // `a`'s ID has been reused
+    /// assert_eq!(world.spawn_at_exact(a, (1,)).unwrap_err().entity(), a);
+    ///
+    /// let mut restored = World::new();
+    /// restored.spawn_at_exact(a, (1,)).unwrap();
+    /// assert!(restored.contains(a));
+    /// assert!(!restored.contains(b));
+    /// ```
+    pub fn spawn_at_exact(
+        &mut self,
+        handle: Entity,
+        components: impl DynamicBundle,
+    ) -> Result<(), EntityCollision> {
+        self.flush();
+        if self.entities.contains_id(handle.id) && !self.entities.contains(handle) {
+            return Err(EntityCollision(handle));
+        }
+        self.spawn_at(handle, components);
+        Ok(())
+    }
+
+    /// Create an entity with certain components and the ID `id`
+    ///
+    /// The new entity's generation is 1 if `id` was never used, or otherwise one more than that of
+    /// its last occupant, so stale handles to earlier entities with the same ID never refer to it.
+    /// Fails if `id` is in use by a live entity, or is otherwise unavailable because it's reserved
+    /// or has exhausted its generations.
+    ///
+    /// The caller is responsible for ensuring `id` doesn't collide with IDs hecs allocates itself,
+    /// e.g. by using a world created with [`external_ids`](Self::external_ids), which never does.
+    ///
+    /// # Example
+    /// ```
+    /// # use hecs::*;
+    /// let mut world = World::external_ids();
+    /// let a = world.spawn_with_id(42, (1,)).unwrap();
+    /// assert_eq!(world.spawn_with_id(42, (2,)).unwrap_err().entity(), a);
+    /// world.despawn(a).unwrap();
+    /// let b = world.spawn_with_id(42, (3,)).unwrap();
+    /// assert_eq!(b.id(), 42);
+    /// assert!(!world.contains(a));
+    /// assert_eq!(*world.get::<&i32>(b).unwrap(), 3);
+    /// ```
+    pub fn spawn_with_id(
+        &mut self,
+        id: u32,
+        components: impl DynamicBundle,
+    ) -> Result<Entity, EntityCollision> {
+        self.flush();
+        let entity = self.entities.vacant(id).map_err(EntityCollision)?;
+        self.spawn_at(entity, components);
+        Ok(entity)
     }
 
     fn spawn_inner(&mut self, entity: Entity, components: impl DynamicBundle) {
+        if !self.sparse.is_empty() {
+            components.with_ids(|ids| self.sparse.assert_dense(ids));
+        }
         let archetype_id = match components.key() {
             Some(k) => {
                 let archetypes = &mut self.archetypes;
@@ -168,11 +360,49 @@ impl World {
             components.put(|ptr, ty| {
                 archetype.put_dynamic(ptr, ty.id(), ty.layout().size(), index);
             });
+            archetype.mark_all_added();
             self.entities.meta[entity.id as usize].location = Location {
                 archetype: archetype_id,
                 index,
             };
+            let types = archetype.type_ids().iter().copied();
+            if self.hooks.any(types.clone()) {
+                self.hooks.fire_insert(entity, archetype, index, types);
+            }
+        }
+    }
+
+    /// Spawn `count` entities with clones of `template`'s components
+    pub(crate) fn spawn_clones(&mut self, template: &BuiltEntityClone, count: u32) -> Vec<Entity> {
+        self.flush();
+        self.entities.reserve(count);
+        let archetype_id =
+            template.with_ids(|ids| self.archetypes.get(ids, || template.type_info()));
+        let archetype = &mut self.archetypes.archetypes[archetype_id as usize];
+        archetype.reserve(count);
+        let hooked = self.hooks.any(archetype.type_ids().iter().copied());
+
+        let mut entities = Vec::with_capacity(count as usize);
+        for _ in 0..count {
+            let entity = self.entities.alloc();
+            unsafe {
+                let index = archetype.allocate(entity.id);
+                template.put(|ptr, ty| {
+                    archetype.put_dynamic(ptr, ty.id(), ty.layout().size(), index);
+                });
+                archetype.mark_all_added();
+                self.entities.meta[entity.id as usize].location = Location {
+                    archetype: archetype_id,
+                    index,
+                };
+                if hooked {
+                    let types = archetype.type_ids().iter().copied();
+                    self.hooks.fire_insert(entity, archetype, index, types);
+                }
+            }
+            entities.push(entity);
         }
+        entities
     }
 
     /// Efficiently spawn a large number of entities with the same statically-typed components
@@ -180,6 +410,10 @@ impl World {
     /// Faster than calling [`spawn`](Self::spawn) repeatedly with the same components, but requires
     /// that component types are known at compile time.
     ///
+    /// Entities are spawned lazily, one per call to [`Iterator::next`] on the returned iterator, so
+    /// `iter` may be computed incrementally. Archetype capacity is reserved up front according to
+    /// `iter`'s size hint. Any remaining items are spawned when the returned iterator is dropped.
+    ///
     /// # Example
     /// ```
     /// # use hecs::*;
@@ -209,15 +443,84 @@ impl World {
             entities: &mut self.entities,
             archetype_id,
             archetype: &mut self.archetypes.archetypes[archetype_id as usize],
+            hooks: &mut self.hooks,
+        }
+    }
+
+    /// Spawn entities with specific [`Entity`] handles, such as during deserialization
+    ///
+    /// Like [`spawn_at`](Self::spawn_at), but fails rather than replacing an existing entity when a
+    /// handle's ID is already in use, including by an earlier item of `iter`. Entities preceding the
+    /// collision remain spawned. Archetype capacity is reserved according to `iter`'s size hint.
+    ///
+    /// # Example
+    /// ```
+    /// # use hecs::*;
+    /// let mut source = World::new();
+    /// let a = source.spawn(());
+    /// let b = source.spawn(());
+    ///
+    /// let mut world = World::new();
+    /// world.spawn_batch_at([(a, (1, true)), (b, (2, false))]).unwrap();
+    /// assert_eq!(*world.get::<&i32>(b).unwrap(), 2);
+    /// let err = world.spawn_batch_at([(a, (3, true))]).unwrap_err();
+    /// assert_eq!(err.entity(), a);
+    /// assert_eq!(*world.get::<&i32>(a).unwrap(), 1);
+    /// ```
+    pub fn spawn_batch_at<I, B>(&mut self, iter: I) -> Result<(), EntityCollision>
+    where
+        I: IntoIterator<Item = (Entity, B)>,
+        B: Bundle + 'static,
+    {
+        self.flush();
+
+        let iter = iter.into_iter();
+        let (lower, upper) = iter.size_hint();
+        self.reserve_inner::<B>(u32::try_from(upper.unwrap_or(lower)).expect("iterator too large"));
+
+        for (handle, components) in iter {
+            if self.entities.contains_id(handle.id) {
+                return Err(EntityCollision(handle));
+            }
+            self.entities.alloc_at(handle);
+            self.spawn_inner(handle, components);
         }
+        Ok(())
     }
 
     /// Super-efficiently spawn the contents of a [`ColumnBatch`]
     ///
-    /// The fastest, but most specialized, way to spawn large numbers of entities. Useful for high
-    /// performance deserialization. Supports dynamic component types.
+    /// The fastest, but most specialized, way to spawn large numbers of entities, and the
+    /// recommended path for high performance deserialization. Supports dynamic component types.
+    /// Entity IDs for every row are allocated at once, and the batch's columns are appended to the
+    /// matching archetype with a single reservation. Uneven columns are impossible here, since
+    /// [`ColumnBatchBuilder::build`](crate::ColumnBatchBuilder::build) fails with
+    /// [`BatchIncomplete`](crate::BatchIncomplete) instead of producing such a batch.
+    ///
+    /// The returned iterator yields the new entities in row order, so the `n`th entity has the
+    /// components written `n`th to each column. Entities are spawned even if it's not consumed.
+    ///
+    /// # Example
+    /// ```
+    /// # use hecs::*;
+    /// let mut types = ColumnBatchType::new();
+    /// types.add::<i32>();
+    /// let mut batch = types.into_batch(2);
+    /// let mut writer = batch.writer::<i32>().unwrap();
+    /// writer.push(1).unwrap();
+    /// writer.push(2).unwrap();
+    /// let batch = batch.build().unwrap();
+    ///
+    /// let mut world = World::new();
+    /// let entities = world.spawn_column_batch(batch).collect::<Vec<_>>();
+    /// assert_eq!(*world.get::<&i32>(entities[0]).unwrap(), 1);
+    /// assert_eq!(*world.get::<&i32>(entities[1]).unwrap(), 2);
+    /// ```
     pub fn spawn_column_batch(&mut self, batch: ColumnBatch) -> SpawnColumnBatchIter<'_> {
         self.flush();
+        if !self.sparse.is_empty() {
+            self.sparse.assert_dense(batch.0.type_ids());
+        }
 
         let archetype = batch.0;
         let entity_count = archetype.len();
@@ -230,8 +533,17 @@ impl World {
         // Fix up entity IDs
         let mut id_alloc_clone = id_alloc.clone();
         let mut index = base as usize;
+        let hooked = self.hooks.any(archetype.type_ids().iter().copied());
         while let Some(id) = id_alloc_clone.next(&self.entities) {
             archetype.set_entity_id(index, id);
+            if hooked {
+                unsafe {
+                    let entity = self.entities.resolve_unknown_gen(id);
+                    let types = archetype.type_ids().iter().copied();
+                    self.hooks
+                        .fire_insert(entity, archetype, index as u32, types);
+                }
+            }
             index += 1;
         }
 
@@ -254,16 +566,17 @@ impl World {
             archetype.len()
         );
 
-        // Drop components of entities that will be replaced
+        self.flush();
+        if !self.sparse.is_empty() {
+            self.sparse.assert_dense(archetype.type_ids());
+        }
+
+        // Despawn entities that will be replaced
         for &handle in handles {
-            let loc = self.entities.alloc_at(handle);
-            if let Some(loc) = loc {
-                if let Some(moved) = unsafe {
-                    self.archetypes.archetypes[loc.archetype as usize].remove(loc.index, true)
-                } {
-                    self.entities.meta[moved as usize].location.index = loc.index;
-                }
-            }
+            self.despawn_id(handle.id);
+        }
+        for &handle in handles {
+            self.entities.alloc_at(handle);
         }
 
         // Store components
@@ -278,6 +591,14 @@ impl World {
                 index: index as u32,
             };
         }
+        if self.hooks.any(archetype.type_ids().iter().copied()) {
+            for (&handle, index) in handles.iter().zip(base..) {
+                unsafe {
+                    let types = archetype.type_ids().iter().copied();
+                    self.hooks.fire_insert(handle, archetype, index, types);
+                }
+            }
+        }
     }
 
     /// Allocate many entities ID concurrently
@@ -290,7 +611,7 @@ impl World {
     /// can also be done explicitly by calling [`flush`](Self::flush).
     ///
     /// Useful for reserving an ID that will later have components attached to it with `insert`.
-    pub fn reserve_entities(&self, count: u32) -> ReserveEntitiesIterator {
+    pub fn reserve_entities(&self, count: u32) -> ReserveEntitiesIterator<'_> {
         self.entities.reserve_entities(count)
     }
 
@@ -301,102 +622,1387 @@ impl World {
         self.entities.reserve_entity()
     }
 
+    /// Allocate `count` entity handles to be spawned later
+    ///
+    /// Unlike [`reserve_entities`](Self::reserve_entities), the handles don't become entities when
+    /// the world is [`flush`](Self::flush)ed. Instead, each is held until it's either spawned with
+    /// [`spawn_at`](Self::spawn_at) or given up with [`release_reserved`](Self::release_reserved),
+    /// and in the meantime its ID won't be handed out by [`spawn`](Self::spawn) or
+    /// `reserve_entities`. A held handle isn't an entity: [`contains`](Self::contains) is false for
+    /// it, it's not counted by [`len`](Self::len) or visited by [`iter`](Self::iter) or queries,
+    /// and operations like [`insert`](Self::insert) fail with [`NoSuchEntity`].
+    ///
+    /// Handles are held across [`clear_retaining_capacity`](Self::clear_retaining_capacity), but
+    /// [`clear`](Self::clear) discards them along with all other entity metadata.
+    ///
+    /// # Example
+    /// ```
+    /// # use hecs::*;
+    /// let mut world = World::new();
+    /// let block = world.reserve_block(2);
+    /// world.flush();
+    /// assert!(!world.contains(block[0]));
+    /// assert_eq!(world.len(), 0);
+    ///
+    /// world.spawn_at(block[0], (42,));
+    /// world.release_reserved(block[1]).unwrap();
+    /// assert_eq!(*world.get::<&i32>(block[0]).unwrap(), 42);
+    /// assert!(!world.is_reserved(block[1]));
+    /// ```
+    pub fn reserve_block(&mut self, count: u32) -> ReservedBlock {
+        self.flush();
+        self.entities.reserve(count);
+        ReservedBlock {
+            entities: (0..count).map(|_| self.entities.hold()).collect(),
+        }
+    }
+
+    /// Give up a handle obtained from [`reserve_block`](Self::reserve_block) without spawning it
+    ///
+    /// The handle's ID becomes available for reuse, and the handle itself is invalidated as if it
+    /// had been spawned and despawned. Fails if `entity` isn't currently held.
+    pub fn release_reserved(&mut self, entity: Entity) -> Result<(), NoSuchEntity> {
+        self.flush();
+        self.entities.release(entity)
+    }
+
+    /// Whether `entity` was obtained from [`reserve_block`](Self::reserve_block) and has been
+    /// neither spawned nor released
+    pub fn is_reserved(&self, entity: Entity) -> bool {
+        self.entities.is_held(entity)
+    }
+
+    /// Number of handles obtained from [`reserve_block`](Self::reserve_block) that have been
+    /// neither spawned nor released
+    #[inline]
+    pub fn reserved_len(&self) -> u32 {
+        self.entities.held()
+    }
+
     /// Destroy an entity and all its components
     ///
+    /// Entities related to `entity` by a [registered relation](Self::register_relation) are
+    /// handled according to that relation's [`OnTargetDespawn`] policy.
+    ///
     /// See also [`take`](Self::take).
     pub fn despawn(&mut self, entity: Entity) -> Result<(), NoSuchEntity> {
         self.flush();
-        let loc = self.entities.free(entity)?;
-        if let Some(moved) =
-            unsafe { self.archetypes.archetypes[loc.archetype as usize].remove(loc.index, true) }
-        {
-            self.entities.meta[moved as usize].location.index = loc.index;
+        self.despawn_inner(entity)?;
+        if !self.relations.is_empty() {
+            self.despawn_relations(entity);
         }
         Ok(())
     }
 
-    /// Ensure at least `additional` entities with exact components `T` can be spawned without reallocating
-    pub fn reserve<T: Bundle + 'static>(&mut self, additional: u32) {
-        self.reserve_inner::<T>(additional);
+    /// Destroy an entity, returning its components
+    ///
+    /// Behaves exactly like [`despawn`](Self::despawn), including for
+    /// [relations](Self::register_relation), except that the entity's components are moved into
+    /// the returned [`EntityBuilder`] rather than dropped. Unlike [`take`](Self::take), the result
+    /// doesn't borrow the world, so it can be kept, inspected with [`EntityBuilder::get`], or
+    /// respawned later, e.g. with [`spawn_at`](Self::spawn_at) to undo the despawn.
+    /// [Sparse](Self::set_sparse) components are dropped.
+    ///
+    /// # Example
+    /// ```
+    /// # use hecs::*;
+    /// let mut world = World::new();
+    /// let a = world.spawn((123, "abc"));
+    /// let mut components = world.despawn_take(a).unwrap();
+    /// assert!(!world.contains(a));
+    /// assert_eq!(*components.get::<&i32>().unwrap(), 123);
+    /// world.spawn_at(a, components.build());
+    /// assert_eq!(*world.get::<&&str>(a).unwrap(), "abc");
+    /// ```
+    pub fn despawn_take(&mut self, entity: Entity) -> Result<EntityBuilder, NoSuchEntity> {
+        let mut components = EntityBuilder::new();
+        components.add_bundle(self.take(entity)?);
+        if !self.relations.is_empty() {
+            self.despawn_relations(entity);
+        }
+        Ok(components)
     }
 
-    fn reserve_inner<T: Bundle + 'static>(&mut self, additional: u32) -> u32 {
+    /// Destroy `entity` and every entity reachable from it through `children_of`
+    ///
+    /// Entities are despawned depth-first, so descendants are despawned, and their remove hooks
+    /// fired, before their ancestors. Entities reachable by multiple paths, including cycles, are
+    /// visited only once, and dead entities returned by `children_of` are ignored. Returns the
+    /// number of entities despawned, including any despawned by [registered
+    /// relations](Self::register_relation).
+    ///
+    /// # Example
+    /// ```
+    /// # use hecs::*;
+    /// struct Children(Vec<Entity>);
+    ///
+    /// let mut world = World::new();
+    /// let leaf = world.spawn(());
+    /// let branch = world.spawn((Children(vec![leaf]),));
+    /// let root = world.spawn((Children(vec![branch, leaf]),));
+    /// let unrelated = world.spawn(());
+    /// let children_of = |world: &World, entity| {
+    ///     world.get::<&Children>(entity).map_or_else(|_| Vec::new(), |x| x.0.clone())
+    /// };
+    /// assert_eq!(world.despawn_recursive(root, children_of), Ok(3));
+    /// assert!(!world.contains(leaf));
+    /// assert!(world.contains(unrelated));
+    /// ```
+    pub fn despawn_recursive(
+        &mut self,
+        entity: Entity,
+        children_of: impl Fn(&World, Entity) -> Vec<Entity>,
+    ) -> Result<u32, NoSuchEntity> {
         self.flush();
-        self.entities.reserve(additional);
+        if !self.contains(entity) {
+            return Err(NoSuchEntity);
+        }
 
-        let archetypes = &mut self.archetypes;
-        let archetype_id = *self
-            .bundle_to_archetype
-            .entry(TypeId::of::<T>())
-            .or_insert_with(|| {
-                T::with_static_ids(|ids| {
-                    archetypes.get(ids, || T::with_static_type_info(|info| info.to_vec()))
-                })
-            });
+        // Find every descendant in post-order before modifying the world
+        let mut visited = HashSet::new();
+        visited.insert(entity);
+        let mut order = Vec::new();
+        let mut stack = vec![(entity, children_of(self, entity).into_iter())];
+        while let Some((_, children)) = stack.last_mut() {
+            match children.next() {
+                Some(child) => {
+                    if self.contains(child) && visited.insert(child) {
+                        let grandchildren = children_of(self, child).into_iter();
+                        stack.push((child, grandchildren));
+                    }
+                }
+                None => order.push(stack.pop().unwrap().0),
+            }
+        }
 
-        self.archetypes.archetypes[archetype_id as usize].reserve(additional);
-        archetype_id
+        let len = self.len();
+        for entity in order {
+            // May have already been despawned by a relation
+            let _ = self.despawn(entity);
+        }
+        Ok(len - self.len())
     }
 
-    /// Despawn all entities
-    ///
-    /// Preserves allocated storage for reuse but clears metadata so that [`Entity`] values will repeat (in contrast to [`despawn`][Self::despawn]).
-    pub fn clear(&mut self) {
-        for x in &mut self.archetypes.archetypes {
-            x.clear();
+    fn despawn_inner(&mut self, entity: Entity) -> Result<(), NoSuchEntity> {
+        if !self.hooks.is_empty() {
+            let loc = self.entities.get(entity)?;
+            let archetype = &self.archetypes.archetypes[loc.archetype as usize];
+            let types = archetype.type_ids().iter().copied();
+            unsafe {
+                self.hooks.fire_remove(entity, archetype, loc.index, types);
+            }
         }
-        self.entities.clear();
+        let loc = self.entities.free(entity)?;
+        let archetype = &mut self.archetypes.archetypes[loc.archetype as usize];
+        let moved = unsafe {
+            match self.drop_queue {
+                None => archetype.remove(loc.index, true),
+                Some(ref queue) => archetype.remove_with(loc.index, |ptr, ty| queue.push(ptr, *ty)),
+            }
+        };
+        if let Some(moved) = moved {
+            self.entities.meta[moved as usize].location.index = loc.index;
+        }
+        if !self.sparse.is_empty() {
+            self.sparse.remove_entity(entity.id);
+        }
+        Ok(())
     }
 
-    /// Whether `entity` still exists
-    pub fn contains(&self, entity: Entity) -> bool {
-        self.entities.contains(entity)
+    /// Apply relation policies for entities targeting the just-despawned `target`
+    fn despawn_relations(&mut self, target: Entity) {
+        let mut targets = vec![target];
+        let mut sources = Vec::new();
+        while let Some(target) = targets.pop() {
+            for i in 0..self.relations.len() {
+                let relation = self.relations[i];
+                (relation.sources)(self, target, &mut sources);
+                for source in sources.drain(..) {
+                    match relation.policy {
+                        OnTargetDespawn::Despawn => {
+                            if self.despawn_inner(source).is_ok() {
+                                targets.push(source);
+                            }
+                        }
+                        OnTargetDespawn::Orphan => (relation.strip)(self, source),
+                    }
+                }
+            }
+        }
     }
 
-    /// Efficiently iterate over all entities that have certain components, using dynamic borrow
-    /// checking
-    ///
-    /// Prefer [`query_mut`](Self::query_mut) when concurrent access to the [`World`] is not required.
-    ///
-    /// Calling `iter` on the returned value yields `(Entity, Q)` tuples, where `Q` is some query
-    /// type. A query type is any type for which an implementation of [`Query`] exists, e.g. `&T`,
-    /// `&mut T`, a tuple of query types, or an `Option` wrapping a query type, where `T` is any
-    /// component type. Components queried with `&mut` must only appear once. Entities which do not
-    /// have a component type referenced outside of an `Option` will be skipped.
-    ///
-    /// Entities are yielded in arbitrary order.
+    /// Keep `R` components consistent with the entities they refer to
     ///
-    /// The returned [`QueryBorrow`] can be further transformed with combinator methods; see its
-    /// documentation for details.
+    /// When an entity is [despawned](Self::despawn), every entity with an `R` component targeting
+    /// it is despawned or has its `R` component removed, according to `policy`. Registering the
+    /// same relation again replaces its policy.
     ///
-    /// Iterating a query will panic if it would violate an existing unique reference or construct
-    /// an invalid unique reference. This occurs when two simultaneously-active queries could expose
-    /// the same entity. Simultaneous queries can access the same component type if and only if the
-    /// world contains no entities that have all components required by both queries, assuming no
-    /// other component borrows are outstanding.
+    /// Finding related entities visits every entity with an `R` component, so despawning becomes
+    /// proportionally more expensive. [`take`](Self::take) and [`clear`](Self::clear) do not
+    /// apply relation policies.
+    pub fn register_relation<R: Relation>(&mut self, policy: OnTargetDespawn) {
+        let info = RelationInfo::of::<R>(policy);
+        match self.relations.iter_mut().find(|x| x.id == info.id) {
+            Some(existing) => *existing = info,
+            None => self.relations.push(info),
+        }
+    }
+
+    /// Entities with a [registered relation](Self::register_relation) targeting `target`
     ///
-    /// Iterating a query yields references with lifetimes bound to the [`QueryBorrow`] returned
-    /// here. To ensure those are invalidated, the return value of this method must be dropped for
-    /// its dynamic borrows from the world to be released. Similarly, lifetime rules ensure that
-    /// references obtained from a query cannot outlive the [`QueryBorrow`].
+    /// Includes [`Disabled`] entities, which are despawned or orphaned along with enabled ones.
     ///
     /// # Example
     /// ```
     /// # use hecs::*;
+    /// struct ChildOf(Entity);
+    /// impl Relation for ChildOf {
+    ///     fn target(&self) -> Entity { self.0 }
+    /// }
+    ///
     /// let mut world = World::new();
-    /// let a = world.spawn((123, true, "abc"));
-    /// let b = world.spawn((456, false));
-    /// let c = world.spawn((42, "def"));
-    /// let entities = world.query::<(&i32, &bool)>()
-    ///     .iter()
-    ///     .map(|(e, (&i, &b))| (e, i, b)) // Copy out of the world
-    ///     .collect::<Vec<_>>();
-    /// assert_eq!(entities.len(), 2);
-    /// assert!(entities.contains(&(a, 123, true)));
-    /// assert!(entities.contains(&(b, 456, false)));
+    /// world.register_relation::<ChildOf>(OnTargetDespawn::Orphan);
+    /// let parent = world.spawn(());
+    /// let child = world.spawn((ChildOf(parent),));
+    /// assert_eq!(world.relations_to(parent).collect::<Vec<_>>(), [child]);
+    /// world.despawn(parent).unwrap();
+    /// assert!(world.contains(child));
+    /// assert!(!world.satisfies::<&ChildOf>(child).unwrap());
     /// ```
-    pub fn query<Q: Query>(&self) -> QueryBorrow<'_, Q> {
-        QueryBorrow::new(self)
+    pub fn relations_to(&self, target: Entity) -> impl Iterator<Item = Entity> {
+        let mut sources = Vec::new();
+        for relation in &self.relations {
+            (relation.sources)(self, target, &mut sources);
+        }
+        sources.into_iter()
+    }
+
+    /// Invoke `f` whenever a `T` component is added to an entity
+    ///
+    /// Fires after the component is stored, whether by [`spawn`](Self::spawn) or any of its
+    /// batched variants, [`insert`](Self::insert), or [`exchange`](Self::exchange), including
+    /// when an existing `T` is replaced. Hooks run in the order they were registered.
+    ///
+    /// Hooks receive no access to the [`World`], so they cannot cause re-entrant structural
+    /// changes; to modify the world in response to an event, record commands into a
+    /// [`CommandBuffer`](crate::CommandBuffer) shared with the hook, e.g. through an
+    /// `Arc<Mutex<_>>`, and run it once the triggering operation returns.
+    ///
+    /// Operations on component types that have no hooks registered incur no additional cost.
+    ///
+    /// # Example
+    /// ```
+    /// # use hecs::*;
+    /// # use std::sync::{Arc, Mutex};
+    /// let names = Arc::new(Mutex::new(Vec::new()));
+    /// let mut world = World::new();
+    /// world.on_insert::<&'static str>({
+    ///     let names = names.clone();
+    ///     move |entity, &name| names.lock().unwrap().push((entity, name))
+    /// });
+    /// let e = world.spawn(("alice", 123));
+    /// assert_eq!(*names.lock().unwrap(), [(e, "alice")]);
+    /// ```
+    pub fn on_insert<T: Component>(&mut self, f: impl FnMut(Entity, &T) + Send + Sync + 'static) {
+        self.hooks.add_insert(f);
+    }
+
+    /// Invoke `f` whenever a `T` component is removed from an entity
+    ///
+    /// Fires immediately before the component is dropped or moved out of the world, by
+    /// [`despawn`](Self::despawn) and its variants, [`remove`](Self::remove), [`take`](Self::take),
+    /// [`exchange`](Self::exchange), [`clear`](Self::clear), or [`insert`](Self::insert) replacing
+    /// the component. The entity is still intact when the hook runs. Mutations made by the hook
+    /// are visible in the value returned by e.g. `remove`.
+    ///
+    /// See [`on_insert`](Self::on_insert) for re-entrancy rules.
+    pub fn on_remove<T: Component>(
+        &mut self,
+        f: impl FnMut(Entity, &mut T) + Send + Sync + 'static,
+    ) {
+        self.hooks.add_remove(f);
+    }
+
+    /// Move the components of despawned entities into `queue` rather than dropping them
+    ///
+    /// Applies to [`despawn`](Self::despawn) and its variants, and to [`clear`](Self::clear). The
+    /// components are dropped by [`DropQueue::flush`], which may be called once the world is no
+    /// longer borrowed, e.g. when `Drop` implementations need access to the world or to other state
+    /// borrowed alongside it. Components that are removed, replaced, or still owned by the world
+    /// when it's dropped are dropped immediately as usual. Pass `None` to restore the default
+    /// behavior; components already queued remain in the queue.
+    ///
+    /// # Example
+    /// ```
+    /// # use hecs::*;
+    /// let queue = DropQueue::new();
+    /// let mut world = World::new();
+    /// world.set_drop_queue(Some(queue.clone()));
+    /// let a = world.spawn((String::from("hello"), 123));
+    /// world.despawn(a).unwrap();
+    /// assert_eq!(queue.len(), 2);
+    /// drop(world);
+    /// queue.flush();
+    /// ```
+    pub fn set_drop_queue(&mut self, queue: Option<DropQueue>) {
+        self.drop_queue = queue;
+    }
+
+    /// Destroy every entity matched by `Q`, returning the number of entities despawned
+    ///
+    /// Only archetypes satisfying `Q` are visited, and no components are borrowed. Like iteration,
+    /// skips [`Disabled`] entities unless `Q` refers to them. Matched entities that a
+    /// [`Relation`] despawns along with an earlier one aren't counted.
+    ///
+    /// # Example
+    /// ```
+    /// # use hecs::*;
+    /// struct Projectile;
+    /// let mut world = World::new();
+    /// world.spawn((Projectile, 1.0f32));
+    /// world.spawn((Projectile,));
+    /// let player = world.spawn((1.0f32,));
+    /// assert_eq!(world.despawn_matching::<With<(), &Projectile>>(), 2);
+    /// assert_eq!(world.len(), 1);
+    /// assert!(world.contains(player));
+    /// ```
+    pub fn despawn_matching<Q: Query>(&mut self) -> usize {
+        self.flush();
+        let mut doomed = Vec::new();
+        for archetype in &self.archetypes.archetypes {
+            if !archetype.is_empty() && prepare_iter::<Q::Fetch>(archetype).is_some() {
+                doomed.extend(
+                    archetype
+                        .ids()
+                        .iter()
+                        .map(|&id| unsafe { self.entities.resolve_unknown_gen(id) }),
+                );
+            }
+        }
+        // Entities may have already been despawned by a relation
+        doomed
+            .into_iter()
+            .filter(|&entity| self.despawn(entity).is_ok())
+            .count()
+    }
+
+    /// Destroy every entity matched by `Q` for which `f` returns `false`
+    ///
+    /// # Example
+    /// ```
+    /// # use hecs::*;
+    /// let mut world = World::new();
+    /// let a = world.spawn((1,));
+    /// let b = world.spawn((2,));
+    /// let c = world.spawn((true,));
+    /// world.retain::<&i32, _>(|_, &x| x % 2 == 0);
+    /// assert!(!world.contains(a));
+    /// assert!(world.contains(b));
+    /// assert!(world.contains(c));
+    /// ```
+    pub fn retain<Q: Query, F>(&mut self, mut f: F)
+    where
+        F: FnMut(Entity, Q::Item<'_>) -> bool,
+    {
+        let doomed = self
+            .query_mut::<Q>()
+            .into_iter()
+            .filter_map(|(entity, item)| (!f(entity, item)).then_some(entity))
+            .collect::<Vec<_>>();
+        for entity in doomed {
+            // May have already been despawned by a relation
+            let _ = self.despawn(entity);
+        }
+    }
+
+    /// Ensure at least `additional` entities with exact components `T` can be spawned without reallocating
+    ///
+    /// Creates the archetype for `T` if necessary, and grows entity metadata storage as well as the
+    /// archetype's columns. Capacity can be inspected with [`memory_report`](Self::memory_report).
+    pub fn reserve<T: Bundle + 'static>(&mut self, additional: u32) {
+        self.reserve_inner::<T>(additional);
+    }
+
+    /// Number of entities each newly created archetype has room for before reallocating
+    ///
+    /// Defaults to 0, deferring allocation until an entity is first moved into the archetype.
+    /// Archetypes created by [`reserve`](Self::reserve) or [`preallocate`](Self::preallocate) are
+    /// instead sized according to the requested number of entities. Existing archetypes are
+    /// unaffected.
+    ///
+    /// # Example
+    /// ```
+    /// # use hecs::*;
+    /// let mut world = World::new();
+    /// world.set_default_archetype_capacity(100);
+    /// world.spawn((0u64,));
+    /// let archetype = world.archetypes().find(|a| a.has::<u64>()).unwrap();
+    /// assert_eq!(archetype.memory_usage().capacity, 100);
+    /// ```
+    pub fn set_default_archetype_capacity(&mut self, capacity: u32) {
+        self.archetypes.default_capacity = capacity;
+    }
+
+    /// Number of entities each newly created archetype has room for, as set by
+    /// [`set_default_archetype_capacity`](Self::set_default_archetype_capacity)
+    pub fn default_archetype_capacity(&self) -> u32 {
+        self.archetypes.default_capacity
+    }
+
+    /// Control whether iteration visits archetypes in an order determined by their component types
+    ///
+    /// By default, queries and [`iter`](Self::iter) visit archetypes in the order they were
+    /// created, which depends on the history of spawns and insertions, so two worlds with identical
+    /// contents, e.g. one loaded from a save and one that reached the same state through play, may
+    /// iterate in different orders. When enabled, archetypes are instead visited in an order that
+    /// depends only on which component types each contains, making iteration order reproducible
+    /// as long as entities within each archetype are also stored in the same order. The order uses
+    /// [`TypeId`]s, so it's reproducible across runs of the same build, but not between different
+    /// builds.
+    ///
+    /// Maintaining the order makes creating an archetype cost time linear in the number of
+    /// existing archetypes. Matching queries against archetypes and iterating their entities are
+    /// unaffected, as is the order of [`archetypes`](Self::archetypes) and
+    /// [`ArchetypeId`](crate::ArchetypeId)s. Other ways of iterating, such as
+    /// [`PreparedQuery`](crate::PreparedQuery) and batched, dynamic, and trait object queries,
+    /// continue to use creation order.
+    ///
+    /// # Example
+    /// ```
+    /// # use hecs::*;
+    /// let mut a = World::new();
+    /// let mut b = World::new();
+    /// a.set_deterministic_order(true);
+    /// b.set_deterministic_order(true);
+    /// a.spawn((1u8,));
+    /// a.spawn((2u8, 'x'));
+    /// b.spawn((2u8, 'x'));
+    /// b.spawn((1u8,));
+    /// let order = |w: &mut World| w.query_mut::<&u8>().into_iter().map(|(_, &x)| x).collect::<Vec<_>>();
+    /// assert_eq!(order(&mut a), order(&mut b));
+    /// ```
+    pub fn set_deterministic_order(&mut self, enabled: bool) {
+        self.archetypes.set_sorted(enabled);
+    }
+
+    /// Whether [`set_deterministic_order`](Self::set_deterministic_order) is enabled
+    pub fn deterministic_order(&self) -> bool {
+        self.archetypes.order.is_some()
+    }
+
+    /// Create the archetype for each batch type in `plan` and reserve space for the accompanying
+    /// number of entities
+    ///
+    /// Like calling [`reserve`](Self::reserve) for every archetype at once, but with component
+    /// types determined at runtime. Subsequent insertions of up to the planned number of entities,
+    /// e.g. while deserializing or spawning [`ColumnBatch`]es, then need not reallocate. Entity
+    /// metadata storage is grown to accommodate the sum of all counts.
+    ///
+    /// # Example
+    /// ```
+    /// # use hecs::*;
+    /// let mut ty = ColumnBatchType::new();
+    /// ty.add::<u64>();
+    /// let mut world = World::new();
+    /// world.preallocate(&[(ty.clone(), 100)]);
+    /// let allocated = world.memory_report().allocated_bytes();
+    /// world.spawn_batch((0..100).map(|i| (i as u64,)));
+    /// assert_eq!(world.memory_report().allocated_bytes(), allocated);
+    /// ```
+    pub fn preallocate(&mut self, plan: &[(ColumnBatchType, usize)]) {
+        self.flush();
+        let total = plan.iter().map(|&(_, n)| n).sum::<usize>();
+        self.entities
+            .reserve(u32::try_from(total).expect("too many entities"));
+        for (ty, n) in plan {
+            let types = ty.sorted_types();
+            let ids = types.iter().map(|ty| ty.id()).collect::<Vec<_>>();
+            let n = u32::try_from(*n).expect("too many entities");
+            let archetype_id = self
+                .archetypes
+                .get_with_capacity(ids.as_slice(), || types, n);
+            self.archetypes.archetypes[archetype_id as usize].reserve(n);
+        }
+    }
+
+    fn reserve_inner<T: Bundle + 'static>(&mut self, additional: u32) -> u32 {
+        self.flush();
+        self.entities.reserve(additional);
+
+        let archetypes = &mut self.archetypes;
+        let archetype_id = *self
+            .bundle_to_archetype
+            .entry(TypeId::of::<T>())
+            .or_insert_with(|| {
+                T::with_static_ids(|ids| {
+                    archetypes.get_with_capacity(
+                        ids,
+                        || T::with_static_type_info(|info| info.to_vec()),
+                        additional,
+                    )
+                })
+            });
+
+        self.archetypes.archetypes[archetype_id as usize].reserve(additional);
+        archetype_id
+    }
+
+    /// Despawn all entities
+    ///
+    /// Preserves allocated storage for reuse but clears metadata so that [`Entity`] values will repeat (in contrast to [`despawn`][Self::despawn]).
+    /// See [`clear_retaining_capacity`](Self::clear_retaining_capacity) to invalidate existing
+    /// handles instead.
+    pub fn clear(&mut self) {
+        self.clear_archetypes();
+        self.entities.clear();
+    }
+
+    /// Despawn all entities, invalidating every existing [`Entity`]
+    ///
+    /// Like [`clear`](Self::clear), component storage is preserved, so a world that's repeatedly
+    /// cleared and refilled with similar entities stops allocating once warmed up. Unlike `clear`,
+    /// generations advance as if each entity had been [`despawn`](Self::despawn)ed, so stale
+    /// handles can't refer to new entities. Under [`EntityAllocatorPolicy::Recycle`], subsequent
+    /// spawns reuse IDs starting from the lowest; under [`EntityAllocatorPolicy::Sequential`], new IDs
+    /// continue to increase.
+    ///
+    /// # Example
+    /// ```
+    /// # use hecs::*;
+    /// let mut world = World::new();
+    /// let a = world.spawn((1,));
+    /// world.clear_retaining_capacity();
+    /// let b = world.spawn((2,));
+    /// assert_eq!(a.id(), b.id());
+    /// assert!(!world.contains(a));
+    /// ```
+    pub fn clear_retaining_capacity(&mut self) {
+        self.flush();
+        self.clear_archetypes();
+        self.entities.free_all();
+    }
+
+    /// Drop every entity's components, firing hooks, without updating entity metadata
+    fn clear_archetypes(&mut self) {
+        if !self.hooks.is_empty() {
+            for archetype in &self.archetypes.archetypes {
+                if !self.hooks.any(archetype.type_ids().iter().copied()) {
+                    continue;
+                }
+                for index in 0..archetype.len() {
+                    unsafe {
+                        let entity = self
+                            .entities
+                            .resolve_unknown_gen(archetype.entity_id(index));
+                        let types = archetype.type_ids().iter().copied();
+                        self.hooks.fire_remove(entity, archetype, index, types);
+                    }
+                }
+            }
+        }
+        for x in &mut self.archetypes.archetypes {
+            match self.drop_queue {
+                None => x.clear(),
+                Some(ref queue) => unsafe { x.clear_with(|ptr, ty| queue.push(ptr, *ty)) },
+            }
+        }
+        self.sparse.clear();
+    }
+
+    /// Allow the IDs of all despawned entities to be reused, lowest first
+    ///
+    /// Under [`EntityAllocatorPolicy::Sequential`], bounds the growth of entity metadata by making
+    /// subsequent spawns fill past gaps before allocating new IDs; subsequent despawns once again
+    /// retire their IDs. Allocation remains deterministic provided every peer compacts at the same
+    /// point. Stale handles to despawned entities remain invalid, as generations are preserved.
+    pub fn compact(&mut self) {
+        self.flush();
+        self.entities.compact();
+    }
+
+    /// Store `value` as the unique resource of type `T`, returning the previous value, if any
+    ///
+    /// Resources are singletons that aren't associated with any entity, and are never visited by
+    /// queries. They persist across [`clear`](Self::clear); see
+    /// [`clear_resources`](Self::clear_resources).
+    ///
+    /// # Example
+    /// ```
+    /// # use hecs::*;
+    /// struct DeltaTime(f32);
+    /// let mut world = World::new();
+    /// world.insert_resource(DeltaTime(0.016));
+    /// world.get_resource_mut::<DeltaTime>().unwrap().0 *= 2.0;
+    /// assert_eq!(world.get_resource::<DeltaTime>().unwrap().0, 0.032);
+    /// assert!(world.remove_resource::<DeltaTime>().is_some());
+    /// assert!(world.get_resource::<DeltaTime>().is_none());
+    /// ```
+    pub fn insert_resource<T: Component>(&mut self, value: T) -> Option<T> {
+        self.resources
+            .insert(TypeId::of::<T>(), Box::new(value))
+            .map(|old| *old.downcast::<T>().unwrap())
+    }
+
+    /// Access the resource of type `T`, if any
+    pub fn get_resource<T: Component>(&self) -> Option<&T> {
+        self.resources
+            .get(&TypeId::of::<T>())
+            .map(|x| x.downcast_ref::<T>().unwrap())
+    }
+
+    /// Uniquely access the resource of type `T`, if any
+    pub fn get_resource_mut<T: Component>(&mut self) -> Option<&mut T> {
+        self.resources
+            .get_mut(&TypeId::of::<T>())
+            .map(|x| x.downcast_mut::<T>().unwrap())
+    }
+
+    /// Remove and return the resource of type `T`, if any
+    pub fn remove_resource<T: Component>(&mut self) -> Option<T> {
+        self.resources
+            .remove(&TypeId::of::<T>())
+            .map(|x| *x.downcast::<T>().unwrap())
+    }
+
+    /// Drop all resources
+    pub fn clear_resources(&mut self) {
+        self.resources.clear();
+    }
+
+    /// Move every entity of `other` into `self`, returning a map from `other`'s handles to their
+    /// new handles in `self`
+    ///
+    /// Entities are given fresh handles so they can't collide with entities already in `self`.
+    /// Components are moved in bulk, one archetype at a time, and entities with the same set of
+    /// component types in both worlds end up sharing an archetype. Components which refer to
+    /// entities, such as [`Relation`]s, are not adjusted; use the returned map to fix them up.
+    /// `other`'s resources, hooks, and registered relations are dropped.
+    ///
+    /// # Example
+    /// ```
+    /// # use hecs::*;
+    /// let mut world = World::new();
+    /// let a = world.spawn((1, true));
+    /// let mut level = World::new();
+    /// let b = level.spawn((2, true));
+    /// let c = level.spawn(("c",));
+    /// let map = world.merge(level);
+    /// assert_eq!(world.len(), 3);
+    /// assert_eq!(*world.get::<&i32>(map[&b]).unwrap(), 2);
+    /// assert_eq!(*world.get::<&&str>(map[&c]).unwrap(), "c");
+    /// assert_eq!(*world.get::<&i32>(a).unwrap(), 1);
+    /// ```
+    pub fn merge(&mut self, mut other: World) -> HashMap<Entity, Entity> {
+        self.flush();
+        other.flush();
+
+        let mut map = HashMap::with_capacity(other.len() as usize);
+        for archetype in mem::take(&mut other.archetypes.archetypes) {
+            if archetype.is_empty() {
+                continue;
+            }
+            let old_ids = archetype.ids().to_vec();
+            let (archetype_id, base) = self.archetypes.insert_batch(archetype);
+            let target = &mut self.archetypes.archetypes[archetype_id as usize];
+            let hooked = self.hooks.any(target.type_ids().iter().copied());
+            for (old_id, index) in old_ids.into_iter().zip(base..) {
+                let entity = self.entities.alloc();
+                target.set_entity_id(index as usize, entity.id);
+                self.entities.meta[entity.id as usize].location = Location {
+                    archetype: archetype_id,
+                    index,
+                };
+                let old = Entity {
+                    id: old_id,
+                    generation: other.entities.meta[old_id as usize].generation,
+                };
+                map.insert(old, entity);
+                if hooked {
+                    unsafe {
+                        let types = target.type_ids().iter().copied();
+                        self.hooks.fire_insert(entity, target, index, types);
+                    }
+                }
+            }
+        }
+        map
+    }
+
+    /// Record the entities that `T` components are removed from
+    ///
+    /// Once enabled, [`removed`](Self::removed) yields every entity which lost a `T` through
+    /// [`remove`](Self::remove), [`remove_one`](Self::remove_one), [`exchange`](Self::exchange), or
+    /// [`take`](Self::take). Despawning an entity, including through [`clear`](Self::clear), counts
+    /// as removal of every one of its components. Replacing a component via
+    /// [`insert`](Self::insert) does not.
+    ///
+    /// # Example
+    /// ```
+    /// # use hecs::*;
+    /// struct Selected;
+    /// let mut world = World::new();
+    /// world.track_removals::<Selected>();
+    /// let a = world.spawn((Selected,));
+    /// let b = world.spawn((Selected,));
+    /// world.remove_one::<Selected>(a).unwrap();
+    /// world.despawn(b).unwrap();
+    /// assert_eq!(world.removed::<Selected>().collect::<Vec<_>>(), [a, b]);
+    /// world.clear_removed::<Selected>();
+    /// assert_eq!(world.removed::<Selected>().count(), 0);
+    /// ```
+    pub fn track_removals<T: Component>(&mut self) {
+        self.hooks.track_removals::<T>();
+    }
+
+    /// Entities from which `T` was removed since the last [`clear_removed`](Self::clear_removed)
+    ///
+    /// Empty unless enabled with [`track_removals`](Self::track_removals). Entities appear in order
+    /// of removal, and may since have been despawned or given a new `T`.
+    pub fn removed<T: Component>(&self) -> impl ExactSizeIterator<Item = Entity> + '_ {
+        self.hooks.removed::<T>().iter().copied()
+    }
+
+    /// Forget the entities recorded by [`removed`](Self::removed)
+    pub fn clear_removed<T: Component>(&mut self) {
+        self.hooks.clear_removed::<T>();
+    }
+
+    /// Maintain an index of entities by their [`Name`] components
+    ///
+    /// Enables [`find_by_name`](Self::find_by_name) and
+    /// [`find_all_by_name`](Self::find_all_by_name). Existing names, including those of
+    /// [`Disabled`] entities, are indexed immediately, and the index is then updated whenever a
+    /// `Name` is added or removed. Calling this again has no effect. The index is local to this
+    /// world; see [`clone_with`](Self::clone_with).
+    ///
+    /// # Example
+    /// ```
+    /// # use hecs::*;
+    /// let mut world = World::new();
+    /// world.index_names();
+    /// let a = world.spawn((Name::new("player"), 100));
+    /// assert_eq!(world.find_by_name("player"), Some(a));
+    /// world.remove_one::<Name>(a).unwrap();
+    /// assert_eq!(world.find_by_name("player"), None);
+    /// ```
+    pub fn index_names(&mut self) {
+        if self.hooks.observer::<Name, NameIndex>().is_some() {
+            return;
+        }
+        let existing = self
+            .query_mut::<IncludeDisabled<&Name>>()
+            .into_iter()
+            .map(|(entity, name)| (entity, name.clone()))
+            .collect::<Vec<_>>();
+        let index = self.hooks.add_observer::<Name, _>(NameIndex::default());
+        for (entity, name) in existing {
+            index.insert(entity, &name);
+        }
+    }
+
+    /// The live entity that was most recently given the [`Name`] `name`, if any
+    ///
+    /// # Panics
+    /// Panics if [`index_names`](Self::index_names) hasn't been called.
+    pub fn find_by_name(&self, name: &str) -> Option<Entity> {
+        self.find_all_by_name(name).last().copied()
+    }
+
+    /// Every live entity with the [`Name`] `name`, from least to most recently named
+    ///
+    /// # Panics
+    /// Panics if [`index_names`](Self::index_names) hasn't been called.
+    pub fn find_all_by_name(&self, name: &str) -> &[Entity] {
+        self.hooks
+            .observer::<Name, NameIndex>()
+            .expect("names aren't indexed; call `World::index_names` first")
+            .get(name)
+    }
+
+    /// Assign a stable slot in a [`DenseIndex`] to every entity with a `K` component
+    ///
+    /// Existing `K` components, including those of [`Disabled`] entities, are assigned slots
+    /// immediately, and the index is then updated whenever a `K` is added or removed. Replacing an
+    /// entity's `K` keeps its slot. Calling this again has no effect. The index isn't carried over
+    /// by [`clone_with`](Self::clone_with).
+    pub fn enable_dense_index<K: Component>(&mut self) {
+        if self.hooks.observer::<K, DenseIndex<K>>().is_some() {
+            return;
+        }
+        let existing = self
+            .query_mut::<IncludeDisabled<&K>>()
+            .into_iter()
+            .map(|(entity, _)| entity)
+            .collect::<Vec<_>>();
+        let index = self.hooks.add_observer::<K, _>(DenseIndex::new());
+        for entity in existing {
+            index.assign(entity);
+        }
+    }
+
+    /// The index maintained for `K` by [`enable_dense_index`](Self::enable_dense_index), if any
+    pub fn dense_index<K: Component>(&self) -> Option<&DenseIndex<K>> {
+        self.hooks.observer::<K, DenseIndex<K>>()
+    }
+
+    /// Group entities into an [`Index`] by the key `key` computes from their `T` components
+    ///
+    /// Entities are filed as soon as they gain a `T` and removed as soon as they lose it. Mutations
+    /// of existing `T`s are picked up by [`index`](Self::index), which refiles the `T`s of every
+    /// archetype whose `T`s were uniquely borrowed since its previous call, as determined by the
+    /// same per-archetype change tracking as [`Changed`](crate::Changed). Replaces any existing
+    /// index for the same `T` and key type. Not copied by [`clone_with`](Self::clone_with).
+    ///
+    /// # Example
+    /// ```
+    /// # use hecs::*;
+    /// struct Position(f32, f32);
+    /// let cell = |p: &Position| ((p.0 / 10.0).floor() as i32, (p.1 / 10.0).floor() as i32);
+    /// let mut world = World::new();
+    /// world.add_index(cell);
+    /// let a = world.spawn((Position(1.0, 2.0),));
+    /// let b = world.spawn((Position(15.0, 2.0),));
+    /// assert_eq!(world.index::<Position, _>().unwrap().bucket(&(0, 0)), [a]);
+    ///
+    /// world.get::<&mut Position>(b).unwrap().0 = 5.0;
+    /// let index = world.index::<Position, (i32, i32)>().unwrap();
+    /// assert_eq!(index.bucket(&(1, 0)), []);
+    /// assert_eq!(index.bucket(&(0, 0)).len(), 2);
+    /// ```
+    pub fn add_index<T, B>(&mut self, key: impl Fn(&T) -> B + Send + Sync + 'static)
+    where
+        T: Component,
+        B: Hash + Eq + Clone + Send + Sync + 'static,
+    {
+        self.flush();
+        let tick = self.archetypes.increment_change_tick();
+        let index = self.hooks.add_observer::<T, _>(Index::new(key, tick));
+        index.refresh(&self.entities.meta, &self.archetypes.archetypes, tick, true);
+    }
+
+    /// The index maintained for `T` by [`add_index`](Self::add_index), if any, brought up to date
+    /// with mutations of `T` components
+    pub fn index<T, B>(&mut self) -> Option<&Index<T, B>>
+    where
+        T: Component,
+        B: Hash + Eq + Clone + Send + Sync + 'static,
+    {
+        self.flush();
+        let index = self.hooks.observer_mut::<T, Index<T, B>>()?;
+        let tick = self.archetypes.increment_change_tick();
+        index.refresh(
+            &self.entities.meta,
+            &self.archetypes.archetypes,
+            tick,
+            false,
+        );
+        Some(index)
+    }
+
+    /// Record the current value of every `T`, making it available through [`Prev<T>`](crate::Prev)
+    /// until the next [`snapshot_history::<T>`](Self::snapshot_history)
+    ///
+    /// Takes the first snapshot immediately. Snapshots are stored as hidden components next to
+    /// each `T`, so entities with recorded history occupy different archetypes than those without.
+    /// Entities that gain a `T` after a snapshot have no previous value, and aren't matched by
+    /// `Prev<T>`, until the next one. Does nothing if history is already enabled for `T`.
+    pub fn enable_history<T: Component + Clone>(&mut self) {
+        if self.history.contains(&TypeId::of::<T>()) {
+            return;
+        }
+        self.history.push(TypeId::of::<T>());
+        self.record_history::<T>();
+    }
+
+    /// Replace the value [`Prev<T>`](crate::Prev) yields for each entity with its current `T`
+    ///
+    /// Typically called once at the end of each frame, so that `Prev<T>` yields the value as of the
+    /// end of the previous frame. Entities that lost their `T` since the last snapshot also lose
+    /// their recorded history.
+    ///
+    /// # Panics
+    /// Panics if [`enable_history::<T>`](Self::enable_history) hasn't been called.
+    ///
+    /// # Example
+    /// ```
+    /// # use hecs::*;
+    /// #[derive(Clone)]
+    /// struct Position(f32);
+    /// let mut world = World::new();
+    /// world.enable_history::<Position>();
+    /// let a = world.spawn((Position(1.0),));
+    /// // `a` has no history until the next snapshot
+    /// assert!(world.query_one_mut::<Prev<Position>>(a).is_err());
+    /// world.snapshot_history::<Position>();
+    /// world.get::<&mut Position>(a).unwrap().0 = 2.0;
+    /// assert_eq!(world.query_one_mut::<Prev<Position>>(a).unwrap().0, 1.0);
+    /// world.snapshot_history::<Position>();
+    /// assert_eq!(world.query_one_mut::<Prev<Position>>(a).unwrap().0, 2.0);
+    /// ```
+    pub fn snapshot_history<T: Component + Clone>(&mut self) {
+        assert!(
+            self.history.contains(&TypeId::of::<T>()),
+            "history isn't enabled for `{}`; call `World::enable_history` first",
+            type_name::<T>()
+        );
+        self.record_history::<T>();
+    }
+
+    fn record_history<T: Component + Clone>(&mut self) {
+        let stale = self
+            .query_mut::<IncludeDisabled<Without<&History<T>, &T>>>()
+            .into_iter()
+            .map(|(entity, _)| entity)
+            .collect::<Vec<_>>();
+        self.remove_batch::<History<T>>(stale);
+
+        let missing = self
+            .query_mut::<IncludeDisabled<Without<&T, &History<T>>>>()
+            .into_iter()
+            .map(|(entity, _)| entity)
+            .collect::<Vec<_>>();
+        self.flush();
+        let groups = self.group_by_archetype(missing);
+        let mut values = groups
+            .iter()
+            .map(|&(_, entity)| History(T::clone(&self.get::<&T>(entity).unwrap())))
+            .collect::<Vec<_>>()
+            .into_iter();
+        self.insert_groups(&groups, || values.next().unwrap());
+
+        for (_, (value, prev)) in self.query_mut::<IncludeDisabled<(&T, &mut History<T>)>>() {
+            prev.0.clone_from(value);
+        }
+    }
+
+    /// Store `T` components outside of archetypes, in a set indexed by entity
+    ///
+    /// Intended for marker components, like `Selected`, that come and go frequently on a handful of
+    /// entities. Normally, adding or removing a component moves an entity to a different archetype,
+    /// and every combination of such markers present at once adds more archetypes for queries to
+    /// visit. Once `T` is sparse, [`insert_one`](Self::insert_one) and
+    /// [`remove_one`](Self::remove_one) add and remove `T` without moving the entity, and any `T`
+    /// components already present are moved into sparse storage immediately. Hooks do not fire for
+    /// sparse components.
+    ///
+    /// Sparse components are not supported by ordinary queries or by [`get`](Self::get): queries
+    /// for `&T` or `With<_, &T>` never match, since queries select whole archetypes and have no way
+    /// to skip individual entities that lack a sparse `T`. Use
+    /// [`get_sparse`](Self::get_sparse) and [`query_sparse`](Self::query_sparse) instead, and look up
+    /// other components of the entities found with [`query_one`](Self::query_one), or
+    /// [`view`](Self::view) for many lookups. This makes sparse components fast to add, remove, and
+    /// iterate on their own, but slower than archetype storage to combine with other components
+    /// across many entities. Inserting a sparse `T` as part of a larger bundle, e.g. with
+    /// [`spawn`](Self::spawn) or [`insert`](Self::insert), panics. Sparse components are dropped
+    /// when their entity is despawned, including by [`take`](Self::take), and are not copied by
+    /// [`clone_with`](Self::clone_with).
+    ///
+    /// # Example
+    /// ```
+    /// # use hecs::*;
+    /// struct Selected;
+    ///
+    /// let mut world = World::new();
+    /// world.set_sparse::<Selected>();
+    /// let a = world.spawn((1,));
+    /// world.insert_one(a, Selected).unwrap();
+    /// assert!(world.get_sparse::<Selected>(a).is_ok());
+    /// assert!(world.get::<&Selected>(a).is_err());
+    /// for (entity, Selected) in world.query_sparse::<Selected>() {
+    ///     assert_eq!(*world.get::<&i32>(entity).unwrap(), 1);
+    /// }
+    /// world.remove_one::<Selected>(a).unwrap();
+    /// assert_eq!(world.query_sparse::<Selected>().len(), 0);
+    /// ```
+    pub fn set_sparse<T: Component>(&mut self) {
+        if self.sparse.get::<T>().is_some() {
+            return;
+        }
+        let entities = self.entities_with::<T>().collect::<Vec<_>>();
+        let values = entities
+            .iter()
+            .map(|&entity| self.remove_one::<T>(entity).unwrap())
+            .collect::<Vec<_>>();
+        let set = self.sparse.register::<T>();
+        for (entity, value) in entities.into_iter().zip(values) {
+            set.insert(entity, value);
+        }
+    }
+
+    /// Whether `T` was passed to [`set_sparse`](Self::set_sparse)
+    pub fn is_sparse<T: Component>(&self) -> bool {
+        self.sparse.get::<T>().is_some()
+    }
+
+    /// Borrow `entity`'s sparse `T` component
+    ///
+    /// Fails with [`MissingComponent`] if `T` isn't [sparse](Self::set_sparse), or `entity` has none.
+    pub fn get_sparse<T: Component>(&self, entity: Entity) -> Result<&T, ComponentError> {
+        self.entities.get(entity)?;
+        self.sparse
+            .get::<T>()
+            .and_then(|set| set.get(entity.id))
+            .ok_or_else(|| MissingComponent::new::<T>().into())
+    }
+
+    /// Uniquely borrow `entity`'s sparse `T` component
+    ///
+    /// See [`get_sparse`](Self::get_sparse).
+    pub fn get_sparse_mut<T: Component>(
+        &mut self,
+        entity: Entity,
+    ) -> Result<&mut T, ComponentError> {
+        self.entities.get(entity)?;
+        self.sparse
+            .get_mut::<T>()
+            .and_then(|set| set.get_mut(entity.id))
+            .ok_or_else(|| MissingComponent::new::<T>().into())
+    }
+
+    /// Iterate over every entity with a sparse `T` component, in unspecified order
+    ///
+    /// Empty if `T` isn't [sparse](Self::set_sparse).
+    pub fn query_sparse<T: Component>(&self) -> impl ExactSizeIterator<Item = (Entity, &T)> + '_ {
+        let (entities, values) = self
+            .sparse
+            .get::<T>()
+            .map_or((&[][..], &[][..]), |set| (set.entities(), set.values()));
+        entities.iter().copied().zip(values)
+    }
+
+    /// Iterate over every entity with a sparse `T` component, uniquely borrowing the components
+    ///
+    /// See [`query_sparse`](Self::query_sparse).
+    pub fn query_sparse_mut<T: Component>(
+        &mut self,
+    ) -> impl ExactSizeIterator<Item = (Entity, &mut T)> + '_ {
+        let (entities, values) = self
+            .sparse
+            .get_mut::<T>()
+            .map_or((&[][..], &mut [][..]), |set| set.split_mut());
+        entities.iter().copied().zip(values)
+    }
+
+    /// Copy every entity, preserving handles, along with components registered in `registry`
+    ///
+    /// Unregistered components are omitted; entities having only unregistered components are still
+    /// copied, without them. Entity allocator state is duplicated exactly, so existing and future
+    /// [`Entity`] handles refer to the same entities in both worlds. Registered relations are
+    /// retained, but resources, hooks, and the indexes maintained by
+    /// [`index_names`](Self::index_names), [`enable_dense_index`](Self::enable_dense_index), and
+    /// [`add_index`](Self::add_index) are not copied.
+    ///
+    /// Panics if any component is already borrowed uniquely.
+    ///
+    /// See [`try_clone_with`](Self::try_clone_with) to fail on unregistered components instead.
+    pub fn clone_with(&self, registry: &CloneRegistry) -> World {
+        self.clone_inner(registry, false).unwrap()
+    }
+
+    /// Like [`clone_with`](Self::clone_with), but fails if a component type is not registered
+    pub fn try_clone_with(&self, registry: &CloneRegistry) -> Result<World, UnregisteredComponent> {
+        self.clone_inner(registry, true)
+    }
+
+    /// Record the components registered in `registry` of every entity
+    ///
+    /// See [`WorldSnapshot::diff`] to compute changes between snapshots, e.g. for replication.
+    pub fn snapshot(&self, registry: &SnapshotRegistry) -> WorldSnapshot {
+        WorldSnapshot::capture(self, registry)
+    }
+
+    fn clone_inner(
+        &self,
+        registry: &CloneRegistry,
+        strict: bool,
+    ) -> Result<World, UnregisteredComponent> {
+        let mut cloned = World::new();
+        cloned
+            .archetypes
+            .set_change_tick(self.archetypes.change_tick.load(Ordering::Relaxed));
+        cloned.entities = self.entities.clone();
+        cloned.relations = self.relations.clone();
+        cloned.type_names = self.type_names.clone();
+        cloned.dynamic_types = self.dynamic_types.clone();
+        cloned.archetypes.set_sorted(self.deterministic_order());
+        for archetype in &self.archetypes.archetypes {
+            if archetype.is_empty() {
+                continue;
+            }
+            let mut batch_type = ColumnBatchType::new();
+            for ty in archetype.types() {
+                match registry.get(ty.id()) {
+                    Some(entry) => {
+                        batch_type.add_dynamic(entry.info);
+                    }
+                    None if strict => return Err(UnregisteredComponent(ty.id())),
+                    None => {}
+                }
+            }
+            let mut batch = batch_type.into_batch(archetype.len());
+            for ty in archetype.types() {
+                if let Some(entry) = registry.get(ty.id()) {
+                    (entry.clone)(archetype, &mut batch);
+                }
+            }
+            let batch = batch.build().expect("batch should be complete");
+
+            let (archetype_id, base) = cloned.archetypes.insert_batch(batch.0);
+            let target = &mut cloned.archetypes.archetypes[archetype_id as usize];
+            for (&id, index) in archetype.ids().iter().zip(base..) {
+                target.set_entity_id(index as usize, id);
+                cloned.entities.meta[id as usize].location = Location {
+                    archetype: archetype_id,
+                    index,
+                };
+            }
+        }
+        Ok(cloned)
+    }
+
+    /// Whether `entity` still exists
+    pub fn contains(&self, entity: Entity) -> bool {
+        self.entities.contains(entity)
+    }
+
+    /// Assign `entity` to the group `tag`
+    ///
+    /// Tags coarsely partition entities independently of their components, e.g. to separate the
+    /// contents of several levels sharing one world, without moving the entity between archetypes
+    /// as a marker component would. Every entity has exactly one of 256 tags, starting at and
+    /// reverting to 0 when despawned. [`QueryBorrow::iter_in_tag`] and
+    /// [`QueryMut::into_iter_in_tag`] restrict iteration to entities with a given tag, but still
+    /// visit every entity matching the query to check its tag, so they're no faster than
+    /// iteration over the whole query.
+    ///
+    /// Tags are stored in one byte per entity ID, allocated up to the highest ID ever tagged with
+    /// a nonzero value.
+    ///
+    /// # Example
+    /// ```
+    /// # use hecs::*;
+    /// let mut world = World::new();
+    /// let a = world.spawn((1,));
+    /// let b = world.spawn((2,));
+    /// world.set_tag(b, 1).unwrap();
+    /// let tagged = world.query::<&i32>().iter_in_tag(1).map(|(e, _)| e).collect::<Vec<_>>();
+    /// assert_eq!(tagged, [b]);
+    /// assert_eq!(world.tag(a), Ok(0));
+    /// ```
+    pub fn set_tag(&mut self, entity: Entity, tag: u8) -> Result<(), NoSuchEntity> {
+        self.flush();
+        self.entities.get(entity)?;
+        self.entities.set_tag(entity.id, tag);
+        Ok(())
+    }
+
+    /// The tag assigned to `entity` by [`set_tag`](Self::set_tag), or 0 if none
+    pub fn tag(&self, entity: Entity) -> Result<u8, NoSuchEntity> {
+        self.entities.get(entity)?;
+        Ok(self.entities.tag(entity.id))
+    }
+
+    #[inline]
+    pub(crate) fn entity_tag(&self, id: u32) -> u8 {
+        self.entities.tag(id)
+    }
+
+    /// Efficiently iterate over all entities that have certain components, using dynamic borrow
+    /// checking
+    ///
+    /// Prefer [`query_mut`](Self::query_mut) when concurrent access to the [`World`] is not required.
+    ///
+    /// Calling `iter` on the returned value yields `(Entity, Q)` tuples, where `Q` is some query
+    /// type. A query type is any type for which an implementation of [`Query`] exists, e.g. `&T`,
+    /// `&mut T`, a tuple of query types, or an `Option` wrapping a query type, where `T` is any
+    /// component type. Components queried with `&mut` must only appear once. Entities which do not
+    /// have a component type referenced outside of an `Option` will be skipped.
+    ///
+    /// Entities are yielded in arbitrary order.
+    ///
+    /// The returned [`QueryBorrow`] can be further transformed with combinator methods; see its
+    /// documentation for details.
+    ///
+    /// Iterating a query will panic if it would violate an existing unique reference or construct
+    /// an invalid unique reference. This occurs when two simultaneously-active queries could expose
+    /// the same entity. Simultaneous queries can access the same component type if and only if the
+    /// world contains no entities that have all components required by both queries, assuming no
+    /// other component borrows are outstanding.
+    ///
+    /// Iterating a query yields references with lifetimes bound to the [`QueryBorrow`] returned
+    /// here. To ensure those are invalidated, the return value of this method must be dropped for
+    /// its dynamic borrows from the world to be released. Similarly, lifetime rules ensure that
+    /// references obtained from a query cannot outlive the [`QueryBorrow`].
+    ///
+    /// # Example
+    /// ```
+    /// # use hecs::*;
+    /// let mut world = World::new();
+    /// let a = world.spawn((123, true, "abc"));
+    /// let b = world.spawn((456, false));
+    /// let c = world.spawn((42, "def"));
+    /// let entities = world.query::<(&i32, &bool)>()
+    ///     .iter()
+    ///     .map(|(e, (&i, &b))| (e, i, b)) // Copy out of the world
+    ///     .collect::<Vec<_>>();
+    /// assert_eq!(entities.len(), 2);
+    /// assert!(entities.contains(&(a, 123, true)));
+    /// assert!(entities.contains(&(b, 456, false)));
+    /// ```
+    pub fn query<Q: Query>(&self) -> QueryBorrow<'_, Q> {
+        QueryBorrow::new(self)
+    }
+
+    /// Divide access to the world's components between two views for use by concurrent systems
+    ///
+    /// The first view may read the component types in `reads` and read or write those in `writes`.
+    /// The second may read every type not in `writes`, and write every type in neither list. Both
+    /// may check for the presence of any component, e.g. with [`With`](crate::With). Each view's
+    /// queries panic if they need access beyond its grant, which makes a system's overreach fail
+    /// deterministically instead of only when it happens to race another system's borrows. The
+    /// world is borrowed uniquely, so the views are its only users while they live.
+    ///
+    /// # Example
+    #[cfg_attr(not(feature = "thread-local"), doc = "```")]
+    #[cfg_attr(feature = "thread-local", doc = "```ignore")]
+    /// # use hecs::*;
+    /// # use std::any::TypeId;
+    /// struct Position(f32);
+    /// struct Velocity(f32);
+    /// struct Health(u32);
+    /// let mut world = World::new();
+    /// world.spawn((Position(0.0), Velocity(1.0), Health(10)));
+    /// let (movement, other) = world.split_access(&[TypeId::of::<Velocity>()], &[TypeId::of::<Position>()]);
+    /// std::thread::scope(|scope| {
+    ///     scope.spawn(move || {
+    ///         for (_, (pos, vel)) in movement.query::<(&mut Position, &Velocity)>().iter() {
+    ///             pos.0 += vel.0;
+    ///         }
+    ///     });
+    ///     scope.spawn(move || {
+    ///         for (_, health) in other.query::<&mut Health>().iter() {
+    ///             health.0 -= 1;
+    ///         }
+    ///         assert!(!other.permits::<&Position>());
+    ///     });
+    /// });
+    /// ```
+    pub fn split_access(
+        &mut self,
+        reads: &[TypeId],
+        writes: &[TypeId],
+    ) -> (AccessView<'_>, AccessView<'_>) {
+        AccessView::split(self, reads, writes)
+    }
+
+    /// Enter a read-only phase in which the world can't change structurally
+    ///
+    /// For as long as the returned [`FrozenWorld`] lives, no entity can be spawned, despawned, or
+    /// gain or lose components, which the compiler enforces by borrowing the world uniquely. Readers
+    /// handed a `&FrozenWorld` may therefore cache entity locations and archetype references for
+    /// the duration. Only shared queries are available, so readers can't conflict with one another.
+    ///
+    /// # Example
+    #[cfg_attr(not(feature = "thread-local"), doc = "```")]
+    #[cfg_attr(feature = "thread-local", doc = "```ignore")]
+    /// # use hecs::*;
+    /// let mut world = World::new();
+    /// world.spawn_batch((0..100).map(|i| (i,)));
+    /// let frozen = world.freeze();
+    /// let frozen = &frozen;
+    /// std::thread::scope(|scope| {
+    ///     for _ in 0..4 {
+    ///         scope.spawn(move || {
+    ///             let sum = frozen.query::<&i32>().iter().map(|(_, &x)| x).sum::<i32>();
+    ///             assert_eq!(sum, 4950);
+    ///         });
+    ///     }
+    /// });
+    /// ```
+    pub fn freeze(&mut self) -> FrozenWorld<'_> {
+        self.flush();
+        FrozenWorld::new(self)
+    }
+
+    /// Query for components identified at runtime, using dynamic borrow checking
+    ///
+    /// Matches entities having every component type in `components`. Components requested with
+    /// [`Access::Read`] are borrowed shared, and those requested with [`Access::Write`] uniquely;
+    /// [`Access::Iterate`] only requires presence. Useful for scripting layers which reflect over
+    /// component layouts themselves.
+    ///
+    /// Panics if a type requested with [`Access::Write`] appears more than once.
+    ///
+    /// # Example
+    /// ```
+    /// # use hecs::*;
+    /// # use std::any::TypeId;
+    /// let mut world = World::new();
+    /// let a = world.spawn((123i32, true));
+    /// world.spawn((456i32,));
+    /// let mut query = world.query_dynamic(&[
+    ///     (TypeId::of::<i32>(), Access::Write),
+    ///     (TypeId::of::<bool>(), Access::Iterate),
+    /// ]);
+    /// for (entity, item) in &mut query {
+    ///     assert_eq!(entity, a);
+    ///     assert_eq!(item.layout(0), std::alloc::Layout::new::<i32>());
+    ///     unsafe { *item.get(0).unwrap().cast::<i32>().as_ptr() += 1; }
+    /// }
+    /// drop(query);
+    /// assert_eq!(*world.get::<&i32>(a).unwrap(), 124);
+    /// ```
+    pub fn query_dynamic(&self, components: &[(TypeId, Access)]) -> DynamicQueryBorrow<'_> {
+        DynamicQueryBorrow::new(self, components)
+    }
+
+    /// Allow `T` components to be visited by [`query_trait::<D>`](Self::query_trait)
+    ///
+    /// `D` is typically a trait object type such as `dyn Trait`, and `cast` converts a reference to
+    /// a `T` into a `D`, usually by unsizing coercion as in `|x| x`. Registering the same `T` for
+    /// the same `D` again replaces the previous `cast`. Registrations persist for the lifetime of
+    /// the world.
+    pub fn register_trait<D: ?Sized + 'static, T: Component>(&mut self, cast: fn(&T) -> &D) {
+        self.traits.register(cast);
+    }
+
+    /// Visit every component whose type was registered for `D` by
+    /// [`register_trait`](Self::register_trait)
+    ///
+    /// Yields each matching component as a `&D` along with the entity it belongs to. An entity
+    /// with several registered component types is visited once for each. Components in
+    /// [sparse storage](Self::set_sparse) and [`Disabled`] entities are skipped.
+    ///
+    /// # Example
+    /// ```
+    /// # use hecs::*;
+    /// trait Damageable {
+    ///     fn health(&self) -> u32;
+    /// }
+    /// struct Crate;
+    /// impl Damageable for Crate {
+    ///     fn health(&self) -> u32 { 10 }
+    /// }
+    /// struct Monster(u32);
+    /// impl Damageable for Monster {
+    ///     fn health(&self) -> u32 { self.0 }
+    /// }
+    ///
+    /// let mut world = World::new();
+    /// world.register_trait::<dyn Damageable, Crate>(|x| x);
+    /// world.register_trait::<dyn Damageable, Monster>(|x| x);
+    /// world.spawn((Crate,));
+    /// world.spawn((Monster(50), true));
+    /// world.spawn((42,));
+    /// let total = world
+    ///     .query_trait::<dyn Damageable>()
+    ///     .iter()
+    ///     .map(|(_, x)| x.health())
+    ///     .sum::<u32>();
+    /// assert_eq!(total, 60);
+    /// ```
+    pub fn query_trait<D: ?Sized + 'static>(&self) -> TraitQueryBorrow<'_, D> {
+        TraitQueryBorrow::new(self, &self.traits)
     }
 
     /// Provide random access to any entity for a given Query.
@@ -404,6 +2010,85 @@ impl World {
         ViewBorrow::new(self)
     }
 
+    /// Query each of `entities`, in order
+    ///
+    /// Yields `None` for entities that don't exist or don't satisfy `Q`. Unlike
+    /// [`query`](Self::query), results follow the caller's order, which is useful for e.g. UI lists
+    /// or batches of networked IDs. Archetype lookups are shared between all entities, as with
+    /// [`view`](Self::view).
+    ///
+    /// # Example
+    /// ```
+    /// # use hecs::*;
+    /// let mut world = World::new();
+    /// let a = world.spawn((1,));
+    /// let b = world.spawn((true,));
+    /// let c = world.spawn((3,));
+    /// let ids = [c, b, a];
+    /// let mut query = world.query_each::<&i32>(&ids);
+    /// let values = query.iter().map(|x| x.copied()).collect::<Vec<_>>();
+    /// assert_eq!(values, [Some(3), None, Some(1)]);
+    /// ```
+    pub fn query_each<'a, Q: Query + QueryShared>(
+        &'a self,
+        entities: &'a [Entity],
+    ) -> QueryEach<'a, Q> {
+        QueryEach::new(self, entities)
+    }
+
+    /// Query each of `entities` in a uniquely borrowed world, in order
+    ///
+    /// Like [`query_each`](Self::query_each), but supports queries yielding unique references.
+    ///
+    /// # Panics
+    /// Panics if `entities` contains duplicates.
+    ///
+    /// # Example
+    /// ```
+    /// # use hecs::*;
+    /// let mut world = World::new();
+    /// let a = world.spawn((1,));
+    /// let b = world.spawn((2,));
+    /// for x in world.query_each_mut::<&mut i32>(&[b, a]).iter().flatten() {
+    ///     *x *= 10;
+    /// }
+    /// assert_eq!(*world.get::<&i32>(a).unwrap(), 10);
+    /// ```
+    pub fn query_each_mut<'a, Q: Query>(&'a mut self, entities: &'a [Entity]) -> QueryEach<'a, Q> {
+        self.flush();
+        QueryEach::new_mut(self, entities)
+    }
+
+    /// Query using a [`PreparedQuery`](crate::PreparedQuery) owned by the world and identified by
+    /// `key`
+    ///
+    /// Like [`query`](Self::query), but archetype matching is cached across calls with the same
+    /// `key`, as with a `PreparedQuery` stored by the caller. Using different `Q`s with the same
+    /// `key` is a logic error, which panics in debug builds, and otherwise discards the cached
+    /// state.
+    ///
+    /// # Example
+    /// ```
+    /// # use hecs::*;
+    /// let mut world = World::new();
+    /// world.spawn((123, true));
+    /// for _ in 0..3 {
+    ///     for (_, x) in world.cached_query::<&mut i32>("double").iter() {
+    ///         *x *= 2;
+    ///     }
+    /// }
+    /// assert_eq!(world.cached_query::<&i32>("read").iter().next().unwrap().1, &984);
+    /// ```
+    pub fn cached_query<Q: Query + 'static>(
+        &mut self,
+        key: &'static str,
+    ) -> PreparedQueryBorrow<'_, Q> {
+        let query = self.query_cache.get::<Q>(key);
+        // Safe because the query is separately allocated, and the cache can't be accessed again
+        // while `self` is borrowed
+        unsafe { (*query.as_ptr()).query(self) }
+    }
+
     /// Provide random access to any entity for a given Query on a uniquely
     /// borrowed world. Like [`view`](Self::view), but faster because dynamic borrow checks can be skipped.
     pub fn view_mut<Q: Query>(&mut self) -> View<'_, Q> {
@@ -420,10 +2105,23 @@ impl World {
         QueryMut::new(self)
     }
 
+    /// Prepare to iterate over `Q` incrementally, a few entities at a time
+    ///
+    /// See [`QueryCursor`].
+    pub fn query_cursor<Q: Query>(&self) -> QueryCursor<Q> {
+        QueryCursor::new(self)
+    }
+
     pub(crate) fn memo(&self) -> (u64, u32) {
         (self.id, self.archetypes.generation())
     }
 
+    /// Whether any live entity has the ID `id`, regardless of generation
+    #[cfg(feature = "row-serialize")]
+    pub(crate) fn contains_id(&self, id: u32) -> bool {
+        self.entities.contains_id(id)
+    }
+
     #[inline(always)]
     pub(crate) fn entities_meta(&self) -> &[EntityMeta] {
         &self.entities.meta
@@ -434,6 +2132,12 @@ impl World {
         &self.archetypes.archetypes
     }
 
+    /// Index of the `n`th archetype visited by iteration
+    #[inline]
+    pub(crate) fn visit_order(&self, n: usize) -> usize {
+        self.archetypes.visit_order(n)
+    }
+
     /// Prepare a query against a single entity, using dynamic borrow checking
     ///
     /// Prefer [`query_one_mut`](Self::query_one_mut) when concurrent access to the [`World`] is not
@@ -484,6 +2188,106 @@ impl World {
         unsafe { Ok(Q::get(&fetch, loc.index as usize)) }
     }
 
+    /// Uniquely borrow the `T` components of each of a sequence of pairs of distinct entities
+    ///
+    /// Each pair is borrowed only until the next is requested, so entities may appear in any
+    /// number of pairs. Pairs naming an entity that doesn't exist or lacks a `T` are skipped, and a
+    /// pair naming the same entity twice panics.
+    ///
+    /// # Example
+    /// ```
+    /// # use hecs::*;
+    /// struct Charge(i32);
+    /// let mut world = World::new();
+    /// let a = world.spawn((Charge(3),));
+    /// let b = world.spawn((Charge(1),));
+    /// let c = world.spawn((Charge(2),));
+    /// let mut pairs = world.query_pairs_mut::<Charge>([(a, b), (b, c)]);
+    /// while let Some((x, y)) = pairs.next() {
+    ///     // Balance each pair
+    ///     let mean = (x.0 + y.0) / 2;
+    ///     x.0 = mean;
+    ///     y.0 = mean;
+    /// }
+    /// assert_eq!(world.get::<&Charge>(c).unwrap().0, 2);
+    /// ```
+    pub fn query_pairs_mut<T: Component>(
+        &mut self,
+        pairs: impl IntoIterator<Item = (Entity, Entity)>,
+    ) -> PairsMut<'_, T, impl Iterator<Item = (Entity, Entity)>> {
+        self.flush();
+        PairsMut::new(self, pairs.into_iter())
+    }
+
+    /// Visit every entity with a `T`, uniquely borrowing it alongside shared access to the `T`s
+    /// of all other entities
+    ///
+    /// Useful when each entity's component is updated based on the same component of others, e.g.
+    /// flocking velocities. Updates are visible to entities visited afterwards. Like queries, skips
+    /// disabled entities.
+    ///
+    /// # Example
+    /// ```
+    /// # use hecs::*;
+    /// struct Boid {
+    ///     x: f32,
+    ///     neighbors: usize,
+    /// }
+    /// let mut world = World::new();
+    /// let a = world.spawn((Boid { x: 0.0, neighbors: 0 },));
+    /// world.spawn((Boid { x: 1.0, neighbors: 0 },));
+    /// world.spawn((Boid { x: 5.0, neighbors: 0 },));
+    /// world.for_each_with_neighbors::<Boid>(|_, boid, others| {
+    ///     boid.neighbors = others
+    ///         .iter()
+    ///         .filter(|(_, other)| (other.x - boid.x).abs() < 2.0)
+    ///         .count();
+    /// });
+    /// assert_eq!(world.get::<&Boid>(a).unwrap().neighbors, 1);
+    /// ```
+    pub fn for_each_with_neighbors<T: Component>(
+        &mut self,
+        mut f: impl FnMut(Entity, &mut T, NeighborReader<'_, T>),
+    ) {
+        self.flush();
+        let meta = self.entities_meta();
+        let archetypes = self.archetypes_inner();
+        let columns = archetypes
+            .iter()
+            .map(|archetype| {
+                if archetype.is_disabled() {
+                    return None;
+                }
+                let state = archetype.get_state::<T>()?;
+                archetype.mark_changed(state);
+                Some(archetype.get_base::<T>(state))
+            })
+            .collect::<Vec<_>>();
+        for (archetype, base) in archetypes.iter().zip(&columns) {
+            let Some(base) = *base else {
+                continue;
+            };
+            for (index, &id) in archetype.ids().iter().enumerate() {
+                let entity = Entity {
+                    id,
+                    generation: meta[id as usize].generation,
+                };
+                // Safe because the reader refuses to access `entity`'s `T`
+                let value = unsafe { &mut *base.as_ptr().add(index) };
+                f(
+                    entity,
+                    value,
+                    NeighborReader {
+                        meta,
+                        archetypes,
+                        columns: &columns,
+                        current: entity,
+                    },
+                );
+            }
+        }
+    }
+
     /// Query a fixed number of distinct entities in a uniquely borrowed world
     ///
     /// Like [`query_one_mut`](Self::query_one_mut), but for multiple entities, which would
@@ -505,6 +2309,86 @@ impl World {
         })
     }
 
+    /// Like [`query_many_mut`](Self::query_many_mut), but fails instead of panicking on duplicate
+    /// entities, and fails as a whole if any entity is missing or doesn't satisfy `Q`
+    ///
+    /// # Example
+    /// ```
+    /// # use hecs::*;
+    /// let mut world = World::new();
+    /// let a = world.spawn((1, 'a'));
+    /// let b = world.spawn((2, 'b'));
+    /// let [(x, _), (y, _)] = world.try_query_many_mut::<(&mut i32, &char), 2>([a, b]).unwrap();
+    /// core::mem::swap(x, y);
+    /// assert_eq!(*world.get::<&i32>(a).unwrap(), 2);
+    /// assert_eq!(
+    ///     world.try_query_many_mut::<&i32, 2>([a, a]).err(),
+    ///     Some(GetManyMutError::Duplicate(a))
+    /// );
+    /// ```
+    pub fn try_query_many_mut<Q: Query, const N: usize>(
+        &mut self,
+        entities: [Entity; N],
+    ) -> Result<[Q::Item<'_>; N], GetManyMutError> {
+        assert_borrow::<Q>();
+        for (i, &a) in entities.iter().enumerate() {
+            if entities[..i].contains(&a) {
+                return Err(GetManyMutError::Duplicate(a));
+            }
+        }
+
+        let items: [Result<Q::Item<'_>, GetManyMutError>; N] = entities.map(|entity| {
+            let loc = self
+                .entities
+                .get(entity)
+                .map_err(|NoSuchEntity| GetManyMutError::NoSuchEntity(entity))?;
+            let archetype = &self.archetypes.archetypes[loc.archetype as usize];
+            let state = Q::Fetch::prepare(archetype).ok_or(GetManyMutError::Unsatisfied(entity))?;
+            let fetch = Q::Fetch::execute(archetype, state);
+            unsafe { Ok(Q::get(&fetch, loc.index as usize)) }
+        });
+        for item in &items {
+            if let Err(e) = item {
+                return Err(e.clone());
+            }
+        }
+        Ok(items.map(|x| match x {
+            Ok(x) => x,
+            Err(_) => unreachable!(),
+        }))
+    }
+
+    /// Uniquely borrow the `T` components of several distinct entities at once
+    ///
+    /// Fails, identifying the offending entity, if an entity occurs more than once, doesn't exist,
+    /// or lacks a `T`. See [`try_query_many_mut`](Self::try_query_many_mut) to borrow multiple
+    /// components per entity.
+    ///
+    /// # Example
+    /// ```
+    /// # use hecs::*;
+    /// struct Gold(u32);
+    /// let mut world = World::new();
+    /// let a = world.spawn((Gold(10),));
+    /// let b = world.spawn((Gold(0),));
+    /// let [from, to] = world.get_many_mut::<Gold, 2>([a, b]).unwrap();
+    /// to.0 += from.0;
+    /// from.0 = 0;
+    /// assert_eq!(world.get::<&Gold>(b).unwrap().0, 10);
+    /// ```
+    pub fn get_many_mut<T: Component, const N: usize>(
+        &mut self,
+        entities: [Entity; N],
+    ) -> Result<[&mut T; N], GetManyMutError> {
+        self.try_query_many_mut::<&mut T, N>(entities)
+            .map_err(|e| match e {
+                GetManyMutError::Unsatisfied(entity) => {
+                    GetManyMutError::MissingComponent(entity, MissingComponent::new::<T>())
+                }
+                e => e,
+            })
+    }
+
     /// Short-hand for [`entity`](Self::entity) followed by [`EntityRef::get`]
     pub fn get<'a, T: ComponentRef<'a>>(
         &'a self,
@@ -516,6 +2400,63 @@ impl World {
             .ok_or_else(MissingComponent::new::<T::Component>)?)
     }
 
+    /// Address and layout of `entity`'s `T` component, if it exists
+    ///
+    /// Intended for exposing components to foreign code. As with [`Archetype::get_base_ptr`], no
+    /// borrow is acquired, so the caller is responsible for ensuring that accesses through the
+    /// pointer don't conflict with other borrows of the component. The pointer remains valid until
+    /// the set of entities in `entity`'s archetype changes, i.e. until any entity is spawned into,
+    /// despawned from, or moved into or out of that archetype, or the world is cleared. Components
+    /// in [sparse storage](Self::set_sparse) aren't reachable this way.
+    ///
+    /// # Example
+    /// ```
+    /// # use hecs::*;
+    /// let mut world = World::new();
+    /// let a = world.spawn((42u32,));
+    /// let (ptr, layout) = world.get_raw::<u32>(a).unwrap();
+    /// assert_eq!(layout, std::alloc::Layout::new::<u32>());
+    /// assert_eq!(unsafe { *ptr.cast::<u32>().as_ptr() }, 42);
+    /// ```
+    pub fn get_raw<T: Component>(&self, entity: Entity) -> Option<(NonNull<u8>, Layout)> {
+        let loc = self.entities.get(entity).ok()?;
+        let archetype = &self.archetypes.archetypes[loc.archetype as usize];
+        let (base, stride) = archetype.get_base_ptr(TypeId::of::<T>())?;
+        let ptr = unsafe { NonNull::new_unchecked(base.as_ptr().add(loc.index as usize * stride)) };
+        Some((ptr, Layout::new::<T>()))
+    }
+
+    /// Like [`get`](Self::get), but panics if `entity` doesn't exist or lacks a `T`
+    ///
+    /// The panic message names the entity and, if it exists, the missing component type.
+    ///
+    /// # Example
+    /// ```
+    /// # use hecs::*;
+    /// let mut world = World::new();
+    /// let e = world.spawn((123,));
+    /// assert_eq!(*world.get_expect::<i32>(e), 123);
+    /// ```
+    #[track_caller]
+    pub fn get_expect<T: Component>(&self, entity: Entity) -> Ref<'_, T> {
+        match self.get::<&T>(entity) {
+            Ok(x) => x,
+            Err(ComponentError::NoSuchEntity) => get_expect_failed::<T>(entity, false),
+            Err(ComponentError::MissingComponent(_)) => get_expect_failed::<T>(entity, true),
+        }
+    }
+
+    /// Like [`get_expect`](Self::get_expect), but uniquely borrows the component without dynamic
+    /// borrow checks
+    #[track_caller]
+    pub fn get_expect_mut<T: Component>(&mut self, entity: Entity) -> &mut T {
+        match self.query_one_mut::<&mut T>(entity) {
+            Ok(x) => x,
+            Err(QueryOneError::NoSuchEntity) => get_expect_failed::<T>(entity, false),
+            Err(QueryOneError::Unsatisfied) => get_expect_failed::<T>(entity, true),
+        }
+    }
+
     /// Short-hand for [`entity`](Self::entity) followed by [`EntityRef::satisfies`]
     pub fn satisfies<Q: Query>(&self, entity: Entity) -> Result<bool, NoSuchEntity> {
         Ok(self.entity(entity)?.satisfies::<Q>())
@@ -545,6 +2486,28 @@ impl World {
         self.entities.resolve_unknown_gen(id)
     }
 
+    /// Reconstruct the live [`Entity`] with the given [`Entity::id`] and [`Entity::generation`]
+    ///
+    /// Returns `None` if no such entity exists, including when `id` has since been reused by a
+    /// different entity. Unlike [`find_entity_from_id`](Self::find_entity_from_id), safe to call
+    /// with untrusted input, e.g. from the network.
+    ///
+    /// # Example
+    /// ```
+    /// # use hecs::*;
+    /// let mut world = World::new();
+    /// let a = world.spawn(());
+    /// assert_eq!(world.resolve(a.id(), a.generation()), Some(a));
+    /// world.despawn(a).unwrap();
+    /// let b = world.spawn(());
+    /// assert_eq!(a.id(), b.id());
+    /// assert_eq!(world.resolve(a.id(), a.generation()), None);
+    /// ```
+    pub fn resolve(&self, id: u32, generation: u32) -> Option<Entity> {
+        let entity = Entity::from_bits(u64::from(generation) << 32 | u64::from(id))?;
+        self.contains(entity).then_some(entity)
+    }
+
     /// Iterate over all entities in the world
     ///
     /// Entities are yielded in arbitrary order. Prefer [`query`](Self::query) for better
@@ -562,7 +2525,75 @@ impl World {
     /// assert!(ids.contains(&b));
     /// ```
     pub fn iter(&self) -> Iter<'_> {
-        Iter::new(&self.archetypes.archetypes, &self.entities)
+        Iter::new(&self.archetypes, &self.entities)
+    }
+
+    /// Iterate over the entities that have a `T` component
+    ///
+    /// Cheaper than a query when only entity IDs are needed, as no components are borrowed. To
+    /// visit the components too, iterate [`query_mut`](Self::query_mut) directly, which also
+    /// yields the entity of every match.
+    ///
+    /// # Example
+    /// ```
+    /// # use hecs::*;
+    /// let mut world = World::new();
+    /// let a = world.spawn((123, true));
+    /// let b = world.spawn((456,));
+    /// world.spawn((false,));
+    /// let mut ids = world.entities_with::<i32>().collect::<Vec<_>>();
+    /// ids.sort();
+    /// assert_eq!(ids, [a, b]);
+    /// ```
+    pub fn entities_with<T: Component>(&self) -> impl Iterator<Item = Entity> + '_ {
+        let meta = &self.entities.meta;
+        self.archetypes
+            .archetypes
+            .iter()
+            .filter(|archetype| archetype.has::<T>())
+            .flat_map(|archetype| archetype.ids())
+            .map(move |&id| Entity {
+                id,
+                generation: meta[id as usize].generation,
+            })
+    }
+
+    /// Find every entity that doesn't satisfy exactly one of the queries in the tuple `T`
+    ///
+    /// Checks the invariant expressed by [`OneOf<T>`](crate::OneOf), e.g. that every entity is
+    /// exactly one of `Alive`, `Dead` or `Spawning`, returning the violators. Includes
+    /// [`Disabled`] entities. Intended for catching data bugs in tests and debug builds.
+    ///
+    /// # Example
+    /// ```
+    /// # use hecs::*;
+    /// struct Alive;
+    /// struct Dead;
+    ///
+    /// let mut world = World::new();
+    /// world.spawn((Alive,));
+    /// world.spawn((Dead,));
+    /// let both = world.spawn((Alive, Dead));
+    /// let neither = world.spawn(());
+    /// let mut violators = world.validate_exclusive::<(&Alive, &Dead)>();
+    /// violators.sort();
+    /// assert_eq!(violators, [both, neither]);
+    /// ```
+    pub fn validate_exclusive<T: Query>(&self) -> Vec<Entity>
+    where
+        T::Fetch: FetchAny,
+    {
+        let meta = &self.entities.meta;
+        self.archetypes
+            .archetypes
+            .iter()
+            .filter(|archetype| !archetype.satisfies::<OneOf<T>>())
+            .flat_map(|archetype| archetype.ids())
+            .map(move |&id| Entity {
+                id,
+                generation: meta[id as usize].generation,
+            })
+            .collect()
     }
 
     /// Add `components` to `entity`
@@ -589,10 +2620,217 @@ impl World {
         self.flush();
 
         let loc = self.entities.get(entity)?;
-        self.insert_inner(entity, components, loc.archetype, loc);
+        self.insert_inner(entity, components, loc.archetype, loc, |ptr, ty| unsafe {
+            ty.drop(ptr)
+        });
+        Ok(())
+    }
+
+    /// Add `components` to `entity`, reporting which component types were newly added and which
+    /// replaced existing components
+    ///
+    /// Otherwise identical to [`insert`](Self::insert).
+    ///
+    /// # Example
+    /// ```
+    /// # use hecs::*;
+    /// # use std::any::TypeId;
+    /// let mut world = World::new();
+    /// let e = world.spawn((123, "abc"));
+    /// let report = world.insert_report(e, (456, true)).unwrap();
+    /// assert_eq!(report.added, [TypeId::of::<bool>()]);
+    /// assert_eq!(report.overwritten, [TypeId::of::<i32>()]);
+    /// ```
+    pub fn insert_report(
+        &mut self,
+        entity: Entity,
+        components: impl DynamicBundle,
+    ) -> Result<InsertReport, NoSuchEntity> {
+        self.flush();
+
+        let loc = self.entities.get(entity)?;
+        let mut added = components.with_ids(|ids| ids.to_vec());
+        let mut overwritten = Vec::new();
+        self.insert_inner(entity, components, loc.archetype, loc, |ptr, ty| unsafe {
+            overwritten.push(ty.id());
+            ty.drop(ptr)
+        });
+        added.retain(|id| !overwritten.contains(id));
+        Ok(InsertReport { added, overwritten })
+    }
+
+    /// Make `entity` have exactly the components in `target`
+    ///
+    /// Like [`insert`](Self::insert), but also drops every component of `entity` whose type doesn't
+    /// occur in `target`, moving the entity directly to its final archetype.
+    ///
+    /// # Example
+    /// ```
+    /// # use hecs::*;
+    /// let mut world = World::new();
+    /// let e = world.spawn((123, "abc"));
+    /// world.reconcile(e, (456, true)).unwrap();
+    /// assert_eq!(*world.get::<&i32>(e).unwrap(), 456);
+    /// assert_eq!(*world.get::<&bool>(e).unwrap(), true);
+    /// assert!(!world.satisfies::<&&str>(e).unwrap());
+    /// ```
+    pub fn reconcile(
+        &mut self,
+        entity: Entity,
+        target: impl DynamicBundle,
+    ) -> Result<(), NoSuchEntity> {
+        self.flush();
+
+        let loc = self.entities.get(entity)?;
+        let intermediate =
+            target.with_ids(|ids| self.drop_components(entity, loc, |id| !ids.contains(&id)));
+        self.insert_inner(entity, target, intermediate, loc, |ptr, ty| unsafe {
+            ty.drop(ptr)
+        });
+        Ok(())
+    }
+
+    /// Drop every component of `entity` whose type is in `ids`
+    pub(crate) fn remove_dynamic(
+        &mut self,
+        entity: Entity,
+        ids: &[TypeId],
+    ) -> Result<(), NoSuchEntity> {
+        self.flush();
+
+        let loc = self.entities.get(entity)?;
+        let intermediate = self.drop_components(entity, loc, |id| ids.contains(&id));
+        self.insert_inner(entity, (), intermediate, loc, |ptr, ty| unsafe {
+            ty.drop(ptr)
+        });
         Ok(())
     }
 
+    /// Drop the components of the entity at `loc` whose types satisfy `doomed`, returning the
+    /// archetype of the remaining components
+    ///
+    /// The entity must be passed to `insert_inner` with the result as its graph origin to be moved
+    /// out of its now partially-dropped storage.
+    fn drop_components(
+        &mut self,
+        entity: Entity,
+        loc: Location,
+        doomed: impl Fn(TypeId) -> bool,
+    ) -> u32 {
+        let source_arch = &self.archetypes.archetypes[loc.archetype as usize];
+        let (removed, retained): (Vec<TypeInfo>, Vec<TypeInfo>) =
+            source_arch.types().iter().partition(|ty| doomed(ty.id()));
+        if removed.is_empty() {
+            return loc.archetype;
+        }
+        unsafe {
+            let types = removed.iter().map(|ty| ty.id());
+            if self.hooks.any(types.clone()) {
+                self.hooks
+                    .fire_remove(entity, source_arch, loc.index, types);
+            }
+            for ty in &removed {
+                let ptr = source_arch
+                    .get_dynamic(ty.id(), ty.layout().size(), loc.index)
+                    .unwrap();
+                ty.drop(ptr.as_ptr());
+            }
+        }
+        let elements = retained.iter().map(|x| x.id()).collect::<Box<_>>();
+        self.archetypes.get(&*elements, move || retained)
+    }
+
+    /// Perform a series of structural changes that are all undone if `f` fails
+    ///
+    /// Operations performed through the [`Transaction`] take effect immediately. If `f` returns
+    /// `Err`, or panics, they are reverted in reverse order before this returns, leaving every
+    /// affected entity with the same handle and components it had beforehand.
+    ///
+    /// # Example
+    /// ```
+    /// # use hecs::*;
+    /// struct Gold(u32);
+    /// struct Sword;
+    ///
+    /// let mut world = World::new();
+    /// let buyer = world.spawn((Gold(10),));
+    /// let seller = world.spawn((Sword,));
+    /// let result = world.transaction(|tx| {
+    ///     tx.remove_one::<Sword>(seller).unwrap();
+    ///     tx.insert_one(buyer, Sword).unwrap();
+    ///     let gold = tx.world().get::<&Gold>(buyer).unwrap().0;
+    ///     if gold < 20 {
+    ///         return Err("not enough gold");
+    ///     }
+    ///     tx.insert_one(buyer, Gold(gold - 20)).unwrap();
+    ///     Ok(())
+    /// });
+    /// assert_eq!(result, Err("not enough gold"));
+    /// assert!(world.satisfies::<&Sword>(seller).unwrap());
+    /// assert!(!world.satisfies::<&Sword>(buyer).unwrap());
+    /// ```
+    pub fn transaction<T, E>(
+        &mut self,
+        f: impl FnOnce(&mut Transaction<'_>) -> Result<T, E>,
+    ) -> Result<T, E> {
+        let mut tx = Transaction::new(self);
+        let result = f(&mut tx);
+        if result.is_ok() {
+            tx.commit();
+        }
+        result
+    }
+
+    /// Add `components` to `entity`, returning the components they replaced
+    ///
+    /// Like [`insert`](Self::insert), but rather than being dropped, components of the same types
+    /// as any in `components` that `entity` already had are moved into the returned
+    /// [`EntityBuilder`]. When replacing a single component, see
+    /// [`replace_one`](Self::replace_one) for convenience.
+    ///
+    /// # Example
+    /// ```
+    /// # use hecs::*;
+    /// let mut world = World::new();
+    /// let e = world.spawn((123, "abc"));
+    /// let old = world.replace(e, (456, true)).unwrap();
+    /// assert_eq!(*old.get::<&i32>().unwrap(), 123);
+    /// assert!(!old.has::<bool>());
+    /// assert_eq!(*world.get::<&i32>(e).unwrap(), 456);
+    /// ```
+    pub fn replace(
+        &mut self,
+        entity: Entity,
+        components: impl DynamicBundle,
+    ) -> Result<EntityBuilder, NoSuchEntity> {
+        self.flush();
+
+        let loc = self.entities.get(entity)?;
+        let mut replaced = EntityBuilder::new();
+        self.insert_inner(entity, components, loc.archetype, loc, |ptr, ty| unsafe {
+            replaced.add_raw(ptr, ty)
+        });
+        Ok(replaced)
+    }
+
+    /// Add `component` to `entity`, returning the `T` it replaced, if any
+    ///
+    /// See [`replace`](Self::replace).
+    pub fn replace_one<T: Component>(
+        &mut self,
+        entity: Entity,
+        component: T,
+    ) -> Result<Option<T>, NoSuchEntity> {
+        self.flush();
+
+        let loc = self.entities.get(entity)?;
+        let mut replaced = None;
+        self.insert_inner(entity, (component,), loc.archetype, loc, |ptr, _| unsafe {
+            replaced = Some(ptr.cast::<T>().read());
+        });
+        Ok(replaced)
+    }
+
     /// The implementation backing [`insert`](Self::insert) exposed so that it can also be used by [`exchange`](Self::exchange).
     ///
     /// Note that `graph_origin` is always equal to `loc.archetype` during insertion. Only for exchange, `graph_origin` identifies
@@ -604,7 +2842,14 @@ impl World {
         components: impl DynamicBundle,
         graph_origin: u32,
         loc: Location,
+        mut replaced: impl FnMut(*mut u8, TypeInfo),
     ) {
+        if !self.sparse.is_empty() {
+            components.with_ids(|ids| self.sparse.assert_dense(ids));
+        }
+        let inserted =
+            components.with_ids(|ids| self.hooks.any(ids.iter().copied()).then(|| ids.to_vec()));
+
         let target_storage;
         let target = match components.key() {
             None => {
@@ -617,69 +2862,434 @@ impl World {
                     let target = self.archetypes.get_insert_target(graph_origin, &components);
                     entry.insert(target)
                 }
-            },
-        };
+            },
+        };
+
+        let source_arch = &mut self.archetypes.archetypes[loc.archetype as usize];
+        unsafe {
+            if inserted.is_some() && !target.replaced.is_empty() {
+                let types = target.replaced.iter().map(|ty| ty.id());
+                self.hooks
+                    .fire_replace(entity, source_arch, loc.index, types);
+            }
+
+            // Dispose of the components we're overwriting
+            for &ty in &target.replaced {
+                let ptr = source_arch
+                    .get_dynamic(ty.id(), ty.layout().size(), loc.index)
+                    .unwrap();
+                replaced(ptr.as_ptr(), ty);
+            }
+
+            if target.index == loc.archetype {
+                // Update components in the current archetype
+                let arch = &mut self.archetypes.archetypes[loc.archetype as usize];
+                components.put(|ptr, ty| {
+                    arch.put_dynamic(ptr, ty.id(), ty.layout().size(), loc.index);
+                    arch.mark_added_dynamic(ty.id());
+                });
+                if let Some(inserted) = inserted {
+                    self.hooks.fire_insert(entity, arch, loc.index, inserted);
+                }
+                return;
+            }
+
+            let (source_arch, target_arch) = index2(
+                &mut self.archetypes.archetypes,
+                loc.archetype as usize,
+                target.index as usize,
+            );
+
+            // Allocate storage in the archetype and update the entity's location to address it
+            let target_index = target_arch.allocate(entity.id);
+            let meta = &mut self.entities.meta[entity.id as usize];
+            meta.location.archetype = target.index;
+            meta.location.index = target_index;
+
+            // Move the new components
+            components.put(|ptr, ty| {
+                target_arch.put_dynamic(ptr, ty.id(), ty.layout().size(), target_index);
+                target_arch.mark_added_dynamic(ty.id());
+            });
+
+            // Move the components we're keeping
+            for &ty in &target.retained {
+                let src = source_arch
+                    .get_dynamic(ty.id(), ty.layout().size(), loc.index)
+                    .unwrap();
+                target_arch.put_dynamic(src.as_ptr(), ty.id(), ty.layout().size(), target_index);
+                let state = source_arch.get_state_dynamic(ty.id()).unwrap();
+                target_arch.inherit_changed(ty.id(), source_arch.changed_tick(state));
+            }
+
+            // Free storage in the old archetype
+            if let Some(moved) = source_arch.remove(loc.index, false) {
+                self.entities.meta[moved as usize].location.index = loc.index;
+            }
+
+            if let Some(inserted) = inserted {
+                self.hooks
+                    .fire_insert(entity, target_arch, target_index, inserted);
+            }
+        }
+    }
+
+    /// Add `component` to `entity`
+    ///
+    /// See [`insert`](Self::insert). If `component`'s type was passed to
+    /// [`set_sparse`](Self::set_sparse), it's stored in sparse storage instead, so `entity` doesn't
+    /// move to a different archetype.
+    pub fn insert_one<T: Component>(
+        &mut self,
+        entity: Entity,
+        component: T,
+    ) -> Result<(), NoSuchEntity> {
+        if let Some(set) = self.sparse.get_mut::<T>() {
+            self.entities.get(entity)?;
+            set.insert(entity, component);
+            return Ok(());
+        }
+        self.insert(entity, (component,))
+    }
+
+    /// Add `component` to `entity` unless it already has a `T`
+    ///
+    /// Returns whether `component` was inserted. If `entity` already has a `T`, `component` is
+    /// dropped, leaving the existing value in place and firing no hooks, which makes this cheaper
+    /// than [`insert_one`](Self::insert_one) for code that repeatedly ensures a marker is present.
+    ///
+    /// # Example
+    /// ```
+    /// # use hecs::*;
+    /// struct Tagged(u32);
+    /// let mut world = World::new();
+    /// let e = world.spawn(());
+    /// assert!(world.insert_one_if_absent(e, Tagged(1)).unwrap());
+    /// assert!(!world.insert_one_if_absent(e, Tagged(2)).unwrap());
+    /// assert_eq!(world.get::<&Tagged>(e).unwrap().0, 1);
+    /// ```
+    pub fn insert_one_if_absent<T: Component>(
+        &mut self,
+        entity: Entity,
+        component: T,
+    ) -> Result<bool, NoSuchEntity> {
+        self.flush();
+        let loc = self.entities.get(entity)?;
+        let present = match self.sparse.get::<T>() {
+            Some(set) => set.get(entity.id).is_some(),
+            None => self.archetypes.archetypes[loc.archetype as usize].has::<T>(),
+        };
+        if present {
+            return Ok(false);
+        }
+        self.insert_one(entity, component)?;
+        Ok(true)
+    }
+
+    /// Uniquely borrow `entity`'s `T` component, first inserting `f()` if it has none
+    ///
+    /// Moves `entity` to a new archetype only if `T` was absent and isn't
+    /// [sparse](Self::set_sparse).
+    ///
+    /// # Example
+    /// ```
+    /// # use hecs::*;
+    /// let mut world = World::new();
+    /// let e = world.spawn(());
+    /// *world.get_or_insert_with(e, || 0).unwrap() += 1;
+    /// *world.get_or_insert_with(e, || 0).unwrap() += 1;
+    /// assert_eq!(*world.get::<&i32>(e).unwrap(), 2);
+    /// ```
+    pub fn get_or_insert_with<T: Component>(
+        &mut self,
+        entity: Entity,
+        f: impl FnOnce() -> T,
+    ) -> Result<&mut T, NoSuchEntity> {
+        self.flush();
+        let loc = self.entities.get(entity)?;
+        if self.is_sparse::<T>() {
+            let set = self.sparse.get_mut::<T>().unwrap();
+            if set.get(entity.id).is_none() {
+                set.insert(entity, f());
+            }
+            return Ok(set.get_mut(entity.id).unwrap());
+        }
+        if !self.archetypes.archetypes[loc.archetype as usize].has::<T>() {
+            self.insert_one(entity, f())?;
+        }
+        Ok(self.query_one_mut::<&mut T>(entity).unwrap())
+    }
+
+    /// Enable or disable `entity`
+    ///
+    /// Disabling adds a [`Disabled`] component, causing queries to skip the entity without
+    /// discarding its other components. Enabling removes it. Has no effect if `entity` is already
+    /// in the requested state.
+    pub fn set_enabled(&mut self, entity: Entity, enabled: bool) -> Result<(), NoSuchEntity> {
+        if self.satisfies::<&Disabled>(entity)? != enabled {
+            return Ok(());
+        }
+        if enabled {
+            self.remove_one::<Disabled>(entity).unwrap();
+            Ok(())
+        } else {
+            self.insert_one(entity, Disabled)
+        }
+    }
+
+    /// Add a clone of `value` to each of `entities`
+    ///
+    /// Equivalent to calling [`insert_one`](Self::insert_one) for each entity, but entities are
+    /// grouped by archetype so that the destination of each group is looked up, and its storage
+    /// grown, only once. Entities that don't exist, and repeated entities, are skipped. Returns the
+    /// number of entities that `value` was inserted into.
+    ///
+    /// # Example
+    /// ```
+    /// # use hecs::*;
+    /// #[derive(Clone)]
+    /// struct Marked;
+    /// let mut world = World::new();
+    /// let entities = world.spawn_batch((0..100).map(|i| (i,))).collect::<Vec<_>>();
+    /// let dead = world.spawn(());
+    /// world.despawn(dead).unwrap();
+    /// let inserted = world.insert_batch(entities.iter().copied().chain([dead]), Marked);
+    /// assert_eq!(inserted, 100);
+    /// assert_eq!(world.query::<&Marked>().iter().count(), 100);
+    /// ```
+    pub fn insert_batch<I, T>(&mut self, entities: I, value: T) -> usize
+    where
+        I: IntoIterator<Item = Entity>,
+        T: Component + Clone,
+    {
+        self.flush();
+        let groups = self.group_by_archetype(entities);
+        if let Some(set) = self.sparse.get_mut::<T>() {
+            for &(_, entity) in &groups {
+                set.insert(entity, value.clone());
+            }
+            return groups.len();
+        }
+        let mut value = Some(value);
+        let mut remaining = groups.len();
+        self.insert_groups(&groups, || {
+            remaining -= 1;
+            match remaining {
+                0 => value.take().unwrap(),
+                _ => value.as_ref().unwrap().clone(),
+            }
+        });
+        groups.len()
+    }
+
+    /// Add `T::default()` to every entity matching `Q` that lacks a `T`
+    ///
+    /// The bulk counterpart of [`get_or_insert_with`](Self::get_or_insert_with), useful e.g. when a
+    /// newly introduced system requires a component that existing entities lack. Entities are
+    /// grouped by archetype as in [`insert_batch`](Self::insert_batch), so an archetype whose
+    /// entities all receive a `T` is moved at once. [`Disabled`] entities are skipped unless `Q`
+    /// refers to them. Returns the number of entities that a `T` was inserted into.
+    ///
+    /// # Example
+    /// ```
+    /// # use hecs::*;
+    /// #[derive(Default)]
+    /// struct Score(u32);
+    /// let mut world = World::new();
+    /// world.spawn_batch((0..10).map(|i| (i,)));
+    /// let a = world.spawn((10, Score(5)));
+    /// world.spawn(("unrelated",));
+    /// assert_eq!(world.ensure_all::<Score, &i32>(), 10);
+    /// assert_eq!(world.ensure_all::<Score, &i32>(), 0);
+    /// assert_eq!(world.get::<&Score>(a).unwrap().0, 5);
+    /// ```
+    pub fn ensure_all<T: Component + Default, Q: Query>(&mut self) -> usize {
+        let entities = self
+            .query_mut::<Without<Q, &T>>()
+            .into_iter()
+            .map(|(entity, _)| entity)
+            .collect::<Vec<_>>();
+        self.flush();
+        if let Some(set) = self.sparse.get_mut::<T>() {
+            let mut inserted = 0;
+            for entity in entities {
+                if set.get(entity.id).is_none() {
+                    set.insert(entity, T::default());
+                    inserted += 1;
+                }
+            }
+            return inserted;
+        }
+        let groups = self.group_by_archetype(entities);
+        self.insert_groups(&groups, T::default);
+        groups.len()
+    }
 
-        let source_arch = &mut self.archetypes.archetypes[loc.archetype as usize];
-        unsafe {
-            // Drop the components we're overwriting
-            for &ty in &target.replaced {
-                let ptr = source_arch
-                    .get_dynamic(ty.id(), ty.layout().size(), loc.index)
-                    .unwrap();
-                ty.drop(ptr.as_ptr());
+    /// Insert a `T` produced by `next` into each of `groups`, sorted by archetype as by
+    /// `group_by_archetype`
+    fn insert_groups<T: Component>(
+        &mut self,
+        groups: &[(u32, Entity)],
+        mut next: impl FnMut() -> T,
+    ) {
+        self.sparse.assert_dense(&[TypeId::of::<T>()]);
+        for group in archetype_runs(groups) {
+            let source = group[0].0;
+            let first = (next(),);
+            let target = self.insert_target(source, &first);
+            if target != source && self.covers_archetype(group) {
+                // Move the whole archetype at once, producing every component beforehand so that
+                // a panic can't leave the target archetype partially initialized
+                let mut values = Vec::with_capacity(group.len());
+                values.push(first.0);
+                values.extend((1..group.len()).map(|_| next()));
+                let (source_arch, target_arch) = index2(
+                    &mut self.archetypes.archetypes,
+                    source as usize,
+                    target as usize,
+                );
+                unsafe {
+                    let base = source_arch.move_all_to(target_arch);
+                    for (index, mut component) in (base..target_arch.len()).zip(values) {
+                        target_arch.put_dynamic(
+                            (&mut component as *mut T).cast(),
+                            TypeId::of::<T>(),
+                            mem::size_of::<T>(),
+                            index,
+                        );
+                        mem::forget(component);
+                        let id = target_arch.entity_id(index);
+                        self.entities.meta[id as usize].location = Location {
+                            archetype: target,
+                            index,
+                        };
+                    }
+                    target_arch.mark_added_dynamic(TypeId::of::<T>());
+                    if self.hooks.any([TypeId::of::<T>()]) {
+                        for index in base..target_arch.len() {
+                            let id = target_arch.entity_id(index);
+                            let entity = self.entities.resolve_unknown_gen(id);
+                            let types = [TypeId::of::<T>()];
+                            self.hooks.fire_insert(entity, target_arch, index, types);
+                        }
+                    }
+                }
+                continue;
             }
 
-            if target.index == loc.archetype {
-                // Update components in the current archetype
-                let arch = &mut self.archetypes.archetypes[loc.archetype as usize];
-                components.put(|ptr, ty| {
-                    arch.put_dynamic(ptr, ty.id(), ty.layout().size(), loc.index);
+            self.archetypes.archetypes[target as usize].reserve(group.len() as u32);
+            let mut first = Some(first);
+            for &(_, entity) in group {
+                let component = first.take().unwrap_or_else(|| (next(),));
+                let loc = self.entities.get(entity).unwrap();
+                self.insert_inner(entity, component, loc.archetype, loc, |ptr, ty| unsafe {
+                    ty.drop(ptr)
                 });
-                return;
             }
+        }
+    }
 
-            let (source_arch, target_arch) = index2(
-                &mut self.archetypes.archetypes,
-                loc.archetype as usize,
-                target.index as usize,
-            );
-
-            // Allocate storage in the archetype and update the entity's location to address it
-            let target_index = target_arch.allocate(entity.id);
-            let meta = &mut self.entities.meta[entity.id as usize];
-            meta.location.archetype = target.index;
-            meta.location.index = target_index;
-
-            // Move the new components
-            components.put(|ptr, ty| {
-                target_arch.put_dynamic(ptr, ty.id(), ty.layout().size(), target_index);
-            });
+    /// Remove and drop the `T` component of each of `entities`
+    ///
+    /// Equivalent to calling [`remove_one`](Self::remove_one) for each entity, but grouped by
+    /// archetype like [`insert_batch`](Self::insert_batch). Entities that don't exist or lack a `T`
+    /// are skipped. Returns the number of components removed.
+    pub fn remove_batch<T: Component>(
+        &mut self,
+        entities: impl IntoIterator<Item = Entity>,
+    ) -> usize {
+        self.flush();
+        if let Some(set) = self.sparse.get_mut::<T>() {
+            return entities
+                .into_iter()
+                .filter(|&entity| self.entities.get(entity).is_ok())
+                .filter(|entity| set.remove(entity.id).is_some())
+                .count();
+        }
+        let groups = self.group_by_archetype(entities);
+        let mut removed = 0;
+        for group in archetype_runs(&groups) {
+            let source = group[0].0;
+            if !self.archetypes.archetypes[source as usize].has::<T>() {
+                continue;
+            }
+            let target =
+                Self::remove_target::<(T,)>(&mut self.archetypes, &mut self.remove_edges, source);
+            if self.covers_archetype(group) {
+                // Move the whole archetype at once
+                let (source_arch, target_arch) = index2(
+                    &mut self.archetypes.archetypes,
+                    source as usize,
+                    target as usize,
+                );
+                unsafe {
+                    if self.hooks.any([TypeId::of::<T>()]) {
+                        for index in 0..source_arch.len() {
+                            let entity = self
+                                .entities
+                                .resolve_unknown_gen(source_arch.entity_id(index));
+                            let types = [TypeId::of::<T>()];
+                            self.hooks.fire_remove(entity, source_arch, index, types);
+                        }
+                    }
+                    let count = source_arch.len();
+                    let state = source_arch.get_state_dynamic(TypeId::of::<T>()).unwrap();
+                    let column = source_arch.get_base_dynamic(state).as_ptr().cast::<T>();
+                    // Entities leave before their `T`s are dropped, so a panicking `drop` can only
+                    // leak the remainder
+                    let base = source_arch.move_all_to(target_arch);
+                    for index in base..target_arch.len() {
+                        let id = target_arch.entity_id(index);
+                        self.entities.meta[id as usize].location = Location {
+                            archetype: target,
+                            index,
+                        };
+                    }
+                    removed += count as usize;
+                    ptr::drop_in_place(ptr::slice_from_raw_parts_mut(column, count as usize));
+                }
+                continue;
+            }
 
-            // Move the components we're keeping
-            for &ty in &target.retained {
-                let src = source_arch
-                    .get_dynamic(ty.id(), ty.layout().size(), loc.index)
-                    .unwrap();
-                target_arch.put_dynamic(src.as_ptr(), ty.id(), ty.layout().size(), target_index)
+            self.archetypes.archetypes[target as usize].reserve(group.len() as u32);
+            for &(_, entity) in group {
+                self.remove_one::<T>(entity).unwrap();
             }
+            removed += group.len();
+        }
+        removed
+    }
 
-            // Free storage in the old archetype
-            if let Some(moved) = source_arch.remove(loc.index, false) {
-                self.entities.meta[moved as usize].location.index = loc.index;
+    /// Find the archetype that inserting `components` into an entity in `origin` would produce
+    fn insert_target(&mut self, origin: u32, components: &impl DynamicBundle) -> u32 {
+        match components.key() {
+            None => self.archetypes.get_insert_target(origin, components).index,
+            Some(key) => {
+                let archetypes = &mut self.archetypes;
+                self.insert_edges
+                    .entry((origin, key))
+                    .or_insert_with(|| archetypes.get_insert_target(origin, components))
+                    .index
             }
         }
     }
 
-    /// Add `component` to `entity`
-    ///
-    /// See [`insert`](Self::insert).
-    pub fn insert_one(
-        &mut self,
-        entity: Entity,
-        component: impl Component,
-    ) -> Result<(), NoSuchEntity> {
-        self.insert(entity, (component,))
+    /// Whether `group`, a run of distinct entities in the same archetype, contains every entity in
+    /// that archetype
+    fn covers_archetype(&self, group: &[(u32, Entity)]) -> bool {
+        group.len() == self.archetypes.archetypes[group[0].0 as usize].len() as usize
+    }
+
+    /// Pair each distinct live entity with its current archetype, sorted by archetype
+    fn group_by_archetype(&self, entities: impl IntoIterator<Item = Entity>) -> Vec<(u32, Entity)> {
+        let mut seen = HashSet::new();
+        let mut entities = entities
+            .into_iter()
+            .filter_map(|entity| Some((self.entities.get(entity).ok()?.archetype, entity)))
+            .filter(|&(_, entity)| seen.insert(entity.id))
+            .collect::<Vec<_>>();
+        entities.sort_by_key(|x| x.0);
+        entities
     }
 
     /// Remove components from `entity`
@@ -703,6 +3313,7 @@ impl World {
     /// ```
     pub fn remove<T: Bundle + 'static>(&mut self, entity: Entity) -> Result<T, ComponentError> {
         self.flush();
+        self.fire_remove_bundle::<T>(entity)?;
 
         // Gather current metadata
         let loc = self.entities.get_mut(entity)?;
@@ -730,10 +3341,11 @@ impl World {
             loc.archetype = target;
             loc.index = target_index;
             if let Some(moved) = unsafe {
-                source_arch.move_to(old_index, |src, ty, size| {
+                source_arch.move_to(old_index, |src, ty, size, changed| {
                     // Only move the components present in the target archetype, i.e. the non-removed ones.
                     if let Some(dst) = target_arch.get_dynamic(ty, size, target_index) {
                         ptr::copy_nonoverlapping(src, dst.as_ptr(), size);
+                        target_arch.inherit_changed(ty, changed);
                     }
                 })
             } {
@@ -744,6 +3356,24 @@ impl World {
         Ok(bundle)
     }
 
+    /// Invoke removal hooks for the `T` components of `entity`, if it has all of them
+    fn fire_remove_bundle<T: Bundle>(&mut self, entity: Entity) -> Result<(), NoSuchEntity> {
+        if !T::with_static_ids(|ids| self.hooks.any(ids.iter().copied())) {
+            return Ok(());
+        }
+        let loc = self.entities.get(entity)?;
+        let archetype = &self.archetypes.archetypes[loc.archetype as usize];
+        T::with_static_ids(|ids| {
+            if ids.iter().all(|&id| archetype.has_dynamic(id)) {
+                unsafe {
+                    let types = ids.iter().copied();
+                    self.hooks.fire_remove(entity, archetype, loc.index, types);
+                }
+            }
+        });
+        Ok(())
+    }
+
     fn remove_target<T: Bundle + 'static>(
         archetypes: &mut ArchetypeSet,
         remove_edges: &mut IndexTypeIdMap<u32>,
@@ -769,8 +3399,15 @@ impl World {
 
     /// Remove the `T` component from `entity`
     ///
-    /// See [`remove`](Self::remove).
+    /// See [`remove`](Self::remove). If `T` was passed to [`set_sparse`](Self::set_sparse), it's
+    /// taken from sparse storage instead.
     pub fn remove_one<T: Component>(&mut self, entity: Entity) -> Result<T, ComponentError> {
+        if let Some(set) = self.sparse.get_mut::<T>() {
+            self.entities.get(entity)?;
+            return set
+                .remove(entity.id)
+                .ok_or_else(|| MissingComponent::new::<T>().into());
+        }
         self.remove::<(T,)>(entity).map(|(x,)| x)
     }
 
@@ -778,12 +3415,15 @@ impl World {
     ///
     /// This has the same effect as calling [`remove::<S>`](Self::remove) and then [`insert::<T>`](Self::insert),
     /// but is more efficient as the intermediate archetype after removal but before insertion is skipped.
+    /// A component type present in both `S` and `T` is replaced: its old value is returned, and the
+    /// value from `components` takes its place.
     pub fn exchange<S: Bundle + 'static, T: DynamicBundle>(
         &mut self,
         entity: Entity,
         components: T,
     ) -> Result<S, ComponentError> {
         self.flush();
+        self.fire_remove_bundle::<S>(entity)?;
 
         // Gather current metadata
         let loc = self.entities.get(entity)?;
@@ -799,7 +3439,9 @@ impl World {
         let intermediate =
             Self::remove_target::<S>(&mut self.archetypes, &mut self.remove_edges, loc.archetype);
 
-        self.insert_inner(entity, components, intermediate, loc);
+        self.insert_inner(entity, components, intermediate, loc, |ptr, ty| unsafe {
+            ty.drop(ptr)
+        });
 
         Ok(bundle)
     }
@@ -862,14 +3504,131 @@ impl World {
         self.archetypes_inner().iter()
     }
 
+    /// The archetype `entity` is stored in, if it exists
+    ///
+    /// Entities with identical component types share an archetype, so this can be used to group
+    /// entities for columnar processing, e.g. with [`archetype`](Self::archetype). Returns `None`
+    /// for entities [reserved](Self::reserve_entity) since the last [`flush`](Self::flush), which
+    /// aren't yet stored in any archetype.
+    ///
+    /// # Example
+    /// ```
+    /// # use hecs::*;
+    /// let mut world = World::new();
+    /// let a = world.spawn((1, true));
+    /// let b = world.spawn((2, false));
+    /// let c = world.spawn((3,));
+    /// let id = world.archetype_id(a).unwrap();
+    /// assert_eq!(world.archetype_id(b), Some(id));
+    /// assert_ne!(world.archetype_id(c), Some(id));
+    /// assert_eq!(world.archetype(id).unwrap().len(), 2);
+    /// ```
+    pub fn archetype_id(&self, entity: Entity) -> Option<ArchetypeId> {
+        let loc = self.entities.get(entity).ok()?;
+        if loc.index == u32::MAX {
+            return None;
+        }
+        Some(ArchetypeId(loc.archetype))
+    }
+
+    /// The archetype identified by `id`, if `id` was obtained from this world
+    pub fn archetype(&self, id: ArchetypeId) -> Option<&Archetype> {
+        self.archetypes_inner().get(id.0 as usize)
+    }
+
+    /// The entities in `archetype`, in the same order as its components
+    ///
+    /// Safe alternative to calling [`find_entity_from_id`](Self::find_entity_from_id) on each of
+    /// [`Archetype::ids`].
+    ///
+    /// # Panics
+    /// Panics if `archetype` was not obtained from this world.
+    pub fn archetype_entities<'a>(
+        &'a self,
+        archetype: &'a Archetype,
+    ) -> impl ExactSizeIterator<Item = Entity> + 'a {
+        assert!(
+            self.archetypes_inner()
+                .as_ptr_range()
+                .contains(&(archetype as *const Archetype)),
+            "archetype belongs to a different world"
+        );
+        archetype.ids().iter().map(move |&id| Entity {
+            id,
+            generation: self.entities.meta[id as usize].generation,
+        })
+    }
+
+    /// Visit every non-empty archetype along with the entities stored in it
+    ///
+    /// Higher-level alternative to [`archetypes`](Self::archetypes) convenient for bulk export,
+    /// where each group of entities with identical component types can be handled at once.
+    ///
+    /// # Example
+    /// ```
+    /// # use hecs::*;
+    /// let mut world = World::new();
+    /// let a = world.spawn((123, true));
+    /// let b = world.spawn((456, false));
+    /// world.spawn(("abc",));
+    /// let view = world
+    ///     .iter_archetypes()
+    ///     .find(|view| view.has::<i32>())
+    ///     .unwrap();
+    /// assert_eq!(view.entities().collect::<Vec<_>>(), [a, b]);
+    /// assert_eq!(*view.get_column::<i32>().unwrap(), [123, 456]);
+    /// ```
+    pub fn iter_archetypes(&self) -> impl Iterator<Item = ArchetypeView<'_>> + '_ {
+        self.archetypes_inner()
+            .iter()
+            .filter(|archetype| !archetype.is_empty())
+            .map(move |archetype| ArchetypeView {
+                meta: self.entities_meta(),
+                archetype,
+            })
+    }
+
     /// Despawn `entity`, yielding a [`DynamicBundle`] of its components
     ///
     /// Useful for moving entities between worlds.
     pub fn take(&mut self, entity: Entity) -> Result<TakenEntity<'_>, NoSuchEntity> {
+        self.take_inner(entity, None)
+    }
+
+    /// Like [`take`](Self::take), but moves `entity`'s [sparse](Self::set_sparse) components into
+    /// `sparse` rather than dropping them
+    pub(crate) fn take_with_sparse(
+        &mut self,
+        entity: Entity,
+        sparse: &mut SparseComponents,
+    ) -> Result<TakenEntity<'_>, NoSuchEntity> {
+        self.take_inner(entity, Some(sparse))
+    }
+
+    /// Give `entity` sparse components taken by [`take_with_sparse`](Self::take_with_sparse)
+    pub(crate) fn restore_sparse(&mut self, entity: Entity, sparse: SparseComponents) {
+        self.sparse.restore_entity(entity, sparse);
+    }
+
+    fn take_inner(
+        &mut self,
+        entity: Entity,
+        sparse: Option<&mut SparseComponents>,
+    ) -> Result<TakenEntity<'_>, NoSuchEntity> {
         self.flush();
         let loc = self.entities.get(entity)?;
+        if !self.sparse.is_empty() {
+            match sparse {
+                Some(sparse) => *sparse = self.sparse.take_entity(entity.id),
+                None => self.sparse.remove_entity(entity.id),
+            }
+        }
         let archetype = &mut self.archetypes.archetypes[loc.archetype as usize];
         unsafe {
+            if !self.hooks.is_empty() {
+                let types = archetype.type_ids().iter().copied();
+                self.hooks.fire_remove(entity, archetype, loc.index, types);
+            }
             Ok(TakenEntity::new(
                 &mut self.entities,
                 entity,
@@ -879,6 +3638,127 @@ impl World {
         }
     }
 
+    /// Despawn every entity as the returned iterator is advanced, yielding their components
+    ///
+    /// Bulk counterpart to [`take`](Self::take). Components are moved, not cloned, into an
+    /// [`EntityBuilder`] per entity, which can be [`build`](EntityBuilder::build)t to spawn the
+    /// entity elsewhere. Entities not yet yielded when the iterator is dropped remain in the world.
+    /// Includes [`Disabled`](crate::Disabled) entities.
+    ///
+    /// # Example
+    /// ```
+    /// # use hecs::*;
+    /// let mut world = World::new();
+    /// world.spawn((1, true));
+    /// world.spawn((2,));
+    /// let mut other = World::new();
+    /// for (_, mut components) in world.drain() {
+    ///     other.spawn(components.build());
+    /// }
+    /// assert!(world.is_empty());
+    /// assert_eq!(other.len(), 2);
+    /// ```
+    pub fn drain(&mut self) -> Drain<'_> {
+        self.drain_inner(|_| true)
+    }
+
+    /// Like [`drain`](Self::drain), but only yields entities matched by `Q`
+    ///
+    /// Like [`despawn_matching`](Self::despawn_matching), components are not borrowed, and
+    /// [`Disabled`](crate::Disabled) entities are skipped unless `Q` includes them.
+    ///
+    /// # Example
+    /// ```
+    /// # use hecs::*;
+    /// let mut world = World::new();
+    /// let a = world.spawn((1, true));
+    /// let b = world.spawn((2,));
+    /// let drained = world.drain_matching::<&bool>().map(|(e, _)| e).collect::<Vec<_>>();
+    /// assert_eq!(drained, [a]);
+    /// assert!(!world.contains(a));
+    /// assert!(world.contains(b));
+    /// ```
+    pub fn drain_matching<Q: Query>(&mut self) -> Drain<'_> {
+        self.drain_inner(|x| prepare_iter::<Q::Fetch>(x).is_some())
+    }
+
+    fn drain_inner(&mut self, matches: fn(&Archetype) -> bool) -> Drain<'_> {
+        self.flush();
+        Drain {
+            world: self,
+            archetype: 0,
+            matches,
+        }
+    }
+
+    /// Release storage beyond that needed for the current entities and components
+    ///
+    /// Worlds never otherwise give memory back, so storage sized for a transient spike in entity
+    /// count remains allocated. Compare [`MemoryReport::allocated_bytes`] with
+    /// [`MemoryReport::used_bytes`] from [`memory_report`](Self::memory_report) to decide when this
+    /// is worthwhile. Subsequent spawns into shrunk archetypes must reallocate, and entity IDs are
+    /// never reclaimed, so the metadata of despawned entities is retained.
+    ///
+    /// # Example
+    /// ```
+    /// # use hecs::*;
+    /// let mut world = World::new();
+    /// let entities = world.spawn_batch((0..1000).map(|i| (i,))).collect::<Vec<_>>();
+    /// for &entity in &entities[10..] {
+    ///     world.despawn(entity).unwrap();
+    /// }
+    /// let before = world.memory_report().allocated_bytes();
+    /// world.shrink_to_fit();
+    /// let report = world.memory_report();
+    /// assert!(report.allocated_bytes() < before);
+    /// assert_eq!(*world.get::<&i32>(entities[3]).unwrap(), 3);
+    /// ```
+    pub fn shrink_to_fit(&mut self) {
+        self.flush();
+        self.entities.shrink_to_fit();
+        for archetype in &mut self.archetypes.archetypes {
+            archetype.shrink_to_fit();
+        }
+    }
+
+    /// Like [`shrink_to_fit`](Self::shrink_to_fit), but only for archetypes matched by `Q`
+    ///
+    /// Useful for reclaiming memory from the components of a particular kind of entity without
+    /// disturbing the capacity reserved for others. Like
+    /// [`despawn_matching`](Self::despawn_matching), components are not borrowed, and
+    /// [`Disabled`](crate::Disabled) entities are skipped unless `Q` includes them.
+    pub fn shrink_to_fit_matching<Q: Query>(&mut self) {
+        for archetype in &mut self.archetypes.archetypes {
+            if prepare_iter::<Q::Fetch>(archetype).is_some() {
+                archetype.shrink_to_fit();
+            }
+        }
+    }
+
+    /// Measure the memory occupied by entities and components, by archetype
+    ///
+    /// # Example
+    /// ```
+    /// # use hecs::*;
+    /// let mut world = World::new();
+    /// world.spawn_batch((0..100).map(|i| (i as u64,)));
+    /// let report = world.memory_report();
+    /// let total = report.components().iter().map(|c| c.used_bytes).sum::<usize>();
+    /// assert_eq!(total, 100 * 8);
+    /// assert!(report.allocated_bytes() >= report.used_bytes());
+    /// ```
+    pub fn memory_report(&self) -> MemoryReport {
+        MemoryReport {
+            entity_bytes: self.entities.meta.capacity() * mem::size_of::<EntityMeta>(),
+            archetypes: self
+                .archetypes
+                .archetypes
+                .iter()
+                .map(|x| x.memory_usage())
+                .collect(),
+        }
+    }
+
     /// Returns a distinct value after `archetypes` is changed
     ///
     /// Store the current value after deriving information from [`archetypes`](Self::archetypes),
@@ -901,12 +3781,232 @@ impl World {
         ArchetypesGeneration(self.archetypes.generation())
     }
 
+    /// Names registered for component types
+    pub fn type_name_registry(&self) -> &TypeNameRegistry {
+        &self.type_names
+    }
+
+    /// Mutable access to the names registered for component types
+    pub fn type_name_registry_mut(&mut self) -> &mut TypeNameRegistry {
+        &mut self.type_names
+    }
+
+    /// Record how to store and drop the component type described by `ty`, for types that have no
+    /// Rust generic available, such as those defined by dynamically loaded plugins
+    ///
+    /// Registration isn't required to store such components: every [`TypeInfo`] handed to
+    /// [`RuntimeBundle`](crate::RuntimeBundle) or [`ColumnBatchType`] carries its own layout and
+    /// destructor, which the world uses to drop each component exactly once, whether it's replaced,
+    /// despawned, cleared, or dropped with the world. Registering a type lets code that
+    /// only knows its `TypeId` recover the `TypeInfo` with
+    /// [`dynamic_component`](Self::dynamic_component). Replaces any previous registration.
+    ///
+    /// # Panics
+    /// Panics if components of the same `TypeId` but a different layout are already stored.
+    ///
+    /// # Example
+    /// ```
+    /// # use hecs::*;
+    /// # use std::{alloc::Layout, any::TypeId, mem::ManuallyDrop};
+    /// struct PluginKey;
+    /// unsafe fn drop_string(x: *mut u8) {
+    ///     x.cast::<String>().drop_in_place();
+    /// }
+    /// let id = TypeId::of::<PluginKey>();
+    /// let mut world = World::new();
+    /// world.register_dynamic_component(TypeInfo::from_parts(id, Layout::new::<String>(), drop_string));
+    ///
+    /// let mut value = ManuallyDrop::new(String::from("loaded"));
+    /// let mut bundle = RuntimeBundle::new();
+    /// unsafe {
+    ///     let ty = world.dynamic_component(id).unwrap();
+    ///     bundle.push_erased((&mut *value as *mut String).cast(), ty);
+    /// }
+    /// let e = world.spawn(bundle);
+    /// assert!(world.entity(e).unwrap().component_types().any(|x| x == id));
+    /// world.despawn(e).unwrap(); // drops the string
+    /// ```
+    pub fn register_dynamic_component(&mut self, ty: TypeInfo) {
+        for archetype in &self.archetypes.archetypes {
+            if let Some(existing) = archetype.types().iter().find(|x| x.id() == ty.id()) {
+                assert_eq!(
+                    existing.layout(),
+                    ty.layout(),
+                    "layout conflicts with stored components of the same type"
+                );
+            }
+        }
+        self.dynamic_types.insert(ty.id(), ty);
+    }
+
+    /// The description of the component type identified by `id` recorded by
+    /// [`register_dynamic_component`](Self::register_dynamic_component), if any
+    pub fn dynamic_component(&self, id: TypeId) -> Option<TypeInfo> {
+        self.dynamic_types.get(&id).copied()
+    }
+
+    /// Precompute which archetypes contain every component type in `B`, for fast membership tests
+    ///
+    /// Useful when an entity's component set is tested far more often than its components are
+    /// read. See [`PresenceMask`].
+    ///
+    /// # Example
+    /// ```
+    /// # use hecs::*;
+    /// let mut world = World::new();
+    /// let a = world.spawn((123, true));
+    /// let b = world.spawn((456,));
+    /// let mask = world.presence_mask::<(i32, bool)>();
+    /// assert!(mask.contains(&world, a));
+    /// assert!(!mask.contains(&world, b));
+    /// ```
+    pub fn presence_mask<B: Bundle>(&self) -> PresenceMask {
+        let mut mask = PresenceMask {
+            memo: (0, 0),
+            types: B::with_static_ids(|ids| ids.to_vec()),
+            archetypes: Vec::new(),
+        };
+        mask.refresh(self);
+        mask
+    }
+
+    /// The archetypes created since [`archetypes_generation`](Self::archetypes_generation)
+    /// returned `generation`
+    ///
+    /// Archetypes are never destroyed, and new archetypes are appended to the end of
+    /// [`archetypes`](Self::archetypes), so information derived from the archetypes can be kept up
+    /// to date by visiting only those returned here, then storing the current generation.
+    ///
+    /// # Example
+    /// ```
+    /// # use hecs::*;
+    /// let mut world = World::new();
+    /// world.spawn((123,));
+    /// let generation = world.archetypes_generation();
+    /// world.spawn((456,));
+    /// assert_eq!(world.archetype_changes_since(generation).len(), 0);
+    /// world.spawn((789, "abc"));
+    /// let mut new = world.archetype_changes_since(generation);
+    /// assert!(new.next().unwrap().has::<&str>());
+    /// assert!(new.next().is_none());
+    /// ```
+    pub fn archetype_changes_since(
+        &self,
+        generation: ArchetypesGeneration,
+    ) -> impl ExactSizeIterator<Item = &'_ Archetype> + '_ {
+        self.archetypes_inner()
+            .get(generation.0 as usize..)
+            .unwrap_or(&[])
+            .iter()
+    }
+
+    /// Advance the change tick, returning a value to later pass to [`QueryBorrow::since`]
+    ///
+    /// Queries filtered with [`Changed`](crate::Changed) or [`Added`](crate::Added) since the
+    /// returned tick will match only components changed or inserted after this call. Each world
+    /// counts its own change ticks, so a tick is meaningless to other worlds, except to clones made
+    /// after it was obtained. Ticks are compared with wrapping arithmetic, so a tick should be
+    /// retained for no more than about 2 billion increments. Components left untouched for a
+    /// billion or so increments are considered changed since any tick older than that.
+    ///
+    /// # Example
+    /// ```
+    /// # use hecs::*;
+    /// let mut world = World::new();
+    /// let a = world.spawn((123,));
+    /// let tick = world.increment_change_tick();
+    /// assert_eq!(world.query::<Changed<i32>>().since(tick).iter().count(), 0);
+    /// world.query_mut::<&mut i32>().into_iter().for_each(|(_, x)| *x += 1);
+    /// assert_eq!(world.query::<Changed<i32>>().since(tick).iter().count(), 1);
+    /// ```
+    pub fn increment_change_tick(&self) -> u32 {
+        self.archetypes.increment_change_tick()
+    }
+
     /// Number of currently live entities
     #[inline]
     pub fn len(&self) -> u32 {
         self.entities.len()
     }
 
+    /// Number of entity IDs permanently retired because their generation saturated
+    ///
+    /// Each ID can be reused for roughly four billion generations before further reuse would risk
+    /// stale handles aliasing new entities. Once that limit is reached, the ID is never allocated
+    /// again by [`spawn`](Self::spawn) or [`reserve_entity`](Self::reserve_entity), and counts
+    /// towards this total until revived by [`spawn_at`](Self::spawn_at) or discarded by
+    /// [`clear`](Self::clear).
+    #[inline]
+    pub fn exhausted_ids(&self) -> usize {
+        self.entities.exhausted() as usize
+    }
+
+    /// Describe every entity ID ever allocated, in order of ID, for diagnostics
+    ///
+    /// Includes free, [exhausted](Self::exhausted_ids), and [held](Self::reserve_block) IDs as
+    /// well as live entities, so stale handles can be compared against the current state of
+    /// their IDs. Entities obtained from [`reserve_entity`](Self::reserve_entity) since the last
+    /// [`flush`](Self::flush) are reported as not live, or omitted if their IDs are new. Reads
+    /// only entity metadata, so may be called regardless of outstanding component borrows.
+    ///
+    /// # Example
+    /// ```
+    /// # use hecs::*;
+    /// let mut world = World::new();
+    /// let a = world.spawn((true,));
+    /// let b = world.spawn(());
+    /// world.despawn(a).unwrap();
+    /// let slots = world.entity_table_dump().collect::<Vec<_>>();
+    /// assert_eq!(slots.len(), 2);
+    /// assert!(!slots[0].live);
+    /// assert_ne!(slots[0].entity(), a);
+    /// assert_eq!(slots[1].entity(), b);
+    /// assert_eq!(slots[1].to_string(), format!("{} live in archetype {}", b, slots[1].archetype.unwrap()));
+    /// ```
+    pub fn entity_table_dump(&self) -> impl ExactSizeIterator<Item = EntitySlot> + '_ {
+        self.entities.meta.iter().enumerate().map(|(id, meta)| {
+            let live = meta.location.index != u32::MAX;
+            EntitySlot {
+                id: id as u32,
+                generation: meta.generation,
+                live,
+                held: meta.is_held(),
+                exhausted: meta.is_exhausted(),
+                archetype: live.then_some(meta.location.archetype),
+            }
+        })
+    }
+
+    /// Number of entities that [`query::<Q>`](Self::query) would visit
+    ///
+    /// Sums the lengths of matching archetypes without borrowing or visiting any components, so
+    /// this is cheap, and is permitted even for queries that would conflict with live borrows.
+    /// Queries like `Option<&T>` or [`Satisfies`](crate::Satisfies) that match any entity count
+    /// every entity in each archetype.
+    ///
+    /// # Example
+    /// ```
+    /// # use hecs::*;
+    /// let mut world = World::new();
+    /// world.spawn((123, true));
+    /// world.spawn((456,));
+    /// world.spawn(("abc",));
+    /// let _borrow = world.query::<&i32>();
+    /// assert_eq!(world.count::<&mut i32>(), 2);
+    /// assert_eq!(world.count::<Without<&i32, &bool>>(), 1);
+    /// assert_eq!(world.count::<Option<&bool>>(), 3);
+    /// ```
+    pub fn count<Q: Query>(&self) -> usize {
+        self.archetypes_inner()
+            .iter()
+            .filter(|archetype| {
+                prepare_iter::<Q::Fetch>(archetype)
+                    .map_or(false, |state| Q::Fetch::filter(archetype, state, 0))
+            })
+            .map(|archetype| archetype.len() as usize)
+            .sum()
+    }
+
     /// Whether no entities are live
     #[inline]
     pub fn is_empty(&self) -> bool {
@@ -914,7 +4014,65 @@ impl World {
     }
 }
 
+macro_rules! get_n_mut {
+    ($(#[$meta:meta])* $name:ident, $($ty:ident),+) => {
+        $(#[$meta])*
+        pub fn $name<$($ty: Component),+>(
+            &mut self,
+            entity: Entity,
+        ) -> Result<($(&mut $ty,)+), ComponentError> {
+            assert_borrow::<($(&mut $ty,)+)>();
+
+            let loc = self.entities.get(entity)?;
+            let archetype = &self.archetypes.archetypes[loc.archetype as usize];
+            $(
+                if !archetype.has::<$ty>() {
+                    return Err(MissingComponent::new::<$ty>().into());
+                }
+            )+
+            let state = <($(&mut $ty,)+) as Query>::Fetch::prepare(archetype).unwrap();
+            let fetch = <($(&mut $ty,)+) as Query>::Fetch::execute(archetype, state);
+            unsafe { Ok(<($(&mut $ty,)+) as Query>::get(&fetch, loc.index as usize)) }
+        }
+    };
+}
+
+// Generated separately, as macros must be defined before use
+impl World {
+    get_n_mut!(
+        /// Uniquely borrow `entity`'s `A` and `B` components without dynamic borrow checks
+        ///
+        /// Equivalent to [`query_one_mut::<(&mut A, &mut B)>`](Self::query_one_mut), but reports
+        /// which component is missing. Panics if `A` and `B` are the same type. See
+        /// [`get3_mut`](Self::get3_mut) and [`get4_mut`](Self::get4_mut) for more components.
+        ///
+        /// # Example
+        /// ```
+        /// # use hecs::*;
+        /// struct Position(f32);
+        /// struct Velocity(f32);
+        /// let mut world = World::new();
+        /// let a = world.spawn((Position(0.0), Velocity(2.0)));
+        /// let (pos, vel) = world.get2_mut::<Position, Velocity>(a).unwrap();
+        /// pos.0 += vel.0;
+        /// assert_eq!(world.get::<&Position>(a).unwrap().0, 2.0);
+        /// ```
+        get2_mut, A, B
+    );
+    get_n_mut!(
+        /// Like [`get2_mut`](Self::get2_mut), for three components
+        get3_mut, A, B, C
+    );
+    get_n_mut!(
+        /// Like [`get2_mut`](Self::get2_mut), for four components
+        get4_mut, A, B, C, D
+    );
+}
+
+// Everything a world owns is either a `Component`, and hence `Send + Sync`, or synchronized
+#[cfg(not(feature = "thread-local"))]
 unsafe impl Send for World {}
+#[cfg(not(feature = "thread-local"))]
 unsafe impl Sync for World {}
 
 impl Default for World {
@@ -923,6 +4081,76 @@ impl Default for World {
     }
 }
 
+/// Lists every live entity and the names of its components, in order of [`Entity::id`]
+///
+/// Component values aren't shown. Names are those registered in the
+/// [`type_name_registry`](World::type_name_registry) if any, or else [`core::any::type_name`] in
+/// debug builds, and are listed in lexicographic order, so output is suitable for comparison
+/// against known-good output in tests. Types lacking both a registered name and a debug build are
+/// shown as their `TypeId`, which is consistent only within a single build.
+///
+/// # Example
+/// ```
+/// # use hecs::*;
+/// struct Position;
+/// let mut world = World::new();
+/// world.type_name_registry_mut().register::<Position>("Position");
+/// world.type_name_registry_mut().register::<u32>("u32");
+/// let a = world.spawn((7u32, Position));
+/// let b = world.spawn(());
+/// assert_eq!(format!("{:?}", world), format!(r#"World {{{a:?}: ["Position", "u32"], {b:?}: []}}"#));
+/// ```
+impl fmt::Debug for World {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = |id: TypeId, fallback: Option<&'static str>| -> String {
+            match self.type_names.get(id).or(fallback) {
+                Some(name) => name.into(),
+                None => format!("{:?}", id),
+            }
+        };
+        // Resolve names once per archetype rather than once per entity
+        let archetypes = self
+            .archetypes
+            .archetypes
+            .iter()
+            .map(|archetype| {
+                let mut names = archetype
+                    .types()
+                    .iter()
+                    .map(|ty| name(ty.id(), ty.type_name()))
+                    .collect::<Vec<_>>();
+                names.sort_unstable();
+                names
+            })
+            .collect::<Vec<_>>();
+        f.write_str("World ")?;
+        let mut map = f.debug_map();
+        for (id, meta) in self.entities.meta.iter().enumerate() {
+            if meta.location.index == u32::MAX {
+                continue;
+            }
+            let entity = Entity {
+                id: id as u32,
+                generation: meta.generation,
+            };
+            let names = &archetypes[meta.location.archetype as usize];
+            if self.sparse.is_empty() {
+                map.entry(&entity, names);
+            } else {
+                let mut names = names.clone();
+                names.extend(
+                    self.sparse
+                        .component_types(entity.id)
+                        .map(|(ty, type_name)| name(ty, Some(type_name))),
+                );
+                names.sort_unstable();
+                map.entry(&entity, &names);
+            }
+        }
+        map.finish()
+    }
+}
+
 impl<'a> IntoIterator for &'a World {
     type IntoIter = Iter<'a>;
     type Item = EntityRef<'a>;
@@ -931,6 +4159,15 @@ impl<'a> IntoIterator for &'a World {
     }
 }
 
+#[cold]
+#[track_caller]
+fn get_expect_failed<T>(entity: Entity, exists: bool) -> ! {
+    if exists {
+        panic!("entity {} missing component {}", entity, type_name::<T>());
+    }
+    panic!("no such entity {}", entity);
+}
+
 fn index2<T>(x: &mut [T], i: usize, j: usize) -> (&mut T, &mut T) {
     assert!(i != j);
     assert!(i < x.len());
@@ -948,6 +4185,16 @@ pub enum ComponentError {
     MissingComponent(MissingComponent),
 }
 
+impl ComponentError {
+    /// Whether the entity exists but lacks the requested component
+    ///
+    /// Distinguishes errors that can be remedied by inserting the component from those caused by
+    /// the entity having been despawned.
+    pub fn is_missing_component(&self) -> bool {
+        matches!(*self, ComponentError::MissingComponent(_))
+    }
+}
+
 #[cfg(feature = "std")]
 impl Error for ComponentError {}
 
@@ -1001,25 +4248,290 @@ impl From<NoSuchEntity> for QueryOneError {
     }
 }
 
+/// Errors that arise when expecting a query to match exactly one entity
+///
+/// Returned by [`QueryBorrow::single`] and [`QueryMut::single`].
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub enum QuerySingleError {
+    /// No entity satisfies the query
+    NoEntities,
+    /// More than one entity satisfies the query
+    MultipleEntities {
+        /// Number of entities satisfying the query
+        count: usize,
+    },
+}
+
+#[cfg(feature = "std")]
+impl Error for QuerySingleError {}
+
+impl fmt::Display for QuerySingleError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        use QuerySingleError::*;
+        match *self {
+            NoEntities => f.write_str("no entities satisfy the query"),
+            MultipleEntities { count } => write!(f, "{} entities satisfy the query", count),
+        }
+    }
+}
+
+/// Errors that arise when borrowing several entities at once
+///
+/// Returned by [`World::get_many_mut`] and [`World::try_query_many_mut`].
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub enum GetManyMutError {
+    /// The entity occurred more than once
+    Duplicate(Entity),
+    /// The entity was already despawned
+    NoSuchEntity(Entity),
+    /// The entity exists but does not satisfy the query
+    Unsatisfied(Entity),
+    /// The entity did not have a requested component
+    MissingComponent(Entity, MissingComponent),
+}
+
+#[cfg(feature = "std")]
+impl Error for GetManyMutError {}
+
+impl fmt::Display for GetManyMutError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        use GetManyMutError::*;
+        match *self {
+            Duplicate(e) => write!(f, "entity {:?} occurs more than once", e),
+            NoSuchEntity(e) => write!(f, "no such entity {:?}", e),
+            Unsatisfied(e) => write!(f, "entity {:?} does not satisfy the query", e),
+            MissingComponent(e, ref x) => write!(f, "entity {:?} is {}", e, x),
+        }
+    }
+}
+
+/// Error indicating that an [`Entity`] handle's ID is already used by a live entity
+///
+/// Returned by [`World::spawn_batch_at`], [`World::spawn_at_exact`], and
+/// [`World::spawn_with_id`].
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub struct EntityCollision(pub(crate) Entity);
+
+impl EntityCollision {
+    /// The handle which could not be spawned
+    pub fn entity(&self) -> Entity {
+        self.0
+    }
+}
+
+#[cfg(feature = "std")]
+impl Error for EntityCollision {}
+
+impl fmt::Display for EntityCollision {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "entity ID {} is already in use", self.0.id)
+    }
+}
+
 /// Types that can be components, implemented automatically for all `Send + Sync + 'static` types
 ///
 /// This is just a convenient shorthand for `Send + Sync + 'static`, and never needs to be
 /// implemented manually.
+///
+/// Components are always stored at their type's alignment, including over-aligned types such as
+/// `#[repr(align(64))]` SIMD vectors, wherever they are staged on their way into the world.
+#[cfg(not(feature = "thread-local"))]
 pub trait Component: Send + Sync + 'static {}
+#[cfg(not(feature = "thread-local"))]
 impl<T: Send + Sync + 'static> Component for T {}
 
+/// Types that can be components, implemented automatically for all `'static` types
+///
+/// Because the `thread-local` feature is enabled, components need not be `Send` or `Sync`, and in
+/// exchange [`World`] and everything borrowing from it is neither `Send` nor `Sync`. This is just a
+/// convenient shorthand for `'static`, and never needs to be implemented manually.
+///
+/// Components are always stored at their type's alignment, including over-aligned types such as
+/// `#[repr(align(64))]` SIMD vectors, wherever they are staged on their way into the world.
+#[cfg(feature = "thread-local")]
+pub trait Component: 'static {}
+#[cfg(feature = "thread-local")]
+impl<T: 'static> Component for T {}
+
+/// Shared access to the `T` components of every entity other than the one being visited by
+/// [`World::for_each_with_neighbors`]
+pub struct NeighborReader<'a, T> {
+    meta: &'a [EntityMeta],
+    archetypes: &'a [Archetype],
+    /// Base of each archetype's `T` column, if it should be visited
+    columns: &'a [Option<NonNull<T>>],
+    current: Entity,
+}
+
+impl<'a, T: Component> NeighborReader<'a, T> {
+    /// The entity whose `T` is uniquely borrowed, and hence inaccessible through this reader
+    pub fn current(&self) -> Entity {
+        self.current
+    }
+
+    /// Get `entity`'s `T`, if it exists and has one
+    ///
+    /// Panics if `entity` is [`current`](Self::current).
+    pub fn get(&self, entity: Entity) -> Option<&T> {
+        assert_ne!(
+            entity, self.current,
+            "component is uniquely borrowed by the entity being visited"
+        );
+        let meta = self.meta.get(entity.id as usize)?;
+        if meta.generation != entity.generation || meta.location.index == u32::MAX {
+            return None;
+        }
+        let base = self.columns[meta.location.archetype as usize]?;
+        unsafe { Some(&*base.as_ptr().add(meta.location.index as usize)) }
+    }
+
+    /// Iterate over every other entity's `T`
+    pub fn iter(&self) -> impl Iterator<Item = (Entity, &T)> + '_ {
+        self.archetypes
+            .iter()
+            .zip(self.columns)
+            .filter_map(|(archetype, base)| Some((archetype, (*base)?)))
+            .flat_map(move |(archetype, base)| {
+                archetype
+                    .ids()
+                    .iter()
+                    .enumerate()
+                    .filter(move |&(_, &id)| id != self.current.id)
+                    .map(move |(index, &id)| {
+                        let entity = Entity {
+                            id,
+                            generation: self.meta[id as usize].generation,
+                        };
+                        (entity, unsafe { &*base.as_ptr().add(index) })
+                    })
+            })
+    }
+}
+
+unsafe impl<T: Sync> Send for NeighborReader<'_, T> {}
+unsafe impl<T: Sync> Sync for NeighborReader<'_, T> {}
+
+/// A group of entities sharing the same component types, obtained from [`World::iter_archetypes`]
+#[derive(Copy, Clone)]
+pub struct ArchetypeView<'a> {
+    meta: &'a [EntityMeta],
+    archetype: &'a Archetype,
+}
+
+impl<'a> ArchetypeView<'a> {
+    /// The underlying archetype
+    pub fn archetype(&self) -> &'a Archetype {
+        self.archetype
+    }
+
+    /// The types of the components every entity in this archetype has, in a consistent order
+    pub fn component_types(&self) -> impl ExactSizeIterator<Item = TypeId> + 'a {
+        self.archetype.component_types()
+    }
+
+    /// Whether the entities in this archetype have a `T` component
+    pub fn has<T: Component>(&self) -> bool {
+        self.archetype.has::<T>()
+    }
+
+    /// The entities in this archetype, in the same order as their components
+    pub fn entities(&self) -> impl ExactSizeIterator<Item = Entity> + 'a {
+        let meta = self.meta;
+        self.archetype.ids().iter().map(move |&id| Entity {
+            id,
+            generation: meta[id as usize].generation,
+        })
+    }
+
+    /// Borrow every entity's `T` component, in the same order as [`entities`](Self::entities)
+    ///
+    /// Returns `None` if the entities don't have a `T`. Panics if the components are already
+    /// uniquely borrowed, e.g. by a concurrent query.
+    pub fn get_column<T: Component>(&self) -> Option<ArchetypeColumn<'a, T>> {
+        self.archetype.get::<&T>()
+    }
+
+    /// Number of entities in this archetype
+    pub fn len(&self) -> u32 {
+        self.archetype.len()
+    }
+
+    /// Whether this archetype contains no entities; never true for views from
+    /// [`World::iter_archetypes`]
+    pub fn is_empty(&self) -> bool {
+        self.archetype.is_empty()
+    }
+}
+
+/// Which archetypes contain a certain set of component types, obtained from
+/// [`World::presence_mask`]
+///
+/// Tests whether an entity has every component in the set by looking up the entity's archetype in
+/// a precomputed table. Entities moving between existing archetypes are accounted for
+/// automatically, but archetypes created after the mask was computed, e.g. when a new combination
+/// of components is first spawned, must be checked individually until [`refresh`](Self::refresh)
+/// is called. Results are always accurate, even when the mask [`is_stale`](Self::is_stale).
+#[derive(Debug, Clone)]
+pub struct PresenceMask {
+    /// World ID and archetype generation the table was computed for
+    memo: (u64, u32),
+    types: Vec<TypeId>,
+    archetypes: Vec<bool>,
+}
+
+impl PresenceMask {
+    /// Whether `entity` exists in `world` and has every component in the set
+    #[inline]
+    pub fn contains(&self, world: &World, entity: Entity) -> bool {
+        let Ok(loc) = world.entities.get(entity) else {
+            return false;
+        };
+        match self.archetypes.get(loc.archetype as usize) {
+            Some(&x) if self.memo.0 == world.id => x,
+            _ => self.matches(&world.archetypes.archetypes[loc.archetype as usize]),
+        }
+    }
+
+    /// Whether archetypes have been added to `world` since the mask was computed, or the mask was
+    /// computed for a different world
+    pub fn is_stale(&self, world: &World) -> bool {
+        self.memo != world.memo()
+    }
+
+    /// Recompute the table for the current archetypes of `world`
+    pub fn refresh(&mut self, world: &World) {
+        if self.memo.0 != world.id {
+            self.archetypes.clear();
+        }
+        let archetypes = world.archetypes_inner();
+        let fresh = archetypes[self.archetypes.len()..]
+            .iter()
+            .map(|archetype| self.matches(archetype))
+            .collect::<Vec<_>>();
+        self.archetypes.extend(fresh);
+        self.memo = world.memo();
+    }
+
+    fn matches(&self, archetype: &Archetype) -> bool {
+        self.types.iter().all(|&ty| archetype.has_dynamic(ty))
+    }
+}
+
 /// Iterator over all of a world's entities
 pub struct Iter<'a> {
-    archetypes: core::slice::Iter<'a, Archetype>,
+    archetypes: &'a ArchetypeSet,
+    /// Positions in visiting order of the archetypes not yet visited
+    remaining: core::ops::Range<usize>,
     entities: &'a Entities,
     current: Option<&'a Archetype>,
     index: u32,
 }
 
 impl<'a> Iter<'a> {
-    fn new(archetypes: &'a [Archetype], entities: &'a Entities) -> Self {
+    fn new(archetypes: &'a ArchetypeSet, entities: &'a Entities) -> Self {
         Self {
-            archetypes: archetypes.iter(),
+            archetypes,
+            remaining: 0..archetypes.archetypes.len(),
             entities,
             current: None,
             index: 0,
@@ -1027,7 +4539,9 @@ impl<'a> Iter<'a> {
     }
 }
 
+#[cfg(not(feature = "thread-local"))]
 unsafe impl Send for Iter<'_> {}
+#[cfg(not(feature = "thread-local"))]
 unsafe impl Sync for Iter<'_> {}
 
 impl<'a> Iterator for Iter<'a> {
@@ -1036,7 +4550,8 @@ impl<'a> Iterator for Iter<'a> {
         loop {
             match self.current {
                 None => {
-                    self.current = Some(self.archetypes.next()?);
+                    let archetype = self.archetypes.visit_order(self.remaining.next()?);
+                    self.current = Some(&self.archetypes.archetypes[archetype]);
                     self.index = 0;
                 }
                 Some(current) => {
@@ -1093,10 +4608,222 @@ impl<A: DynamicBundle> core::iter::FromIterator<A> for World {
     }
 }
 
+/// Human-readable names for component types, looked up by [`TypeId`]
+///
+/// Stored in every [`World`] and accessed with [`World::type_name_registry`]. Names are supplied by
+/// the application, unlike those from [`core::any::type_name`], which aren't guaranteed to be
+/// stable. Useful for inspectors and debug overlays, e.g. with
+/// [`ComponentTypes`](crate::ComponentTypes).
+///
+/// # Example
+/// ```
+/// # use hecs::*;
+/// struct Position;
+/// let mut world = World::new();
+/// world.type_name_registry_mut().register::<Position>("Position");
+/// let a = world.spawn((Position, 42));
+/// let mut query = world.query_one::<ComponentTypes>(a).unwrap();
+/// let mut names = query
+///     .get()
+///     .unwrap()
+///     .iter()
+///     .map(|&ty| world.type_name_registry().get(ty))
+///     .collect::<Vec<_>>();
+/// names.sort();
+/// assert_eq!(names, [None, Some("Position")]);
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct TypeNameRegistry {
+    names: TypeIdMap<&'static str>,
+}
+
+impl TypeNameRegistry {
+    /// Create an empty registry
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Name `T` as `name`, replacing any previous name
+    pub fn register<T: Component>(&mut self, name: &'static str) -> &mut Self {
+        self.register_dynamic(TypeId::of::<T>(), name)
+    }
+
+    /// Name the type identified by `id` as `name`, replacing any previous name
+    pub fn register_dynamic(&mut self, id: TypeId, name: &'static str) -> &mut Self {
+        self.names.insert(id, name);
+        self
+    }
+
+    /// The name registered for the type identified by `id`, if any
+    pub fn get(&self, id: TypeId) -> Option<&'static str> {
+        self.names.get(&id).copied()
+    }
+}
+
+/// Handles allocated by [`World::reserve_block`]
+///
+/// Dereferences to a slice of the handles, in allocation order. Dropping the block has no effect
+/// on the handles it contains; see [`World::release_reserved`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ReservedBlock {
+    entities: Vec<Entity>,
+}
+
+impl ReservedBlock {
+    /// The reserved handles, in allocation order
+    pub fn entities(&self) -> &[Entity] {
+        &self.entities
+    }
+}
+
+impl Deref for ReservedBlock {
+    type Target = [Entity];
+
+    fn deref(&self) -> &[Entity] {
+        &self.entities
+    }
+}
+
+impl IntoIterator for ReservedBlock {
+    type Item = Entity;
+    type IntoIter = vec::IntoIter<Entity>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.entities.into_iter()
+    }
+}
+
+impl<'a> IntoIterator for &'a ReservedBlock {
+    type Item = &'a Entity;
+    type IntoIter = core::slice::Iter<'a, Entity>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.entities.iter()
+    }
+}
+
+/// The state of an entity ID, as reported by [`World::entity_table_dump`]
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct EntitySlot {
+    /// The ID described
+    pub id: u32,
+    /// Generation of the live entity with this ID, or that the next entity to use it will have
+    pub generation: NonZeroU32,
+    /// Whether an entity currently has this ID
+    pub live: bool,
+    /// Whether the ID is [held](World::reserve_block) for a handle that hasn't been spawned
+    pub held: bool,
+    /// Whether the ID's generations are [exhausted](World::exhausted_ids), retiring it
+    pub exhausted: bool,
+    /// Index in [`World::archetypes`] of the archetype storing the live entity's components
+    pub archetype: Option<u32>,
+}
+
+impl EntitySlot {
+    /// The handle of the live entity with this ID, or that the next entity to use it will have
+    pub fn entity(&self) -> Entity {
+        Entity {
+            id: self.id,
+            generation: self.generation,
+        }
+    }
+}
+
+impl fmt::Display for EntitySlot {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.archetype {
+            Some(archetype) => write!(f, "{} live in archetype {}", self.entity(), archetype),
+            None if self.held => write!(f, "{} held", self.entity()),
+            None if self.exhausted => write!(f, "{} exhausted", self.entity()),
+            None => write!(f, "{} free", self.entity()),
+        }
+    }
+}
+
+/// Effects of inserting components, as reported by [`World::insert_report`]
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct InsertReport {
+    /// Types of the components the entity didn't previously have
+    pub added: Vec<TypeId>,
+    /// Types of the components that replaced, and dropped, existing components
+    pub overwritten: Vec<TypeId>,
+}
+
+/// Memory occupied by a [`World`], as reported by [`World::memory_report`]
+#[derive(Debug, Clone)]
+pub struct MemoryReport {
+    /// Bytes allocated for entity metadata, i.e. generations and locations
+    pub entity_bytes: usize,
+    /// Memory occupied by each archetype, in the same order as [`World::archetypes`]
+    pub archetypes: Vec<ArchetypeMemory>,
+}
+
+impl MemoryReport {
+    /// Total bytes allocated, including unused capacity
+    pub fn allocated_bytes(&self) -> usize {
+        self.entity_bytes
+            + self
+                .archetypes
+                .iter()
+                .map(|x| x.allocated_bytes())
+                .sum::<usize>()
+    }
+
+    /// Total bytes occupied by live components
+    pub fn used_bytes(&self) -> usize {
+        self.archetypes.iter().map(|x| x.used_bytes()).sum()
+    }
+
+    /// Memory occupied by each component type, summed across all archetypes
+    pub fn components(&self) -> Vec<ColumnMemory> {
+        let mut totals = TypeIdMap::<ColumnMemory>::default();
+        for column in self.archetypes.iter().flat_map(|x| &x.columns) {
+            match totals.entry(column.type_id) {
+                Entry::Occupied(mut entry) => {
+                    let total = entry.get_mut();
+                    total.used_bytes += column.used_bytes;
+                    total.allocated_bytes += column.allocated_bytes;
+                }
+                Entry::Vacant(entry) => {
+                    entry.insert(*column);
+                }
+            }
+        }
+        totals.into_values().collect()
+    }
+}
+
+/// Split `entities`, sorted by archetype, into runs sharing the same archetype
+fn archetype_runs(entities: &[(u32, Entity)]) -> impl Iterator<Item = &[(u32, Entity)]> {
+    let mut rest = entities;
+    core::iter::from_fn(move || {
+        let archetype = rest.first()?.0;
+        let n = rest.iter().take_while(|x| x.0 == archetype).count();
+        let (run, tail) = rest.split_at(n);
+        rest = tail;
+        Some(run)
+    })
+}
+
 /// Determines freshness of information derived from [`World::archetypes`]
 #[derive(Debug, Copy, Clone, Eq, PartialEq)]
 pub struct ArchetypesGeneration(u32);
 
+/// Identifies an [`Archetype`] within a [`World`], obtained from [`World::archetype_id`]
+///
+/// Archetypes are never removed, so an ID remains valid for the life of its world and always
+/// refers to the same set of component types. IDs are small integers, equal to the archetype's
+/// position in [`World::archetypes`], and are meaningless when used with a different world.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub struct ArchetypeId(u32);
+
+impl ArchetypeId {
+    /// The archetype's position in [`World::archetypes`]
+    pub fn index(self) -> u32 {
+        self.0
+    }
+}
+
 /// Entity IDs created by [`World::spawn_batch`]
 pub struct SpawnBatchIter<'a, I>
 where
@@ -1107,6 +4834,7 @@ where
     entities: &'a mut Entities,
     archetype_id: u32,
     archetype: &'a mut Archetype,
+    hooks: &'a mut Hooks,
 }
 
 impl<I> Drop for SpawnBatchIter<'_, I>
@@ -1136,10 +4864,17 @@ where
                     .put_dynamic(ptr, ty.id(), ty.layout().size(), index);
             });
         }
+        self.archetype.mark_all_added();
         self.entities.meta[entity.id as usize].location = Location {
             archetype: self.archetype_id,
             index,
         };
+        if !self.hooks.is_empty() {
+            unsafe {
+                let types = self.archetype.type_ids().iter().copied();
+                self.hooks.fire_insert(entity, self.archetype, index, types);
+            }
+        }
         Some(entity)
     }
 
@@ -1158,6 +4893,52 @@ where
     }
 }
 
+/// Iterator despawning entities, returned by [`World::drain`] and [`World::drain_matching`]
+pub struct Drain<'a> {
+    world: &'a mut World,
+    /// Index of the next archetype to draw from
+    archetype: usize,
+    matches: fn(&Archetype) -> bool,
+}
+
+impl Iterator for Drain<'_> {
+    type Item = (Entity, EntityBuilder);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let id = loop {
+            let archetype = self.world.archetypes.archetypes.get(self.archetype)?;
+            if !archetype.is_empty() && (self.matches)(archetype) {
+                // Taking the last entity avoids moving any other
+                break archetype.entity_id(archetype.len() - 1);
+            }
+            self.archetype += 1;
+        };
+        let entity = unsafe { self.world.entities.resolve_unknown_gen(id) };
+        let mut components = EntityBuilder::new();
+        unsafe {
+            self.world
+                .take(entity)
+                .unwrap()
+                .put(|ptr, ty| components.add_raw(ptr, ty));
+        }
+        Some((entity, components))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.len(), Some(self.len()))
+    }
+}
+
+impl ExactSizeIterator for Drain<'_> {
+    fn len(&self) -> usize {
+        self.world.archetypes.archetypes[self.archetype..]
+            .iter()
+            .filter(|x| (self.matches)(x))
+            .map(|x| x.len() as usize)
+            .sum()
+    }
+}
+
 /// Iterator over [`Entity`]s spawned by [`World::spawn_column_batch()`]
 pub struct SpawnColumnBatchIter<'a> {
     pending_end: usize,
@@ -1195,39 +4976,130 @@ struct ArchetypeSet {
     /// Maps sorted component type sets to archetypes
     index: HashMap<Box<[TypeId]>, u32>,
     archetypes: Vec<Archetype>,
+    /// Number of entities newly created archetypes have room for
+    default_capacity: u32,
+    /// IDs of every archetype sorted by component types, if iteration should visit them in that
+    /// order
+    order: Option<Vec<u32>>,
+    /// Tick at which changes to components are currently recorded
+    change_tick: AtomicU32,
 }
 
 impl ArchetypeSet {
     fn new() -> Self {
         // `flush` assumes archetype 0 always exists, representing entities with no components.
-        Self {
+        let mut this = Self {
             index: Some((Box::default(), 0)).into_iter().collect(),
             archetypes: vec![Archetype::new(Vec::new())],
+            default_capacity: 0,
+            order: None,
+            change_tick: AtomicU32::new(0),
+        };
+        this.set_change_tick(1);
+        this
+    }
+
+    /// Record subsequent changes at `tick`
+    fn set_change_tick(&mut self, tick: u32) {
+        *self.change_tick.get_mut() = tick;
+        for archetype in &mut self.archetypes {
+            archetype.set_change_tick(tick);
+        }
+    }
+
+    /// Advance the change tick, returning the tick that all prior changes were recorded at
+    fn increment_change_tick(&self) -> u32 {
+        let old = self.change_tick.fetch_add(1, Ordering::Relaxed);
+        let new = old.wrapping_add(1);
+        let clamp = new % TICK_CLAMP_INTERVAL == 0;
+        for archetype in &self.archetypes {
+            archetype.advance_change_tick(new);
+            if clamp {
+                archetype.clamp_ticks(new);
+            }
+        }
+        old
+    }
+
+    /// Start or stop maintaining `order`
+    fn set_sorted(&mut self, sorted: bool) {
+        if !sorted {
+            self.order = None;
+            return;
+        }
+        if self.order.is_some() {
+            return;
+        }
+        let archetypes = &self.archetypes;
+        let mut order = (0..archetypes.len() as u32).collect::<Vec<_>>();
+        order.sort_unstable_by(|&a, &b| {
+            archetypes[a as usize]
+                .type_ids()
+                .cmp(archetypes[b as usize].type_ids())
+        });
+        self.order = Some(order);
+    }
+
+    /// Index of the `n`th archetype visited by iteration
+    #[inline]
+    fn visit_order(&self, n: usize) -> usize {
+        match self.order {
+            None => n,
+            Some(ref order) => order[n] as usize,
         }
     }
 
+    /// Add the newly created archetype `id` to `order`, if maintained
+    fn add_to_order(&mut self, id: u32) {
+        let order = match self.order {
+            Some(ref mut x) => x,
+            None => return,
+        };
+        let archetypes = &self.archetypes;
+        let types = archetypes[id as usize].type_ids();
+        let i = order.partition_point(|&x| archetypes[x as usize].type_ids() < types);
+        order.insert(i, id);
+    }
+
     /// Find the archetype ID that has exactly `components`
     fn get<T: Borrow<[TypeId]> + Into<Box<[TypeId]>>>(
         &mut self,
         components: T,
         info: impl FnOnce() -> Vec<TypeInfo>,
+    ) -> u32 {
+        self.get_with_capacity(components, info, self.default_capacity)
+    }
+
+    /// Like `get`, but a newly created archetype has room for `capacity` entities rather than
+    /// the default
+    fn get_with_capacity<T: Borrow<[TypeId]> + Into<Box<[TypeId]>>>(
+        &mut self,
+        components: T,
+        info: impl FnOnce() -> Vec<TypeInfo>,
+        capacity: u32,
     ) -> u32 {
         self.index
             .get(components.borrow())
             .copied()
-            .unwrap_or_else(|| self.insert(components.into(), info()))
+            .unwrap_or_else(|| self.insert(components.into(), info(), capacity))
     }
 
-    fn insert(&mut self, components: Box<[TypeId]>, info: Vec<TypeInfo>) -> u32 {
+    fn insert(&mut self, components: Box<[TypeId]>, info: Vec<TypeInfo>, capacity: u32) -> u32 {
         let x = self.archetypes.len() as u32;
-        self.archetypes.push(Archetype::new(info));
+        let mut archetype = Archetype::new(info);
+        archetype.set_change_tick(*self.change_tick.get_mut());
+        if capacity != 0 {
+            archetype.reserve(capacity);
+        }
+        self.archetypes.push(archetype);
         let old = self.index.insert(components, x);
         debug_assert!(old.is_none(), "inserted duplicate archetype");
+        self.add_to_order(x);
         x
     }
 
     /// Returns archetype ID and starting location index
-    fn insert_batch(&mut self, archetype: Archetype) -> (u32, u32) {
+    fn insert_batch(&mut self, mut archetype: Archetype) -> (u32, u32) {
         let ids = archetype
             .types()
             .iter()
@@ -1247,8 +5119,11 @@ impl ArchetypeSet {
             Entry::Vacant(x) => {
                 // Brand new archetype
                 let id = self.archetypes.len() as u32;
+                archetype.set_change_tick(*self.change_tick.get_mut());
+                archetype.mark_all_added();
                 self.archetypes.push(archetype);
                 x.insert(id);
+                self.add_to_order(id);
                 (id, 0)
             }
         }
@@ -1333,6 +5208,26 @@ impl Hasher for IndexTypeIdHasher {
 mod tests {
     use super::*;
 
+    #[test]
+    fn change_ticks_clamped() {
+        use crate::Changed;
+
+        let mut world = World::new();
+        let a = world.spawn((0i32,));
+        // Skip ahead over 2^31 ticks, far enough for clamped ticks to wrap, stopping at every clamp
+        for step in 1..=(3 << 30) / TICK_CLAMP_INTERVAL + 1 {
+            world
+                .archetypes
+                .set_change_tick(step * TICK_CLAMP_INTERVAL - 1);
+            world.increment_change_tick();
+        }
+        let tick = world.increment_change_tick();
+        assert_eq!(world.query::<Changed<i32>>().since(tick).iter().count(), 0);
+        assert_eq!(world.query::<Changed<i32>>().iter().count(), 1);
+        *world.get::<&mut i32>(a).unwrap() = 1;
+        assert_eq!(world.query::<Changed<i32>>().since(tick).iter().count(), 1);
+    }
+
     #[test]
     fn reuse_empty() {
         let mut world = World::new();
@@ -0,0 +1,87 @@
+//! Lives in its own test binary, since measuring allocations requires a global allocator
+
+#![cfg(feature = "row-serialize")]
+
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::io::{self, BufWriter, Write};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use hecs::serialize::row::{self, SerializeContext};
+use hecs::{EntityRef, World};
+use serde::ser::SerializeMap;
+use serde::Serialize;
+
+struct Counting;
+
+static CURRENT: AtomicUsize = AtomicUsize::new(0);
+static PEAK: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for Counting {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let current = CURRENT.fetch_add(layout.size(), Ordering::Relaxed) + layout.size();
+        PEAK.fetch_max(current, Ordering::Relaxed);
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        CURRENT.fetch_sub(layout.size(), Ordering::Relaxed);
+        System.dealloc(ptr, layout)
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: Counting = Counting;
+
+#[derive(Serialize)]
+struct Position([f32; 3]);
+
+struct Context;
+
+impl SerializeContext for Context {
+    fn serialize_entity<S>(&mut self, entity: EntityRef<'_>, mut map: S) -> Result<S::Ok, S::Error>
+    where
+        S: SerializeMap,
+    {
+        row::try_serialize::<Position, _, _>(&entity, "position", &mut map)?;
+        map.end()
+    }
+
+    fn component_count(&self, entity: EntityRef<'_>) -> Option<usize> {
+        Some(entity.has::<Position>() as usize)
+    }
+}
+
+/// Discards its input, counting the bytes
+struct Sink(usize);
+
+impl Write for Sink {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0 += buf.len();
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+#[test]
+fn bounded_memory() {
+    const BUFFER: usize = 4096;
+    let mut world = World::new();
+    world.spawn_batch((0..100_000).map(|i| (Position([i as f32; 3]),)));
+
+    let mut sink = Sink(0);
+    let baseline = CURRENT.load(Ordering::Relaxed);
+    PEAK.store(baseline, Ordering::Relaxed);
+    {
+        let mut writer = BufWriter::with_capacity(BUFFER, &mut sink);
+        let mut serializer = bincode::Serializer::new(&mut writer, bincode::options());
+        row::serialize(&world, &mut Context, &mut serializer).unwrap();
+        writer.flush().unwrap();
+    }
+    let peak = PEAK.load(Ordering::Relaxed) - baseline;
+
+    assert!(sink.0 > 100 * BUFFER);
+    assert!(peak < 4 * BUFFER, "peak allocation of {peak} bytes");
+}
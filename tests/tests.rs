@@ -6,6 +6,7 @@
 // http://opensource.org/licenses/MIT>, at your option. This file may not be
 // copied, modified, or distributed except according to those terms.
 
+use std::any::TypeId;
 use std::borrow::Cow;
 
 use hecs::*;
@@ -281,6 +282,21 @@ fn invalidate_prepared_query() {
     assert!(ents.contains(&(e, 123, "abc")));
     assert!(ents.contains(&(f, 456, "def")));
     assert!(ents.contains(&(g, 789, "ghi")));
+
+    // Newly created archetypes are picked up alongside previously matched ones
+    let h = world.spawn(("jkl", 1, 'x'));
+    let view = query.view_mut(&mut world);
+    assert_eq!(view.get(e), Some((&123, &"abc")));
+    assert_eq!(view.get(h), Some((&1, &"jkl")));
+
+    // Switching worlds discards state from the old one
+    let mut other = World::new();
+    let i = other.spawn(('y', 2, "mno"));
+    let ents = query
+        .query_mut(&mut other)
+        .map(|(e, (&i, &s))| (e, i, s))
+        .collect::<Vec<_>>();
+    assert_eq!(ents, [(i, 2, "mno")]);
 }
 
 #[test]
@@ -797,6 +813,42 @@ fn remove_missing() {
     assert!(world.remove_one::<bool>(e).is_err());
 }
 
+#[test]
+fn component_error_kinds() {
+    let mut world = World::new();
+    let e = world.spawn(("abc", 123));
+    let err = world.get::<&bool>(e).unwrap_err();
+    assert!(err.is_missing_component());
+    match err {
+        ComponentError::MissingComponent(ref x) => assert_eq!(x.type_name(), "bool"),
+        ComponentError::NoSuchEntity => unreachable!(),
+    }
+    assert_eq!(err.to_string(), "missing bool component");
+    assert!(world
+        .get::<&mut bool>(e)
+        .unwrap_err()
+        .is_missing_component());
+    assert!(world
+        .remove::<(i32, bool)>(e)
+        .unwrap_err()
+        .is_missing_component());
+    assert!(world
+        .remove_one::<bool>(e)
+        .unwrap_err()
+        .is_missing_component());
+
+    world.despawn(e).unwrap();
+    for err in [
+        world.get::<&i32>(e).err().unwrap(),
+        world.get::<&mut i32>(e).err().unwrap(),
+        world.remove::<(i32,)>(e).unwrap_err(),
+        world.remove_one::<i32>(e).unwrap_err(),
+    ] {
+        assert_eq!(err, ComponentError::NoSuchEntity);
+        assert!(!err.is_missing_component());
+    }
+}
+
 #[test]
 fn exchange_components() {
     let mut world = World::new();
@@ -810,6 +862,17 @@ fn exchange_components() {
     assert!(world.get::<&String>(entity).is_err());
     assert!(world.get::<&i32>(entity).is_ok());
     assert!(world.get::<&bool>(entity).is_ok());
+
+    let (old, _) = world
+        .exchange::<(i32, bool), _>(entity, (456, 'x'))
+        .unwrap();
+    assert_eq!(old, 123);
+    assert_eq!(*world.get::<&i32>(entity).unwrap(), 456);
+    assert!(world.get::<&bool>(entity).is_err());
+    assert!(world.get::<&char>(entity).is_ok());
+
+    assert!(world.exchange::<(bool,), _>(entity, (1u8,)).is_err());
+    assert!(world.get::<&u8>(entity).is_err());
 }
 
 #[test]
@@ -833,6 +896,51 @@ fn reserve() {
     assert!(entities.contains(&b));
 }
 
+#[test]
+fn reserve_block() {
+    let mut world = World::new();
+    let block = world.reserve_block(3);
+    assert_eq!(block.len(), 3);
+    assert_eq!(world.reserved_len(), 3);
+
+    // Held handles survive flushing and spawning without becoming entities
+    let _ = world.reserve_entity();
+    world.flush();
+    let other = world.spawn((true,));
+    assert!(!block.contains(&other));
+    assert_eq!(world.len(), 2);
+    assert_eq!(world.iter().count(), 2);
+    for &entity in &block {
+        assert!(world.is_reserved(entity));
+        assert!(!world.contains(entity));
+        assert!(world.insert_one(entity, 1).is_err());
+        assert!(world.despawn(entity).is_err());
+    }
+
+    world.spawn_at(block[0], (1,));
+    assert!(world.contains(block[0]));
+    assert!(!world.is_reserved(block[0]));
+    assert_eq!(world.len(), 3);
+
+    world.clear_retaining_capacity();
+    assert!(world.is_reserved(block[1]));
+
+    world.release_reserved(block[1]).unwrap();
+    assert!(world.release_reserved(block[1]).is_err());
+    assert!(world.release_reserved(block[0]).is_err());
+    assert_eq!(world.reserved_len(), 1);
+    let reused = world.spawn(());
+    assert_eq!(reused.id(), block[1].id());
+    assert_ne!(reused, block[1]);
+    for _ in 0..4 {
+        assert_ne!(world.spawn(()).id(), block[2].id());
+    }
+
+    world.spawn_at(block[2], ("c",));
+    assert_eq!(world.reserved_len(), 0);
+    assert_eq!(*world.get::<&&str>(block[2]).unwrap(), "c");
+}
+
 #[test]
 fn query_batched() {
     let mut world = World::new();
@@ -1036,6 +1144,54 @@ fn query_or() {
     assert!(results.contains(&(g, "jkl", Or::Both(456, false))));
 }
 
+#[test]
+fn query_or_filter() {
+    struct Enemy;
+    struct Hazard;
+    struct Dead;
+
+    fn matches<Q: Query>(world: &World) -> Vec<Entity> {
+        let mut result = world
+            .query::<Q>()
+            .iter()
+            .map(|(e, _)| e)
+            .collect::<Vec<_>>();
+        result.sort();
+        result
+    }
+
+    let mut world = World::new();
+    let enemy = world.spawn((1, Enemy));
+    let hazard = world.spawn((2, Hazard));
+    let both = world.spawn((3, Enemy, Hazard));
+    let dead = world.spawn((4, Enemy, Dead));
+    let neither = world.spawn((5,));
+    world.spawn((Enemy, Hazard));
+
+    assert_eq!(
+        matches::<With<&i32, Or<&Enemy, &Hazard>>>(&world),
+        [enemy, hazard, both, dead]
+    );
+    assert_eq!(
+        matches::<Without<&i32, Or<&Enemy, &Hazard>>>(&world),
+        [neither]
+    );
+    assert_eq!(
+        matches::<Without<&i32, Or<Without<&Enemy, &Dead>, &Hazard>>>(&world),
+        [dead, neither]
+    );
+    assert_eq!(
+        matches::<With<&i32, Or<With<&Enemy, &Hazard>, Or<&Dead, Without<&Hazard, &Enemy>>>>>(
+            &world
+        ),
+        [hazard, both, dead]
+    );
+    assert_eq!(
+        matches::<(&i32, Satisfies<With<&Enemy, Or<&Hazard, &Dead>>>)>(&world),
+        [enemy, hazard, both, dead, neither]
+    );
+}
+
 #[test]
 fn len() {
     let mut world = World::new();
@@ -1065,6 +1221,140 @@ fn take() {
     assert!(!world_b.contains(e2));
 }
 
+#[test]
+fn despawn_take() {
+    let mut world = World::new();
+    world.register_relation::<ChildOf>(OnTargetDespawn::Despawn);
+    let a = world.spawn(("abc".to_string(), 42));
+    let child = world.spawn((ChildOf(a),));
+    let mut components = world.despawn_take(a).unwrap();
+    assert!(!world.contains(a));
+    assert!(!world.contains(child));
+    assert!(world.despawn_take(a).is_err());
+    assert_eq!(components.get::<&String>().unwrap().as_str(), "abc");
+    assert!(!components.has::<ChildOf>());
+
+    // The slot is freed as by `despawn`
+    let reused = world.spawn(());
+    let b = world.spawn(components.build());
+    assert_eq!(reused.id(), child.id());
+    assert_eq!(b.id(), a.id());
+    assert_ne!(b, a);
+    assert_eq!(*world.get::<&i32>(b).unwrap(), 42);
+}
+
+#[test]
+fn tags() {
+    let mut world = World::new();
+    let a = world.spawn((1,));
+    let b = world.spawn((2, true));
+    let c = world.spawn((3,));
+    world.set_tag(a, 7).unwrap();
+    world.set_tag(b, 7).unwrap();
+    world.set_tag(c, 1).unwrap();
+    assert_eq!(world.tag(b), Ok(7));
+
+    let mut tagged = world
+        .query::<&i32>()
+        .iter_in_tag(7)
+        .map(|(e, &x)| (e, x))
+        .collect::<Vec<_>>();
+    tagged.sort();
+    assert_eq!(tagged, [(a, 1), (b, 2)]);
+    for (_, x) in world.query_mut::<&mut i32>().into_iter_in_tag(1) {
+        *x = 30;
+    }
+    assert_eq!(*world.get::<&i32>(c).unwrap(), 30);
+
+    // Tags don't affect archetypes, and are forgotten on despawn
+    assert_eq!(world.archetypes().filter(|x| !x.is_empty()).count(), 2);
+    world.despawn(a).unwrap();
+    assert!(world.tag(a).is_err());
+    assert!(world.set_tag(a, 1).is_err());
+    let d = world.spawn((4,));
+    assert_eq!(d.id(), a.id());
+    assert_eq!(world.tag(d), Ok(0));
+    assert_eq!(world.query::<&i32>().iter_in_tag(0).count(), 1);
+
+    world.clear_retaining_capacity();
+    let e = world.spawn((5,));
+    assert_eq!(world.tag(e), Ok(0));
+}
+
+#[test]
+fn entity_table_dump() {
+    let mut world = World::new();
+    let a = world.spawn((1,));
+    let b = world.spawn(());
+    let c = world.spawn((2,));
+    let held = world.reserve_block(1)[0];
+    world.despawn(b).unwrap();
+
+    // Component borrows don't conflict
+    let mut query = world.query::<&mut i32>();
+    let _borrow = query.iter();
+    let slots = world.entity_table_dump().collect::<Vec<_>>();
+    assert_eq!(slots.len(), 4);
+    assert_eq!(slots[0].entity(), a);
+    assert!(slots[0].live);
+    assert_eq!(slots[0].archetype, slots[2].archetype);
+    assert_eq!(slots[1].id, b.id());
+    assert!(!slots[1].live);
+    assert_eq!(slots[1].archetype, None);
+    assert!(slots[1].generation.get() > b.generation());
+    assert_eq!(slots[2].entity(), c);
+    assert_eq!(slots[3].entity(), held);
+    assert!(!slots[3].live);
+    assert!(slots[3].held && !slots[1].held);
+    assert_eq!(slots[1].to_string(), format!("{} free", slots[1].entity()));
+    assert_eq!(slots[3].to_string(), format!("{} held", held));
+}
+
+#[test]
+#[cfg(feature = "thread-local")]
+fn thread_local_components() {
+    use std::rc::Rc;
+
+    let shared = Rc::new(17);
+    let mut world = World::new();
+    let e = world.spawn((Rc::clone(&shared),));
+    assert_eq!(Rc::strong_count(&shared), 2);
+    for (_, x) in world.query::<&Rc<i32>>().iter() {
+        assert_eq!(**x, 17);
+    }
+    world.despawn(e).unwrap();
+    assert_eq!(Rc::strong_count(&shared), 1);
+}
+
+#[test]
+fn world_debug_is_deterministic() {
+    let mut a = World::new();
+    let x = a.spawn((1, true));
+    let y = a.spawn(("abc", 2u8));
+    a.insert_one(x, 'c').unwrap();
+
+    // Same entities, different archetype layout and creation order
+    let mut b = World::new();
+    b.spawn_at(y, (2u8, "abc"));
+    b.spawn_at(x, ('c',));
+    b.insert(x, (true, 1)).unwrap();
+    assert_ne!(
+        a.archetypes().map(|x| x.len()).collect::<Vec<_>>(),
+        b.archetypes().map(|x| x.len()).collect::<Vec<_>>()
+    );
+    assert_eq!(format!("{:?}", a), format!("{:?}", b));
+
+    a.type_name_registry_mut()
+        .register::<i32>("Int")
+        .register::<bool>("Bool")
+        .register::<char>("Char");
+    a.despawn(y).unwrap();
+    assert_eq!(
+        format!("{:?}", a),
+        format!(r#"World {{{:?}: ["Bool", "Char", "Int"]}}"#, x)
+    );
+}
+
 #[test]
 fn empty_archetype_conflict() {
     let mut world = World::new();
@@ -1123,3 +1413,3433 @@ fn query_many_duplicate() {
     let e = world.spawn(());
     _ = world.query_many_mut::<(), 2>([e, e]);
 }
+
+#[test]
+fn change_detection() {
+    let mut world = World::new();
+    let a = world.spawn((1, true));
+    let b = world.spawn((2,));
+
+    let tick = world.increment_change_tick();
+    assert_eq!(world.query::<Changed<i32>>().since(tick).iter().count(), 0);
+    assert_eq!(world.query::<Added<i32>>().since(tick).iter().count(), 0);
+
+    *world.get::<&mut i32>(b).unwrap() = 3;
+    let changed = world
+        .query::<With<&i32, Changed<i32>>>()
+        .since(tick)
+        .iter()
+        .map(|(e, &x)| (e, x))
+        .collect::<Vec<_>>();
+    assert_eq!(changed, [(b, 3)]);
+    assert_eq!(world.query::<Added<i32>>().since(tick).iter().count(), 0);
+
+    let tick = world.increment_change_tick();
+    for (_, x) in world.query_mut::<&mut i32>().with::<&bool>() {
+        *x += 1;
+    }
+    let changed = world
+        .query_mut::<(&i32, Changed<i32>)>()
+        .since(tick)
+        .into_iter()
+        .map(|(e, _)| e)
+        .collect::<Vec<_>>();
+    assert_eq!(changed, [a]);
+
+    // Filtered-out archetypes aren't marked changed by a unique borrow
+    let tick = world.increment_change_tick();
+    *world.get::<&mut i32>(a).unwrap() += 1;
+    for _ in 0..2 {
+        let changed = world
+            .query::<(&mut i32, Changed<i32>)>()
+            .since(tick)
+            .iter()
+            .map(|(e, _)| e)
+            .collect::<Vec<_>>();
+        assert_eq!(changed, [a]);
+    }
+
+    let tick = world.increment_change_tick();
+    world.insert(b, (false, "b")).unwrap();
+    world.spawn((4, true, 'c'));
+    let added = world
+        .query::<Without<(&i32, Added<bool>), &char>>()
+        .since(tick)
+        .iter()
+        .map(|(e, _)| e)
+        .collect::<Vec<_>>();
+    assert_eq!(added, [b]);
+}
+
+#[test]
+fn prepared_change_detection() {
+    let mut world = World::new();
+    let a = world.spawn((1,));
+    world.spawn((2, true));
+
+    let mut query = PreparedQuery::<(&i32, Changed<i32>)>::default();
+    assert_eq!(query.query(&world).iter().count(), 2);
+    assert_eq!(query.query(&world).iter().count(), 0);
+
+    *world.get::<&mut i32>(a).unwrap() = 3;
+    let changed = query
+        .query_mut(&mut world)
+        .map(|(e, (&x, ()))| (e, x))
+        .collect::<Vec<_>>();
+    assert_eq!(changed, [(a, 3)]);
+    assert_eq!(query.query_mut(&mut world).len(), 0);
+}
+
+#[test]
+fn prepared_query_without_change_filter_keeps_tick() {
+    let mut world = World::new();
+    world.spawn((1,));
+    let mut query = PreparedQuery::<&i32>::default();
+    let before = world.increment_change_tick();
+    assert_eq!(query.query(&world).iter().count(), 1);
+    assert_eq!(query.query_mut(&mut world).len(), 1);
+    assert_eq!(world.increment_change_tick(), before + 1);
+
+    let mut query = PreparedQuery::<Or<&bool, Changed<i32>>>::default();
+    query.query(&world).iter().count();
+    assert_eq!(world.increment_change_tick(), before + 3);
+}
+
+#[test]
+#[should_panic(expected = "can't be excluded")]
+fn without_change_filter() {
+    let mut world = World::new();
+    world.spawn((1, true));
+    world
+        .query::<Without<&i32, Changed<bool>>>()
+        .iter()
+        .for_each(|_| ());
+}
+
+#[test]
+fn change_detection_across_moves() {
+    let mut world = World::new();
+    let a = world.spawn((1, 'a'));
+    let b = world.spawn((2,));
+    let tick = world.increment_change_tick();
+    *world.get::<&mut i32>(a).unwrap() = 3;
+    let changed = |world: &World| {
+        let mut entities = world
+            .query::<Changed<i32>>()
+            .since(tick)
+            .iter()
+            .map(|(e, ())| e)
+            .collect::<Vec<_>>();
+        entities.sort();
+        entities
+    };
+
+    // Moving `a` to other archetypes keeps its mark
+    world.insert_one(a, true).unwrap();
+    assert_eq!(changed(&world), [a]);
+    world.remove_one::<char>(a).unwrap();
+    assert_eq!(changed(&world), [a]);
+    // Back in `b`'s archetype, the mark covers `b` as well
+    world.remove_one::<bool>(a).unwrap();
+    assert_eq!(changed(&world), [a, b]);
+}
+
+#[test]
+fn change_ticks_per_world() {
+    let mut world = World::new();
+    let other = World::new();
+    let a = world.spawn((1,));
+    let tick = world.increment_change_tick();
+    for _ in 0..10 {
+        other.increment_change_tick();
+    }
+    assert_eq!(world.increment_change_tick(), tick + 1);
+    assert_eq!(world.query::<Changed<i32>>().since(tick).iter().count(), 0);
+
+    *world.get::<&mut i32>(a).unwrap() = 2;
+    let mut registry = CloneRegistry::default();
+    registry.register::<i32>();
+    let cloned = world.clone_with(&registry);
+    assert_eq!(cloned.increment_change_tick(), tick + 2);
+    assert_eq!(cloned.query::<Changed<i32>>().since(tick).iter().count(), 1);
+}
+
+#[cfg(not(feature = "thread-local"))]
+#[test]
+#[cfg_attr(miri, ignore)]
+fn parallel_batched() {
+    let mut world = World::new();
+    world.spawn_batch((0..25_000).map(|i| (i as u64,)));
+    world.spawn_batch((0..25_000).map(|i| (i as u64, true)));
+    world.spawn_batch((0..25_000).map(|i| (i as u64, "abc")));
+    world.spawn_batch((0..25_000).map(|i| (i as u64, 1.0f32)));
+
+    let serial = world.query::<&u64>().iter().map(|(_, &x)| x).sum::<u64>();
+
+    let batches = world
+        .query_mut::<&mut u64>()
+        .into_iter_batched(4096)
+        .collect::<Vec<_>>();
+    std::thread::scope(|scope| {
+        for batch in batches {
+            scope.spawn(move || batch.for_each(|(_, x)| *x *= 2));
+        }
+    });
+
+    let mut query = world.query::<&u64>();
+    let parallel = std::thread::scope(|scope| {
+        let workers = query
+            .iter_batched(4096)
+            .map(|batch| scope.spawn(move || batch.map(|(_, &x)| x).sum::<u64>()))
+            .collect::<Vec<_>>();
+        workers
+            .into_iter()
+            .map(|worker| worker.join().unwrap())
+            .sum::<u64>()
+    });
+    assert_eq!(parallel, 2 * serial);
+}
+
+#[test]
+#[cfg(all(feature = "rayon", not(feature = "thread-local")))]
+#[cfg_attr(miri, ignore)]
+fn parallel_rayon() {
+    use rayon::prelude::*;
+
+    let mut world = World::new();
+    world.spawn_batch((0..25_000).map(|i| (i as u64,)));
+    world.spawn_batch((0..25_000).map(|i| (i as u64, true)));
+    world.spawn_batch((0..25_000).map(|i| (i as u64, "abc")));
+    world.spawn_batch((0..25_000).map(|i| (i as u64, 1.0f32)));
+
+    let serial = world.query::<&u64>().iter().map(|(_, &x)| x).sum::<u64>();
+    let parallel = world
+        .query::<&u64>()
+        .par_iter()
+        .map(|(_, &x)| x)
+        .sum::<u64>();
+    assert_eq!(parallel, serial);
+
+    world
+        .query_mut::<&mut u64>()
+        .into_par_iter()
+        .for_each(|(_, x)| *x *= 2);
+    let mut entities = world
+        .query::<&u64>()
+        .par_iter()
+        .map(|(entity, &x)| (entity, x))
+        .collect::<Vec<_>>();
+    assert_eq!(entities.iter().map(|&(_, x)| x).sum::<u64>(), 2 * serial);
+    entities.sort_unstable_by_key(|&(entity, _)| entity);
+    entities.dedup_by_key(|&mut (entity, _)| entity);
+    assert_eq!(entities.len(), 100_000);
+}
+
+#[cfg(not(feature = "thread-local"))]
+#[test]
+fn split_access() {
+    use std::any::TypeId;
+
+    let mut world = World::new();
+    let e = world.spawn((1i32, true, 'a'));
+    let (first, second) = world.split_access(&[TypeId::of::<bool>()], &[TypeId::of::<i32>()]);
+    assert_eq!(first.access(TypeId::of::<char>()), Access::Iterate);
+    assert_eq!(second.access(TypeId::of::<i32>()), Access::Iterate);
+    assert_eq!(second.access(TypeId::of::<bool>()), Access::Read);
+    assert_eq!(second.access(TypeId::of::<char>()), Access::Write);
+
+    // Disjoint accesses run concurrently
+    std::thread::scope(|scope| {
+        let a = scope.spawn(|| {
+            for (_, (x, _)) in first.query::<With<(&mut i32, &bool), &char>>().iter() {
+                *x += 1;
+            }
+        });
+        let b = scope.spawn(|| {
+            for (_, (c, _)) in second.query::<(&mut char, &bool)>().iter() {
+                *c = 'b';
+            }
+        });
+        a.join().unwrap();
+        b.join().unwrap();
+    });
+
+    // Accesses conflicting with the other view panic on every run
+    std::thread::scope(|scope| {
+        let a = scope.spawn(|| {
+            first.query::<&char>();
+        });
+        let b = scope.spawn(|| {
+            second.query_one::<&i32>(e).unwrap();
+        });
+        assert!(a.join().is_err());
+        assert!(b.join().is_err());
+    });
+
+    assert_eq!(*world.get::<&i32>(e).unwrap(), 2);
+    assert_eq!(*world.get::<&char>(e).unwrap(), 'b');
+}
+
+#[test]
+fn over_aligned() {
+    #[repr(align(64))]
+    struct Aligned(u8);
+
+    fn check(world: &World) -> u32 {
+        let mut sum = 0;
+        for (_, x) in world.query::<&Aligned>().iter() {
+            assert_eq!(x as *const Aligned as usize % 64, 0);
+            sum += u32::from(x.0);
+        }
+        sum
+    }
+
+    let mut world = World::new();
+    // Mix in less-aligned components so columns don't start at the allocation's base by chance
+    let a = world.spawn((1u8, Aligned(0)));
+    world.spawn_batch((0..100).map(|i| (Aligned(i), i as u16)));
+    world.spawn(EntityBuilder::new().add(true).add(Aligned(1)).build());
+    let mut cmd = CommandBuffer::new();
+    cmd.spawn(('a', Aligned(2)));
+    cmd.run_on(&mut world);
+    world.insert_one(a, 0u32).unwrap();
+    assert_eq!(check(&world), (0..100).sum::<u32>() + 3);
+}
+
+#[test]
+fn freeze() {
+    let mut world = World::new();
+    let a = world.spawn((1, true));
+    let b = world.reserve_entity();
+    {
+        let frozen = world.freeze();
+        assert_eq!(frozen.len(), 2);
+        assert!(frozen.contains(b));
+        assert_eq!(*frozen.get::<i32>(a).unwrap(), 1);
+        assert!(frozen.get::<i32>(b).unwrap_err().is_missing_component());
+        assert_eq!(frozen.query_one::<&bool>(a).unwrap().get(), Some(&true));
+        let archetypes = frozen.archetypes().collect::<Vec<_>>();
+        let mut query = frozen.query::<&i32>();
+        assert_eq!(query.iter().count(), 1);
+        assert_eq!(frozen.archetypes().count(), archetypes.len());
+        assert!(archetypes.iter().any(|x| x.has::<i32>() && x.len() == 1));
+    }
+    world.despawn(a).unwrap();
+    assert_eq!(world.freeze().len(), 1);
+}
+
+#[test]
+fn despawn_matching() {
+    let mut world = World::new();
+    let a = world.spawn((1, true));
+    let b = world.spawn((2, "b"));
+    let c = world.spawn((true, 'c'));
+    let d = world.spawn((3, true, 'd'));
+    assert_eq!(world.despawn_matching::<With<(), &bool>>(), 3);
+    assert_eq!(world.len(), 1);
+    assert!(!world.contains(a));
+    assert!(world.contains(b));
+    assert!(!world.contains(c));
+    assert!(!world.contains(d));
+    assert_eq!(world.despawn_matching::<&bool>(), 0);
+
+    // Freed slots are reused
+    let e = world.spawn((4,));
+    assert!([a.id(), c.id(), d.id()].contains(&e.id()));
+    assert_eq!(world.query::<&i32>().iter().count(), 2);
+}
+
+#[test]
+fn retain() {
+    let mut world = World::new();
+    let entities = world.spawn_batch((0..10).map(|i| (i,))).collect::<Vec<_>>();
+    let other = world.spawn((true,));
+    world.retain::<&mut i32, _>(|_, x| {
+        *x *= 10;
+        *x < 50
+    });
+    assert_eq!(world.len(), 6);
+    assert!(world.contains(other));
+    for (i, &e) in entities.iter().enumerate() {
+        assert_eq!(world.contains(e), i < 5);
+    }
+    assert_eq!(*world.get::<&i32>(entities[4]).unwrap(), 40);
+}
+
+struct ChildOf(Entity);
+
+impl Relation for ChildOf {
+    fn target(&self) -> Entity {
+        self.0
+    }
+}
+
+struct Owner(Entity);
+
+impl Relation for Owner {
+    fn target(&self) -> Entity {
+        self.0
+    }
+}
+
+#[test]
+fn relation_despawn() {
+    let mut world = World::new();
+    world.register_relation::<ChildOf>(OnTargetDespawn::Despawn);
+    let root = world.spawn(());
+    let a = world.spawn((ChildOf(root),));
+    let b = world.spawn((ChildOf(a), 1));
+    let c = world.spawn((ChildOf(a),));
+    let unrelated = world.spawn((ChildOf(world.reserve_entity()),));
+    let mut children = world.relations_to(a).collect::<Vec<_>>();
+    children.sort();
+    assert_eq!(children, [b, c]);
+
+    world.despawn(root).unwrap();
+    assert!(!world.contains(a));
+    assert!(!world.contains(b));
+    assert!(!world.contains(c));
+    assert!(world.contains(unrelated));
+    assert_eq!(world.len(), 2);
+}
+
+#[test]
+fn relation_despawn_in_bulk() {
+    let mut world = World::new();
+    world.register_relation::<ChildOf>(OnTargetDespawn::Despawn);
+    let parent = world.spawn((1,));
+    let child = world.spawn((ChildOf(parent), 2));
+    let other = world.spawn((3,));
+    assert_eq!(world.despawn_matching::<&i32>(), 2);
+    assert!(!world.contains(parent));
+    assert!(!world.contains(child));
+    assert!(!world.contains(other));
+
+    let parent = world.spawn((1,));
+    let child = world.spawn((ChildOf(parent), 2));
+    world.retain::<&i32, _>(|_, _| false);
+    assert!(!world.contains(parent));
+    assert!(!world.contains(child));
+    assert!(world.is_empty());
+}
+
+#[test]
+fn relation_disabled_source() {
+    let mut world = World::new();
+    world.register_relation::<ChildOf>(OnTargetDespawn::Despawn);
+    world.register_relation::<Owner>(OnTargetDespawn::Orphan);
+    let parent = world.spawn(());
+    let child = world.spawn((ChildOf(parent), Disabled));
+    let item = world.spawn((Owner(parent), Disabled));
+    let mut sources = world.relations_to(parent).collect::<Vec<_>>();
+    sources.sort();
+    assert_eq!(sources, [child, item]);
+
+    world.despawn(parent).unwrap();
+    assert!(!world.contains(child));
+    assert!(world.contains(item));
+    assert!(world.get::<&Owner>(item).is_err());
+}
+
+#[test]
+fn relation_orphan() {
+    let mut world = World::new();
+    world.register_relation::<ChildOf>(OnTargetDespawn::Despawn);
+    world.register_relation::<Owner>(OnTargetDespawn::Orphan);
+    let owner = world.spawn(());
+    let item = world.spawn((Owner(owner), "sword"));
+    let other = world.spawn(());
+    let part = world.spawn((Owner(other), ChildOf(item)));
+    assert_eq!(world.relations_to(owner).collect::<Vec<_>>(), [item]);
+
+    world.despawn(owner).unwrap();
+    assert!(world.contains(item));
+    assert!(world.get::<&Owner>(item).is_err());
+    assert_eq!(*world.get::<&&str>(item).unwrap(), "sword");
+    assert!(world.contains(part));
+    assert_eq!(world.relations_to(owner).count(), 0);
+
+    // Changing policy
+    world.register_relation::<ChildOf>(OnTargetDespawn::Orphan);
+    world.despawn(item).unwrap();
+    assert!(world.contains(part));
+    assert!(world.get::<&ChildOf>(part).is_err());
+    assert_eq!(world.get::<&Owner>(part).unwrap().0, other);
+}
+
+#[test]
+fn resources() {
+    struct Time(f32);
+
+    let mut world = World::new();
+    assert!(world.get_resource::<Time>().is_none());
+    assert!(world.insert_resource(Time(1.0)).is_none());
+    assert_eq!(world.insert_resource(Time(2.0)).unwrap().0, 1.0);
+    world.insert_resource(42u32);
+    world.get_resource_mut::<Time>().unwrap().0 += 1.0;
+    assert_eq!(world.get_resource::<Time>().unwrap().0, 3.0);
+
+    // Resources aren't entities
+    assert_eq!(world.len(), 0);
+    assert_eq!(world.query::<&u32>().iter().count(), 0);
+
+    world.spawn((1u32,));
+    world.clear();
+    assert_eq!(*world.get_resource::<u32>().unwrap(), 42);
+    assert_eq!(world.remove_resource::<u32>(), Some(42));
+    assert_eq!(world.remove_resource::<u32>(), None);
+
+    world.clear_resources();
+    assert!(world.get_resource::<Time>().is_none());
+}
+
+#[test]
+fn query_dynamic() {
+    let mut world = World::new();
+    let a = world.spawn((1i32, 2.5f64));
+    let b = world.spawn((2i32, 3.5f64, "b"));
+    world.spawn((3i32,));
+
+    let mut query = world.query_dynamic(&[
+        (TypeId::of::<f64>(), Access::Read),
+        (TypeId::of::<i32>(), Access::Write),
+    ]);
+    assert_eq!(query.iter().len(), 2);
+    let mut seen = Vec::new();
+    for (entity, item) in &mut query {
+        assert_eq!(item.len(), 2);
+        assert_eq!(item.type_id(1), TypeId::of::<i32>());
+        assert_eq!(item.access(0), Access::Read);
+        assert_eq!(item.layout(0), core::alloc::Layout::new::<f64>());
+        unsafe {
+            let x = *item.get(0).unwrap().cast::<f64>().as_ptr();
+            *item.get(1).unwrap().cast::<i32>().as_ptr() += x as i32;
+        }
+        seen.push(entity);
+    }
+    seen.sort();
+    assert_eq!(seen, [a, b]);
+
+    // Shared borrows don't conflict
+    assert_eq!(world.query::<&f64>().iter().count(), 2);
+    drop(query);
+
+    assert_eq!(*world.get::<&i32>(a).unwrap(), 3);
+    assert_eq!(*world.get::<&i32>(b).unwrap(), 5);
+
+    let mut query = world.query_dynamic(&[(TypeId::of::<&str>(), Access::Iterate)]);
+    let (entity, item) = query.iter().next().unwrap();
+    assert_eq!(entity, b);
+    assert!(item.get(0).is_none());
+}
+
+#[test]
+#[should_panic(expected = "already borrowed")]
+fn query_dynamic_borrow_conflict() {
+    let mut world = World::new();
+    world.spawn((1i32,));
+    let _query = world.query_dynamic(&[(TypeId::of::<i32>(), Access::Write)]);
+    world.query::<&i32>().iter().count();
+}
+
+#[test]
+#[should_panic(expected = "query violates a unique borrow")]
+fn query_dynamic_duplicate_write() {
+    let world = World::new();
+    world.query_dynamic(&[
+        (TypeId::of::<i32>(), Access::Read),
+        (TypeId::of::<i32>(), Access::Write),
+    ]);
+}
+
+trait Describe {
+    fn describe(&self) -> String;
+}
+
+impl Describe for i32 {
+    fn describe(&self) -> String {
+        format!("int {}", self)
+    }
+}
+
+impl Describe for bool {
+    fn describe(&self) -> String {
+        format!("bool {}", self)
+    }
+}
+
+impl Describe for () {
+    fn describe(&self) -> String {
+        "unit".into()
+    }
+}
+
+#[test]
+fn query_trait() {
+    let mut world = World::new();
+    assert_eq!(world.query_trait::<dyn Describe>().iter().len(), 0);
+    world.register_trait::<dyn Describe, i32>(|x| x);
+    world.register_trait::<dyn Describe, bool>(|x| x);
+    world.register_trait::<dyn Describe, ()>(|x| x);
+    let a = world.spawn((1, 'a'));
+    let b = world.spawn((2, true));
+    let c = world.spawn(((),));
+    let d = world.spawn(('d',));
+    world.spawn((3, Disabled));
+
+    let query = world.query_trait::<dyn Describe>();
+    let iter = query.iter();
+    assert_eq!(iter.len(), 4);
+    let mut seen = iter.map(|(e, x)| (e, x.describe())).collect::<Vec<_>>();
+    seen.sort();
+    let mut expected = vec![
+        (a, "int 1".to_string()),
+        (b, "int 2".to_string()),
+        (b, "bool true".to_string()),
+        (c, "unit".to_string()),
+    ];
+    expected.sort();
+    assert_eq!(seen, expected);
+    assert!(seen.iter().all(|&(e, _)| e != d));
+
+    // Shared borrows coexist with shared queries
+    assert_eq!(world.query::<&i32>().iter().count(), 2);
+    drop(query);
+
+    // Re-registering replaces the cast
+    world.register_trait::<dyn Describe, i32>(|_| &());
+    world.despawn(b).unwrap();
+    let seen = world
+        .query_trait::<dyn Describe>()
+        .iter()
+        .map(|(_, x)| x.describe())
+        .collect::<Vec<_>>();
+    assert_eq!(seen, ["unit", "unit"]);
+}
+
+#[test]
+#[should_panic(expected = "already borrowed")]
+fn query_trait_borrow_conflict() {
+    let mut world = World::new();
+    world.register_trait::<dyn Describe, i32>(|x| x);
+    world.spawn((1i32,));
+    let _query = world.query_trait::<dyn Describe>();
+    world.query::<&mut i32>().iter().count();
+}
+
+#[test]
+fn clone_with() {
+    let mut registry = CloneRegistry::new();
+    registry.register::<i32>().register::<String>();
+    assert!(registry.contains::<i32>());
+    assert!(!registry.contains::<bool>());
+
+    let mut world = World::new();
+    let a = world.spawn((1, String::from("a")));
+    let b = world.spawn((2, true));
+    let c = world.spawn((String::from("c"),));
+    let dead = world.spawn(());
+    world.despawn(dead).unwrap();
+    let d = world.spawn((false,));
+    world.spawn((3,));
+
+    let mut cloned = world.clone_with(&registry);
+    assert_eq!(cloned.len(), world.len());
+    assert_eq!(*cloned.get::<&String>(a).unwrap(), "a");
+    assert_eq!(*cloned.get::<&i32>(a).unwrap(), 1);
+    // `b` loses its bool, joining the entity spawned with `(3,)` in one archetype
+    assert_eq!(*cloned.get::<&i32>(b).unwrap(), 2);
+    assert!(cloned.get::<&bool>(b).is_err());
+    assert_eq!(*cloned.get::<&String>(c).unwrap(), "c");
+    assert!(cloned.contains(d));
+    assert!(cloned.entity(d).unwrap().component_types().next().is_none());
+    assert!(!cloned.contains(dead));
+    assert_eq!(cloned.query_mut::<&i32>().into_iter().count(), 3);
+
+    // Allocator state is preserved
+    assert_eq!(world.spawn(()), cloned.spawn(()));
+
+    // Worlds are independent
+    *cloned.get::<&mut String>(a).unwrap() = String::from("changed");
+    assert_eq!(*world.get::<&String>(a).unwrap(), "a");
+
+    assert_eq!(
+        world.try_clone_with(&registry).err().unwrap().type_id(),
+        TypeId::of::<bool>()
+    );
+    registry.register::<bool>();
+    let cloned = world.try_clone_with(&registry).unwrap();
+    assert!(*cloned.get::<&bool>(b).unwrap());
+}
+
+#[test]
+fn lifecycle_hooks() {
+    use std::sync::{Arc, Mutex};
+
+    let log = Arc::new(Mutex::new(Vec::new()));
+    let mut world = World::new();
+    world.on_insert::<i32>({
+        let log = log.clone();
+        move |e, &x| log.lock().unwrap().push(("insert", e, x))
+    });
+    world.on_remove::<i32>({
+        let log = log.clone();
+        move |e, x| {
+            log.lock().unwrap().push(("remove", e, *x));
+            *x += 100;
+        }
+    });
+    let take = |log: &Arc<Mutex<Vec<_>>>| std::mem::take(&mut *log.lock().unwrap());
+
+    let a = world.spawn((1, true));
+    let b = world.spawn((true,));
+    assert_eq!(take(&log), [("insert", a, 1)]);
+
+    world.insert_one(b, 2).unwrap();
+    world.insert(a, (3, 'a')).unwrap();
+    assert_eq!(
+        take(&log),
+        [("insert", b, 2), ("remove", a, 1), ("insert", a, 3)]
+    );
+
+    // Failed removals fire nothing
+    assert!(world.remove::<(i32, char)>(b).is_err());
+    assert_eq!(world.remove_one::<i32>(b).unwrap(), 102);
+    assert_eq!(take(&log), [("remove", b, 2)]);
+
+    let batch = world.spawn_batch((4..6).map(|x| (x,))).collect::<Vec<_>>();
+    assert_eq!(
+        take(&log),
+        [("insert", batch[0], 4), ("insert", batch[1], 5)]
+    );
+
+    world.despawn(a).unwrap();
+    assert_eq!(take(&log), [("remove", a, 3)]);
+
+    let mut other = World::new();
+    let moved = other.spawn(world.take(batch[0]).unwrap());
+    assert_eq!(take(&log), [("remove", batch[0], 4)]);
+    assert_eq!(*other.get::<&i32>(moved).unwrap(), 104);
+
+    world.clear();
+    assert_eq!(take(&log), [("remove", batch[1], 5)]);
+}
+
+#[test]
+fn spawn_at_replacement_fires_hooks() {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    let removed = Arc::new(AtomicUsize::new(0));
+    let mut world = World::new();
+    world.on_remove::<i32>({
+        let removed = removed.clone();
+        move |_, _| {
+            removed.fetch_add(1, Ordering::Relaxed);
+        }
+    });
+    world.set_sparse::<bool>();
+    world.index_names();
+    let a = world.spawn((Name::new("a"), 1i32));
+    world.insert_one(a, true).unwrap();
+    world.spawn_at(a, (2i32,));
+    assert_eq!(removed.load(Ordering::Relaxed), 1);
+    assert_eq!(world.find_by_name("a"), None);
+    assert!(world.get_sparse::<bool>(a).is_err());
+    assert_eq!(*world.get::<&i32>(a).unwrap(), 2);
+
+    let b = world.spawn((Name::new("b"), 3i32));
+    world.insert_one(b, true).unwrap();
+    let mut types = ColumnBatchType::new();
+    types.add::<i32>();
+    let mut batch = types.into_batch(1);
+    batch.writer::<i32>().unwrap().push(4).unwrap();
+    world.spawn_column_batch_at(&[b], batch.build().unwrap());
+    assert_eq!(removed.load(Ordering::Relaxed), 2);
+    assert_eq!(world.find_by_name("b"), None);
+    assert!(world.get_sparse::<bool>(b).is_err());
+    assert_eq!(*world.get::<&i32>(b).unwrap(), 4);
+    assert_eq!(world.len(), 2);
+}
+
+#[test]
+fn name_index() {
+    let mut world = World::new();
+    let a = world.spawn((Name::new("a"),));
+    world.index_names();
+    assert_eq!(world.find_by_name("a"), Some(a));
+
+    // Duplicates resolve to the most recently named entity
+    let b = world.spawn((Name::new("a"), 1));
+    assert_eq!(world.find_all_by_name("a"), [a, b]);
+    assert_eq!(world.find_by_name("a"), Some(b));
+
+    // Renaming by insertion moves the entity to the new name
+    world.insert_one(b, Name::new("b")).unwrap();
+    assert_eq!(world.find_all_by_name("a"), [a]);
+    assert_eq!(world.find_by_name("b"), Some(b));
+    world.insert_one(a, Name::new("b")).unwrap();
+    assert_eq!(world.find_by_name("a"), None);
+    assert_eq!(world.find_all_by_name("b"), [b, a]);
+
+    world.remove::<(Name, i32)>(b).unwrap();
+    assert_eq!(world.find_all_by_name("b"), [a]);
+    let mut other = World::new();
+    other.spawn(world.take(a).unwrap());
+    assert_eq!(world.find_by_name("b"), None);
+    let c = world.spawn((Name::new("b"),));
+    assert_eq!(world.find_by_name("b"), Some(c));
+    world.despawn(c).unwrap();
+    assert!(world.find_all_by_name("b").is_empty());
+}
+
+#[test]
+fn name_index_includes_disabled() {
+    let mut world = World::new();
+    let a = world.spawn((Name::new("a"), Disabled));
+    world.index_names();
+    assert_eq!(world.find_by_name("a"), Some(a));
+    world.despawn(a).unwrap();
+    assert_eq!(world.find_by_name("a"), None);
+}
+
+#[test]
+fn dense_index() {
+    struct Key;
+    let mut world = World::new();
+    let a = world.spawn((Key, 0));
+    assert!(world.dense_index::<Key>().is_none());
+    world.enable_dense_index::<Key>();
+    let b = world.spawn((Key, 1));
+    let c = world.spawn((2,));
+    {
+        let index = world.dense_index::<Key>().unwrap();
+        assert_eq!(index.slot_of(a), Some(0));
+        assert_eq!(index.slot_of(b), Some(1));
+        assert_eq!(index.slot_of(c), None);
+    }
+
+    // Freed slots are reused; moves and replacements keep slots
+    world.despawn(a).unwrap();
+    world.insert_one(c, Key).unwrap();
+    world.insert(b, (Key, true)).unwrap();
+    let index = world.dense_index::<Key>().unwrap();
+    assert_eq!(index.slot_of(a), None);
+    assert_eq!(index.slot_of(b), Some(1));
+    assert_eq!(index.slot_of(c), Some(0));
+    assert_eq!(index.iter().collect::<Vec<_>>(), [(0, c), (1, b)]);
+    let mut query = world.query::<&i32>();
+    let mut dense = index.iter_dense(&mut query).collect::<Vec<_>>();
+    dense.sort_by_key(|&(slot, _, _)| slot);
+    assert_eq!(dense, [(0, c, &2), (1, b, &1)]);
+    drop(query);
+
+    world.remove_one::<Key>(b).unwrap();
+    let d = world.spawn((Key,));
+    let index = world.dense_index::<Key>().unwrap();
+    assert_eq!(index.slot_of(d), Some(1));
+    assert_eq!((index.len(), index.slot_count()), (2, 2));
+    world.clear();
+    assert!(world.dense_index::<Key>().unwrap().is_empty());
+}
+
+#[test]
+fn dense_index_includes_disabled() {
+    struct Key;
+    let mut world = World::new();
+    let a = world.spawn((Key, Disabled));
+    world.enable_dense_index::<Key>();
+    let b = world.spawn((Key,));
+    let index = world.dense_index::<Key>().unwrap();
+    assert_eq!(index.slot_of(a), Some(0));
+    assert_eq!(index.slot_of(b), Some(1));
+    world.despawn(a).unwrap();
+    assert_eq!(world.dense_index::<Key>().unwrap().slot_of(a), None);
+}
+
+#[test]
+fn component_index() {
+    struct Position(i32);
+    let mut world = World::new();
+    let a = world.spawn((Position(3),));
+    world.add_index(|p: &Position| p.0 / 10);
+    let b = world.spawn((Position(14), true));
+    let c = world.spawn((Position(17),));
+    let sorted = |x: &[Entity]| {
+        let mut x = x.to_vec();
+        x.sort();
+        x
+    };
+    {
+        let index = world.index::<Position, i32>().unwrap();
+        assert_eq!(index.bucket(&0), [a]);
+        assert_eq!(sorted(index.bucket(&1)), [b, c]);
+        assert_eq!(index.key_of(c), Some(&1));
+    }
+
+    // Mutations are picked up on the next access, and archetype moves are invisible
+    for (_, p) in world.query_mut::<&mut Position>() {
+        p.0 += 10;
+    }
+    world.insert_one(a, 'a').unwrap();
+    world.remove_one::<bool>(b).unwrap();
+    let index = world.index::<Position, i32>().unwrap();
+    assert_eq!(index.bucket(&0), []);
+    assert_eq!(index.bucket(&1), [a]);
+    assert_eq!(sorted(index.bucket(&2)), [b, c]);
+    assert_eq!(index.buckets().count(), 2);
+
+    world.despawn(b).unwrap();
+    world.remove_one::<Position>(c).unwrap();
+    world.insert_one(a, Position(99)).unwrap();
+    let index = world.index::<Position, i32>().unwrap();
+    assert_eq!(index.bucket(&2), []);
+    assert_eq!(index.bucket(&9), [a]);
+    assert_eq!(index.key_of(b), None);
+    assert!(world.index::<Position, u8>().is_none());
+}
+
+#[test]
+#[should_panic(expected = "index_names")]
+fn name_index_disabled() {
+    let world = World::new();
+    world.find_by_name("a");
+}
+
+#[test]
+fn history() {
+    #[derive(Clone, Debug, PartialEq)]
+    struct Position(i32);
+    let mut world = World::new();
+    let a = world.spawn((Position(1), true));
+    let b = world.spawn((Position(2),));
+    world.enable_history::<Position>();
+    let c = world.spawn((Position(3),));
+
+    for (_, (x, prev)) in world.query_mut::<(&mut Position, Prev<Position>)>() {
+        x.0 += 10 * prev.0;
+    }
+    let mut prev = world
+        .query_mut::<Prev<Position>>()
+        .into_iter()
+        .map(|(e, x)| (e, x.0))
+        .collect::<Vec<_>>();
+    prev.sort_unstable();
+    assert_eq!(prev, [(a, 1), (b, 2)]);
+    assert_eq!(*world.get::<&Position>(c).unwrap(), Position(3));
+
+    world.remove_one::<Position>(b).unwrap();
+    world.set_enabled(c, false).unwrap();
+    world.snapshot_history::<Position>();
+    assert_eq!(world.query_one_mut::<Prev<Position>>(a).unwrap().0, 11);
+    assert!(world.query_one_mut::<Prev<Position>>(b).is_err());
+    assert_eq!(
+        world
+            .query_one_mut::<With<Prev<Position>, &Disabled>>(c)
+            .unwrap()
+            .0,
+        3
+    );
+
+    // A lost history isn't resurrected with the component
+    world.insert_one(b, Position(5)).unwrap();
+    assert!(world.query_one_mut::<Prev<Position>>(b).is_err());
+    world.snapshot_history::<Position>();
+    assert_eq!(world.query_one_mut::<Prev<Position>>(b).unwrap().0, 5);
+}
+
+#[test]
+#[should_panic(expected = "enable_history")]
+fn history_disabled() {
+    let mut world = World::new();
+    world.snapshot_history::<i32>();
+}
+
+#[test]
+fn prepared_query_one() {
+    let mut world = World::new();
+    let a = world.spawn((1, true));
+    let b = world.spawn((2,));
+    let mut prepared = PreparedQuery::<(&mut i32, &bool)>::new();
+    *prepared.query_one(&world, a).unwrap().get().unwrap().0 += 10;
+    assert!(prepared.query_one(&world, b).unwrap().get().is_none());
+
+    // Changing archetypes invalidates the cache
+    world.insert_one(b, false).unwrap();
+    world.insert_one(a, 'a').unwrap();
+    assert_eq!(
+        prepared.query_one(&world, a).unwrap().get(),
+        Some((&mut 11, &true))
+    );
+    assert_eq!(
+        prepared.query_one(&world, b).unwrap().get(),
+        Some((&mut 2, &false))
+    );
+
+    world.despawn(a).unwrap();
+    assert!(prepared.query_one(&world, a).is_err());
+}
+
+#[test]
+#[should_panic(expected = "already borrowed")]
+fn prepared_query_one_borrow_conflict() {
+    let mut world = World::new();
+    let a = world.spawn((1,));
+    let mut prepared = PreparedQuery::<&mut i32>::new();
+    let mut one = prepared.query_one(&world, a).unwrap();
+    let _x = one.get().unwrap();
+    world.query::<&i32>().iter().count();
+}
+
+#[test]
+fn memory_report() {
+    let mut world = World::new();
+    world.spawn_batch((0..10).map(|i| (i as u32, i as u64)));
+    world.spawn_batch((0..5).map(|i| (i as u64,)));
+
+    let archetype = world
+        .archetypes()
+        .find(|a| a.has::<u32>() && a.has::<u64>())
+        .unwrap();
+    let memory = archetype.memory_usage();
+    assert_eq!(memory.len, 10);
+    assert!(memory.capacity >= 10);
+    assert_eq!(memory.entity_bytes, memory.capacity as usize * 4);
+    let column = memory
+        .columns
+        .iter()
+        .find(|c| c.type_id == TypeId::of::<u64>())
+        .unwrap();
+    assert_eq!(column.layout, std::alloc::Layout::new::<u64>());
+    assert_eq!(column.used_bytes, 80);
+    assert_eq!(column.allocated_bytes, memory.capacity as usize * 8);
+    assert_eq!(memory.used_bytes(), 10 * 12);
+
+    let report = world.memory_report();
+    assert_eq!(report.archetypes.len(), world.archetypes().len());
+    let components = report.components();
+    assert_eq!(components.len(), 2);
+    let u64s = components
+        .iter()
+        .find(|c| c.type_id == TypeId::of::<u64>())
+        .unwrap();
+    assert_eq!(u64s.used_bytes, 15 * 8);
+    assert_eq!(report.used_bytes(), 15 * 8 + 10 * 4);
+    assert!(report.allocated_bytes() >= report.used_bytes() + report.entity_bytes);
+}
+
+#[test]
+fn default_archetype_capacity() {
+    fn capacity<T: Component>(world: &World) -> u32 {
+        let archetype = world.archetypes().find(|a| a.has::<T>()).unwrap();
+        archetype.memory_usage().capacity
+    }
+
+    let mut world = World::new();
+    assert_eq!(world.default_archetype_capacity(), 0);
+    world.set_default_archetype_capacity(100);
+    world.spawn((0u64,));
+    let allocated = world
+        .memory_report()
+        .archetypes
+        .iter()
+        .map(|a| a.allocated_bytes())
+        .sum::<usize>();
+    for i in 1..100 {
+        world.spawn((i as u64,));
+    }
+    assert_eq!(capacity::<u64>(&world), 100);
+    assert_eq!(
+        world
+            .memory_report()
+            .archetypes
+            .iter()
+            .map(|a| a.allocated_bytes())
+            .sum::<usize>(),
+        allocated
+    );
+
+    // Explicit reservations override the default
+    world.reserve::<(char,)>(200);
+    assert_eq!(capacity::<char>(&world), 200);
+    let mut ty = ColumnBatchType::new();
+    ty.add::<bool>();
+    world.preallocate(&[(ty, 300)]);
+    assert_eq!(capacity::<bool>(&world), 300);
+}
+
+#[test]
+fn snapshot_diff() {
+    let mut registry = SnapshotRegistry::new();
+    registry.register::<i32>().register::<&'static str>();
+
+    let mut server = World::new();
+    let a = server.spawn((1, "a", 'x'));
+    let b = server.spawn((2,));
+    let mut client = World::new();
+
+    let initial = WorldSnapshot::diff(&WorldSnapshot::default(), &server.snapshot(&registry));
+    assert_eq!(initial.spawned().len(), 2);
+    initial.apply(&mut client);
+    assert_eq!(*client.get::<&i32>(a).unwrap(), 1);
+    assert_eq!(*client.get::<&&str>(a).unwrap(), "a");
+    assert!(client.get::<&char>(a).is_err());
+
+    let old = server.snapshot(&registry);
+    assert!(WorldSnapshot::diff(&old, &server.snapshot(&registry)).is_empty());
+
+    *server.get::<&mut i32>(b).unwrap() = 3;
+    server.remove_one::<&str>(a).unwrap();
+    *server.get::<&mut char>(a).unwrap() = 'y';
+    let c = server.spawn(("c",));
+    let delta = WorldSnapshot::diff(&old, &server.snapshot(&registry));
+    assert_eq!(delta.spawned(), &[c]);
+    assert!(delta.despawned().is_empty());
+    assert_eq!(
+        delta.removed().collect::<Vec<_>>(),
+        [(a, TypeId::of::<&str>())]
+    );
+    let changed = delta.changed().collect::<Vec<_>>();
+    assert_eq!(changed.len(), 2);
+    for (entity, components) in changed {
+        assert_eq!(components.component_types().count(), 1);
+        assert!(entity == b || entity == c);
+    }
+
+    delta.apply(&mut client);
+    assert_eq!(*client.get::<&i32>(b).unwrap(), 3);
+    assert!(client.get::<&&str>(a).is_err());
+    assert_eq!(*client.get::<&&str>(c).unwrap(), "c");
+
+    let old = server.snapshot(&registry);
+    server.despawn(a).unwrap();
+    let delta = WorldSnapshot::diff(&old, &server.snapshot(&registry));
+    assert_eq!(delta.despawned(), &[a]);
+    delta.apply(&mut client);
+    assert!(!client.contains(a));
+    assert_eq!(client.len(), 2);
+}
+
+#[test]
+fn spawn_batch_lazy() {
+    let mut world = World::new();
+    let mut produced = 0;
+    let mut iter = world.spawn_batch((0..10).map(|i| {
+        produced += 1;
+        (i,)
+    }));
+    assert_eq!(iter.size_hint(), (10, Some(10)));
+    iter.next().unwrap();
+    iter.next().unwrap();
+    drop(iter);
+    assert_eq!(produced, 10);
+    assert_eq!(world.len(), 10);
+}
+
+#[test]
+fn spawn_batch_at() {
+    let mut source = World::new();
+    let handles = source.spawn_batch((0..4).map(|_| ())).collect::<Vec<_>>();
+    source.despawn(handles[1]).unwrap();
+    let reused = source.spawn(());
+    assert_eq!(reused.id(), handles[1].id());
+
+    let mut world = World::new();
+    world
+        .spawn_batch_at([(handles[3], (3,)), (reused, (1,))])
+        .unwrap();
+    assert_eq!(*world.get::<&i32>(handles[3]).unwrap(), 3);
+    assert_eq!(*world.get::<&i32>(reused).unwrap(), 1);
+    assert!(!world.contains(handles[1]));
+    assert_eq!(world.len(), 2);
+
+    let err = world
+        .spawn_batch_at([(handles[0], (0,)), (handles[1], (10,)), (handles[2], (2,))])
+        .unwrap_err();
+    assert_eq!(err.entity(), handles[1]);
+    assert_eq!(*world.get::<&i32>(handles[0]).unwrap(), 0);
+    assert_eq!(*world.get::<&i32>(reused).unwrap(), 1);
+    assert!(!world.contains(handles[2]));
+
+    // Fresh entities don't collide with explicitly placed ones
+    let fresh = world.spawn((5,));
+    assert!(fresh.id() != handles[0].id() && fresh.id() != handles[3].id());
+}
+
+#[test]
+fn get_n_mut() {
+    let mut world = World::new();
+    let a = world.spawn((1, 'a', true, 2.0f32));
+    let (n, ch) = world.get2_mut::<i32, char>(a).unwrap();
+    *n += 1;
+    *ch = 'b';
+    let (n, ch, b) = world.get3_mut::<i32, char, bool>(a).unwrap();
+    assert_eq!((*n, *ch, *b), (2, 'b', true));
+    let (_, _, _, x) = world.get4_mut::<i32, char, bool, f32>(a).unwrap();
+    *x = 3.0;
+    assert_eq!(*world.get::<&f32>(a).unwrap(), 3.0);
+
+    assert_eq!(
+        world.get3_mut::<i32, char, u8>(a).err(),
+        Some(ComponentError::MissingComponent(
+            MissingComponent::new::<u8>()
+        ))
+    );
+    world.despawn(a).unwrap();
+    assert_eq!(
+        world.get2_mut::<i32, char>(a).err(),
+        Some(ComponentError::NoSuchEntity)
+    );
+}
+
+#[test]
+#[should_panic(expected = "query violates a unique borrow")]
+fn get_n_mut_aliased() {
+    let mut world = World::new();
+    let a = world.spawn((1,));
+    let _ = world.get2_mut::<i32, i32>(a);
+}
+
+#[test]
+fn get_many_mut() {
+    let mut world = World::new();
+    let a = world.spawn((1, 'a'));
+    let b = world.spawn((2,));
+    let c = world.spawn(('c',));
+    let [x, y] = world.get_many_mut::<i32, 2>([a, b]).unwrap();
+    core::mem::swap(x, y);
+    assert_eq!(*world.get::<&i32>(a).unwrap(), 2);
+    assert_eq!(*world.get::<&i32>(b).unwrap(), 1);
+
+    assert_eq!(
+        world.get_many_mut::<i32, 3>([a, b, a]).err(),
+        Some(GetManyMutError::Duplicate(a))
+    );
+    assert_eq!(
+        world.get_many_mut::<i32, 2>([a, c]).err(),
+        Some(GetManyMutError::MissingComponent(
+            c,
+            MissingComponent::new::<i32>()
+        ))
+    );
+    world.despawn(b).unwrap();
+    assert_eq!(
+        world.get_many_mut::<i32, 2>([a, b]).err(),
+        Some(GetManyMutError::NoSuchEntity(b))
+    );
+    assert_eq!(
+        world
+            .try_query_many_mut::<(&mut i32, &char), 2>([a, c])
+            .err(),
+        Some(GetManyMutError::Unsatisfied(c))
+    );
+    let [(n, ch)] = world
+        .try_query_many_mut::<(&i32, &mut char), 1>([a])
+        .unwrap();
+    *ch = 'z';
+    assert_eq!(*n, 2);
+    assert_eq!(*world.get::<&char>(a).unwrap(), 'z');
+}
+
+#[test]
+fn removal_tracking() {
+    let mut world = World::new();
+    let a = world.spawn((1, true));
+    let b = world.spawn((2, true));
+    let c = world.spawn((3,));
+    let d = world.spawn((4, 'd'));
+    assert_eq!(world.removed::<i32>().count(), 0);
+    world.track_removals::<i32>();
+
+    world.remove::<(i32, bool)>(a).unwrap();
+    world.remove_one::<bool>(b).unwrap();
+    world.insert_one(b, 5).unwrap();
+    assert!(world.remove_one::<i32>(a).is_err());
+    world.despawn(c).unwrap();
+    world.exchange_one::<i32, _>(d, 'x').unwrap();
+    assert_eq!(world.removed::<i32>().collect::<Vec<_>>(), [a, c, d]);
+    assert_eq!(world.removed::<bool>().count(), 0);
+
+    world.clear_removed::<i32>();
+    assert_eq!(world.removed::<i32>().len(), 0);
+    drop(world.take(b).unwrap());
+    assert_eq!(world.removed::<i32>().collect::<Vec<_>>(), [b]);
+}
+
+#[test]
+fn query_single() {
+    let mut world = World::new();
+    assert_eq!(
+        world.query::<&i32>().single().err(),
+        Some(QuerySingleError::NoEntities)
+    );
+    let a = world.spawn((1, true));
+    world.spawn(("x",));
+    assert_eq!(world.query::<&i32>().single().unwrap(), (a, &1));
+    {
+        let (entity, (x, _)) = world.query_mut::<(&mut i32, &bool)>().single().unwrap();
+        assert_eq!(entity, a);
+        *x = 2;
+    }
+    world.spawn((3,));
+    world.spawn((4, 'c'));
+    assert_eq!(
+        world.query_mut::<&i32>().single().err(),
+        Some(QuerySingleError::MultipleEntities { count: 3 })
+    );
+    assert_eq!(
+        world.query::<&i32>().with::<&bool>().single().unwrap(),
+        (a, &2)
+    );
+}
+
+#[test]
+fn merge_worlds() {
+    let mut world = World::new();
+    let a = world.spawn((1, true));
+    let b = world.spawn(("b",));
+    world.despawn(b).unwrap();
+    let archetypes = world.archetypes().count();
+
+    let mut other = World::new();
+    let c = other.spawn((2, false));
+    let d = other.spawn(());
+    let e = other.spawn((3, 'e'));
+    other.spawn_batch((0..10).map(|i| (i, true)));
+    let map = world.merge(other);
+
+    assert_eq!(map.len(), 13);
+    assert_eq!(world.len(), 14);
+    assert_eq!(*world.get::<&i32>(map[&c]).unwrap(), 2);
+    assert!(!*world.get::<&bool>(map[&c]).unwrap());
+    assert!(world.contains(map[&d]));
+    assert_eq!(*world.get::<&char>(map[&e]).unwrap(), 'e');
+    assert_eq!(*world.get::<&i32>(a).unwrap(), 1);
+    // Only the (i32, char) archetype is new
+    assert_eq!(world.archetypes().count(), archetypes + 1);
+    let mut new = map.values().copied().collect::<Vec<_>>();
+    new.sort();
+    new.dedup();
+    assert_eq!(new.len(), 13);
+    assert!(!new.contains(&a));
+
+    let sum = world.query::<&i32>().iter().map(|(_, &x)| x).sum::<i32>();
+    assert_eq!(sum, 1 + 2 + 3 + (0..10).sum::<i32>());
+    world.despawn(map[&c]).unwrap();
+    assert_eq!(
+        world.query::<&bool>().iter().count(),
+        1 + 10,
+        "merged archetype remains consistent after removal"
+    );
+}
+
+#[test]
+fn reserve_prevents_reallocation() {
+    let mut world = World::new();
+    world.spawn((0u8,));
+    world.reserve::<(u32, u64)>(1000);
+    let capacities = |world: &World| {
+        let report = world.memory_report();
+        let archetype = report
+            .archetypes
+            .iter()
+            .find(|x| x.columns.iter().any(|c| c.type_id == TypeId::of::<u64>()))
+            .unwrap()
+            .clone();
+        (report.entity_bytes, archetype)
+    };
+    let (entity_bytes, before) = capacities(&world);
+    assert_eq!(before.len, 0);
+    assert!(before.capacity >= 1000);
+
+    for i in 0..1000 {
+        world.spawn((i as u32, i as u64));
+    }
+    let (entity_bytes_after, after) = capacities(&world);
+    assert_eq!(after.len, 1000);
+    assert_eq!(after.capacity, before.capacity);
+    assert_eq!(after.allocated_bytes(), before.allocated_bytes());
+    assert_eq!(entity_bytes_after, entity_bytes);
+}
+
+#[test]
+fn insert_remove_batch() {
+    #[derive(Clone, Debug, PartialEq)]
+    struct Marked(u32);
+
+    let mut world = World::new();
+    let a = world.spawn_batch((0..10).map(|i| (i,))).collect::<Vec<_>>();
+    let b = world
+        .spawn_batch((0..10).map(|i| (i, true)))
+        .collect::<Vec<_>>();
+    let c = world.spawn((Marked(0),));
+    let dead = world.spawn(());
+    world.despawn(dead).unwrap();
+
+    let targets = a
+        .iter()
+        .step_by(2)
+        .chain(&b)
+        .copied()
+        .chain([c, dead, b[0]])
+        .collect::<Vec<_>>();
+    assert_eq!(world.insert_batch(targets, Marked(7)), 16);
+    assert_eq!(world.query::<&Marked>().iter().count(), 16);
+    assert_eq!(*world.get::<&Marked>(c).unwrap(), Marked(7));
+    assert_eq!(*world.get::<&i32>(a[4]).unwrap(), 4);
+    assert_eq!(*world.get::<&i32>(b[9]).unwrap(), 9);
+    assert!(world.get::<&Marked>(a[1]).is_err());
+
+    let removed = world.remove_batch::<Marked>(a.iter().chain(&b).copied().chain([dead, b[3]]));
+    assert_eq!(removed, 15);
+    assert_eq!(
+        world.query::<&Marked>().iter().collect::<Vec<_>>(),
+        [(c, &Marked(7))]
+    );
+    assert_eq!(world.query::<&i32>().iter().count(), 20);
+    assert!(*world.get::<&bool>(b[5]).unwrap());
+
+    // Batches covering entire archetypes
+    world.track_removals::<Marked>();
+    let inserted = std::sync::Arc::new(std::sync::atomic::AtomicUsize::new(0));
+    let counter = inserted.clone();
+    world.on_insert::<Marked>(move |_, _| {
+        counter.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+    });
+    assert_eq!(world.insert_batch(b.iter().rev().copied(), Marked(3)), 10);
+    assert_eq!(inserted.load(std::sync::atomic::Ordering::Relaxed), 10);
+    for &entity in &b {
+        assert_eq!(*world.get::<&Marked>(entity).unwrap(), Marked(3));
+        assert!(*world.get::<&bool>(entity).unwrap());
+    }
+    assert_eq!(*world.get::<&i32>(b[6]).unwrap(), 6);
+    assert_eq!(world.remove_batch::<Marked>(b.iter().copied()), 10);
+    assert_eq!(world.removed::<Marked>().count(), 10);
+    assert_eq!(world.query::<(&i32, &bool)>().iter().count(), 10);
+    assert_eq!(*world.get::<&i32>(b[6]).unwrap(), 6);
+    assert!(world.get::<&Marked>(b[6]).is_err());
+}
+
+#[test]
+fn batch_panic_safety() {
+    use std::panic::{catch_unwind, AssertUnwindSafe};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    struct Counted(Arc<AtomicUsize>);
+    impl Clone for Counted {
+        fn clone(&self) -> Self {
+            // Fails once two clones exist
+            assert!(Arc::strong_count(&self.0) < 4);
+            Self(self.0.clone())
+        }
+    }
+    impl Drop for Counted {
+        fn drop(&mut self) {
+            self.0.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    let drops = Arc::new(AtomicUsize::new(0));
+    let mut world = World::new();
+    let entities = world.spawn_batch((0..4).map(|i| (i,))).collect::<Vec<_>>();
+
+    // A panicking clone leaves every entity where it was
+    let value = Counted(drops.clone());
+    let result = catch_unwind(AssertUnwindSafe(|| {
+        world.insert_batch(entities.iter().copied(), value)
+    }));
+    assert!(result.is_err());
+    assert_eq!(drops.load(Ordering::Relaxed), 3);
+    assert_eq!(world.query::<&Counted>().iter().count(), 0);
+    for (i, &entity) in entities.iter().enumerate() {
+        assert_eq!(*world.get::<&i32>(entity).unwrap(), i as i32);
+    }
+
+    // A panicking hook leaves every entity with its new component
+    world.on_insert::<bool>(|_, _| panic!());
+    let result = catch_unwind(AssertUnwindSafe(|| {
+        world.insert_batch(entities.iter().copied(), true)
+    }));
+    assert!(result.is_err());
+    assert_eq!(world.query::<(&i32, &bool)>().iter().count(), 4);
+
+    // A panicking hook removes nothing
+    drops.store(0, Ordering::Relaxed);
+    for &entity in &entities {
+        world.insert_one(entity, Counted(drops.clone())).unwrap();
+    }
+    let mut removals = 0;
+    world.on_remove::<Counted>(move |_, _| {
+        removals += 1;
+        assert!(removals < 3);
+    });
+    let result = catch_unwind(AssertUnwindSafe(|| {
+        world.remove_batch::<Counted>(entities.iter().copied())
+    }));
+    assert!(result.is_err());
+    assert_eq!(drops.load(Ordering::Relaxed), 0);
+    assert_eq!(world.query::<&Counted>().iter().count(), 4);
+    drop(world);
+    assert_eq!(drops.load(Ordering::Relaxed), 4);
+}
+
+#[test]
+fn ensure_all() {
+    #[derive(Default, Debug, PartialEq)]
+    struct Health(u32);
+
+    let mut world = World::new();
+    let a = world.spawn_batch((0..10).map(|i| (i,))).collect::<Vec<_>>();
+    let b = world.spawn((10, Health(5)));
+    let c = world.spawn((11, true));
+    let d = world.spawn((true,));
+    let e = world.spawn((12, Disabled));
+    let archetypes = world.archetypes().len();
+
+    assert_eq!(world.ensure_all::<Health, &i32>(), 11);
+    assert_eq!(*world.get::<&Health>(a[3]).unwrap(), Health(0));
+    assert_eq!(*world.get::<&i32>(a[3]).unwrap(), 3);
+    assert_eq!(*world.get::<&Health>(b).unwrap(), Health(5));
+    assert_eq!(*world.get::<&Health>(c).unwrap(), Health(0));
+    assert!(world.get::<&Health>(d).is_err());
+    assert!(world.get::<&Health>(e).is_err());
+    // The fully covered archetype was moved wholesale, leaving it empty
+    assert_eq!(world.archetypes().len(), archetypes + 1);
+    assert!(world
+        .archetypes()
+        .any(|x| x.has::<i32>() && x.component_types().len() == 1 && x.is_empty()));
+
+    assert_eq!(world.ensure_all::<Health, &i32>(), 0);
+    assert_eq!(world.ensure_all::<Health, ()>(), 1);
+    assert!(world.get::<&Health>(d).is_ok());
+}
+
+#[test]
+fn entities_with() {
+    let mut world = World::new();
+    let a = world.spawn((1, "a"));
+    let b = world.spawn((2,));
+    world.spawn(("c",));
+    let d = world.spawn((4, true));
+    world.despawn(b).unwrap();
+    let b = world.spawn((5,));
+
+    let mut ids = world.entities_with::<i32>().collect::<Vec<_>>();
+    ids.sort();
+    let mut expected = vec![a, b, d];
+    expected.sort();
+    assert_eq!(ids, expected);
+    assert_eq!(world.entities_with::<u8>().count(), 0);
+
+    // No components are borrowed
+    let _borrow = world.query::<&mut i32>();
+    assert_eq!(world.entities_with::<i32>().count(), 3);
+}
+
+#[test]
+fn despawn_recursive() {
+    struct Children(Vec<Entity>);
+
+    let mut world = World::new();
+    let order = std::sync::Arc::new(std::sync::Mutex::new(Vec::new()));
+    let log = order.clone();
+    world.on_remove::<i32>(move |entity, _| log.lock().unwrap().push(entity));
+
+    let leaf = world.spawn((0,));
+    let branch = world.spawn((1, Children(vec![leaf])));
+    let root = world.spawn((2, Children(vec![branch, leaf])));
+    // Cycle back to the root
+    world.insert_one(leaf, Children(vec![root])).unwrap();
+    let unrelated = world.spawn((3,));
+
+    let children_of = |world: &World, entity| {
+        world
+            .get::<&Children>(entity)
+            .map_or_else(|_| Vec::new(), |x| x.0.clone())
+    };
+    assert_eq!(world.despawn_recursive(root, children_of), Ok(3));
+    assert_eq!(*order.lock().unwrap(), [leaf, branch, root]);
+    assert!(world.contains(unrelated));
+    assert_eq!(world.len(), 1);
+    assert_eq!(
+        world.despawn_recursive(root, children_of),
+        Err(NoSuchEntity)
+    );
+}
+
+#[test]
+fn disabled_entities() {
+    let mut world = World::new();
+    let a = world.spawn((1, true));
+    let b = world.spawn((2, true));
+    let c = world.spawn((3,));
+    world.set_enabled(b, false).unwrap();
+    // Idempotent
+    world.set_enabled(b, false).unwrap();
+    world.set_enabled(c, true).unwrap();
+    assert!(world.satisfies::<&Disabled>(b).unwrap());
+
+    let mut ids = world
+        .query::<&i32>()
+        .iter()
+        .map(|(e, _)| e)
+        .collect::<Vec<_>>();
+    ids.sort();
+    assert_eq!(ids, [a, c]);
+    assert_eq!(world.query::<&i32>().iter().len(), 2);
+    assert_eq!(world.query_mut::<&mut i32>().into_iter().count(), 2);
+    assert_eq!(world.query::<(&i32, &bool)>().iter().count(), 1);
+    assert_eq!(world.query::<IncludeDisabled<&i32>>().iter().count(), 3);
+    assert_eq!(
+        world
+            .query::<With<&i32, &Disabled>>()
+            .iter()
+            .map(|(e, _)| e)
+            .collect::<Vec<_>>(),
+        [b]
+    );
+    assert_eq!(world.query::<Option<&Disabled>>().iter().count(), 3);
+    assert!(world.query::<&i32>().view().get(b).is_none());
+    assert!(world.query::<&i32>().view().get(a).is_some());
+    let mut prepared = PreparedQuery::<&i32>::new();
+    assert_eq!(prepared.query(&world).iter().count(), 2);
+    let mut dynamic = world.query_dynamic(&[(TypeId::of::<i32>(), Access::Read)]);
+    assert_eq!(dynamic.iter().count(), 2);
+    drop(dynamic);
+
+    // Still accessible directly
+    assert_eq!(*world.get::<&i32>(b).unwrap(), 2);
+    assert_eq!(world.len(), 3);
+    assert_eq!(world.iter().count(), 3);
+
+    // Borrowing a disabled entity's components doesn't conflict with default queries
+    {
+        let mut disabled = world.query::<With<&mut i32, &Disabled>>();
+        let _disabled = disabled.iter();
+        for (_, x) in world.query::<&mut i32>().iter() {
+            *x += 10;
+        }
+    }
+    assert_eq!(*world.get::<&i32>(b).unwrap(), 2);
+
+    world.set_enabled(b, true).unwrap();
+    assert!(!world.satisfies::<&Disabled>(b).unwrap());
+    assert_eq!(world.query::<&i32>().iter().count(), 3);
+    world.despawn(b).unwrap();
+    assert!(world.set_enabled(b, false).is_err());
+}
+
+#[test]
+fn replace_components() {
+    let mut world = World::new();
+    let e = world.spawn((1, "a"));
+    let archetypes = world.archetypes().len();
+
+    // Already present, so the entity stays put
+    assert_eq!(world.replace_one(e, 2).unwrap(), Some(1));
+    assert_eq!(world.archetypes().len(), archetypes);
+    assert_eq!(*world.get::<&i32>(e).unwrap(), 2);
+
+    assert_eq!(world.replace_one(e, true).unwrap(), None);
+    assert!(*world.get::<&bool>(e).unwrap());
+
+    let old = world.replace(e, (3, "b", 1.5f32)).unwrap();
+    assert_eq!(old.component_types().count(), 2);
+    assert_eq!(*old.get::<&i32>().unwrap(), 2);
+    assert_eq!(*old.get::<&&str>().unwrap(), "a");
+    assert_eq!(*world.get::<&i32>(e).unwrap(), 3);
+    assert_eq!(*world.get::<&f32>(e).unwrap(), 1.5);
+
+    // Replaced values are owned by the caller, not dropped
+    let value = std::sync::Arc::new(());
+    world.insert_one(e, value.clone()).unwrap();
+    let previous = world.replace_one(e, std::sync::Arc::new(())).unwrap();
+    assert_eq!(std::sync::Arc::strong_count(&value), 2);
+    drop(previous);
+    assert_eq!(std::sync::Arc::strong_count(&value), 1);
+
+    world.despawn(e).unwrap();
+    assert!(world.replace_one(e, 4).is_err());
+}
+
+#[test]
+fn sorted_queries() {
+    let mut world = World::new();
+    let entities = (0..10)
+        .map(|i| world.spawn((i, i % 3 == 0)))
+        .collect::<Vec<_>>();
+    // Shuffle storage order
+    world.remove_one::<bool>(entities[1]).unwrap();
+    world.insert_one(entities[1], true).unwrap();
+    world.despawn(entities[4]).unwrap();
+
+    let mut expected = entities.clone();
+    expected.remove(4);
+    let ids = world
+        .query::<&i32>()
+        .sorted_by_id()
+        .map(|(e, _)| e)
+        .collect::<Vec<_>>();
+    assert_eq!(ids, expected);
+
+    let values = world
+        .query::<(&i32, &bool)>()
+        .sorted_by_key(|&(_, &flag)| flag)
+        .map(|(_, (&i, _))| i)
+        .collect::<Vec<_>>();
+    assert_eq!(values, [2, 5, 7, 8, 0, 1, 3, 6, 9]);
+
+    for (_, x) in world.query::<&mut i32>().sorted_by_key(|x| -**x) {
+        *x *= 2;
+    }
+    assert_eq!(*world.get::<&i32>(entities[9]).unwrap(), 18);
+}
+
+#[test]
+fn archetype_entities() {
+    let mut world = World::new();
+    let a = world.spawn((1,));
+    let b = world.spawn((2,));
+    world.despawn(a).unwrap();
+    let c = world.spawn((3,));
+    let archetype = world.archetypes().find(|x| x.has::<i32>()).unwrap();
+    let entities = world.archetype_entities(archetype).collect::<Vec<_>>();
+    assert_eq!(entities, [b, c]);
+    assert_eq!(&*archetype.get::<&i32>().unwrap(), &[2, 3]);
+}
+
+#[test]
+fn archetype_id() {
+    let mut world = World::new();
+    let a = world.spawn((1, true));
+    let b = world.spawn((2,));
+    let c = world.spawn((3, false));
+    let r = world.reserve_entity();
+
+    let mut buckets = std::collections::HashMap::<ArchetypeId, Vec<Entity>>::new();
+    for e in [a, b, c] {
+        buckets
+            .entry(world.archetype_id(e).unwrap())
+            .or_default()
+            .push(e);
+    }
+    assert_eq!(buckets.len(), 2);
+    for (&id, entities) in &buckets {
+        let archetype = world.archetype(id).unwrap();
+        assert_eq!(archetype.len() as usize, entities.len());
+        assert!(std::ptr::eq(
+            archetype,
+            world.archetypes().nth(id.index() as usize).unwrap()
+        ));
+    }
+    assert!(world.archetype_id(r).is_none());
+    world.flush();
+    assert_eq!(
+        world
+            .archetype(world.archetype_id(r).unwrap())
+            .unwrap()
+            .len(),
+        1
+    );
+
+    // IDs survive structural changes to other entities
+    let id = world.archetype_id(a).unwrap();
+    world.insert_one(c, "abc").unwrap();
+    world.despawn(b).unwrap();
+    assert_eq!(world.archetype_id(a), Some(id));
+    assert!(world.archetype_id(b).is_none());
+    assert_ne!(world.archetype_id(c), Some(id));
+    assert!(world.archetype(id).unwrap().has::<bool>());
+}
+
+#[test]
+fn deterministic_order() {
+    fn populate(world: &mut World, reverse: bool) {
+        let mut spawns: Vec<fn(&mut World)> = vec![
+            |w| {
+                w.spawn((1u8,));
+            },
+            |w| {
+                w.spawn((2u8, 'a'));
+            },
+            |w| {
+                w.spawn((3u8, 'b', true));
+            },
+            |w| {
+                w.spawn((4u8, 5u16));
+            },
+        ];
+        if reverse {
+            spawns.reverse();
+        }
+        for spawn in spawns {
+            spawn(world);
+        }
+    }
+    fn query_order(world: &mut World) -> Vec<u8> {
+        world
+            .query_mut::<&u8>()
+            .into_iter()
+            .map(|(_, &x)| x)
+            .collect()
+    }
+    fn iter_order(world: &World) -> Vec<u8> {
+        world.iter().map(|e| *e.get::<&u8>().unwrap()).collect()
+    }
+
+    let mut a = World::new();
+    let mut b = World::new();
+    assert!(!a.deterministic_order());
+    a.set_deterministic_order(true);
+    populate(&mut a, false);
+    populate(&mut b, true);
+    assert_eq!(query_order(&mut b), [4, 3, 2, 1]);
+    // Enabling after the fact sorts existing archetypes
+    b.set_deterministic_order(true);
+    assert!(b.deterministic_order());
+    assert_eq!(query_order(&mut a), query_order(&mut b));
+    assert_eq!(iter_order(&a), iter_order(&b));
+    assert_eq!(a.query::<&u8>().iter().len(), 4);
+
+    // Archetypes created later are placed by their component types
+    a.spawn((6u8, 7u32));
+    b.spawn((6u8, 7u32));
+    assert_eq!(query_order(&mut a), query_order(&mut b));
+    let mut registry = CloneRegistry::default();
+    registry
+        .register::<u8>()
+        .register::<char>()
+        .register::<bool>()
+        .register::<u16>()
+        .register::<u32>();
+    let mut clone = a.clone_with(&registry);
+    assert!(clone.deterministic_order());
+    assert_eq!(query_order(&mut clone), query_order(&mut a));
+
+    b.set_deterministic_order(false);
+    assert_eq!(query_order(&mut b), [4, 3, 2, 1, 6]);
+}
+
+#[test]
+#[should_panic(expected = "different world")]
+fn archetype_entities_foreign() {
+    let mut world = World::new();
+    world.spawn((1,));
+    let other = World::new();
+    let archetype = world.archetypes().last().unwrap();
+    let _ = other.archetype_entities(archetype);
+}
+
+#[test]
+fn spawn_at_exact() {
+    let mut world = World::new();
+    world.spawn(());
+    let mut handle = world.spawn(());
+    for _ in 0..3 {
+        world.despawn(handle).unwrap();
+        handle = world.spawn(());
+    }
+    let saved = handle.to_string();
+
+    let mut restored = World::new();
+    let parsed = saved.parse::<Entity>().unwrap();
+    restored.spawn_at_exact(parsed, (7,)).unwrap();
+    assert!(restored.contains(handle));
+    assert_eq!(*restored.get::<&i32>(handle).unwrap(), 7);
+    // Replacing the same handle is allowed
+    restored.spawn_at_exact(handle, (8,)).unwrap();
+    assert_eq!(*restored.get::<&i32>(handle).unwrap(), 8);
+    assert_eq!(restored.len(), 1);
+
+    // Fresh entities don't collide with the reconstructed one
+    let fresh = (0..4).map(|_| restored.spawn(())).collect::<Vec<_>>();
+    assert!(fresh.iter().all(|&e| e.id() != handle.id()));
+    assert!(restored.contains(handle));
+
+    // A stale handle to a live ID is rejected
+    restored.despawn(handle).unwrap();
+    let reused = restored.spawn(());
+    assert_eq!(reused.id(), handle.id());
+    assert_eq!(
+        restored.spawn_at_exact(handle, ()).unwrap_err().entity(),
+        handle
+    );
+    assert!(restored.contains(reused));
+}
+
+#[test]
+fn spawn_with_id() {
+    let mut world = World::external_ids();
+    let a = world.spawn_with_id(3, (1,)).unwrap();
+    let b = world.spawn_with_id(0, (2,)).unwrap();
+    assert_eq!((a.id(), b.id()), (3, 0));
+    assert_eq!(world.spawn_with_id(3, ()).unwrap_err().entity(), a);
+    assert_eq!(world.len(), 2);
+
+    // Reused IDs get a fresh generation
+    world.despawn(a).unwrap();
+    let c = world.spawn_with_id(3, (3,)).unwrap();
+    assert_eq!(c.id(), a.id());
+    assert_ne!(c, a);
+    assert!(!world.contains(a));
+    assert_eq!(*world.get::<&i32>(c).unwrap(), 3);
+
+    // Explicit IDs on an ordinary world are skipped by later allocation
+    let mut world = World::new();
+    let x = world.spawn_with_id(1, ()).unwrap();
+    let fresh = (0..3).map(|_| world.spawn(())).collect::<Vec<_>>();
+    assert!(fresh.iter().all(|&e| e.id() != x.id()));
+}
+
+#[test]
+#[should_panic(expected = "assigned externally")]
+fn external_ids_spawn() {
+    let mut world = World::external_ids();
+    world.spawn(());
+}
+
+#[test]
+#[should_panic(expected = "assigned externally")]
+fn external_ids_reserve() {
+    let world = World::external_ids();
+    world.reserve_entity();
+}
+
+#[test]
+fn query_each() {
+    let mut world = World::new();
+    let a = world.spawn((1, true));
+    let b = world.spawn((2,));
+    let c = world.spawn((3, false));
+    let dead = world.spawn((4,));
+    world.despawn(dead).unwrap();
+
+    let ids = [c, dead, a, b, a];
+    let mut query = world.query_each::<(&i32, &bool)>(&ids);
+    let results = query
+        .iter()
+        .map(|x| x.map(|(&i, &b)| (i, b)))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        results,
+        [
+            Some((3, false)),
+            None,
+            Some((1, true)),
+            None,
+            Some((1, true))
+        ]
+    );
+    assert_eq!(query.iter().len(), 5);
+    drop(query);
+
+    let ids = [b, dead, c];
+    for x in world.query_each_mut::<&mut i32>(&ids).iter().flatten() {
+        *x += 10;
+    }
+    assert_eq!(*world.get::<&i32>(a).unwrap(), 1);
+    assert_eq!(*world.get::<&i32>(b).unwrap(), 12);
+    assert_eq!(*world.get::<&i32>(c).unwrap(), 13);
+}
+
+#[test]
+#[should_panic(expected = "more than once")]
+fn query_each_mut_duplicate() {
+    let mut world = World::new();
+    let a = world.spawn((1,));
+    world.query_each_mut::<&mut i32>(&[a, a]);
+}
+
+#[test]
+fn query_access() {
+    fn id<T: 'static>() -> TypeId {
+        TypeId::of::<T>()
+    }
+
+    assert_eq!(
+        <(&i32, &mut bool)>::access(),
+        [(id::<i32>(), Access::Read), (id::<bool>(), Access::Write)]
+    );
+    assert_eq!(
+        <(&i32, Option<&mut i32>)>::access(),
+        [(id::<i32>(), Access::Write)]
+    );
+    assert_eq!(
+        <Without<Or<&i32, &mut u8>, (&bool, Changed<u16>)>>::access(),
+        [
+            (id::<i32>(), Access::Read),
+            (id::<u8>(), Access::Write),
+            (id::<bool>(), Access::Iterate),
+            (id::<u16>(), Access::Iterate),
+        ]
+    );
+    assert_eq!(
+        <(Satisfies<&mut i32>, With<&i32, &bool>)>::access(),
+        [(id::<i32>(), Access::Read), (id::<bool>(), Access::Iterate)]
+    );
+    assert_eq!(<() as Query>::access(), []);
+}
+
+#[test]
+#[cfg(feature = "macros")]
+fn derived_query_access() {
+    #[derive(Query)]
+    #[allow(dead_code)]
+    struct Foo<'a> {
+        x: &'a i32,
+        y: &'a mut bool,
+        z: Option<&'a u8>,
+    }
+
+    assert_eq!(
+        Foo::access(),
+        [
+            (TypeId::of::<i32>(), Access::Read),
+            (TypeId::of::<bool>(), Access::Write),
+            (TypeId::of::<u8>(), Access::Read),
+        ]
+    );
+}
+
+#[test]
+fn runtime_bundle_drops() {
+    use std::mem::ManuallyDrop;
+    use std::sync::Arc;
+
+    unsafe fn push<T: Component>(bundle: &mut RuntimeBundle, x: T) {
+        let mut x = ManuallyDrop::new(x);
+        bundle.push_erased((&mut *x as *mut T).cast(), TypeInfo::of::<T>());
+    }
+
+    let marker = Arc::new(());
+    let mut world = World::new();
+
+    // Spawned components are owned by the world
+    let mut bundle = RuntimeBundle::new();
+    unsafe {
+        push(&mut bundle, 7u8);
+        push(&mut bundle, marker.clone());
+        push(&mut bundle, 42u64);
+        // Replaces, and drops, the first `Arc`
+        push(&mut bundle, marker.clone());
+    }
+    assert_eq!(Arc::strong_count(&marker), 2);
+    let mut expected = [
+        TypeInfo::of::<u8>(),
+        TypeInfo::of::<Arc<()>>(),
+        TypeInfo::of::<u64>(),
+    ];
+    expected.sort();
+    assert!(bundle.component_types().eq(expected.iter().map(|x| x.id())));
+    let e = world.spawn(bundle);
+    assert_eq!(Arc::strong_count(&marker), 2);
+    assert_eq!(*world.get::<&u64>(e).unwrap(), 42);
+    assert_eq!(*world.get::<&u8>(e).unwrap(), 7);
+    world.despawn(e).unwrap();
+    assert_eq!(Arc::strong_count(&marker), 1);
+
+    // Inserting into an existing entity
+    let e = world.spawn((true,));
+    let mut bundle = RuntimeBundle::new();
+    unsafe {
+        push(&mut bundle, marker.clone());
+    }
+    world.insert(e, bundle).unwrap();
+    assert!(world.satisfies::<(&bool, &Arc<()>)>(e).unwrap());
+    assert_eq!(Arc::strong_count(&marker), 2);
+    world.clear();
+    assert_eq!(Arc::strong_count(&marker), 1);
+
+    // Unspawned components are dropped with the bundle
+    let mut bundle = RuntimeBundle::new();
+    unsafe {
+        push(&mut bundle, marker.clone());
+        push(&mut bundle, String::from("abc"));
+    }
+    assert_eq!(Arc::strong_count(&marker), 2);
+    drop(bundle);
+    assert_eq!(Arc::strong_count(&marker), 1);
+}
+
+#[test]
+fn dynamic_component_drops() {
+    use std::alloc::Layout;
+    use std::mem::ManuallyDrop;
+    use std::sync::Arc;
+
+    // Stands in for a type defined by a plugin, known here only by its ID and an `Arc` layout
+    struct PluginType;
+    unsafe fn drop_arc(x: *mut u8) {
+        x.cast::<Arc<()>>().drop_in_place();
+    }
+    let id = TypeId::of::<PluginType>();
+    let info = TypeInfo::from_parts(id, Layout::new::<Arc<()>>(), drop_arc);
+
+    let marker = Arc::new(());
+    let bundle = |world: &World| {
+        let mut bundle = RuntimeBundle::new();
+        let mut x = ManuallyDrop::new(marker.clone());
+        unsafe {
+            bundle.push_erased(
+                (&mut *x as *mut Arc<()>).cast(),
+                world.dynamic_component(id).unwrap(),
+            );
+        }
+        bundle
+    };
+
+    let mut world = World::new();
+    assert!(world.dynamic_component(id).is_none());
+    world.register_dynamic_component(info);
+    assert_eq!(world.dynamic_component(id).unwrap().layout(), info.layout());
+
+    let entities = (0..4)
+        .map(|i| {
+            let b = bundle(&world);
+            let e = world.spawn(b);
+            world.insert_one(e, i).unwrap();
+            e
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(Arc::strong_count(&marker), 5);
+    world.despawn(entities[0]).unwrap();
+    assert_eq!(Arc::strong_count(&marker), 4);
+    let b = bundle(&world);
+    world.insert(entities[1], b).unwrap();
+    assert_eq!(Arc::strong_count(&marker), 4);
+    world.clear();
+    assert_eq!(Arc::strong_count(&marker), 1);
+
+    let b = bundle(&world);
+    world.spawn(b);
+    drop(world);
+    assert_eq!(Arc::strong_count(&marker), 1);
+}
+
+#[test]
+#[should_panic(expected = "layout conflicts")]
+fn dynamic_component_layout_conflict() {
+    use std::alloc::Layout;
+    unsafe fn drop_nothing(_: *mut u8) {}
+    let mut world = World::new();
+    world.spawn((1u32,));
+    world.register_dynamic_component(TypeInfo::from_parts(
+        TypeId::of::<u32>(),
+        Layout::new::<u64>(),
+        drop_nothing,
+    ));
+}
+
+#[test]
+fn resolve() {
+    let mut world = World::new();
+    let a = world.spawn((1,));
+    assert_eq!(world.resolve(a.id(), a.generation()), Some(a));
+    assert_eq!(world.resolve(a.id(), a.generation() + 1), None);
+    assert_eq!(world.resolve(a.id(), 0), None);
+    assert_eq!(world.resolve(a.id() + 1, a.generation()), None);
+
+    world.despawn(a).unwrap();
+    assert_eq!(world.resolve(a.id(), a.generation()), None);
+    let b = world.spawn((2,));
+    assert_eq!(b.id(), a.id());
+    assert_eq!(world.resolve(a.id(), a.generation()), None);
+    assert_eq!(world.resolve(b.id(), b.generation()), Some(b));
+}
+
+#[test]
+fn drain() {
+    let mut world = World::new();
+    let a = world.spawn((1, "a"));
+    let b = world.spawn((2,));
+    let c = world.spawn((3, "c"));
+    let d = world.spawn((true,));
+    world.set_enabled(c, false).unwrap();
+
+    // Partially consumed, leaving the rest intact
+    let (first, mut components) = {
+        let mut drain = world.drain_matching::<&i32>();
+        assert_eq!(drain.len(), 2);
+        let first = drain.next().unwrap();
+        assert_eq!(drain.len(), 1);
+        first
+    };
+    assert!(!world.contains(first));
+    assert_eq!(world.len(), 3);
+    let mut other = World::new();
+    let moved = other.spawn(components.build());
+    assert_eq!(
+        *other.get::<&i32>(moved).unwrap(),
+        if first == a { 1 } else { 2 }
+    );
+
+    let mut removed = Vec::new();
+    for (entity, mut components) in world.drain() {
+        removed.push(entity);
+        assert_eq!(components.has::<i32>(), entity != d);
+        other.spawn(components.build());
+    }
+    removed.sort();
+    let mut expected = vec![a, b, c, d];
+    expected.retain(|&x| x != first);
+    assert_eq!(removed, expected);
+    assert!(world.is_empty());
+    assert_eq!(other.len(), 4);
+    assert_eq!(
+        other
+            .query_mut::<IncludeDisabled<&&str>>()
+            .into_iter()
+            .count(),
+        2
+    );
+
+    // Removal hooks fire for drained entities
+    let e = world.spawn((4, "e"));
+    world.on_remove::<i32>(|_, x| assert_eq!(*x, 4));
+    world.track_removals::<i32>();
+    assert_eq!(world.drain().count(), 1);
+    assert!(world.removed::<i32>().eq([e]));
+}
+
+#[test]
+fn satisfies_alongside_unique_borrow() {
+    struct Stunned;
+
+    let mut world = World::new();
+    let a = world.spawn((1, Stunned));
+    let b = world.spawn((2,));
+
+    for (_, (x, stunned)) in world.query_mut::<(&mut i32, Satisfies<&i32>)>() {
+        assert!(stunned);
+        *x += 10;
+    }
+
+    let mut stunned = world.query::<&mut Stunned>();
+    let _stunned = stunned.iter().collect::<Vec<_>>();
+    let mut results = world
+        .query::<(&i32, Satisfies<&mut Stunned>)>()
+        .iter()
+        .map(|(e, (&x, s))| (e, x, s))
+        .collect::<Vec<_>>();
+    results.sort();
+    assert_eq!(results, [(a, 11, true), (b, 12, false)]);
+}
+
+#[test]
+fn sequential_allocator() {
+    fn run() -> Vec<Entity> {
+        let mut world = World::with_allocator(EntityAllocatorPolicy::Sequential);
+        let a = world.spawn((1,));
+        let b = world.spawn((2,));
+        world.despawn(a).unwrap();
+        let c = world.reserve_entity();
+        let d = world.spawn((3,));
+        world.despawn(b).unwrap();
+        world.spawn_at(a, (4,));
+        let e = world.spawn((5,));
+        assert!(world.contains(a) && world.contains(c));
+        assert_eq!(world.len(), 4);
+        world.despawn(d).unwrap();
+        world.compact();
+        let f = world.spawn((6,));
+        let g = world.spawn((7,));
+        assert!(!world.contains(b));
+        assert!(!world.contains(d));
+        vec![a, b, c, d, e, f, g]
+    }
+
+    let ids = run().iter().map(|x| x.id()).collect::<Vec<_>>();
+    assert_eq!(ids, [0, 1, 2, 3, 4, 1, 3]);
+    assert_eq!(run(), run());
+}
+
+#[test]
+fn sequential_allocator_spawn_at_compacted() {
+    let mut world = World::with_allocator(EntityAllocatorPolicy::Sequential);
+    let a = world.spawn(());
+    let b = world.spawn(());
+    world.despawn(a).unwrap();
+    world.despawn(b).unwrap();
+    world.compact();
+    world.spawn_at(b, (1,));
+    let c = world.spawn((2,));
+    let d = world.spawn((3,));
+    assert_eq!(c.id(), a.id());
+    assert_ne!(d.id(), b.id());
+    assert_eq!(world.len(), 3);
+    assert_eq!(*world.get::<&i32>(b).unwrap(), 1);
+}
+
+#[test]
+fn clear_retaining_capacity() {
+    let mut world = World::new();
+    let frame = |world: &mut World| {
+        world.clear_retaining_capacity();
+        (0..100)
+            .map(|i| world.spawn((i, i as f32)))
+            .collect::<Vec<_>>()
+    };
+    let first = frame(&mut world);
+    let allocated = world.memory_report().allocated_bytes();
+    let second = frame(&mut world);
+    assert_eq!(world.memory_report().allocated_bytes(), allocated);
+    assert_eq!(world.len(), 100);
+    for (a, b) in first.iter().zip(&second) {
+        assert_eq!(a.id(), b.id());
+        assert!(!world.contains(*a));
+        assert_eq!(*world.get::<&i32>(*b).unwrap(), b.id() as i32);
+    }
+
+    world.track_removals::<i32>();
+    let reserved = world.reserve_entity();
+    world.clear_retaining_capacity();
+    assert_eq!(world.removed::<i32>().len(), 100);
+    assert!(world.is_empty());
+    assert!(!world.contains(reserved));
+
+    let mut world = World::with_allocator(EntityAllocatorPolicy::Sequential);
+    let a = world.spawn(());
+    world.clear_retaining_capacity();
+    assert_eq!(world.spawn(()).id(), a.id() + 1);
+}
+
+#[test]
+fn insert_one_if_absent() {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    struct Marker;
+    let inserts = Arc::new(AtomicUsize::new(0));
+    let mut world = World::new();
+    world.on_insert::<Marker>({
+        let inserts = inserts.clone();
+        move |_, _| {
+            inserts.fetch_add(1, Ordering::Relaxed);
+        }
+    });
+    let e = world.spawn((1,));
+    assert!(world.insert_one_if_absent(e, Marker).unwrap());
+    let generation = world.archetypes_generation();
+    for _ in 0..3 {
+        assert!(!world.insert_one_if_absent(e, Marker).unwrap());
+    }
+    assert_eq!(inserts.load(Ordering::Relaxed), 1);
+    assert_eq!(world.archetypes_generation(), generation);
+    assert!(world.satisfies::<(&i32, &Marker)>(e).unwrap());
+
+    world.set_sparse::<bool>();
+    assert!(world.insert_one_if_absent(e, true).unwrap());
+    assert!(!world.insert_one_if_absent(e, false).unwrap());
+    assert!(*world.get_sparse::<bool>(e).unwrap());
+
+    world.despawn(e).unwrap();
+    assert!(world.insert_one_if_absent(e, Marker).is_err());
+}
+
+#[test]
+fn get_or_insert_with() {
+    let mut world = World::new();
+    let a = world.spawn((1, true));
+    let b = world.spawn((true,));
+
+    *world
+        .get_or_insert_with(a, || -> i32 { unreachable!() })
+        .unwrap() += 1;
+    assert_eq!(*world.get::<&i32>(a).unwrap(), 2);
+
+    let archetypes = world.archetypes().len();
+    let x = world.get_or_insert_with(b, || 10).unwrap();
+    *x += 1;
+    assert_eq!(*world.get::<&i32>(b).unwrap(), 11);
+    assert!(*world.get::<&bool>(b).unwrap());
+    assert_eq!(world.archetypes().len(), archetypes);
+
+    let c = world.reserve_entity();
+    assert_eq!(*world.get_or_insert_with(c, || "c").unwrap(), "c");
+
+    world.despawn(a).unwrap();
+    assert!(world.get_or_insert_with(a, || 0).is_err());
+}
+
+#[test]
+fn query_pairs_mut() {
+    let mut world = World::new();
+    let a = world.spawn((1,));
+    let b = world.spawn((2,));
+    let c = world.spawn((3,));
+    let d = world.spawn(("no i32",));
+    let e = world.spawn((4,));
+    world.despawn(e).unwrap();
+    let mut pairs = world.query_pairs_mut::<i32>([(a, b), (b, d), (e, c), (c, a)]);
+    let mut visited = 0;
+    while let Some((x, y)) = pairs.next() {
+        core::mem::swap(x, y);
+        visited += 1;
+    }
+    assert_eq!(visited, 2);
+    assert_eq!(*world.get::<&i32>(a).unwrap(), 3);
+    assert_eq!(*world.get::<&i32>(b).unwrap(), 1);
+    assert_eq!(*world.get::<&i32>(c).unwrap(), 2);
+}
+
+#[test]
+#[should_panic(expected = "entity paired with itself")]
+fn query_pairs_mut_self_pair() {
+    let mut world = World::new();
+    let a = world.spawn((1,));
+    world.query_pairs_mut::<i32>([(a, a)]).next();
+}
+
+#[test]
+fn reconcile() {
+    let mut world = World::new();
+    world.track_removals::<&'static str>();
+    let a = world.spawn((1, "a", 'a'));
+    let b = world.spawn((2, "b"));
+    world.reconcile(a, (10, true)).unwrap();
+    assert_eq!(*world.get::<&i32>(a).unwrap(), 10);
+    assert!(*world.get::<&bool>(a).unwrap());
+    assert!(!world.satisfies::<Or<&&str, &char>>(a).unwrap());
+    assert!(world.removed::<&'static str>().eq([a]));
+
+    // Nothing to remove
+    world.reconcile(b, (20, "bb", 1.5f32)).unwrap();
+    assert_eq!(*world.get::<&&str>(b).unwrap(), "bb");
+    assert_eq!(*world.get::<&f32>(b).unwrap(), 1.5);
+
+    // Drop everything
+    let marker = std::sync::Arc::new(());
+    let c = world.spawn((marker.clone(), 3));
+    world.reconcile(c, ()).unwrap();
+    assert_eq!(std::sync::Arc::strong_count(&marker), 1);
+    assert!(world.contains(c));
+    assert!(!world.satisfies::<&i32>(c).unwrap());
+    assert_eq!(world.query::<()>().iter().count(), 3);
+}
+
+#[test]
+fn zip_by_id() {
+    let mut world = World::new();
+    let a = world.spawn((0u32, true));
+    world.spawn((0u32,));
+    let c = world.spawn((0u32,));
+    world.despawn(a).unwrap();
+    let weights = [10, 20, 30];
+    for (e, x, weight) in world.query::<&mut u32>().zip_by_id(&weights[..2]) {
+        assert_eq!(weight.is_some(), e.id() < 2);
+        *x = weight.copied().unwrap_or(1);
+    }
+    assert_eq!(*world.get::<&u32>(c).unwrap(), 1);
+    assert_eq!(
+        world.query::<&u32>().iter().map(|(_, &x)| x).sum::<u32>(),
+        21
+    );
+}
+
+#[test]
+fn flatten_by() {
+    struct Inventory(Vec<u32>);
+    let mut world = World::new();
+    let a = world.spawn((Inventory(vec![1, 2]), true));
+    world.spawn((Inventory(vec![]),));
+    let c = world.spawn((Inventory(vec![3]),));
+    let mut query = world.query::<(&Inventory, Option<&bool>)>();
+    let mut items = query
+        .flatten_by(|(inv, flag)| if flag.is_some() { &inv.0[1..] } else { &inv.0 })
+        .collect::<Vec<_>>();
+    items.sort_by_key(|&(_, &x)| x);
+    assert_eq!(items, [(a, &2), (c, &3)]);
+}
+
+#[test]
+fn filter_entities() {
+    let mut world = World::new();
+    let entities = (0..10).map(|i| world.spawn((i,))).collect::<Vec<_>>();
+    world.spawn((10, true));
+    let handled = entities[2..8]
+        .iter()
+        .copied()
+        .collect::<std::collections::HashSet<_>>();
+
+    let mut query = world.query::<&i32>();
+    let mut rest = query
+        .excluding(&handled)
+        .map(|(_, &x)| x)
+        .collect::<Vec<_>>();
+    rest.sort_unstable();
+    assert_eq!(rest, [0, 1, 8, 9, 10]);
+
+    let mut visited = 0;
+    let odd = query
+        .filter_entities(|e| {
+            visited += 1;
+            e.id() % 2 == 1
+        })
+        .count();
+    assert_eq!(odd, 5);
+    assert_eq!(visited, 11);
+}
+
+#[test]
+fn get_expect() {
+    let mut world = World::new();
+    let e = world.spawn((1, "a"));
+    assert_eq!(*world.get_expect::<&str>(e), "a");
+    *world.get_expect_mut::<i32>(e) += 1;
+    assert_eq!(*world.get_expect::<i32>(e), 2);
+}
+
+#[test]
+#[should_panic(expected = "entity 0v1 missing component bool")]
+fn get_expect_missing_component() {
+    let mut world = World::new();
+    let e = world.spawn((1,));
+    world.get_expect::<bool>(e);
+}
+
+#[test]
+#[should_panic(expected = "no such entity 0v1")]
+fn get_expect_mut_no_such_entity() {
+    let mut world = World::new();
+    let e = world.spawn((1,));
+    world.despawn(e).unwrap();
+    world.get_expect_mut::<i32>(e);
+}
+
+#[test]
+fn archetype_changes_since() {
+    let mut world = World::new();
+    let start = world.archetypes_generation();
+    let mut matching = 0;
+    let mut checkpoint = start;
+    for i in 0..4 {
+        world.spawn((i,));
+        world.spawn((i, i as f32));
+        matching += world
+            .archetype_changes_since(checkpoint)
+            .filter(|a| a.has::<i32>())
+            .count();
+        checkpoint = world.archetypes_generation();
+    }
+    assert_eq!(matching, 2);
+    assert_eq!(
+        world.archetype_changes_since(start).len(),
+        world.archetypes().len() - 1
+    );
+    world.clear();
+    assert_eq!(world.archetype_changes_since(checkpoint).len(), 0);
+}
+
+#[test]
+fn spawn_ref() {
+    let mut world = World::new();
+    let other = world.spawn((0, "other"));
+    let mut e = world.spawn_ref((1, "a"));
+    assert!(e.has::<&str>());
+    assert!(!e.satisfies::<&bool>());
+    assert_eq!(e.len(), 2);
+    assert_eq!(e.get::<&str>(), Some(&"a"));
+    assert!(e.get_mut::<bool>().is_none());
+    *e.get_mut::<i32>().unwrap() += 10;
+    {
+        let (x, s) = e.query::<(&mut i32, &mut &str)>().unwrap();
+        *x *= 2;
+        *s = "b";
+    }
+    assert!(e.query::<&bool>().is_none());
+    assert_eq!(e.get::<i32>(), Some(&22));
+    let e = e.entity();
+    assert_eq!(*world.get::<&i32>(e).unwrap(), 22);
+    assert_eq!(*world.get::<&&str>(e).unwrap(), "b");
+    assert_eq!(*world.get::<&i32>(other).unwrap(), 0);
+}
+
+#[test]
+fn count() {
+    let mut world = World::new();
+    let a = world.spawn((1, true));
+    world.spawn((2,));
+    world.spawn(("abc",));
+    world.spawn_batch((0..10).map(|i| (i, 'x')));
+    assert_eq!(world.count::<()>(), 13);
+    assert_eq!(world.count::<(&i32, &mut char)>(), 10);
+    assert_eq!(world.count::<Or<&bool, &&str>>(), 2);
+    assert_eq!(world.count::<Satisfies<&bool>>(), 13);
+    world.set_enabled(a, false).unwrap();
+    assert_eq!(world.count::<&i32>(), 11);
+    assert_eq!(world.count::<IncludeDisabled<&i32>>(), 12);
+    world.despawn(a).unwrap();
+    assert_eq!(world.count::<&bool>(), 0);
+}
+
+#[test]
+fn for_each_with_neighbors() {
+    let mut world = World::new();
+    let stale = world.spawn((0,));
+    world.despawn(stale).unwrap();
+    let a = world.spawn((1,));
+    assert_eq!(a.id(), stale.id());
+    let b = world.spawn((2, true));
+    let c = world.spawn((4, 'c'));
+    let disabled = world.spawn((100,));
+    world.set_enabled(disabled, false).unwrap();
+    let other = world.spawn(("no i32",));
+
+    let mut visited = Vec::new();
+    world.for_each_with_neighbors::<i32>(|entity, x, neighbors| {
+        assert_eq!(neighbors.current(), entity);
+        let mut seen = neighbors.iter().map(|(e, _)| e).collect::<Vec<_>>();
+        seen.sort();
+        let mut expected = vec![a, b, c];
+        expected.retain(|&e| e != entity);
+        assert_eq!(seen, expected);
+        assert!(neighbors.get(stale).is_none());
+        assert!(neighbors.get(disabled).is_none());
+        assert!(neighbors.get(other).is_none());
+        *x += neighbors.iter().map(|(_, &y)| y).sum::<i32>();
+        visited.push(entity);
+    });
+    visited.sort();
+    assert_eq!(visited, [a, b, c]);
+    assert_eq!(*world.get::<&i32>(disabled).unwrap(), 100);
+}
+
+#[test]
+fn neighbors_get_other() {
+    let mut world = World::new();
+    let a = world.spawn((1,));
+    let b = world.spawn((10, 'b'));
+    world.for_each_with_neighbors::<i32>(|entity, x, neighbors| {
+        let other = if entity == a { b } else { a };
+        *x += *neighbors.get(other).unwrap();
+    });
+    assert_eq!(*world.get::<&i32>(a).unwrap(), 11);
+    assert_eq!(*world.get::<&i32>(b).unwrap(), 21);
+}
+
+#[test]
+#[should_panic(expected = "component is uniquely borrowed by the entity being visited")]
+fn neighbors_get_self() {
+    let mut world = World::new();
+    world.spawn((1,));
+    world.for_each_with_neighbors::<i32>(|entity, _, neighbors| {
+        neighbors.get(entity);
+    });
+}
+
+#[test]
+fn insert_report() {
+    let mut world = World::new();
+    let e = world.spawn((1, "a"));
+    let report = world.insert_report(e, (2, true, 'c')).unwrap();
+    assert_eq!(report.overwritten, [TypeId::of::<i32>()]);
+    let mut added = report.added;
+    added.sort();
+    let mut expected = vec![TypeId::of::<bool>(), TypeId::of::<char>()];
+    expected.sort();
+    assert_eq!(added, expected);
+    assert_eq!(*world.get::<&i32>(e).unwrap(), 2);
+
+    let report = world.insert_report(e, ()).unwrap();
+    assert_eq!(report, InsertReport::default());
+
+    world.despawn(e).unwrap();
+    assert!(world.insert_report(e, (3,)).is_err());
+}
+
+#[test]
+fn cached_query() {
+    let mut world = World::new();
+    world.spawn((1, true));
+    assert_eq!(world.cached_query::<&i32>("ints").iter().count(), 1);
+    world.spawn((2, 'x'));
+    world.spawn(("no ints",));
+    let sum = world
+        .cached_query::<&i32>("ints")
+        .iter()
+        .map(|(_, &x)| x)
+        .sum::<i32>();
+    assert_eq!(sum, 3);
+    assert_eq!(world.cached_query::<&bool>("bools").iter().count(), 1);
+
+    // Change detection is tracked per key
+    for (_, x) in world.cached_query::<&mut i32>("writer").iter() {
+        *x += 1;
+    }
+    let changed = world.cached_query::<Changed<i32>>("changed").iter().count();
+    assert_eq!(changed, 2);
+    let changed = world.cached_query::<Changed<i32>>("changed").iter().count();
+    assert_eq!(changed, 0);
+}
+
+#[test]
+#[cfg(debug_assertions)]
+#[should_panic(expected = "used with a different query type")]
+fn cached_query_mismatch() {
+    let mut world = World::new();
+    world.cached_query::<&i32>("key").iter().count();
+    world.cached_query::<&bool>("key").iter().count();
+}
+
+#[test]
+fn exhausted_ids() {
+    let mut world = World::new();
+    let a = Entity::from_bits(u64::from(u32::MAX - 1) << 32).unwrap();
+    world.spawn_at(a, (1,));
+    world.despawn(a).unwrap();
+    let b = world.spawn((2,));
+    assert_eq!(b.id(), a.id());
+    assert_eq!(b.to_bits().get() >> 32, u64::from(u32::MAX));
+    assert_eq!(world.exhausted_ids(), 0);
+
+    world.despawn(b).unwrap();
+    assert_eq!(world.exhausted_ids(), 1);
+    let slot = world.entity_table_dump().nth(b.id() as usize).unwrap();
+    assert!(slot.exhausted && !slot.held);
+    assert_eq!(slot.to_string(), format!("{} exhausted", slot.entity()));
+    assert!(!world.contains(b));
+    assert!(world.get::<&i32>(b).is_err());
+    assert!(world.view::<&i32>().get(b).is_none());
+    let c = world.reserve_entity();
+    let d = world.spawn((3,));
+    assert_ne!(c.id(), b.id());
+    assert_ne!(d.id(), b.id());
+
+    world.clear_retaining_capacity();
+    assert_eq!(world.exhausted_ids(), 1);
+    assert!(world.spawn(()).id() != b.id());
+
+    world.spawn_at(b, (4,));
+    assert_eq!(world.exhausted_ids(), 0);
+    assert_eq!(*world.get::<&i32>(b).unwrap(), 4);
+}
+
+#[test]
+fn iter_archetypes() {
+    let mut world = World::new();
+    let a = world.spawn((1, true));
+    let b = world.spawn((2,));
+    let c = world.spawn((3, false));
+    let d = world.spawn((4,));
+    world.despawn(d).unwrap();
+    world.spawn_batch(core::iter::empty::<(f32,)>());
+
+    let views = world.iter_archetypes().collect::<Vec<_>>();
+    assert_eq!(views.len(), 2);
+    for view in &views {
+        let types = view.component_types().collect::<Vec<_>>();
+        assert!(types.contains(&TypeId::of::<i32>()));
+        let ids = view.entities().collect::<Vec<_>>();
+        assert_eq!(ids.len(), view.len() as usize);
+        let column = view.get_column::<i32>().unwrap();
+        if view.has::<bool>() {
+            assert_eq!(types.len(), 2);
+            assert_eq!(ids, [a, c]);
+            assert_eq!(*column, [1, 3]);
+            assert_eq!(*view.get_column::<bool>().unwrap(), [true, false]);
+        } else {
+            assert_eq!(types.len(), 1);
+            assert_eq!(ids, [b]);
+            assert_eq!(*column, [2]);
+            assert!(view.get_column::<bool>().is_none());
+        }
+    }
+}
+
+#[test]
+#[cfg_attr(feature = "thread-local", allow(clippy::arc_with_non_send_sync))]
+fn drop_queue() {
+    use std::sync::{Arc, Mutex};
+
+    let marker = Arc::new(());
+    let queue = DropQueue::new();
+    let mut world = World::new();
+    world.set_drop_queue(Some(queue.clone()));
+    let a = world.spawn((marker.clone(), 1));
+    let b = world.spawn((marker.clone(),));
+    world.despawn(a).unwrap();
+    assert!(!world.contains(a));
+    assert_eq!(queue.len(), 2);
+    assert_eq!(Arc::strong_count(&marker), 3);
+    queue.flush();
+    assert!(queue.is_empty());
+    assert_eq!(Arc::strong_count(&marker), 2);
+
+    world.clear();
+    assert!(!world.contains(b));
+    assert_eq!(Arc::strong_count(&marker), 2);
+    world.set_drop_queue(None);
+    world.spawn((marker.clone(),));
+    world.clear();
+    assert_eq!(Arc::strong_count(&marker), 2);
+    drop(queue);
+    assert_eq!(Arc::strong_count(&marker), 1);
+
+    /// Despawns another entity from the world when dropped
+    struct Despawner(Entity, Arc<Mutex<World>>);
+
+    impl Drop for Despawner {
+        fn drop(&mut self) {
+            self.1.lock().unwrap().despawn(self.0).unwrap();
+        }
+    }
+
+    let queue = DropQueue::new();
+    let shared = Arc::new(Mutex::new(World::new()));
+    let (a, b) = {
+        let mut world = shared.lock().unwrap();
+        world.set_drop_queue(Some(queue.clone()));
+        let b = world.spawn((marker.clone(),));
+        let a = world.spawn((Despawner(b, shared.clone()),));
+        (a, b)
+    };
+    shared.lock().unwrap().despawn(a).unwrap();
+    assert!(shared.lock().unwrap().contains(b));
+    queue.flush();
+    assert!(!shared.lock().unwrap().contains(b));
+    assert!(queue.is_empty());
+    assert_eq!(Arc::strong_count(&marker), 1);
+}
+
+#[test]
+fn archetype_slices() {
+    let mut world = World::new();
+    let a = world.spawn((1, 1.0f32));
+    let b = world.spawn((2, 2.0f32, true));
+    let c = world.spawn((3, 3.0f32, true));
+    world.spawn((4,));
+    world.spawn((5, 5.0f32, Disabled));
+    let empty = world.spawn((6, 6.0f32, 'x'));
+    world.despawn(empty).unwrap();
+
+    let mut chunks = 0;
+    let mut seen = Vec::new();
+    for chunk in world
+        .query_mut::<(&mut i32, &f32)>()
+        .into_archetype_slices()
+    {
+        chunks += 1;
+        assert!(!chunk.is_empty());
+        assert_eq!(chunk.ids().len(), chunk.len());
+        let entities = chunk.entities().collect::<Vec<_>>();
+        let (ints, floats) = chunk.into_slices();
+        assert_eq!(ints.len(), entities.len());
+        assert_eq!(floats.len(), entities.len());
+        for (i, f) in ints.iter_mut().zip(floats) {
+            *i += *f as i32;
+        }
+        seen.extend(entities);
+    }
+    assert_eq!(chunks, 2);
+    seen.sort();
+    assert_eq!(seen, [a, b, c]);
+    assert_eq!(*world.get::<&i32>(a).unwrap(), 2);
+    assert_eq!(*world.get::<&i32>(c).unwrap(), 6);
+
+    // Unique access is recorded as a change
+    let tick = world.increment_change_tick();
+    for chunk in world
+        .query_mut::<&mut i32>()
+        .with::<&bool>()
+        .into_archetype_slices()
+    {
+        chunk.into_slices()[0] += 1;
+    }
+    assert_eq!(world.query::<Changed<i32>>().since(tick).iter().count(), 2);
+}
+
+#[cfg(not(feature = "thread-local"))]
+#[test]
+fn split_at_archetype() {
+    let mut world = World::new();
+    let mut expected = Vec::new();
+    for i in 0..100 {
+        let e = match i % 3 {
+            0 => world.spawn((i,)),
+            1 => world.spawn((i, true)),
+            _ => world.spawn((i, "abc")),
+        };
+        expected.push((e, i * 2));
+    }
+    world.spawn((1.0f32,));
+
+    let chunks = world.query_mut::<&mut i32>().split_at_archetype();
+    assert_eq!(chunks.len(), 3);
+    assert_eq!(chunks.iter().map(|chunk| chunk.len()).sum::<usize>(), 100);
+    let mut visited = std::thread::scope(|scope| {
+        let handles = chunks
+            .into_iter()
+            .map(|chunk| {
+                assert!(chunk.archetype().has::<i32>());
+                scope.spawn(move || {
+                    let mut visited = Vec::new();
+                    for (e, x) in chunk {
+                        *x *= 2;
+                        visited.push(e);
+                    }
+                    visited
+                })
+            })
+            .collect::<Vec<_>>();
+        handles
+            .into_iter()
+            .flat_map(|handle| handle.join().unwrap())
+            .collect::<Vec<_>>()
+    });
+    visited.sort_unstable();
+    let mut ids = expected.iter().map(|&(e, _)| e).collect::<Vec<_>>();
+    ids.sort_unstable();
+    assert_eq!(visited, ids);
+    for (e, x) in expected {
+        assert_eq!(*world.get::<&i32>(e).unwrap(), x);
+    }
+}
+
+#[test]
+fn presence_mask() {
+    let mut world = World::new();
+    let a = world.spawn((1, true));
+    let b = world.spawn((2,));
+    let reserved = world.reserve_entity();
+    let mut mask = world.presence_mask::<(i32, bool)>();
+    assert!(!mask.is_stale(&world));
+    assert!(mask.contains(&world, a));
+    assert!(!mask.contains(&world, b));
+    assert!(!mask.contains(&world, reserved));
+
+    // Moving between known archetypes doesn't invalidate the mask
+    world.insert_one(b, false).unwrap();
+    world.remove_one::<bool>(a).unwrap();
+    assert!(!mask.is_stale(&world));
+    assert!(!mask.contains(&world, a));
+    assert!(mask.contains(&world, b));
+
+    // New archetypes are checked individually until refreshed
+    let c = world.spawn((3, true, "abc"));
+    assert!(mask.is_stale(&world));
+    assert!(mask.contains(&world, c));
+    mask.refresh(&world);
+    assert!(!mask.is_stale(&world));
+    assert!(mask.contains(&world, c));
+
+    world.despawn(c).unwrap();
+    assert!(!mask.contains(&world, c));
+
+    let mut other = World::new();
+    let d = other.spawn((4,));
+    assert!(mask.is_stale(&other));
+    assert!(!mask.contains(&other, d));
+    mask.refresh(&other);
+    assert!(!mask.contains(&other, d));
+
+    assert!(world.presence_mask::<()>().contains(&world, a));
+}
+
+#[test]
+fn spawn_clones() {
+    let mut builder = EntityBuilderClone::new();
+    builder.add(7).add(String::from("prefab"));
+    let prefab = builder.build();
+
+    let mut world = World::new();
+    let one = prefab.spawn_clone(&mut world);
+    let archetypes = world.archetypes().len();
+    let many = prefab.spawn_clones(&mut world, 50);
+    assert_eq!(world.archetypes().len(), archetypes);
+    assert_eq!(many.len(), 50);
+    assert_eq!(world.len(), 51);
+    for &e in many.iter().chain([&one]) {
+        assert_eq!(*world.get::<&i32>(e).unwrap(), 7);
+        assert_eq!(*world.get::<&String>(e).unwrap(), "prefab");
+    }
+    *world.get::<&mut String>(many[0]).unwrap() = "changed".into();
+    assert_eq!(*world.get::<&String>(many[1]).unwrap(), "prefab");
+    assert_eq!(*prefab.spawn_clones(&mut world, 0), []);
+}
+
+#[test]
+fn preallocate() {
+    let mut a = ColumnBatchType::new();
+    a.add::<u32>().add::<u64>();
+    let mut b = ColumnBatchType::new();
+    b.add::<u8>();
+
+    let mut world = World::new();
+    world.spawn((1u8,));
+    world.preallocate(&[(a.clone(), 500), (b.clone(), 1000)]);
+    let archetypes = world.archetypes().len();
+    let allocated = world.memory_report().allocated_bytes();
+
+    let mut batch = a.into_batch(500);
+    let mut x = batch.writer::<u32>().unwrap();
+    (0..500).for_each(|i| x.push(i).unwrap());
+    let mut y = batch.writer::<u64>().unwrap();
+    (0..500).for_each(|i| y.push(i).unwrap());
+    world.spawn_column_batch(batch.build().unwrap());
+    world.spawn_batch((0..999).map(|i| (i as u8,)));
+
+    assert_eq!(world.len(), 1500);
+    assert_eq!(world.archetypes().len(), archetypes);
+    assert_eq!(world.memory_report().allocated_bytes(), allocated);
+}
+
+#[test]
+fn component_types_query() {
+    struct Position;
+    struct Velocity;
+
+    let mut world = World::new();
+    world
+        .type_name_registry_mut()
+        .register::<Position>("Position")
+        .register::<Velocity>("Velocity");
+    let a = world.spawn((Position, Velocity));
+    let b = world.spawn((Position,));
+    let c = world.spawn(());
+
+    let registry = world.type_name_registry();
+    let mut names = world
+        .query::<ComponentTypes>()
+        .iter()
+        .map(|(e, types)| {
+            let mut names = types
+                .iter()
+                .map(|&ty| registry.get(ty).unwrap())
+                .collect::<Vec<_>>();
+            names.sort_unstable();
+            (e, names)
+        })
+        .collect::<Vec<_>>();
+    names.sort_unstable_by_key(|&(e, _)| e);
+    assert_eq!(
+        names,
+        [
+            (a, vec!["Position", "Velocity"]),
+            (b, vec!["Position"]),
+            (c, vec![])
+        ]
+    );
+
+    // Borrows nothing, so combines with unique access
+    let mut query = world.query::<(&mut Position, ComponentTypes)>();
+    assert_eq!(query.iter().map(|(_, (_, ty))| ty.len()).sum::<usize>(), 3);
+    drop(query);
+
+    let cloned = world.clone_with(&CloneRegistry::default());
+    assert_eq!(
+        cloned.type_name_registry().get(TypeId::of::<Velocity>()),
+        Some("Velocity")
+    );
+    assert_eq!(cloned.type_name_registry().get(TypeId::of::<i32>()), None);
+}
+
+#[test]
+fn transaction() {
+    let mut world = World::new();
+    let a = world.spawn((1i32, "a"));
+    let b = world.spawn((2i32,));
+    let c = world.spawn((3i32, true));
+
+    let result = world.transaction(|tx| {
+        let d = tx.spawn((4i32,));
+        tx.despawn(a)?;
+        tx.insert(b, (20i32, 'b'))?;
+        tx.remove_one::<bool>(c).map_err(|_| NoSuchEntity)?;
+        tx.insert_one(d, 'd')?;
+        assert!(tx.world().contains(d));
+        assert!(!tx.world().contains(a));
+        assert_eq!(*tx.world().get::<&i32>(b).unwrap(), 20);
+        tx.despawn(d)?;
+        tx.despawn(d)
+    });
+    assert_eq!(result, Err(NoSuchEntity));
+    assert_eq!(world.len(), 3);
+    assert_eq!(*world.get::<&i32>(a).unwrap(), 1);
+    assert_eq!(*world.get::<&&str>(a).unwrap(), "a");
+    assert_eq!(*world.get::<&i32>(b).unwrap(), 2);
+    assert!(!world.satisfies::<&char>(b).unwrap());
+    assert!(*world.get::<&bool>(c).unwrap());
+
+    let d = world
+        .transaction(|tx| {
+            tx.despawn(a)?;
+            tx.insert(b, (20i32, 'b'))?;
+            Ok::<_, NoSuchEntity>(tx.spawn((4i32,)))
+        })
+        .unwrap();
+    assert!(!world.contains(a));
+    assert_eq!(*world.get::<&i32>(b).unwrap(), 20);
+    assert_eq!(*world.get::<&char>(b).unwrap(), 'b');
+    assert_eq!(*world.get::<&i32>(d).unwrap(), 4);
+}
+
+#[test]
+fn transaction_restores_sparse() {
+    let mut world = World::new();
+    world.set_sparse::<bool>();
+    let a = world.spawn((1i32,));
+    world.insert_one(a, true).unwrap();
+    let result = world.transaction(|tx| {
+        tx.despawn(a)?;
+        assert_eq!(tx.world().query_sparse::<bool>().len(), 0);
+        Err::<(), _>(NoSuchEntity)
+    });
+    assert!(result.is_err());
+    assert_eq!(*world.get::<&i32>(a).unwrap(), 1);
+    assert!(*world.get_sparse::<bool>(a).unwrap());
+
+    world.transaction(|tx| tx.despawn(a)).unwrap();
+    assert_eq!(world.query_sparse::<bool>().len(), 0);
+}
+
+#[test]
+fn sparse_components() {
+    #[derive(Debug)]
+    struct Selected(u32);
+
+    let mut world = World::new();
+    let a = world.spawn((1i32, Selected(1)));
+    let b = world.spawn((2i32,));
+    world.set_sparse::<Selected>();
+    assert!(world.is_sparse::<Selected>());
+    assert!(!world.is_sparse::<i32>());
+    assert!(world.get::<&Selected>(a).is_err());
+    assert_eq!(world.get_sparse::<Selected>(a).unwrap().0, 1);
+
+    let generation = world.archetypes_generation();
+    world.insert_one(b, Selected(2)).unwrap();
+    assert_eq!(world.archetypes_generation(), generation);
+    world.get_sparse_mut::<Selected>(b).unwrap().0 = 20;
+    let mut selected = world
+        .query_sparse::<Selected>()
+        .map(|(e, x)| (e, x.0))
+        .collect::<Vec<_>>();
+    selected.sort_by_key(|&(_, x)| x);
+    assert_eq!(selected, [(a, 1), (b, 20)]);
+    for (_, x) in world.query_sparse_mut::<Selected>() {
+        x.0 += 1;
+    }
+
+    assert_eq!(world.remove_one::<Selected>(a).unwrap().0, 2);
+    assert!(matches!(
+        world.remove_one::<Selected>(a),
+        Err(ComponentError::MissingComponent(_))
+    ));
+    assert_eq!(world.query_sparse::<Selected>().len(), 1);
+    world.despawn(b).unwrap();
+    assert_eq!(world.query_sparse::<Selected>().len(), 0);
+    assert_eq!(
+        world.get_sparse::<Selected>(b).unwrap_err(),
+        ComponentError::NoSuchEntity
+    );
+    assert_eq!(world.insert_one(b, Selected(3)).unwrap_err(), NoSuchEntity);
+}
+
+#[test]
+#[should_panic(expected = "stored sparsely")]
+fn sparse_in_bundle() {
+    let mut world = World::new();
+    world.set_sparse::<bool>();
+    world.spawn((1i32, true));
+}
+
+#[test]
+fn sparse_batch_insertion() {
+    #[derive(Clone, Default)]
+    struct Selected(u32);
+
+    let mut world = World::new();
+    world.set_sparse::<Selected>();
+    let a = world.spawn((1i32,));
+    let b = world.spawn((2i32,));
+    let c = world.spawn((true,));
+    let generation = world.archetypes_generation();
+    assert_eq!(world.insert_batch([a, a], Selected(1)), 1);
+    assert_eq!(world.get_sparse::<Selected>(a).unwrap().0, 1);
+    assert!(world.get::<&Selected>(a).is_err());
+
+    assert_eq!(world.ensure_all::<Selected, &i32>(), 1);
+    assert_eq!(world.get_sparse::<Selected>(a).unwrap().0, 1);
+    assert_eq!(world.get_sparse::<Selected>(b).unwrap().0, 0);
+    assert!(world.get_sparse::<Selected>(c).is_err());
+    assert_eq!(world.ensure_all::<Selected, &i32>(), 0);
+    assert_eq!(world.archetypes_generation(), generation);
+
+    assert_eq!(world.remove_batch::<Selected>([a, c]), 1);
+    assert!(world.get_sparse::<Selected>(a).is_err());
+    assert_eq!(world.query_sparse::<Selected>().len(), 1);
+}
+
+#[test]
+fn sparse_get_or_insert_with() {
+    let mut world = World::new();
+    world.set_sparse::<u8>();
+    let a = world.spawn((1i32,));
+    *world.get_or_insert_with(a, || 1u8).unwrap() += 1;
+    *world.get_or_insert_with(a, || 10u8).unwrap() += 1;
+    assert_eq!(*world.get_sparse::<u8>(a).unwrap(), 3);
+    assert!(world.get::<&u8>(a).is_err());
+}
+
+#[test]
+fn query_cursor() {
+    let mut world = World::new();
+    let empty = world.spawn(());
+    for i in 0..3 {
+        world.spawn((i,));
+    }
+    world.spawn((3, true));
+    let mut cursor = world.query_cursor::<&mut i32>();
+    let mut visited = Vec::new();
+    for (_, x) in cursor.advance(&mut world, 2) {
+        visited.push(*x);
+        *x *= 10;
+    }
+    assert_eq!(visited.len(), 2);
+    assert!(!cursor.is_finished());
+
+    // Structural changes between advances don't disturb the position
+    world.spawn((4, "new archetype"));
+    world.insert_one(empty, 5).unwrap();
+    for (_, x) in cursor.advance(&mut world, 100) {
+        visited.push(*x);
+        *x *= 10;
+    }
+    assert!(cursor.is_finished());
+    visited.sort_unstable();
+    assert_eq!(visited, [0, 1, 2, 3, 4, 5]);
+
+    // A new pass starts from the beginning
+    let mut cursor_all = world.query_cursor::<()>();
+    assert_eq!(cursor_all.advance(&mut world, 100).count(), 6);
+    assert_eq!(
+        cursor
+            .advance(&mut world, 100)
+            .map(|(_, x)| *x)
+            .sum::<i32>(),
+        150
+    );
+
+    // Moving to another world restarts the pass
+    let mut other = World::new();
+    other.spawn((7,));
+    let mut ones = world.query_cursor::<&i32>();
+    assert_eq!(ones.advance(&mut world, 1).count(), 1);
+    assert_eq!(
+        ones.advance(&mut other, 5)
+            .map(|(_, &x)| x)
+            .collect::<Vec<_>>(),
+        [7]
+    );
+}
+
+#[test]
+fn query_cursor_exact_boundary() {
+    let mut world = World::new();
+    world.spawn((1,));
+    world.spawn((2, true));
+    world.spawn(("unrelated",));
+    let mut cursor = world.query_cursor::<&i32>();
+    assert_eq!(cursor.advance(&mut world, 1).count(), 1);
+    assert!(!cursor.is_finished());
+    assert_eq!(cursor.advance(&mut world, 1).count(), 1);
+    assert!(cursor.is_finished());
+    assert_eq!(cursor.advance(&mut world, 2).count(), 2);
+    assert!(cursor.is_finished());
+}
+
+#[test]
+fn query_cursor_deterministic_order() {
+    let mut world = World::new();
+    world.set_deterministic_order(true);
+    world.spawn((3u8, true));
+    world.spawn((2u8, 'c'));
+    world.spawn((1u8,));
+    let expected = world
+        .query_mut::<&u8>()
+        .into_iter()
+        .map(|(_, &x)| x)
+        .collect::<Vec<_>>();
+    let mut cursor = world.query_cursor::<&u8>();
+    let mut visited = Vec::new();
+    while !cursor.is_finished() {
+        visited.extend(cursor.advance(&mut world, 1).map(|(_, &x)| x));
+    }
+    assert_eq!(visited, expected);
+
+    // Archetypes created earlier in the order don't disturb the position
+    let mut cursor = world.query_cursor::<&u8>();
+    let first = cursor
+        .advance(&mut world, 2)
+        .map(|(_, &x)| x)
+        .collect::<Vec<_>>();
+    assert_eq!(first, expected[..2]);
+    world.spawn((0u8, 0u16));
+    world.spawn((0u8, 0u32));
+    world.spawn((0u8, 0u64));
+    world.spawn((0u8, 0i16));
+    let order = world
+        .query_mut::<&u8>()
+        .into_iter()
+        .map(|(_, &x)| x)
+        .collect::<Vec<_>>();
+    // Resumes from the third original archetype, wherever the new ones were placed
+    let start = order.iter().position(|&x| x == expected[2]).unwrap();
+    let rest = cursor
+        .advance(&mut world, 10)
+        .map(|(_, &x)| x)
+        .collect::<Vec<_>>();
+    assert_eq!(rest, order[start..]);
+}
+
+#[test]
+fn raw_component_access() {
+    let mut world = World::new();
+    let a = world.spawn((1u16, 'a'));
+    let b = world.spawn((2u16, 'b'));
+    let (ptr, layout) = world.get_raw::<char>(b).unwrap();
+    assert_eq!(layout, core::alloc::Layout::new::<char>());
+    assert_eq!(unsafe { *ptr.cast::<char>().as_ptr() }, 'b');
+    assert!(world.get_raw::<bool>(a).is_none());
+
+    let archetype = world.archetypes().find(|x| x.has::<u16>()).unwrap();
+    let (base, stride) = archetype.get_base_ptr(TypeId::of::<u16>()).unwrap();
+    assert_eq!(stride, 2);
+    let values = (0..archetype.len() as usize)
+        .map(|i| unsafe { *base.as_ptr().add(i * stride).cast::<u16>() })
+        .collect::<Vec<_>>();
+    assert_eq!(values, [1, 2]);
+    assert!(archetype.get_base_ptr(TypeId::of::<bool>()).is_none());
+    world.despawn(b).unwrap();
+    assert!(world.get_raw::<char>(b).is_none());
+}
+
+#[test]
+fn one_of() {
+    struct Alive;
+    struct Dead;
+    struct Spawning;
+
+    let mut world = World::new();
+    let a = world.spawn((Alive, 1));
+    let b = world.spawn((Dead, 2));
+    let c = world.spawn((Spawning,));
+    let ab = world.spawn((Alive, Dead));
+    let abc = world.spawn((Alive, Dead, Spawning));
+    let none = world.spawn((3,));
+
+    type States = (&'static Alive, &'static Dead, &'static Spawning);
+    let mut matched = world
+        .query::<OneOf<States>>()
+        .iter()
+        .map(|(e, ())| e)
+        .collect::<Vec<_>>();
+    matched.sort();
+    assert_eq!(matched, [a, b, c]);
+    assert!(world.satisfies::<OneOf<States>>(a).unwrap());
+    assert!(!world.satisfies::<OneOf<States>>(abc).unwrap());
+
+    // Doesn't borrow, so may be combined with unique borrows of the same components
+    for (_, (_, ())) in world.query_mut::<(&mut Alive, OneOf<States>)>() {}
+
+    let mut violators = world.validate_exclusive::<States>();
+    violators.sort();
+    assert_eq!(violators, [ab, abc, none]);
+    world.set_enabled(ab, false).unwrap();
+    assert!(world.validate_exclusive::<States>().contains(&ab));
+    assert!(world.validate_exclusive::<(&Spawning,)>().contains(&a));
+}
+
+#[test]
+fn shrink_to_fit() {
+    let mut world = World::new();
+    let flags = world
+        .spawn_batch((0..100).map(|i| (i, true)))
+        .collect::<Vec<_>>();
+    let strings = world
+        .spawn_batch((0..100).map(|i| (i, "abc")))
+        .collect::<Vec<_>>();
+    for &entity in flags.iter().chain(&strings).skip(1) {
+        world.despawn(entity).unwrap();
+    }
+    let capacity = |world: &World| {
+        let report = world.memory_report();
+        report
+            .archetypes
+            .iter()
+            .filter(|x| x.len > 0 || x.capacity > 0)
+            .map(|x| x.capacity)
+            .collect::<Vec<_>>()
+    };
+    assert!(capacity(&world).iter().all(|&x| x >= 100));
+
+    world.shrink_to_fit_matching::<&bool>();
+    let shrunk = capacity(&world);
+    assert_eq!(shrunk.iter().filter(|&&x| x == 1).count(), 1);
+    assert!(shrunk.iter().any(|&x| x >= 100));
+
+    world.shrink_to_fit();
+    assert!(capacity(&world).iter().all(|&x| x <= 1));
+    assert_eq!(*world.get::<&i32>(flags[0]).unwrap(), 0);
+
+    // Shrunk archetypes, including empty ones, can grow again
+    world.spawn_batch((0..100).map(|i| (i, "def")));
+    world.spawn((1, true));
+    assert_eq!(world.query_mut::<&i32>().into_iter().count(), 102);
+}
+
+#[test]
+fn tracked_query_items() {
+    let mut world = World::new();
+    let a = world.spawn((1, true));
+    let b = world.spawn((2,));
+    let tick = world.increment_change_tick();
+
+    // Reading through `TrackedMut` doesn't count as a change
+    for (_, x) in world.query_mut::<TrackedMut<i32>>() {
+        assert!(*x > 0);
+        assert!(!x.is_changed_since(tick));
+        assert!(!x.is_added_since(tick));
+    }
+    assert_eq!(
+        world
+            .query_mut::<Changed<i32>>()
+            .since(tick)
+            .into_iter()
+            .count(),
+        0
+    );
+
+    for (e, mut x) in world.query_mut::<TrackedMut<i32>>() {
+        if e == a {
+            *x += 10;
+            assert!(x.is_changed_since(tick));
+            assert!(!x.is_added_since(tick));
+        } else {
+            *x.bypass_change_detection() += 20;
+            assert!(!x.is_changed_since(tick));
+        }
+    }
+    let mut query = world.query::<(Tracked<i32>, Option<&bool>)>();
+    for (e, (x, flag)) in query.iter() {
+        assert_eq!(x.is_changed_since(tick), flag.is_some());
+        assert!(x.changed_tick() >= x.added_tick());
+        assert_eq!(*x, if e == a { 11 } else { 22 });
+    }
+    drop(query);
+
+    let tick = world.increment_change_tick();
+    world.insert_one(b, 'b').unwrap();
+    let x = world.query_one_mut::<Tracked<char>>(b).unwrap();
+    assert!(x.is_added_since(tick));
+    assert_eq!(*x.into_inner(), 'b');
+}
+
+#[test]
+#[should_panic(expected = "query violates a unique borrow")]
+fn tracked_mut_aliasing() {
+    let mut world = World::new();
+    world.spawn((1,));
+    world.query_mut::<(TrackedMut<i32>, &i32)>();
+}